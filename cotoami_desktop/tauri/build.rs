@@ -27,6 +27,7 @@ fn main() {
                 "create_database",
                 "open_database",
                 "new_owner_password",
+                "import_cotoami_json",
                 "connect_to_servers",
             ]),
         ),
@@ -4,7 +4,10 @@ use std::{
     sync::Arc,
 };
 
-use cotoami_db::prelude::*;
+use cotoami_db::{
+    import::cotoami::{CotoamiImportOptions, CotoamiImportReport},
+    prelude::*,
+};
 use cotoami_node::prelude::*;
 use tauri::Manager;
 use tracing::debug;
@@ -253,6 +256,27 @@ fn normalize_path<P: AsRef<Path>>(path: P) -> Result<String, Error> {
     ))
 }
 
+/// Imports a JSON dump file exported from the original Cotoami into the local node.
+///
+/// The progress will be notified to the frontend as
+/// [LocalNodeEvent::ImportProgress] events.
+#[tauri::command]
+pub async fn import_cotoami_json(
+    state: tauri::State<'_, NodeState>,
+    file: String,
+    options: CotoamiImportOptions,
+) -> Result<CotoamiImportReport, Error> {
+    debug!("Importing a Cotoami JSON dump: {file}");
+    let json = tokio::fs::read_to_string(&file)
+        .await
+        .map_err(|e| Error::new("file-system-error", e.to_string()))?;
+    let opr = state.local_node_as_operator()?;
+    state
+        .import_cotoami_json(json, options, Arc::new(opr))
+        .await
+        .map_err(Error::from)
+}
+
 #[tauri::command]
 pub async fn new_owner_password(
     app_handle: tauri::AppHandle,
@@ -94,16 +94,26 @@ mod tests {
                 node_name = "Hello"
                 session_minutes = 1440
                 changes_chunk_size = 30
+                changes_chunk_bytes = 16777216
+                snapshot_threshold = 1000
                 max_message_size_as_client = 1073741824
                 max_message_size_as_server = 67108864
+                backup_retention = 7
+                compaction_margin = 1000
+                graph_query_max_cotos = 1000
 
                 [00000000-0000-0000-0000-000000000002]
                 db_dir = "/path/to/db2"
                 node_name = "Bye"
                 session_minutes = 1440
                 changes_chunk_size = 30
+                changes_chunk_bytes = 16777216
+                snapshot_threshold = 1000
                 max_message_size_as_client = 1073741824
                 max_message_size_as_server = 67108864
+                backup_retention = 7
+                compaction_margin = 1000
+                graph_query_max_cotos = 1000
             "#})
         );
         Ok(())
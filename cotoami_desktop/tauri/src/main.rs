@@ -80,6 +80,7 @@ fn main() {
             commands::db::create_database,
             commands::db::open_database,
             commands::db::new_owner_password,
+            commands::db::import_cotoami_json,
             commands::conn::connect_to_servers
         ])
         .build(tauri::generate_context!())
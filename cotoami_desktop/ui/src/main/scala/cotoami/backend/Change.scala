@@ -35,6 +35,7 @@ trait ChangeJson extends js.Object {
   val DeleteIto: js.UndefOr[DeleteIto] = js.native
   val ChangeItoOrder: js.UndefOr[ChangeItoOrder] = js.native
   val ChangeOwnerNode: js.UndefOr[ChangeOwnerNode] = js.native
+  val MediaOmitted: js.UndefOr[MediaOmitted] = js.native
 }
 
 object ChangeJson {
@@ -122,4 +123,16 @@ object ChangeJson {
     val to: String = js.native
     val last_change_number: Double = js.native
   }
+
+  @js.native
+  trait MediaOmitted extends js.Object {
+    val change: ChangeJson = js.native
+    val media: OmittedMedia = js.native
+  }
+
+  @js.native
+  trait OmittedMedia extends js.Object {
+    val hash: String = js.native
+    val media_type: String = js.native
+  }
 }
@@ -169,6 +169,10 @@ object Changelog {
           )
         }
       )
+      .orElse(
+        // The media will be fetched separately by the backend.
+        change.MediaOmitted.toOption.map(json => applyChange(json.change, model))
+      )
       .getOrElse((model, Cmd.none))
 
   private def createCoto(
@@ -0,0 +1 @@
+ALTER TABLE child_nodes DROP COLUMN changes_acknowledged;
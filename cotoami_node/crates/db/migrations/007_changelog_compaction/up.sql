@@ -0,0 +1,4 @@
+-- Serial number of the last change that the child node has acknowledged to have
+-- received, which is known from its request for the following changes.
+-- The changelog can be compacted up to the minimum of these numbers.
+ALTER TABLE child_nodes ADD COLUMN changes_acknowledged BIGINT NOT NULL DEFAULT 0;
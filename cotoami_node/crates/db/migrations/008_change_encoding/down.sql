@@ -0,0 +1,2 @@
+-- The entries already re-encoded can't be read by the versions before this migration.
+DROP TABLE changelog_reencoding;
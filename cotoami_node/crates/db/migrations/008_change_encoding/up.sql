@@ -0,0 +1,12 @@
+-- Changes in the changelog are now stored in a versioned encoding with field names
+-- (cotoami_db::models::changelog::Change::ENCODING_VERSION) instead of the positional
+-- MessagePack, which can't be decoded once a field has been added to a variant.
+--
+-- Since the encoding is done in Rust, this migration only queues the existing entries,
+-- which will be re-encoded and removed from the queue when the database is opened.
+CREATE TABLE changelog_reencoding (
+  -- Serial number of a changelog entry to be re-encoded
+  serial_number INTEGER NOT NULL PRIMARY KEY
+);
+
+INSERT INTO changelog_reencoding (serial_number) SELECT serial_number FROM changelog;
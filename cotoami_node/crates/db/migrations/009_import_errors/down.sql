@@ -0,0 +1,2 @@
+DROP INDEX changelog_import_errors;
+ALTER TABLE changelog DROP COLUMN imported_from;
@@ -0,0 +1,12 @@
+-- UUID of the parent node from which a change has been imported, which is
+-- NULL for the changes created in the local node.
+ALTER TABLE changelog ADD COLUMN imported_from TEXT;
+
+-- The parents of the existing changes are unknown, so failed imports are assumed
+-- to be from their origins if the origins are parents of the local node.
+UPDATE changelog SET imported_from = origin_node_id
+WHERE import_error IS NOT NULL
+  AND origin_node_id IN (SELECT node_id FROM parent_nodes);
+
+CREATE INDEX changelog_import_errors ON changelog(imported_from)
+WHERE import_error IS NOT NULL;
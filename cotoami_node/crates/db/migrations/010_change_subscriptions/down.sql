@@ -0,0 +1,4 @@
+ALTER TABLE child_nodes DROP COLUMN subscription_recursive;
+ALTER TABLE child_nodes DROP COLUMN subscribed_cotonoma_ids;
+ALTER TABLE parent_nodes DROP COLUMN subscription_recursive;
+ALTER TABLE parent_nodes DROP COLUMN subscribed_cotonoma_ids;
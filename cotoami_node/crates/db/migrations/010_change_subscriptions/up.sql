@@ -0,0 +1,9 @@
+-- Cotonomas (comma-separated UUIDs) to which the local node subscribes as a child
+-- of the parent node. NULL means replicating the whole database of the parent.
+ALTER TABLE parent_nodes ADD COLUMN subscribed_cotonoma_ids TEXT;
+ALTER TABLE parent_nodes ADD COLUMN subscription_recursive BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Cotonomas to which the child node has subscribed in its current session.
+-- Changes outside of them will be sent to the child as placeholders.
+ALTER TABLE child_nodes ADD COLUMN subscribed_cotonoma_ids TEXT;
+ALTER TABLE child_nodes ADD COLUMN subscription_recursive BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,3 @@
+DROP TABLE missing_media;
+ALTER TABLE child_nodes DROP COLUMN lazy_media;
+ALTER TABLE parent_nodes DROP COLUMN lazy_media;
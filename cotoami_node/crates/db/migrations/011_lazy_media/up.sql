@@ -0,0 +1,25 @@
+-- TRUE if the local node requests the parent node to omit media content from changes.
+ALTER TABLE parent_nodes ADD COLUMN lazy_media BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- TRUE if the child node has requested to omit media content from changes
+-- in its current session.
+ALTER TABLE child_nodes ADD COLUMN lazy_media BOOLEAN NOT NULL DEFAULT FALSE;
+
+--
+-- Media content of cotos that has been omitted from the imported changes
+-- and not yet been fetched from a parent.
+--
+CREATE TABLE missing_media (
+  -- UUID of the coto whose media content is missing
+  coto_id TEXT NOT NULL PRIMARY KEY,
+
+  -- Content hash of the missing media (SHA-256 in hex)
+  hash TEXT NOT NULL,
+
+  -- MIME type of the missing media
+  media_type TEXT NOT NULL,
+
+  created_at DATETIME NOT NULL, -- UTC
+
+  FOREIGN KEY(coto_id) REFERENCES cotos(uuid) ON DELETE CASCADE
+);
@@ -0,0 +1 @@
+ALTER TABLE parent_nodes DROP COLUMN forked_at_serial_number;
@@ -0,0 +1,3 @@
+-- The last serial number of the local changelog when the local node was forked from
+-- the parent node. The local changes after it have diverged from the parent.
+ALTER TABLE parent_nodes ADD COLUMN forked_at_serial_number INTEGER;
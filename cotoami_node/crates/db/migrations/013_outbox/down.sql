@@ -0,0 +1,2 @@
+DROP TABLE handled_requests;
+DROP TABLE outbox;
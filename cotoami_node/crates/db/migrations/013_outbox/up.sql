@@ -0,0 +1,37 @@
+--
+-- Requests to parent nodes that have been queued while disconnected from them,
+-- to be sent in order when reconnected.
+--
+CREATE TABLE outbox (
+  -- Serial number of the queued request, which determines the order to send
+  serial_number INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+
+  -- UUID to be sent with the request so that the parent won't handle it twice
+  idempotency_key TEXT NOT NULL UNIQUE,
+
+  -- UUID of the parent node to which the request will be sent
+  parent_node_id TEXT NOT NULL,
+
+  -- Command of the request serialized by the node layer
+  command BLOB NOT NULL,
+
+  created_at DATETIME NOT NULL, -- UTC
+
+  FOREIGN KEY(parent_node_id) REFERENCES parent_nodes(node_id) ON DELETE CASCADE
+);
+CREATE INDEX outbox_parent_node_id ON outbox(parent_node_id);
+
+--
+-- Idempotency keys of the requests from child nodes that have been handled.
+--
+CREATE TABLE handled_requests (
+  idempotency_key TEXT NOT NULL PRIMARY KEY,
+
+  -- UUID of the child node that has sent the request
+  node_id TEXT NOT NULL,
+
+  created_at DATETIME NOT NULL, -- UTC
+
+  FOREIGN KEY(node_id) REFERENCES child_nodes(node_id) ON DELETE CASCADE
+);
+CREATE INDEX handled_requests_created_at ON handled_requests(created_at);
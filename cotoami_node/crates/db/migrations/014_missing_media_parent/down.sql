@@ -0,0 +1,12 @@
+-- A column referring to another table can't be dropped, so the table is rebuilt.
+CREATE TABLE missing_media_without_parent (
+  coto_id TEXT NOT NULL PRIMARY KEY,
+  hash TEXT NOT NULL,
+  media_type TEXT NOT NULL,
+  created_at DATETIME NOT NULL, -- UTC
+  FOREIGN KEY(coto_id) REFERENCES cotos(uuid) ON DELETE CASCADE
+);
+INSERT INTO missing_media_without_parent
+  SELECT coto_id, hash, media_type, created_at FROM missing_media;
+DROP TABLE missing_media;
+ALTER TABLE missing_media_without_parent RENAME TO missing_media;
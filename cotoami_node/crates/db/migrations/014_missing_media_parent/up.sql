@@ -0,0 +1,5 @@
+-- UUID of the parent node from which the change omitting the media has been imported,
+-- which is NULL if the parent is unknown (recorded before this column was added) or
+-- has been unregistered.
+ALTER TABLE missing_media ADD COLUMN parent_node_id TEXT
+  REFERENCES parent_nodes(node_id) ON DELETE SET NULL;
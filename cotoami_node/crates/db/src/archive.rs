@@ -0,0 +1,434 @@
+//! A portable archive of a node's own data (cotos, cotonomas, itos, media and node metadata).
+//!
+//! An archive is a tar file that consists of the following entries:
+//!
+//! * `nodes.jsonl` - Other nodes referenced as authors of the archived entities
+//! * `cotonomas.jsonl` - [ArchivedCotonoma]s in an order where parents come first
+//! * `cotos.jsonl` - [ArchivedCoto]s in an order where originals come before reposts
+//! * `itos.jsonl` - [Ito]s between the archived cotos
+//! * `media/<coto-id>` - Media content of each coto stored as a separate file
+//! * `manifest.json` - [ArchiveManifest] describing the archive
+//!
+//! Every entity keeps its UUID and timestamps in the archive so that it can be
+//! recreated in another node by [crate::import::archive::import_archive].
+
+use std::{
+    collections::{HashMap, HashSet},
+    io::{BufRead, Read, Seek, SeekFrom, Write},
+};
+
+use anyhow::{anyhow, bail, ensure, Result};
+use chrono::NaiveDateTime;
+use diesel::sqlite::SqliteConnection;
+
+use crate::{
+    current_datetime,
+    db::{
+        error::DatabaseError,
+        op::{Context, Operation},
+        ops::{coto_ops, cotonoma_ops, ito_ops, node_ops, node_role_ops::local_ops},
+        transactions::{
+            cotos::{resolve_scope_filter, Scope},
+            DatabaseSession,
+        },
+    },
+    models::{coto::Coto, cotonoma::Cotonoma, ito::Ito, node::Node, Id},
+};
+
+/// Identifier of the archive format stored in [ArchiveManifest::format].
+pub const ARCHIVE_FORMAT: &str = "cotoami-archive";
+
+/// The latest version of the archive format.
+///
+/// It should be incremented whenever an incompatible change is made to the format.
+/// An archive of a newer version than this can't be read.
+pub const ARCHIVE_VERSION: u32 = 1;
+
+const MANIFEST: &str = "manifest.json";
+const NODES: &str = "nodes.jsonl";
+const COTONOMAS: &str = "cotonomas.jsonl";
+const COTOS: &str = "cotos.jsonl";
+const ITOS: &str = "itos.jsonl";
+const MEDIA_DIR: &str = "media/";
+
+/// Number of cotos to be loaded at once during an export
+const EXPORT_PAGE_SIZE: i64 = 500;
+
+/// Metadata of an archive.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ArchiveManifest {
+    /// Always [ARCHIVE_FORMAT]
+    pub format: String,
+
+    /// Version of the format in which the archive has been written
+    pub version: u32,
+
+    /// The node whose data has been archived
+    pub node: Node,
+
+    /// ID of the root cotonoma of [Self::node], which will be replaced with
+    /// the destination cotonoma in an import.
+    pub root_cotonoma_id: Id<Cotonoma>,
+
+    /// ID of the coto of the root cotonoma, which will be replaced with the coto of
+    /// the destination cotonoma in an import.
+    pub root_coto_id: Id<Coto>,
+
+    /// The scope in which the data has been exported
+    pub scope: Scope,
+
+    pub nodes: usize,
+    pub cotonomas: usize,
+    pub cotos: usize,
+    pub itos: usize,
+    pub media: usize,
+
+    pub exported_at: NaiveDateTime,
+}
+
+/// A line of `cotonomas.jsonl`.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ArchivedCotonoma {
+    pub cotonoma: Cotonoma,
+    pub coto: Coto,
+
+    /// Path of the archive entry containing the media content of the coto
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub media: Option<String>,
+}
+
+/// A line of `cotos.jsonl`.
+///
+/// The media content of [Self::coto] is stored in a separate entry specified by [Self::media].
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ArchivedCoto {
+    pub coto: Coto,
+
+    /// Path of the archive entry containing the media content of the coto
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub media: Option<String>,
+}
+
+impl DatabaseSession<'_> {
+    /// Writes the local node's data in the given scope as an archive to the `writer`.
+    ///
+    /// Entities that originated in other nodes will be excluded since they can't be
+    /// imported as part of another node. Itos will be archived only if both ends are
+    /// included in the archive (the root cotonoma of the local node counts as included).
+    ///
+    /// The data is read in a single read transaction, so that the archive will be
+    /// consistent even if the database is updated during the export.
+    pub fn export_archive<W: Write>(&mut self, scope: Scope, writer: W) -> Result<ArchiveManifest> {
+        self.read_transaction(|ctx: &mut Context<'_, SqliteConnection>| export(ctx, scope, writer))
+    }
+}
+
+fn export<W: Write>(
+    ctx: &mut Context<'_, SqliteConnection>,
+    scope: Scope,
+    writer: W,
+) -> Result<ArchiveManifest> {
+    let (_, local_node) = local_ops::get_pair()
+        .run(ctx)?
+        .ok_or(DatabaseError::LocalNodeNotYetInitialized)?;
+    let root_cotonoma_id = local_node
+        .root_cotonoma_id
+        .ok_or(DatabaseError::RootCotonomaNotFound)?;
+    let (root_cotonoma, root_coto) = cotonoma_ops::try_get_pair(&root_cotonoma_id).run(ctx)??;
+    let scope = match scope {
+        Scope::All => Scope::Node(local_node.uuid),
+        Scope::Node(node_id) if node_id != local_node.uuid => {
+            bail!("Only the data of the local node can be archived: {node_id}")
+        }
+        scope => scope,
+    };
+
+    let exported_at = current_datetime();
+    let mut builder = tar::Builder::new(writer);
+    let mut media = 0;
+
+    // Cotos in the scope, whose media content is written out on the way.
+    let scope_filter = resolve_scope_filter(ctx, scope.clone())?;
+    let mut cotos = Vec::new();
+    let mut page_index = 0;
+    loop {
+        let page = coto_ops::recently_inserted(
+            scope_filter
+                .as_ref()
+                .map(|e| e.as_ref().map_right(Vec::as_slice)),
+            false,
+            EXPORT_PAGE_SIZE,
+            page_index,
+        )
+        .run(ctx)?;
+        for coto in page.rows {
+            if coto.node_id != local_node.uuid || coto.uuid == root_coto.uuid {
+                continue;
+            }
+            let mut archived = ArchivedCoto { coto, media: None };
+            if let Some(content) = archived.coto.media_content.take() {
+                let path = format!("{MEDIA_DIR}{}", archived.coto.uuid);
+                append(&mut builder, &path, content.as_ref(), exported_at)?;
+                archived.media = Some(path);
+                media += 1;
+            }
+            // Will be restored by importing the reposts.
+            archived.coto.reposted_in_ids = None;
+            cotos.push(archived);
+        }
+        if (page_index + 1) * page.size >= page.total_rows {
+            break;
+        }
+        page_index += 1;
+    }
+
+    // The cotonoma of the scope itself is posted outside of the scope.
+    if let Scope::Cotonoma((cotonoma_id, _)) = &scope {
+        if *cotonoma_id != root_cotonoma.uuid {
+            let (_, mut coto) = cotonoma_ops::try_get_pair(cotonoma_id).run(ctx)??;
+            if coto.node_id == local_node.uuid {
+                coto.reposted_in_ids = None;
+                cotos.push(ArchivedCoto { coto, media: None });
+            }
+        }
+    }
+
+    // Originals first, then in chronological order.
+    cotos.sort_by_key(|archived| (archived.coto.is_repost(), archived.coto.created_at));
+
+    // Cotonomas
+    let cotonomas = cotonoma_ops::get_by_coto_ids(
+        cotos
+            .iter()
+            .map(|archived| &archived.coto)
+            .filter(|coto| coto.is_cotonoma && !coto.is_repost())
+            .map(|coto| coto.uuid)
+            .collect(),
+    )
+    .run(ctx)?;
+    let mut cotonomas: HashMap<Id<Coto>, Cotonoma> = cotonomas
+        .into_iter()
+        .map(|cotonoma| (cotonoma.coto_id, cotonoma))
+        .collect();
+    let mut coto_lines = Vec::new();
+    let mut cotonoma_lines = Vec::new();
+    for ArchivedCoto { coto, media } in cotos {
+        match cotonomas.remove(&coto.uuid) {
+            Some(cotonoma) => cotonoma_lines.push(ArchivedCotonoma {
+                cotonoma,
+                coto,
+                media,
+            }),
+            None => coto_lines.push(ArchivedCoto { coto, media }),
+        }
+    }
+    let cotonoma_lines = sort_cotonomas(cotonoma_lines);
+
+    // Itos between the archived cotos
+    let mut coto_ids: HashSet<Id<Coto>> = coto_lines
+        .iter()
+        .map(|archived| archived.coto.uuid)
+        .chain(cotonoma_lines.iter().map(|archived| archived.coto.uuid))
+        .collect();
+    coto_ids.insert(root_coto.uuid);
+    let source_ids: Vec<Id<Coto>> = coto_ids.iter().copied().collect();
+    let mut itos: Vec<Ito> = Vec::new();
+    for ids in source_ids.chunks(EXPORT_PAGE_SIZE as usize) {
+        itos.extend(ito_ops::outgoing(ids).run(ctx)?.into_iter().filter(|ito| {
+            ito.node_id == local_node.uuid && coto_ids.contains(&ito.target_coto_id)
+        }));
+    }
+    itos.sort_by_key(|ito| ito.created_at);
+
+    // Other nodes referenced as authors
+    let author_ids: HashSet<Id<Node>> = coto_lines
+        .iter()
+        .map(|archived| archived.coto.posted_by_id)
+        .chain(
+            cotonoma_lines
+                .iter()
+                .map(|archived| archived.coto.posted_by_id),
+        )
+        .chain(itos.iter().map(|ito| ito.created_by_id))
+        .filter(|id| *id != local_node.uuid)
+        .collect();
+    let mut nodes: Vec<Node> = node_ops::map_from_ids(&author_ids)
+        .run(ctx)?
+        .into_values()
+        .collect();
+    nodes.sort_by_key(|node| node.created_at);
+
+    append(&mut builder, NODES, &to_jsonl(&nodes)?, exported_at)?;
+    append(
+        &mut builder,
+        COTONOMAS,
+        &to_jsonl(&cotonoma_lines)?,
+        exported_at,
+    )?;
+    append(&mut builder, COTOS, &to_jsonl(&coto_lines)?, exported_at)?;
+    append(&mut builder, ITOS, &to_jsonl(&itos)?, exported_at)?;
+
+    let manifest = ArchiveManifest {
+        format: ARCHIVE_FORMAT.into(),
+        version: ARCHIVE_VERSION,
+        node: local_node,
+        root_cotonoma_id: root_cotonoma.uuid,
+        root_coto_id: root_coto.uuid,
+        scope,
+        nodes: nodes.len(),
+        cotonomas: cotonoma_lines.len(),
+        cotos: coto_lines.len(),
+        itos: itos.len(),
+        media,
+        exported_at,
+    };
+    append(
+        &mut builder,
+        MANIFEST,
+        &serde_json::to_vec_pretty(&manifest)?,
+        exported_at,
+    )?;
+    builder.into_inner()?.flush()?;
+    Ok(manifest)
+}
+
+/// Reads an archive written by [DatabaseSession::export_archive].
+///
+/// The entries are indexed when opening an archive, and each of them will be read
+/// on demand, so that media content doesn't have to be loaded all at once.
+pub struct ArchiveReader<R> {
+    reader: R,
+    manifest: ArchiveManifest,
+
+    /// Path -> (position, size) of each entry
+    entries: HashMap<String, (u64, u64)>,
+}
+
+impl<R: Read + Seek> ArchiveReader<R> {
+    pub fn new(reader: R) -> Result<Self> {
+        let mut archive = tar::Archive::new(reader);
+        let mut entries = HashMap::new();
+        for entry in archive.entries_with_seek()? {
+            let entry = entry?;
+            let path = entry.path()?.to_string_lossy().into_owned();
+            entries.insert(path, (entry.raw_file_position(), entry.size()));
+        }
+        let mut reader = archive.into_inner();
+        let manifest: ArchiveManifest =
+            serde_json::from_slice(&read_entry(&mut reader, &entries, MANIFEST)?)?;
+        ensure!(
+            manifest.format == ARCHIVE_FORMAT,
+            "Not a Cotoami archive: {}",
+            manifest.format
+        );
+        ensure!(
+            manifest.version <= ARCHIVE_VERSION,
+            "Unsupported archive version: {} (supported up to {ARCHIVE_VERSION})",
+            manifest.version
+        );
+        Ok(Self {
+            reader,
+            manifest,
+            entries,
+        })
+    }
+
+    pub fn manifest(&self) -> &ArchiveManifest { &self.manifest }
+
+    pub fn nodes(&mut self) -> Result<Vec<Node>> { self.read_jsonl(NODES) }
+
+    pub fn cotonomas(&mut self) -> Result<Vec<ArchivedCotonoma>> { self.read_jsonl(COTONOMAS) }
+
+    pub fn cotos(&mut self) -> Result<Vec<ArchivedCoto>> { self.read_jsonl(COTOS) }
+
+    pub fn itos(&mut self) -> Result<Vec<Ito>> { self.read_jsonl(ITOS) }
+
+    /// Reads the media content stored at the given path ([ArchivedCoto::media]).
+    pub fn media(&mut self, path: &str) -> Result<Vec<u8>> {
+        ensure!(path.starts_with(MEDIA_DIR), "Not a media path: {path}");
+        read_entry(&mut self.reader, &self.entries, path)
+    }
+
+    fn read_jsonl<T: serde::de::DeserializeOwned>(&mut self, path: &str) -> Result<Vec<T>> {
+        let bytes = read_entry(&mut self.reader, &self.entries, path)?;
+        let mut values = Vec::new();
+        for (i, line) in bytes.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            values.push(
+                serde_json::from_str(&line)
+                    .map_err(|e| anyhow!("Invalid line {} in {path}: {e}", i + 1))?,
+            );
+        }
+        Ok(values)
+    }
+}
+
+fn read_entry<R: Read + Seek>(
+    reader: &mut R,
+    entries: &HashMap<String, (u64, u64)>,
+    path: &str,
+) -> Result<Vec<u8>> {
+    let (position, size) = *entries
+        .get(path)
+        .ok_or_else(|| anyhow!("Missing archive entry: {path}"))?;
+    // Read up to the size in the header, which can't be trusted for preallocation.
+    let mut bytes = Vec::new();
+    reader.seek(SeekFrom::Start(position))?;
+    reader.by_ref().take(size).read_to_end(&mut bytes)?;
+    ensure!(
+        bytes.len() as u64 == size,
+        "Truncated archive entry: {path} ({} of {size} bytes)",
+        bytes.len()
+    );
+    Ok(bytes)
+}
+
+/// Sorts the cotonomas so that each parent comes before its children.
+fn sort_cotonomas(mut cotonomas: Vec<ArchivedCotonoma>) -> Vec<ArchivedCotonoma> {
+    let ids: HashSet<Id<Cotonoma>> = cotonomas.iter().map(|c| c.cotonoma.uuid).collect();
+    let mut sorted_ids = HashSet::new();
+    let mut sorted = Vec::with_capacity(cotonomas.len());
+    while !cotonomas.is_empty() {
+        let (ready, pending): (Vec<_>, Vec<_>) = cotonomas.into_iter().partition(|c| {
+            c.coto
+                .posted_in_id
+                .map(|parent| !ids.contains(&parent) || sorted_ids.contains(&parent))
+                .unwrap_or(true)
+        });
+        if ready.is_empty() {
+            // Shouldn't happen unless the cotonomas form a cycle.
+            sorted.extend(pending);
+            break;
+        }
+        sorted_ids.extend(ready.iter().map(|c| c.cotonoma.uuid));
+        sorted.extend(ready);
+        cotonomas = pending;
+    }
+    sorted
+}
+
+fn to_jsonl<T: serde::Serialize>(values: &[T]) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    for value in values {
+        serde_json::to_writer(&mut bytes, value)?;
+        bytes.push(b'\n');
+    }
+    Ok(bytes)
+}
+
+fn append<W: Write>(
+    builder: &mut tar::Builder<W>,
+    path: &str,
+    data: &[u8],
+    mtime: NaiveDateTime,
+) -> Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(mtime.and_utc().timestamp().max(0) as u64);
+    builder.append_data(&mut header, path, data)?;
+    Ok(())
+}
@@ -16,13 +16,17 @@ use url::Url;
 
 use crate::{
     db::{
-        error::*, globals::Globals, op::WriteConn, ops::node_role_ops::local_ops,
+        error::*,
+        globals::Globals,
+        op::WriteConn,
+        ops::{changelog_ops, node_role_ops::local_ops},
         transactions::DatabaseSession,
     },
     models::node::{Node, Principal},
 };
 
 pub mod error;
+pub mod fsck;
 pub mod globals;
 pub mod op;
 pub mod ops;
@@ -83,6 +87,7 @@ impl Database {
             globals: Globals::default(),
         };
         db.run_migrations()?;
+        db.reencode_changes()?;
         db.globals.init(&mut db.new_ro_conn()?)?;
 
         info!("Database launched:");
@@ -121,12 +126,48 @@ impl Database {
         ))
     }
 
+    /// Writes a backup of the database into a new file at `path`.
+    ///
+    /// Unlike copying the database file, it is safe to take a backup while the database
+    /// is in use, since it is taken by SQLite (`VACUUM INTO`) in a read transaction.
+    /// The backup is a standalone database file that can be restored by placing it as
+    /// `cotoami.db` in a database directory.
+    pub fn backup_to<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        ensure!(
+            !path.exists(),
+            DatabaseError::InvalidFilePath {
+                path: path.to_path_buf(),
+                reason: "The file already exists".into(),
+            }
+        );
+        let path_str = path.to_str().ok_or(DatabaseError::InvalidFilePath {
+            path: path.to_path_buf(),
+            reason: "Invalid path".into(),
+        })?;
+        debug!("Backing up the database to {path:?}...");
+        sqlite::vacuum_into(&mut self.new_ro_conn()?, path_str)
+    }
+
     fn run_migrations(&self) -> Result<()> {
         let mut conn = SqliteConnection::establish(&self.file_uri)?;
         conn.run_pending_migrations(Self::MIGRATIONS).unwrap();
         Ok(())
     }
 
+    /// Re-encodes the changes stored in an older encoding, which have been queued
+    /// by a migration.
+    fn reencode_changes(&self) -> Result<()> {
+        let reencoded = op::run_write(
+            &mut self.rw_conn.lock(),
+            changelog_ops::reencode_queued_changes(),
+        )?;
+        if reencoded > 0 {
+            info!("Re-encoded {reencoded} changes in the changelog.");
+        }
+        Ok(())
+    }
+
     fn new_ro_conn(&self) -> Result<SqliteConnection> { new_ro_conn(&self.file_uri) }
 
     pub fn globals(&self) -> &Globals { &self.globals }
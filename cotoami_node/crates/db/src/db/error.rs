@@ -47,6 +47,12 @@ pub enum DatabaseError {
     #[error("The local node has already been forked from: {parent_node_id}")]
     AlreadyForkedFromParent { parent_node_id: Id<Node> },
 
+    #[error("The local node has not been forked from: {parent_node_id}")]
+    NotForkedFromParent { parent_node_id: Id<Node> },
+
+    #[error("The subscription to {parent_node_id} can't be widened after receiving changes.")]
+    SubscriptionWidened { parent_node_id: Id<Node> },
+
     #[error("Node role conflict with: {with}")]
     NodeRoleConflict { with: String },
 
@@ -55,6 +61,9 @@ pub enum DatabaseError {
 
     #[error("Reposts cannot be connected.")]
     RepostsCannotBeConnected,
+
+    #[error("Invalid graph query at {position}: {reason}")]
+    InvalidGraphQuery { position: usize, reason: String },
 }
 
 impl DatabaseError {
@@ -66,7 +75,9 @@ impl DatabaseError {
     }
 }
 
-#[derive(Debug, derive_more::Display)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, derive_more::Display, serde::Serialize, serde::Deserialize,
+)]
 pub enum EntityKind {
     #[display("node")]
     Node,
@@ -94,4 +105,7 @@ pub enum EntityKind {
     Cotonoma,
     #[display("ito")]
     Ito,
+
+    #[display("changelog_entry")]
+    ChangelogEntry,
 }
@@ -0,0 +1,345 @@
+//! Integrity check of a database (`fsck`)
+//!
+//! Since a database is a state machine fed by the `changelog`, the contents of the
+//! entity tables should be reproducible by replaying the changelog from scratch.
+//! [Database::fsck] replays the changelog into a scratch database and compares the
+//! resulting cotos, cotonomas and itos with the live tables to find divergence that
+//! could be caused by a change partially applied, a bug or a manual edit. It also runs
+//! SQLite's `integrity_check` and `foreign_key_check` against the live database.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::Path,
+};
+
+use anyhow::{anyhow, Result};
+use diesel::{prelude::*, sql_types::Text, sqlite::SqliteConnection};
+use diesel_migrations::MigrationHarness;
+use serde::Serialize;
+use tempfile::NamedTempFile;
+use tracing::{debug, info};
+
+use super::{
+    error::EntityKind,
+    new_rw_conn,
+    op::{composite_op, run_read, run_write, Operation},
+    ops::{changelog_ops, coto_ops, cotonoma_ops, ito_ops},
+    to_file_uri, Database,
+};
+use crate::models::{
+    changelog::{Change, ChangelogEntry},
+    coto::Coto,
+    Id,
+};
+
+/// The number of changelog entries to be loaded at once during replay
+const REPLAY_CHUNK_SIZE: i64 = 100;
+
+/// Result of [Database::fsck].
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct FsckReport {
+    /// Problems reported by `PRAGMA integrity_check`
+    pub integrity_errors: Vec<String>,
+
+    /// Rows violating foreign key constraints reported by `PRAGMA foreign_key_check`
+    pub foreign_key_violations: Vec<ForeignKeyViolation>,
+
+    /// Number of changelog entries replayed
+    pub changes_replayed: usize,
+
+    /// Changelog entries whose replay result differs from the recorded one:
+    /// (serial number, error)
+    ///
+    /// A change recorded with an import error is skipped since it has not been applied
+    /// to the database. An imported change failing in replay is retried after the
+    /// following changes, since it could have been applied later by retrying the import.
+    pub replay_errors: Vec<(i64, String)>,
+
+    /// Entities whose state differs between the live tables and the replayed changelog
+    pub mismatches: Vec<Mismatch>,
+}
+
+impl FsckReport {
+    /// Returns `true` if no problems have been found.
+    pub fn is_ok(&self) -> bool {
+        self.integrity_errors.is_empty()
+            && self.foreign_key_violations.is_empty()
+            && self.replay_errors.is_empty()
+            && self.mismatches.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, QueryableByName)]
+pub struct ForeignKeyViolation {
+    /// Table containing the violating row
+    #[diesel(sql_type = Text)]
+    pub table: String,
+
+    /// Rowid of the violating row (`None` for a `WITHOUT ROWID` table)
+    #[diesel(sql_type = diesel::sql_types::Nullable<diesel::sql_types::BigInt>)]
+    pub rowid: Option<i64>,
+
+    /// Table referred to by the violated constraint
+    #[diesel(sql_type = Text)]
+    pub parent: String,
+}
+
+/// An entity whose state differs between the live tables and the replayed changelog.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Mismatch {
+    pub kind: EntityKind,
+    pub id: String,
+    pub problem: MismatchProblem,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum MismatchProblem {
+    /// The entity exists in the replayed changelog, but not in the live table.
+    MissingInDatabase,
+
+    /// The entity exists in the live table, but not in the replayed changelog.
+    MissingInChangelog,
+
+    /// The entity has different values in the listed fields.
+    Fields(Vec<String>),
+}
+
+#[derive(QueryableByName)]
+struct IntegrityCheck {
+    #[diesel(sql_type = Text)]
+    integrity_check: String,
+}
+
+impl Database {
+    /// Checks the integrity of this database.
+    ///
+    /// The scratch database is created as a temporary file in the root directory,
+    /// which requires as much free space as the database itself.
+    pub fn fsck(&self) -> Result<FsckReport> {
+        // Run in a single read transaction to see a consistent snapshot
+        // while the database is in use.
+        self.new_ro_conn()?
+            .transaction(|ro_conn| self.fsck_in_snapshot(ro_conn))
+    }
+
+    fn fsck_in_snapshot(&self, ro_conn: &mut SqliteConnection) -> Result<FsckReport> {
+        // SQLite checks
+        let mut report = FsckReport {
+            integrity_errors: diesel::sql_query("PRAGMA integrity_check")
+                .load::<IntegrityCheck>(ro_conn)?
+                .into_iter()
+                .map(|row| row.integrity_check)
+                .filter(|message| message != "ok")
+                .collect(),
+            foreign_key_violations: diesel::sql_query("PRAGMA foreign_key_check").load(ro_conn)?,
+            ..Default::default()
+        };
+
+        // Replay the changelog into a scratch database
+        let scratch = ScratchFile::new(&self.root_dir)?;
+        let mut scratch_conn = new_rw_conn(&to_file_uri(scratch.path())?)?;
+        scratch_conn
+            .run_pending_migrations(Self::MIGRATIONS)
+            .map_err(|e| anyhow!(e))?;
+
+        // New images have been resized to fit within the image size of the local node
+        // both in the local changes and the imported ones.
+        let image_max_size = self
+            .globals
+            .local_node()
+            .and_then(|local_node| local_node.image_max_size());
+        let last = run_read(ro_conn, changelog_ops::last_serial_number())?.unwrap_or(0);
+        let mut replay = |change: &Change| {
+            run_write(
+                &mut scratch_conn,
+                composite_op(|ctx| {
+                    // Apply the change in a savepoint as in importing changes.
+                    Ok(ctx
+                        .savepoint(|ctx| {
+                            changelog_ops::apply_change(change, image_max_size).run(ctx)
+                        })
+                        .err())
+                }),
+            )
+        };
+        // Imported changes that have failed in replay, which could have been applied
+        // later by retrying ([changelog_ops::retry_import]) in the live database.
+        let mut failed_imports: Vec<(ChangelogEntry, anyhow::Error)> = Vec::new();
+        let mut from = 1;
+        while from <= last {
+            let (logs, _) = run_read(ro_conn, changelog_ops::chunk(from, REPLAY_CHUNK_SIZE, None))?;
+            for log in logs.iter() {
+                from = log.serial_number + 1;
+                report.changes_replayed += 1;
+
+                // A change with an import error has not been applied to the live database.
+                if log.import_error.is_some() {
+                    continue;
+                }
+                match replay(&log.change)? {
+                    None => {
+                        // The change could be what the failed imports were waiting for.
+                        let mut retried = Vec::new();
+                        for (failed, _) in failed_imports.drain(..) {
+                            if let Some(e) = replay(&failed.change)? {
+                                retried.push((failed, e));
+                            }
+                        }
+                        failed_imports = retried;
+                    }
+                    Some(e) if log.imported_from.is_some() => {
+                        failed_imports.push((log.clone(), e));
+                    }
+                    Some(e) => {
+                        report
+                            .replay_errors
+                            .push((log.serial_number, format!("{e:#}")));
+                    }
+                }
+            }
+            if logs.is_empty() {
+                break;
+            }
+        }
+        report.replay_errors.extend(
+            failed_imports
+                .into_iter()
+                .map(|(log, e)| (log.serial_number, format!("{e:#}"))),
+        );
+        report
+            .replay_errors
+            .sort_by_key(|(serial_number, _)| *serial_number);
+        debug!("{} changes have been replayed.", report.changes_replayed);
+
+        // Compare the live tables with the replayed ones
+        report.mismatches.extend(compare_entities(
+            EntityKind::Cotonoma,
+            to_map(run_read(ro_conn, cotonoma_ops::all())?, |c| c.uuid),
+            to_map(run_read(&mut scratch_conn, cotonoma_ops::all())?, |c| {
+                c.uuid
+            }),
+        )?);
+        // The media omitted from the imported changes ([Change::MediaOmitted]) is stored
+        // (and resized) without a change when it has been fetched, so it is excluded
+        // from the comparison.
+        let lazy_media: HashSet<Id<Coto>> =
+            run_read(&mut scratch_conn, changelog_ops::media::all_missing())?
+                .into_iter()
+                .map(|missing| missing.coto_id)
+                .collect();
+        let without_lazy_media = |cotos: Vec<Coto>| {
+            to_map(
+                cotos.into_iter().map(|mut coto| {
+                    if lazy_media.contains(&coto.uuid) {
+                        coto.media_content = None;
+                        coto.media_type = None;
+                    }
+                    coto
+                }),
+                |c| c.uuid,
+            )
+        };
+        report.mismatches.extend(compare_entities(
+            EntityKind::Coto,
+            without_lazy_media(run_read(ro_conn, coto_ops::all())?),
+            without_lazy_media(run_read(&mut scratch_conn, coto_ops::all())?),
+        )?);
+        report.mismatches.extend(compare_entities(
+            EntityKind::Ito,
+            to_map(run_read(ro_conn, ito_ops::all())?, |i| i.uuid),
+            to_map(run_read(&mut scratch_conn, ito_ops::all())?, |i| i.uuid),
+        )?);
+
+        info!(
+            "fsck: {} integrity errors, {} foreign key violations, \
+            {} replay errors, {} mismatches",
+            report.integrity_errors.len(),
+            report.foreign_key_violations.len(),
+            report.replay_errors.len(),
+            report.mismatches.len()
+        );
+        Ok(report)
+    }
+}
+
+fn to_map<T, K: ToString>(
+    entities: impl IntoIterator<Item = T>,
+    id: impl Fn(&T) -> K,
+) -> HashMap<String, T> {
+    entities
+        .into_iter()
+        .map(|entity| (id(&entity).to_string(), entity))
+        .collect()
+}
+
+/// Compares entities by their serialized fields, which don't include local-only
+/// fields such as `rowid`.
+fn compare_entities<T: Serialize>(
+    kind: EntityKind,
+    mut live: HashMap<String, T>,
+    replayed: HashMap<String, T>,
+) -> Result<Vec<Mismatch>> {
+    let mut mismatches = Vec::new();
+    for (id, replayed) in replayed {
+        let Some(live) = live.remove(&id) else {
+            mismatches.push(Mismatch {
+                kind,
+                id,
+                problem: MismatchProblem::MissingInDatabase,
+            });
+            continue;
+        };
+        let (serde_json::Value::Object(live), serde_json::Value::Object(replayed)) =
+            (serde_json::to_value(live)?, serde_json::to_value(replayed)?)
+        else {
+            continue;
+        };
+        let mut fields: Vec<String> = live
+            .iter()
+            .filter(|(field, value)| replayed.get(*field) != Some(*value))
+            .map(|(field, _)| field.clone())
+            .collect();
+        if !fields.is_empty() {
+            fields.sort();
+            mismatches.push(Mismatch {
+                kind,
+                id,
+                problem: MismatchProblem::Fields(fields),
+            });
+        }
+    }
+    mismatches.extend(live.into_keys().map(|id| Mismatch {
+        kind,
+        id,
+        problem: MismatchProblem::MissingInChangelog,
+    }));
+    mismatches.sort_by(|a, b| a.id.cmp(&b.id));
+    Ok(mismatches)
+}
+
+/// A scratch database created as a temporary file in a directory, which will be
+/// deleted (with its WAL files) when dropped.
+struct ScratchFile(NamedTempFile);
+
+impl ScratchFile {
+    fn new(dir: &Path) -> Result<Self> {
+        let file = tempfile::Builder::new()
+            .prefix("fsck-")
+            .suffix(".db")
+            .tempfile_in(dir)?;
+        Ok(Self(file))
+    }
+
+    fn path(&self) -> &Path { self.0.path() }
+}
+
+impl Drop for ScratchFile {
+    fn drop(&mut self) {
+        for suffix in ["-wal", "-shm"] {
+            let mut path = self.path().as_os_str().to_owned();
+            path.push(suffix);
+            fs::remove_file(path).ok();
+        }
+    }
+}
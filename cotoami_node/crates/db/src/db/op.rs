@@ -63,6 +63,27 @@ impl<'a, Conn> Context<'a, Conn> {
     pub fn conn(&mut self) -> &mut Conn { self.conn }
 }
 
+impl Context<'_, WriteConn> {
+    /// Runs `f` in a savepoint, which will be rolled back if `f` returns an error
+    /// without aborting the enclosing transaction.
+    pub fn savepoint<T, F>(&mut self, f: F) -> Result<T>
+    where
+        F: FnOnce(&mut Self) -> Result<T>,
+    {
+        AnsiTransactionManager::begin_transaction(self.conn.deref_mut())?;
+        match f(self) {
+            Ok(value) => {
+                AnsiTransactionManager::commit_transaction(self.conn.deref_mut())?;
+                Ok(value)
+            }
+            Err(e) => {
+                AnsiTransactionManager::rollback_transaction(self.conn.deref_mut())?;
+                Err(e)
+            }
+        }
+    }
+}
+
 /////////////////////////////////////////////////////////////////////////////
 // Composite Operation
 /////////////////////////////////////////////////////////////////////////////
@@ -14,10 +14,16 @@ pub(crate) mod changelog_ops;
 pub(crate) mod coto_ops;
 pub(crate) mod cotonoma_ops;
 pub(crate) mod graph_ops;
+pub(crate) mod graph_query_ops;
 pub(crate) mod ito_ops;
 pub(crate) mod node_ops;
 pub(crate) mod node_role_ops;
 
+/// Max number of IDs to be passed to a single `IN` clause, which keeps a query within
+/// the limit of SQLite host parameters. More IDs have to be split into chunks
+/// (or filtered in memory).
+pub(crate) const MAX_IDS_IN_CLAUSE: usize = 1000;
+
 /////////////////////////////////////////////////////////////////////////////
 // Pagination
 /////////////////////////////////////////////////////////////////////////////
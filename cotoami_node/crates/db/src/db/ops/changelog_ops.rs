@@ -1,22 +1,31 @@
 //! Changelog related operations
 
-use std::ops::DerefMut;
+use std::{
+    collections::{HashMap, HashSet},
+    ops::DerefMut,
+};
 
 use anyhow::ensure;
 use diesel::{dsl::max, prelude::*};
 use tracing::debug;
+use uuid::Uuid;
 
 use super::{coto_ops, cotonoma_ops, ito_ops, node_ops, node_role_ops::parent_ops};
 use crate::{
-    db::{error::*, op::*},
+    db::{error::*, op::*, ops::Page},
     models::{
-        changelog::{Change, ChangelogEntry, NewChangelogEntry},
+        changelog::{Change, ChangeOrigin, ChangelogEntry, ChangelogSnapshot, NewChangelogEntry},
         node::{local::LocalNode, parent::ParentNode, Node},
-        Id,
+        FieldDiff, Id,
     },
-    schema::changelog,
+    schema::{changelog, changelog_reencoding},
 };
 
+pub mod compaction;
+pub mod fork;
+pub mod media;
+pub mod subscription;
+
 pub(crate) fn last_serial_number<Conn: ReadConn>() -> impl Operation<Conn, Option<i64>> {
     read_op(move |conn| {
         changelog::table
@@ -38,13 +47,74 @@ fn last_origin_serial_number<Conn: ReadConn>(
     })
 }
 
+pub(crate) fn get<Conn: ReadConn>(
+    serial_number: i64,
+) -> impl Operation<Conn, Option<ChangelogEntry>> {
+    read_op(move |conn| {
+        changelog::table
+            .find(serial_number)
+            .first(conn)
+            .optional()
+            .map_err(anyhow::Error::from)
+    })
+}
+
+pub(crate) fn try_get<Conn: ReadConn>(
+    serial_number: i64,
+) -> impl Operation<Conn, Result<ChangelogEntry, DatabaseError>> {
+    get(serial_number).map(move |entry| {
+        entry.ok_or(DatabaseError::not_found(
+            EntityKind::ChangelogEntry,
+            serial_number.to_string(),
+        ))
+    })
+}
+
+/// Returns the changes imported from the parent that failed to be applied to the
+/// local node in order of the serial number.
+pub(crate) fn failed_imports<'a, Conn: ReadConn>(
+    parent_node_id: &'a Id<Node>,
+    page_size: i64,
+    page_index: i64,
+) -> impl Operation<Conn, Page<ChangelogEntry>> + 'a {
+    read_op(move |conn| {
+        super::paginate(
+            conn,
+            page_size,
+            page_index,
+            || {
+                changelog::table
+                    .filter(changelog::imported_from.eq(parent_node_id))
+                    .filter(changelog::import_error.is_not_null())
+            },
+            |query| query.order(changelog::serial_number.asc()),
+        )
+    })
+}
+
+define_sql_function! {
+    /// SQLite's `length(X)`, which returns the number of bytes of a BLOB.
+    fn length(x: diesel::sql_types::Binary) -> diesel::sql_types::BigInt;
+}
+
+/// Returns up to `limit` changes from the serial number `from`, along with the last
+/// serial number in the changelog.
+///
+/// If `byte_budget` is given, the chunk will be cut so that the total size of the
+/// encoded changes doesn't exceed it. The first change is always included even if it
+/// exceeds the budget by itself, so that the replication can always make progress.
 pub(crate) fn chunk<Conn: ReadConn>(
     from: i64,
     limit: i64,
+    byte_budget: Option<usize>,
 ) -> impl Operation<Conn, (Vec<ChangelogEntry>, i64)> {
     composite_op::<Conn, _, _>(move |ctx| {
         let last = last_serial_number().run(ctx)?.unwrap_or(0);
         if from >= 1 && from <= last {
+            let limit = match byte_budget {
+                Some(budget) => limit_by_bytes(from, limit, budget).run(ctx)?,
+                None => limit,
+            };
             Ok((
                 changelog::table
                     .filter(changelog::serial_number.ge(from))
@@ -63,6 +133,102 @@ pub(crate) fn chunk<Conn: ReadConn>(
     })
 }
 
+/// Counts the changes from `from` (up to `limit`) that fit in the `byte_budget`,
+/// which is at least one.
+///
+/// The budget is compared with the stored sizes of the changes, which are an
+/// approximation of the size of the entries to be sent.
+fn limit_by_bytes<Conn: ReadConn>(
+    from: i64,
+    limit: i64,
+    byte_budget: usize,
+) -> impl Operation<Conn, i64> {
+    read_op(move |conn| {
+        let sizes: Vec<i64> = changelog::table
+            .select(length(changelog::change))
+            .filter(changelog::serial_number.ge(from))
+            .order(changelog::serial_number.asc())
+            .limit(limit)
+            .load(conn)?;
+        let mut total = 0;
+        let mut count = 0;
+        for size in sizes {
+            total += size as usize;
+            if count > 0 && total > byte_budget {
+                break;
+            }
+            count += 1;
+        }
+        Ok(count)
+    })
+}
+
+/// Number of changelog entries to be loaded at once to collect the origins of a snapshot
+const SNAPSHOT_CHUNK_SIZE: i64 = 100;
+
+pub(crate) fn snapshot<Conn: ReadConn>() -> impl Operation<Conn, ChangelogSnapshot> {
+    composite_op::<Conn, _, _>(move |ctx| {
+        let last_serial_number = last_serial_number().run(ctx)?.unwrap_or(0);
+
+        // The media of cotonomas can't be omitted ([Change::omit_media]).
+        let mut cotos = coto_ops::all().run(ctx)?;
+        let mut omitted_media = HashMap::new();
+        for coto in cotos.iter_mut().filter(|coto| !coto.is_cotonoma) {
+            if let Some(media) = coto.omit_media() {
+                omitted_media.insert(coto.uuid, media);
+            }
+        }
+        let itos = ito_ops::all().run(ctx)?;
+
+        // Collect the origins of the changes covered by the snapshot.
+        let entities: HashSet<Uuid> = cotos
+            .iter()
+            .map(|coto| coto.uuid.as_uuid())
+            .chain(itos.iter().map(|ito| ito.uuid.as_uuid()))
+            .collect();
+        let mut origins = Vec::new();
+        let mut creations = HashMap::new();
+        let mut from = 1;
+        while from <= last_serial_number {
+            let (logs, _) = chunk(from, SNAPSHOT_CHUNK_SIZE, None).run(ctx)?;
+            let Some(last_log) = logs.last() else { break };
+            from = last_log.serial_number + 1;
+            for log in logs.iter() {
+                origins.push(log.origin());
+                if let Some(uuid) = created_entity(&log.change).filter(|id| entities.contains(id)) {
+                    creations.insert(uuid, log.origin());
+                }
+            }
+        }
+
+        Ok(ChangelogSnapshot {
+            last_serial_number,
+            nodes: node_ops::all().run(ctx)?,
+            cotonomas: cotonoma_ops::all().run(ctx)?,
+            cotos,
+            itos,
+            omitted_media,
+            origins,
+            creations,
+        })
+    })
+}
+
+/// Returns the UUID of the coto or ito created by the change.
+fn created_entity(change: &Change) -> Option<Uuid> {
+    match change {
+        Change::CreateNode {
+            root: Some((_, coto)),
+            ..
+        }
+        | Change::CreateCoto(coto)
+        | Change::CreateCotonoma(_, coto) => Some(coto.uuid.as_uuid()),
+        Change::CreateIto(ito) => Some(ito.uuid.as_uuid()),
+        Change::MediaOmitted { change, .. } => created_entity(change),
+        _ => None,
+    }
+}
+
 pub(crate) fn log_change<'a>(
     change: &'a Change,
     local_node_id: &'a Id<Node>,
@@ -87,16 +253,18 @@ pub(crate) fn insert<'a>(
     })
 }
 
-pub(crate) fn contains_change<Conn: ReadConn>(
-    log: &ChangelogEntry,
-) -> impl Operation<Conn, bool> + '_ {
+pub(crate) fn contains_change<Conn: ReadConn>(log: &ChangelogEntry) -> impl Operation<Conn, bool> {
+    contains_origin(log.origin())
+}
+
+fn contains_origin<Conn: ReadConn>(origin: ChangeOrigin) -> impl Operation<Conn, bool> {
     read_op(move |conn| {
         changelog::table
             .count()
             .filter(
                 changelog::origin_node_id
-                    .eq(log.origin_node_id)
-                    .and(changelog::origin_serial_number.eq(log.origin_serial_number)),
+                    .eq(origin.node_id)
+                    .and(changelog::origin_serial_number.eq(origin.serial_number)),
             )
             .get_result(conn)
             .map(|c: i64| c > 0)
@@ -104,6 +272,84 @@ pub(crate) fn contains_change<Conn: ReadConn>(
     })
 }
 
+/// Applies a change that failed to be imported again, and returns the entry updated
+/// with the result (`import_error` will be cleared if it has been applied successfully).
+///
+/// The change will be applied in a savepoint so that a failed attempt leaves nothing
+/// but the new error. A change from a parent that the local node has been forked from
+/// can't be retried.
+pub(crate) fn retry_import(
+    serial_number: i64,
+    image_max_size: Option<u32>,
+) -> impl Operation<WriteConn, ChangelogEntry> {
+    composite_op::<WriteConn, _, _>(move |ctx| {
+        let log = try_get(serial_number).run(ctx)??;
+        ensure!(
+            log.import_error.is_some(),
+            "The change {serial_number} has been imported without errors."
+        );
+        // Changes from a parent that the local node has been forked from can't be applied.
+        if let Some(parent_node_id) = log.imported_from {
+            ensure!(
+                !parent_ops::get(&parent_node_id)
+                    .run(ctx)?
+                    .is_some_and(|parent| parent.forked),
+                DatabaseError::AlreadyForkedFromParent { parent_node_id }
+            );
+        }
+        let import_error = ctx
+            .savepoint(|ctx| {
+                apply_change(&log.change, image_max_size).run(ctx)?;
+                if let Some(parent_node_id) = &log.imported_from {
+                    media::set_parent_of_omitted(&log.change, parent_node_id).run(ctx)?;
+                }
+                Ok(())
+            })
+            .err()
+            .map(|e| format!("{e:#?}"));
+        diesel::update(changelog::table.find(serial_number))
+            .set(changelog::import_error.eq(import_error))
+            .execute(ctx.conn().deref_mut())?;
+        try_get(serial_number)
+            .run(ctx)?
+            .map_err(anyhow::Error::from)
+    })
+}
+
+/// Re-encodes the changes queued in `changelog_reencoding` (by the migration
+/// `008_change_encoding`) into the current encoding, and returns the number of them.
+pub(crate) fn reencode_queued_changes() -> impl Operation<WriteConn, usize> {
+    const CHUNK_SIZE: i64 = 100;
+    composite_op::<WriteConn, _, _>(move |ctx| {
+        let mut reencoded = 0;
+        loop {
+            let serial_numbers: Vec<i64> = changelog_reencoding::table
+                .select(changelog_reencoding::serial_number)
+                .order(changelog_reencoding::serial_number.asc())
+                .limit(CHUNK_SIZE)
+                .load(ctx.conn().deref_mut())?;
+            if serial_numbers.is_empty() {
+                return Ok(reencoded);
+            }
+            let entries: Vec<ChangelogEntry> = changelog::table
+                .filter(changelog::serial_number.eq_any(&serial_numbers))
+                .load(ctx.conn().deref_mut())?;
+            for entry in entries.iter() {
+                // `Change` is always written in the current encoding.
+                diesel::update(changelog::table.find(entry.serial_number))
+                    .set(changelog::change.eq(&entry.change))
+                    .execute(ctx.conn().deref_mut())?;
+            }
+            reencoded += entries.len();
+            diesel::delete(
+                changelog_reencoding::table
+                    .filter(changelog_reencoding::serial_number.eq_any(&serial_numbers)),
+            )
+            .execute(ctx.conn().deref_mut())?;
+        }
+    })
+}
+
 /// Imports a change sent from the `parent_node`.
 ///
 /// ## Ensure to apply changes in order of the serial number for each `parent_node`
@@ -118,6 +364,8 @@ pub(crate) fn contains_change<Conn: ReadConn>(
 /// The same changes (with the same serial number in the same origin) could be sent
 /// from multiple parent nodes. If the database already contains the same change,
 /// the change will be ignored yet the `changes_received` will be incremented.
+/// A placeholder ([ChangelogEntry::into_placeholder]) will be ignored in the same way
+/// so that the original change can be imported from another parent.
 pub(crate) fn import_change<'a>(
     log: &'a ChangelogEntry,
     parent_node: &'a mut ParentNode,
@@ -144,7 +392,11 @@ pub(crate) fn import_change<'a>(
         );
 
         // Import the change only if the same change has not yet been imported before.
-        let imported_log = if contains_change(log).run(ctx)? {
+        // A placeholder won't be recorded since the original change could be received
+        // from another parent later.
+        let imported_log = if log.is_placeholder() {
+            None
+        } else if contains_change(log).run(ctx)? {
             debug!(
                 "Skipping change {} from parent:{} (origin: {}, number: {})",
                 log.serial_number,
@@ -154,10 +406,14 @@ pub(crate) fn import_change<'a>(
             );
             None
         } else {
-            let apply_result = apply_change(&log.change, local_node).run(ctx);
+            // Apply the change in a savepoint not to leave a partially applied change.
+            let apply_result = ctx.savepoint(|ctx| {
+                apply_change(&log.change, local_node.image_max_size()).run(ctx)?;
+                media::set_parent_of_omitted(&log.change, &parent_node.node_id).run(ctx)
+            });
 
             // Record the applied change log.
-            let mut log_to_import = log.to_import();
+            let mut log_to_import = log.to_import(&parent_node.node_id);
             if let Err(e) = apply_result {
                 // https://docs.rs/anyhow/latest/anyhow/struct.Error.html#display-representations
                 log_to_import.set_import_error(format!("{e:#?}"));
@@ -180,11 +436,138 @@ pub(crate) fn import_change<'a>(
     })
 }
 
-fn apply_change<'a>(
-    change: &'a Change,
+/// Installs a snapshot sent from the `parent_node` that has not sent any changes yet.
+///
+/// The entities in the snapshot will be applied and logged so that the local changelog
+/// keeps reproducing the database, which also enables the local node to bootstrap its
+/// own children. The cotos and itos are logged as the changes that have created them
+/// in their origins ([ChangelogSnapshot::creations]), and the other changes covered by
+/// the snapshot as [Change::None], so that the same changes received from other parents
+/// will be skipped by [import_change]. The omitted media will be recorded as missing.
+///
+/// Entities that already exist in the local database or whose creations have already
+/// been imported will be skipped. After the installation, [ParentNode::changes_received]
+/// will be set to [ChangelogSnapshot::last_serial_number].
+pub(crate) fn install_snapshot<'a>(
+    snapshot: &'a ChangelogSnapshot,
+    parent_node: &'a mut ParentNode,
     local_node: &'a LocalNode,
-) -> impl Operation<WriteConn, ()> + 'a {
-    let image_max_size = local_node.image_max_size();
+) -> impl Operation<WriteConn, Vec<ChangelogEntry>> + 'a {
+    composite_op::<WriteConn, _, _>(move |ctx| {
+        ensure!(
+            !parent_node.forked,
+            DatabaseError::AlreadyForkedFromParent {
+                parent_node_id: parent_node.node_id
+            }
+        );
+        ensure!(
+            parent_node.changes_received == 0,
+            "A snapshot can't be installed after receiving changes from: {}",
+            parent_node.node_id
+        );
+
+        let mut logs = Vec::new();
+        for node in snapshot.nodes.iter() {
+            if node_ops::upsert(node).run(ctx)?.is_some() {
+                let change = Change::UpsertNode(node.clone());
+                logs.push(log_change(&change, &local_node.node_id).run(ctx)?);
+            }
+        }
+        let mut logged_origins = HashSet::new();
+        for change in snapshot_changes(snapshot) {
+            let origin = created_entity(&change).and_then(|id| snapshot.creations.get(&id));
+            let exists = match &change {
+                Change::CreateCoto(coto) | Change::CreateCotonoma(_, coto) => {
+                    coto_ops::get(&coto.uuid).run(ctx)?.is_some()
+                }
+                Change::MediaOmitted { change, .. } => match change.as_ref() {
+                    Change::CreateCoto(coto) => coto_ops::get(&coto.uuid).run(ctx)?.is_some(),
+                    _ => false,
+                },
+                Change::CreateIto(ito) => ito_ops::get(&ito.uuid).run(ctx)?.is_some(),
+                _ => false,
+            };
+            if exists {
+                continue;
+            }
+            if let Some(origin) = origin {
+                if contains_origin(*origin).run(ctx)? {
+                    continue; // deleted after imported from another parent
+                }
+            }
+
+            // Resize images in the same way as importing changes.
+            apply_change(&change, local_node.image_max_size()).run(ctx)?;
+            media::set_parent_of_omitted(&change, &parent_node.node_id).run(ctx)?;
+            logs.push(match origin {
+                Some(origin) => {
+                    logged_origins.insert(*origin);
+                    insert(&change.new_imported_entry(origin, &parent_node.node_id)).run(ctx)?
+                }
+                None => log_change(&change, &local_node.node_id).run(ctx)?,
+            });
+        }
+        for origin in snapshot.origins.iter() {
+            if !logged_origins.contains(origin) && !contains_origin(*origin).run(ctx)? {
+                let entry = Change::None.new_imported_entry(origin, &parent_node.node_id);
+                logs.push(insert(&entry).run(ctx)?);
+            }
+        }
+
+        if snapshot.last_serial_number > 0 {
+            *parent_node = parent_ops::skip_changes_by_snapshot(
+                &parent_node.node_id,
+                snapshot.last_serial_number,
+            )
+            .run(ctx)?;
+        }
+        Ok(logs)
+    })
+}
+
+/// Converts the cotos, cotonomas and itos in a snapshot into creation changes in
+/// an order where every change can be applied after the preceding ones: originals
+/// before reposts, cotonomas before the cotos posted in them, and cotos before
+/// the itos connecting them.
+fn snapshot_changes(snapshot: &ChangelogSnapshot) -> Vec<Change> {
+    let mut cotonomas: HashMap<_, _> = snapshot
+        .cotonomas
+        .iter()
+        .map(|cotonoma| (cotonoma.coto_id, cotonoma))
+        .collect();
+    let mut changes: Vec<(_, u8, Change)> = Vec::new();
+    for coto in snapshot.cotos.iter() {
+        // `reposted_in_ids` will be restored by inserting the reposts.
+        let mut coto = coto.clone();
+        coto.reposted_in_ids = None;
+        let (created_at, rank) = (coto.created_at, if coto.is_repost() { 1 } else { 0 });
+        // A promoted coto is created before the cotonoma, so the cotonoma has to
+        // be created at the timestamp of the coto.
+        let change = match cotonomas.remove(&coto.uuid) {
+            Some(cotonoma) => Change::CreateCotonoma(cotonoma.clone(), coto),
+            None => match snapshot.omitted_media.get(&coto.uuid) {
+                Some(media) => Change::MediaOmitted {
+                    change: Box::new(Change::CreateCoto(coto)),
+                    media: media.clone(),
+                },
+                None => Change::CreateCoto(coto),
+            },
+        };
+        changes.push((created_at, rank, change));
+    }
+    for ito in snapshot.itos.iter() {
+        changes.push((ito.created_at, 2, Change::CreateIto(ito.clone())));
+    }
+    changes.sort_by_key(|(created_at, rank, _)| (*created_at, *rank));
+    changes.into_iter().map(|(_, _, change)| change).collect()
+}
+
+/// Applies a change to the database, in which new images will be resized to fit
+/// within `image_max_size`.
+pub(crate) fn apply_change(
+    change: &Change,
+    image_max_size: Option<u32>,
+) -> impl Operation<WriteConn, ()> + '_ {
     composite_op::<WriteConn, _, _>(move |ctx| {
         match change {
             Change::None => (),
@@ -224,6 +607,10 @@ fn apply_change<'a>(
             } => {
                 // Accept the image size from a parent by skipping resizing (image_max_size as None).
                 coto_ops::edit(coto_id, diff, None, Some(*updated_at)).run(ctx)?;
+                if !matches!(diff.media_content, FieldDiff::None) {
+                    // The missing media has been replaced.
+                    media::delete_missing(coto_id).run(ctx)?;
+                }
             }
             Change::Promote {
                 coto_id,
@@ -271,6 +658,10 @@ fn apply_change<'a>(
             Change::ChangeItoOrder { ito_id, new_order } => {
                 ito_ops::change_order(ito_id, *new_order).run(ctx)?;
             }
+            Change::MediaOmitted { change: inner, .. } => {
+                apply_change(inner, image_max_size).run(ctx)?;
+                media::record_omitted(change).run(ctx)?;
+            }
         }
         Ok(())
     })
@@ -0,0 +1,503 @@
+//! Changelog compaction
+//!
+//! Entries up to a watermark (which all the child nodes have already received) can be
+//! rewritten as long as replaying the whole changelog still results in the same state.
+//! Since [super::import_change] requires serial numbers without gaps, obsolete entries
+//! are not deleted but rewritten into [Change::None]. The following entries are
+//! regarded as obsolete:
+//!
+//! * A run of edits to the same entity with no other changes to it in between, where
+//!   every edit except the last one is obsolete and the last one will be rewritten into
+//!   an edit merging all of them (`EditCoto`, `EditIto`), or replaced by the last one as
+//!   is (`RenameNode`, `SetNodeIcon`, `RenameCotonoma`).
+//! * All the changes to a coto that has been created and deleted without being reposted,
+//!   promoted, or connected by itos, as long as the timestamp of the cotonoma in which it
+//!   was posted will be updated again by a succeeding change.
+//!
+//! Entries with an import error are left intact and regarded as a change to every entity
+//! they refer to, which prevents the surrounding entries from being compacted.
+
+use std::collections::{HashMap, HashSet};
+
+use super::*;
+use crate::{
+    db::ops::node_role_ops::child_ops,
+    models::{
+        coto::{Coto, CotoContentDiff},
+        cotonoma::Cotonoma,
+        ito::{Ito, ItoContentDiff},
+        FieldDiff,
+    },
+};
+
+/// The number of changelog entries to be loaded at once during compaction
+const CHUNK_SIZE: i64 = 100;
+
+/// An entity that a change refers to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum Key {
+    Node(Id<Node>),
+    Coto(Id<Coto>),
+    Cotonoma(Id<Cotonoma>),
+    Ito(Id<Ito>),
+}
+
+/// What a change does, as far as compaction is concerned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    EditCoto,
+    EditIto,
+    RenameNode,
+    SetNodeIcon,
+    RenameCotonoma,
+    /// Creation of a coto that is neither a cotonoma nor a repost
+    CreatePlainCoto {
+        posted_in_id: Option<Id<Cotonoma>>,
+    },
+    DeleteCoto,
+    Other,
+}
+
+impl Kind {
+    /// Returns `true` if the last change of a run of this kind contains
+    /// the effects of the preceding ones.
+    fn supersedes_itself(&self) -> bool {
+        matches!(
+            self,
+            Self::EditCoto
+                | Self::EditIto
+                | Self::RenameNode
+                | Self::SetNodeIcon
+                | Self::RenameCotonoma
+        )
+    }
+}
+
+#[derive(Debug)]
+struct Summary {
+    kind: Kind,
+
+    /// `false` if the entry is above the watermark or has an import error
+    compactable: bool,
+}
+
+/// A summary of the changelog built by scanning it from the first entry.
+#[derive(Default)]
+struct Scan {
+    summaries: HashMap<i64, Summary>,
+
+    /// Serial numbers of the entries referring to each entity in order
+    history: HashMap<Key, Vec<i64>>,
+
+    /// Serial numbers of the last entries updating the timestamp of each cotonoma
+    last_timestamp_updates: HashMap<Id<Cotonoma>, i64>,
+
+    /// Cotonoma <-> coto of a cotonoma
+    cotonoma_cotos: HashMap<Id<Cotonoma>, Id<Coto>>,
+    coto_cotonomas: HashMap<Id<Coto>, Id<Cotonoma>>,
+
+    /// Root cotonoma of each node, which will be renamed along with the node
+    node_roots: HashMap<Id<Node>, Id<Cotonoma>>,
+
+    /// Cotos whose cotonoma is unknown (promoted by a legacy `Promote` change),
+    /// which will never be compacted.
+    pinned: HashSet<Key>,
+}
+
+impl Scan {
+    fn add(&mut self, log: &ChangelogEntry, watermark: i64) {
+        let serial = log.serial_number;
+        let mut keys = Vec::new();
+        let kind = match &log.change {
+            Change::None => Kind::Other,
+            Change::CreateNode { node, root } => {
+                keys.push(Key::Node(node.uuid));
+                self.set_root(node);
+                if let Some((cotonoma, coto)) = root {
+                    self.add_cotonoma(cotonoma.uuid, coto.uuid);
+                    keys.extend([Key::Cotonoma(cotonoma.uuid), Key::Coto(coto.uuid)]);
+                }
+                Kind::Other
+            }
+            Change::UpsertNode(node) => {
+                keys.push(Key::Node(node.uuid));
+                self.set_root(node);
+                Kind::Other
+            }
+            Change::RenameNode { node_id, .. } => {
+                keys.push(Key::Node(*node_id));
+                if let Some(root_id) = self.node_roots.get(node_id) {
+                    keys.push(Key::Cotonoma(*root_id));
+                }
+                Kind::RenameNode
+            }
+            Change::SetNodeIcon { node_id, .. } => {
+                keys.push(Key::Node(*node_id));
+                Kind::SetNodeIcon
+            }
+            Change::SetRootCotonoma {
+                node_id,
+                cotonoma_id,
+            } => {
+                self.node_roots.insert(*node_id, *cotonoma_id);
+                keys.push(Key::Node(*node_id));
+                Kind::Other
+            }
+            Change::CreateCoto(coto) => {
+                keys.push(Key::Coto(coto.uuid));
+                if let Some(posted_in_id) = coto.posted_in_id {
+                    self.update_timestamp(posted_in_id, serial, log);
+                }
+                if let Some(repost_of_id) = coto.repost_of_id {
+                    keys.push(Key::Coto(repost_of_id));
+                    Kind::Other
+                } else if coto.is_cotonoma {
+                    Kind::Other
+                } else {
+                    Kind::CreatePlainCoto {
+                        posted_in_id: coto.posted_in_id,
+                    }
+                }
+            }
+            Change::EditCoto { coto_id, .. } => {
+                keys.push(Key::Coto(*coto_id));
+                Kind::EditCoto
+            }
+            Change::PromoteCoto {
+                coto_id,
+                cotonoma_id,
+                ..
+            } => {
+                self.add_cotonoma(*cotonoma_id, *coto_id);
+                keys.extend([Key::Coto(*coto_id), Key::Cotonoma(*cotonoma_id)]);
+                Kind::Other
+            }
+            Change::Promote { coto_id, .. } => {
+                self.pinned.insert(Key::Coto(*coto_id));
+                keys.push(Key::Coto(*coto_id));
+                Kind::Other
+            }
+            Change::DeleteCoto { coto_id, .. } => {
+                keys.push(Key::Coto(*coto_id));
+                Kind::DeleteCoto
+            }
+            Change::CreateCotonoma(cotonoma, coto) => {
+                self.add_cotonoma(cotonoma.uuid, coto.uuid);
+                keys.extend([Key::Coto(coto.uuid), Key::Cotonoma(cotonoma.uuid)]);
+                if let Some(posted_in_id) = coto.posted_in_id {
+                    self.update_timestamp(posted_in_id, serial, log);
+                }
+                Kind::Other
+            }
+            Change::RenameCotonoma { cotonoma_id, .. } => {
+                keys.push(Key::Cotonoma(*cotonoma_id));
+                self.update_timestamp(*cotonoma_id, serial, log);
+                Kind::RenameCotonoma
+            }
+            Change::CreateIto(ito) => {
+                keys.extend([
+                    Key::Ito(ito.uuid),
+                    Key::Coto(ito.source_coto_id),
+                    Key::Coto(ito.target_coto_id),
+                ]);
+                Kind::Other
+            }
+            Change::EditIto { ito_id, .. } => {
+                keys.push(Key::Ito(*ito_id));
+                Kind::EditIto
+            }
+            Change::DeleteIto { ito_id } | Change::ChangeItoOrder { ito_id, .. } => {
+                keys.push(Key::Ito(*ito_id));
+                Kind::Other
+            }
+            // A change without media is neither merged nor cleared, which also
+            // prevents the surrounding changes to the coto from being compacted.
+            Change::MediaOmitted { change, .. } => {
+                match change.as_ref() {
+                    Change::CreateCoto(coto) => {
+                        keys.push(Key::Coto(coto.uuid));
+                        if let Some(posted_in_id) = coto.posted_in_id {
+                            self.update_timestamp(posted_in_id, serial, log);
+                        }
+                    }
+                    Change::EditCoto { coto_id, .. } => keys.push(Key::Coto(*coto_id)),
+                    _ => (),
+                }
+                Kind::Other
+            }
+        };
+
+        // A cotonoma and its coto are updated together.
+        for key in keys.clone() {
+            match key {
+                Key::Coto(id) => {
+                    if let Some(cotonoma_id) = self.coto_cotonomas.get(&id) {
+                        keys.push(Key::Cotonoma(*cotonoma_id));
+                    }
+                }
+                Key::Cotonoma(id) => {
+                    if let Some(coto_id) = self.cotonoma_cotos.get(&id) {
+                        keys.push(Key::Coto(*coto_id));
+                    }
+                }
+                _ => (),
+            }
+        }
+        keys.sort();
+        keys.dedup();
+
+        let compactable = serial <= watermark
+            && log.import_error.is_none()
+            && !keys.iter().any(|key| self.pinned.contains(key));
+        for key in keys {
+            self.history.entry(key).or_default().push(serial);
+        }
+        self.summaries.insert(serial, Summary { kind, compactable });
+    }
+
+    fn add_cotonoma(&mut self, cotonoma_id: Id<Cotonoma>, coto_id: Id<Coto>) {
+        self.cotonoma_cotos.insert(cotonoma_id, coto_id);
+        self.coto_cotonomas.insert(coto_id, cotonoma_id);
+    }
+
+    fn set_root(&mut self, node: &Node) {
+        if let Some(root_id) = node.root_cotonoma_id {
+            self.node_roots.insert(node.uuid, root_id);
+        }
+    }
+
+    fn update_timestamp(&mut self, cotonoma_id: Id<Cotonoma>, serial: i64, log: &ChangelogEntry) {
+        if log.import_error.is_none() {
+            self.last_timestamp_updates.insert(cotonoma_id, serial);
+        }
+    }
+
+    fn summary(&self, serial: i64) -> &Summary {
+        self.summaries
+            .get(&serial)
+            .unwrap_or_else(|| unreachable!())
+    }
+
+    /// Returns the histories of transient cotos to be cleared and the runs to be
+    /// merged into their last entries.
+    fn plan(&self) -> (Vec<Vec<i64>>, Vec<Vec<i64>>) {
+        let mut cleared = HashSet::new();
+        let mut histories = Vec::new();
+        let mut runs = Vec::new();
+
+        // Cotos created and deleted
+        for (key, serials) in self.history.iter() {
+            let Key::Coto(_) = key else { continue };
+            if self.is_transient_coto(serials) {
+                cleared.extend(serials.iter().copied());
+                histories.push(serials.clone());
+            }
+        }
+        histories.sort();
+
+        // Runs of edits
+        for serials in self.history.values() {
+            let mut run: Vec<i64> = Vec::new();
+            for serial in serials.iter().copied().chain([0]) {
+                let continues = run.last().is_some_and(|last| {
+                    let (last, current) = (self.summary(*last), self.summaries.get(&serial));
+                    current.is_some_and(|current| current.compactable && current.kind == last.kind)
+                });
+                if !continues {
+                    if run.len() > 1 && run.iter().all(|serial| !cleared.contains(serial)) {
+                        runs.push(std::mem::take(&mut run));
+                    }
+                    run.clear();
+                }
+                if let Some(summary) = self.summaries.get(&serial) {
+                    if summary.compactable && summary.kind.supersedes_itself() {
+                        run.push(serial);
+                    }
+                }
+            }
+        }
+        // An entry could be in runs of multiple entities (ex. a cotonoma and its coto).
+        let mut seen = HashSet::new();
+        runs.retain(|run| run.iter().all(|serial| seen.insert(*serial)));
+        runs.sort();
+
+        (histories, runs)
+    }
+
+    /// Returns `true` if the history of a coto consists of its creation, edits, and
+    /// deletion that can be safely removed from the changelog.
+    fn is_transient_coto(&self, serials: &[i64]) -> bool {
+        let (Some(first), Some(last)) = (serials.first(), serials.last()) else {
+            return false;
+        };
+        let Kind::CreatePlainCoto { posted_in_id } = self.summary(*first).kind else {
+            return false;
+        };
+        if self.summary(*last).kind != Kind::DeleteCoto {
+            return false;
+        }
+        let all_compactable = serials.iter().all(|serial| {
+            let summary = self.summary(*serial);
+            summary.compactable
+                && (*serial == *first || *serial == *last || summary.kind == Kind::EditCoto)
+        });
+        // The cotonoma timestamp updated by the creation has to be overwritten later.
+        let timestamp_overwritten = posted_in_id.is_none_or(|cotonoma_id| {
+            self.last_timestamp_updates
+                .get(&cotonoma_id)
+                .is_some_and(|update| update > first)
+        });
+        all_compactable && timestamp_overwritten
+    }
+}
+
+/// Returns the serial number up to which the changelog can be compacted, that is,
+/// the minimum number of the changes acknowledged by the child nodes (or the last
+/// number if there are no child nodes) minus the `margin`.
+pub(crate) fn watermark<Conn: ReadConn>(margin: i64) -> impl Operation<Conn, i64> {
+    composite_op::<Conn, _, _>(move |ctx| {
+        let acknowledged = match child_ops::min_changes_acknowledged().run(ctx)? {
+            Some(acknowledged) => acknowledged,
+            None => last_serial_number().run(ctx)?.unwrap_or(0),
+        };
+        Ok(acknowledged - margin)
+    })
+}
+
+/// Entries to be rewritten by a compaction, each group of which should be rewritten
+/// atomically to keep the changelog replayable.
+#[derive(Debug, Default)]
+pub(crate) struct Plan {
+    pub watermark: i64,
+
+    /// Histories of the cotos created and deleted, to be cleared
+    pub cleared: Vec<Vec<i64>>,
+
+    /// Runs of edits, to be merged into their last entries
+    pub runs: Vec<Vec<i64>>,
+}
+
+/// Scans the changelog to plan the compaction of the entries up to the `watermark`.
+///
+/// Since the changelog is only appended to (except for the import errors to be
+/// cleared), the plan remains valid after new entries have been added to it.
+pub(crate) fn plan<Conn: ReadConn>(watermark: i64) -> impl Operation<Conn, Plan> {
+    composite_op::<Conn, _, _>(move |ctx| {
+        let last = last_serial_number().run(ctx)?.unwrap_or(0);
+        let watermark = watermark.min(last);
+        if watermark < 1 {
+            return Ok(Plan {
+                watermark,
+                ..Default::default()
+            });
+        }
+
+        // Scan the whole changelog since entries above the watermark can also
+        // affect what can be compacted.
+        let mut scan = Scan::default();
+        let mut from = 1;
+        while from <= last {
+            let (logs, _) = chunk(from, CHUNK_SIZE, None).run(ctx)?;
+            let Some(last_log) = logs.last() else { break };
+            from = last_log.serial_number + 1;
+            for log in logs.iter() {
+                scan.add(log, watermark);
+            }
+        }
+
+        let (cleared, runs) = scan.plan();
+        Ok(Plan {
+            watermark,
+            cleared,
+            runs,
+        })
+    })
+}
+
+/// Rewrites the given entries into [Change::None] and returns the number of them.
+pub(crate) fn clear(serials: &[i64]) -> impl Operation<WriteConn, usize> + '_ {
+    composite_op::<WriteConn, _, _>(move |ctx| {
+        for serial in serials {
+            update_change(*serial, &Change::None).run(ctx)?;
+        }
+        Ok(serials.len())
+    })
+}
+
+/// Merges a run of edits into its last entry, clears the preceding ones, and
+/// returns the number of the cleared entries.
+pub(crate) fn merge_run(run: &[i64]) -> impl Operation<WriteConn, usize> + '_ {
+    composite_op::<WriteConn, _, _>(move |ctx| {
+        let Some((last, preceding)) = run.split_last() else {
+            return Ok(0);
+        };
+        let mut merged = try_get(*preceding.first().unwrap_or(last)).run(ctx)??.change;
+        for serial in preceding.iter().skip(1).chain([last]) {
+            merged = merge(merged, try_get(*serial).run(ctx)??.change);
+        }
+        clear(preceding).run(ctx)?;
+        update_change(*last, &merged).run(ctx)?;
+        Ok(preceding.len())
+    })
+}
+
+/// Merges a change into the preceding one of the same kind.
+fn merge(preceding: Change, change: Change) -> Change {
+    match (preceding, change) {
+        (
+            Change::EditCoto {
+                diff: preceding, ..
+            },
+            Change::EditCoto {
+                coto_id,
+                diff,
+                updated_at,
+            },
+        ) => Change::EditCoto {
+            coto_id,
+            diff: CotoContentDiff {
+                content: merge_field(preceding.content, diff.content),
+                summary: merge_field(preceding.summary, diff.summary),
+                media_content: merge_field(preceding.media_content, diff.media_content),
+                geolocation: merge_field(preceding.geolocation, diff.geolocation),
+                datetime_range: merge_field(preceding.datetime_range, diff.datetime_range),
+            },
+            updated_at,
+        },
+        (
+            Change::EditIto {
+                diff: preceding, ..
+            },
+            Change::EditIto {
+                ito_id,
+                diff,
+                updated_at,
+            },
+        ) => Change::EditIto {
+            ito_id,
+            diff: ItoContentDiff {
+                description: merge_field(preceding.description, diff.description),
+                details: merge_field(preceding.details, diff.details),
+            },
+            updated_at,
+        },
+        // The other kinds of changes in a run set absolute values.
+        (_, change) => change,
+    }
+}
+
+fn merge_field<T>(preceding: FieldDiff<T>, diff: FieldDiff<T>) -> FieldDiff<T> {
+    match diff {
+        FieldDiff::None => preceding,
+        diff => diff,
+    }
+}
+
+fn update_change(serial_number: i64, change: &Change) -> impl Operation<WriteConn, usize> + '_ {
+    write_op(move |conn| {
+        diesel::update(changelog::table.find(serial_number))
+            .set(changelog::change.eq(change))
+            .execute(conn.deref_mut())
+            .map_err(anyhow::Error::from)
+    })
+}
@@ -0,0 +1,279 @@
+//! Forking from a parent and reconciling with it
+//!
+//! A child node can deliberately fork from a parent ([ParentNode::forked]) to stop
+//! replicating its changes while keeping the replicated entities. A forked child keeps
+//! making its own changes, some of which could involve the entities owned by the parent
+//! (an ito to a parent's coto, a repost of it, a coto posted in a parent's cotonoma, etc.).
+//! The point of the fork in the local changelog is recorded in
+//! [ParentNode::forked_at_serial_number] to tell which local changes have diverged.
+//!
+//! The child can later reconcile with the parent by re-attaching to it, after which the
+//! changes made in the parent during the fork will be imported as usual. Before that,
+//! the pending parent changes can be checked against the local changes since the fork:
+//! a parent change conflicts with local changes if they involve the same parent-owned
+//! entities ([ForkConflict]). Conflicts are reported rather than rejected, and a parent
+//! change that fails to be applied will be recorded as a failed import to be retried.
+
+use std::collections::HashSet;
+
+use anyhow::Result;
+
+use super::*;
+use crate::{
+    db::ops::node_role_ops::{network_role_of, set_network_disabled},
+    models::{
+        coto::Coto,
+        cotonoma::Cotonoma,
+        ito::Ito,
+        node::parent::{ChangeSummary, ForkConflict},
+    },
+};
+
+/// Entities involved in a change
+#[derive(Default)]
+struct Involved {
+    cotos: HashSet<Id<Coto>>,
+    cotonomas: HashSet<Id<Cotonoma>>,
+    itos: HashSet<Id<Ito>>,
+}
+
+impl Involved {
+    fn of<Conn: ReadConn>(change: &Change, ctx: &mut Context<'_, Conn>) -> Result<Self> {
+        let mut involved = Self::default();
+        involved.add(change, ctx)?;
+        Ok(involved)
+    }
+
+    fn add<Conn: ReadConn>(&mut self, change: &Change, ctx: &mut Context<'_, Conn>) -> Result<()> {
+        match change {
+            Change::None
+            | Change::CreateNode { .. }
+            | Change::UpsertNode(_)
+            | Change::RenameNode { .. }
+            | Change::SetNodeIcon { .. }
+            | Change::SetRootCotonoma { .. } => (),
+            Change::CreateCoto(coto) => {
+                self.cotos.insert(coto.uuid);
+                self.cotos.extend(coto.repost_of_id);
+                self.cotonomas.extend(coto.posted_in_id);
+            }
+            Change::EditCoto { coto_id, .. }
+            | Change::PromoteCoto { coto_id, .. }
+            | Change::DeleteCoto { coto_id, .. }
+            | Change::Promote { coto_id, .. } => {
+                self.cotos.insert(*coto_id);
+            }
+            Change::CreateCotonoma(cotonoma, coto) => {
+                self.cotos.insert(coto.uuid);
+                self.cotonomas.insert(cotonoma.uuid);
+                self.cotonomas.extend(coto.posted_in_id);
+            }
+            Change::RenameCotonoma { cotonoma_id, .. } => {
+                self.cotonomas.insert(*cotonoma_id);
+            }
+            Change::CreateIto(ito) => self.add_ito(ito),
+            Change::EditIto { ito_id, .. }
+            | Change::DeleteIto { ito_id }
+            | Change::ChangeItoOrder { ito_id, .. } => {
+                self.itos.insert(*ito_id);
+                if let Some(ito) = ito_ops::get(ito_id).run(ctx)? {
+                    self.add_ito(&ito);
+                }
+            }
+            Change::MediaOmitted { change, .. } => self.add(change, ctx)?,
+        }
+        Ok(())
+    }
+
+    fn add_ito(&mut self, ito: &Ito) {
+        self.itos.insert(ito.uuid);
+        self.cotos.insert(ito.source_coto_id);
+        self.cotos.insert(ito.target_coto_id);
+    }
+
+    /// Retains only the entities owned by the given node in the current state.
+    fn owned_by<Conn: ReadConn>(
+        mut self,
+        node_id: &Id<Node>,
+        ctx: &mut Context<'_, Conn>,
+    ) -> Result<Self> {
+        let mut cotos = HashSet::new();
+        for id in self.cotos.drain() {
+            if coto_ops::get(&id)
+                .run(ctx)?
+                .is_some_and(|c| c.node_id == *node_id)
+            {
+                cotos.insert(id);
+            }
+        }
+        let mut cotonomas = HashSet::new();
+        for id in self.cotonomas.drain() {
+            if cotonoma_ops::get(&id)
+                .run(ctx)?
+                .is_some_and(|c| c.node_id == *node_id)
+            {
+                cotonomas.insert(id);
+            }
+        }
+        let mut itos = HashSet::new();
+        for id in self.itos.drain() {
+            if ito_ops::get(&id)
+                .run(ctx)?
+                .is_some_and(|i| i.node_id == *node_id)
+            {
+                itos.insert(id);
+            }
+        }
+        Ok(Self {
+            cotos,
+            cotonomas,
+            itos,
+        })
+    }
+
+    fn is_empty(&self) -> bool {
+        self.cotos.is_empty() && self.cotonomas.is_empty() && self.itos.is_empty()
+    }
+
+    fn summarize(&self, log: &ChangelogEntry) -> ChangeSummary {
+        fn sorted<T: Copy + Ord>(ids: &HashSet<T>) -> Vec<T> {
+            let mut ids: Vec<T> = ids.iter().copied().collect();
+            ids.sort();
+            ids
+        }
+        ChangeSummary {
+            serial_number: log.serial_number,
+            kind: log.change.kind().into(),
+            cotos: sorted(&self.cotos),
+            cotonomas: sorted(&self.cotonomas),
+            itos: sorted(&self.itos),
+        }
+    }
+
+    fn intersects(&self, other: &Self) -> bool {
+        !self.cotos.is_disjoint(&other.cotos)
+            || !self.cotonomas.is_disjoint(&other.cotonomas)
+            || !self.itos.is_disjoint(&other.itos)
+    }
+}
+
+/// Forks the local node from a parent at the current end of the local changelog,
+/// disabling the network role to the parent to stop the replication.
+pub(crate) fn fork(parent_node_id: &Id<Node>) -> impl Operation<WriteConn, ParentNode> + '_ {
+    composite_op::<WriteConn, _, _>(move |ctx| {
+        let parent = parent_ops::get(parent_node_id)
+            .run(ctx)?
+            .ok_or(DatabaseError::not_found(
+                EntityKind::ParentNode,
+                *parent_node_id,
+            ))?;
+        ensure!(
+            !parent.forked,
+            DatabaseError::AlreadyForkedFromParent {
+                parent_node_id: parent.node_id
+            }
+        );
+        let serial_number = last_serial_number().run(ctx)?.unwrap_or(0);
+        let parent = parent_ops::set_forked_at(parent_node_id, Some(serial_number)).run(ctx)?;
+        if network_role_of(parent_node_id).run(ctx)?.is_some() {
+            set_network_disabled(parent_node_id, true).run(ctx)?;
+        }
+        Ok(parent)
+    })
+}
+
+/// Re-attaches the local node to a parent from which it has been forked,
+/// enabling the network role to the parent to resume the replication.
+pub(crate) fn reattach(parent_node_id: &Id<Node>) -> impl Operation<WriteConn, ParentNode> + '_ {
+    composite_op::<WriteConn, _, _>(move |ctx| {
+        let parent = parent_ops::get(parent_node_id)
+            .run(ctx)?
+            .ok_or(DatabaseError::not_found(
+                EntityKind::ParentNode,
+                *parent_node_id,
+            ))?;
+        ensure!(
+            parent.forked,
+            DatabaseError::NotForkedFromParent {
+                parent_node_id: parent.node_id
+            }
+        );
+        let parent = parent_ops::set_forked_at(parent_node_id, None).run(ctx)?;
+        if network_role_of(parent_node_id).run(ctx)?.is_some() {
+            set_network_disabled(parent_node_id, false).run(ctx)?;
+        }
+        Ok(parent)
+    })
+}
+
+/// Returns the local changes since the fork from the parent that involve the entities
+/// owned by the parent.
+pub(crate) fn diverged_changes<'a, Conn: ReadConn>(
+    parent: &'a ParentNode,
+    local_node_id: &'a Id<Node>,
+) -> impl Operation<Conn, Vec<ChangelogEntry>> + 'a {
+    composite_op::<Conn, _, _>(move |ctx| {
+        ensure!(
+            parent.forked,
+            DatabaseError::NotForkedFromParent {
+                parent_node_id: parent.node_id
+            }
+        );
+        // A parent forked before the fork point was recorded diverged from the beginning.
+        let forked_at = parent.forked_at_serial_number.unwrap_or(0);
+        let logs: Vec<ChangelogEntry> = changelog::table
+            .filter(changelog::serial_number.gt(forked_at))
+            .filter(changelog::origin_node_id.eq(local_node_id))
+            .order(changelog::serial_number.asc())
+            .load(ctx.conn().read())?;
+        let mut diverged = Vec::new();
+        for log in logs {
+            if !Involved::of(&log.change, ctx)?
+                .owned_by(&parent.node_id, ctx)?
+                .is_empty()
+            {
+                diverged.push(log);
+            }
+        }
+        Ok(diverged)
+    })
+}
+
+/// Summarizes the changes in the parent and returns them along with the ones that
+/// conflict with the local changes since the fork (returned by [diverged_changes]).
+///
+/// The parent changes can be given in chunks to be summarized one by one.
+pub(crate) fn conflicts<'a, Conn: ReadConn>(
+    parent_node_id: &'a Id<Node>,
+    parent_changes: &'a [ChangelogEntry],
+    local_changes: &'a [ChangelogEntry],
+) -> impl Operation<Conn, (Vec<ChangeSummary>, Vec<ForkConflict>)> + 'a {
+    composite_op::<Conn, _, _>(move |ctx| {
+        let local_involved: Vec<(i64, Involved)> = local_changes
+            .iter()
+            .map(|log| {
+                let involved = Involved::of(&log.change, ctx)?.owned_by(parent_node_id, ctx)?;
+                Ok((log.serial_number, involved))
+            })
+            .collect::<Result<_>>()?;
+        let mut summaries = Vec::new();
+        let mut conflicts = Vec::new();
+        for log in parent_changes {
+            let involved = Involved::of(&log.change, ctx)?;
+            let summary = involved.summarize(log);
+            let local_changes: Vec<i64> = local_involved
+                .iter()
+                .filter(|(_, local)| local.intersects(&involved))
+                .map(|(serial_number, _)| *serial_number)
+                .collect();
+            if !local_changes.is_empty() {
+                conflicts.push(ForkConflict {
+                    parent_change: summary.clone(),
+                    local_changes,
+                });
+            }
+            summaries.push(summary);
+        }
+        Ok((summaries, conflicts))
+    })
+}
@@ -0,0 +1,164 @@
+//! Lazy media replication
+//!
+//! A child node can request its parent to omit media content from the changes
+//! ([Change::omit_media]) so that replication won't be blocked by large media over
+//! a slow link. A change without media content carries the hash of the media instead,
+//! by which the child fetches the media later, and until then, the media of the coto
+//! is recorded as [MissingMedia].
+
+use std::borrow::Cow;
+
+use anyhow::{bail, ensure};
+
+use super::*;
+use crate::{
+    models::coto::{
+        process_media_content, Coto, CotoMedia, MissingMedia, NewMissingMedia, OmittedMedia,
+    },
+    schema::{cotos, missing_media},
+};
+
+pub(crate) fn get_missing<Conn: ReadConn>(
+    coto_id: &Id<Coto>,
+) -> impl Operation<Conn, Option<MissingMedia>> + '_ {
+    read_op(move |conn| {
+        missing_media::table
+            .find(coto_id)
+            .first(conn)
+            .optional()
+            .map_err(anyhow::Error::from)
+    })
+}
+
+/// Returns all the missing media in the order in which they have been found missing.
+pub(crate) fn all_missing<Conn: ReadConn>() -> impl Operation<Conn, Vec<MissingMedia>> {
+    read_op(move |conn| {
+        missing_media::table
+            .order(missing_media::created_at.asc())
+            .load::<MissingMedia>(conn)
+            .map_err(anyhow::Error::from)
+    })
+}
+
+/// Returns the missing media to be fetched from the given parent, which includes
+/// the ones whose parent is unknown, in the order in which they have been found missing.
+pub(crate) fn missing_from<Conn: ReadConn>(
+    parent_node_id: &Id<Node>,
+) -> impl Operation<Conn, Vec<MissingMedia>> + '_ {
+    read_op(move |conn| {
+        missing_media::table
+            .filter(
+                missing_media::parent_node_id
+                    .eq(parent_node_id)
+                    .or(missing_media::parent_node_id.is_null()),
+            )
+            .order(missing_media::created_at.asc())
+            .load::<MissingMedia>(conn)
+            .map_err(anyhow::Error::from)
+    })
+}
+
+/// Records the media omitted from a change to the coto as missing, replacing
+/// the one recorded by a preceding change.
+pub(crate) fn set_missing<'a>(
+    coto_id: &'a Id<Coto>,
+    media: &'a OmittedMedia,
+) -> impl Operation<WriteConn, MissingMedia> + 'a {
+    write_op(move |conn| {
+        diesel::replace_into(missing_media::table)
+            .values(NewMissingMedia::new(coto_id, media))
+            .get_result(conn.deref_mut())
+            .map_err(anyhow::Error::from)
+    })
+}
+
+pub(crate) fn delete_missing(coto_id: &Id<Coto>) -> impl Operation<WriteConn, bool> + '_ {
+    write_op(move |conn| {
+        let affected =
+            diesel::delete(missing_media::table.find(coto_id)).execute(conn.deref_mut())?;
+        Ok(affected > 0)
+    })
+}
+
+/// Returns the media content of the coto if its hash matches the given one.
+///
+/// It returns `None` if the media has been replaced or deleted since the hash was
+/// sent, or has not yet been fetched in this node.
+pub(crate) fn get_by_hash<'a, Conn: ReadConn>(
+    coto_id: &'a Id<Coto>,
+    hash: &'a str,
+) -> impl Operation<Conn, Option<CotoMedia>> + 'a {
+    composite_op::<Conn, _, _>(move |ctx| {
+        let Some(coto) = coto_ops::get(coto_id).run(ctx)? else {
+            return Ok(None);
+        };
+        Ok(coto
+            .media_content()
+            .filter(|(content, _)| Coto::media_hash(content.as_ref()) == hash)
+            .map(|(content, media_type)| CotoMedia {
+                content,
+                media_type,
+            }))
+    })
+}
+
+/// Stores the fetched content of a missing media into the coto.
+///
+/// The content must match the hash of the missing media, and it returns `None`
+/// if the media is no longer missing (fetched or replaced by another change).
+/// An image will be resized to fit within `image_max_size` after the hash has been
+/// verified, in the same way as the images in the changes imported with media.
+/// The timestamp of the coto won't be updated since it is not a change to the coto.
+pub(crate) fn store_missing<'a>(
+    coto_id: &'a Id<Coto>,
+    content: &'a [u8],
+    image_max_size: Option<u32>,
+) -> impl Operation<WriteConn, Option<Coto>> + 'a {
+    composite_op::<WriteConn, _, _>(move |ctx| {
+        let Some(missing) = get_missing(coto_id).run(ctx)? else {
+            return Ok(None);
+        };
+        ensure!(
+            Coto::media_hash(content) == missing.hash,
+            "The content doesn't match the hash of the missing media: {}",
+            missing.hash
+        );
+        let content =
+            process_media_content(Cow::from(content), &missing.media_type, image_max_size)?;
+        let coto: Coto = diesel::update(cotos::table.find(coto_id))
+            .set((
+                cotos::media_content.eq(Some(content.as_ref())),
+                cotos::media_type.eq(Some(&missing.media_type)),
+            ))
+            .get_result(ctx.conn().deref_mut())?;
+        delete_missing(coto_id).run(ctx)?;
+        Ok(Some(coto))
+    })
+}
+
+/// Records the media omitted from a change ([Change::MediaOmitted]) as missing,
+/// which must be called after applying the change itself.
+pub(super) fn record_omitted(change: &Change) -> impl Operation<WriteConn, MissingMedia> + '_ {
+    composite_op::<WriteConn, _, _>(move |ctx| {
+        let Some((coto_id, media)) = change.omitted_media() else {
+            bail!("Media can't be omitted from the change: {change:?}");
+        };
+        set_missing(coto_id, media).run(ctx)
+    })
+}
+
+/// Records the parent from which a change omitting media ([Change::MediaOmitted])
+/// has been imported, so that the media will be fetched from the parent.
+pub(super) fn set_parent_of_omitted<'a>(
+    change: &'a Change,
+    parent_node_id: &'a Id<Node>,
+) -> impl Operation<WriteConn, ()> + 'a {
+    write_op(move |conn| {
+        if let Some((coto_id, _)) = change.omitted_media() {
+            diesel::update(missing_media::table.find(coto_id))
+                .set(missing_media::parent_node_id.eq(Some(parent_node_id)))
+                .execute(conn.deref_mut())?;
+        }
+        Ok(())
+    })
+}
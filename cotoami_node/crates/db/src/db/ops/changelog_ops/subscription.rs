@@ -0,0 +1,185 @@
+//! Selective replication
+//!
+//! A child node can subscribe to specific cotonomas of its parent ([ChangeSubscription]).
+//! The parent filters the changes to be sent to such a child by the current state of its
+//! database: a change relevant to the subscription is sent as is, and the others are sent
+//! as placeholders ([ChangelogEntry::into_placeholder]) so that the child can keep
+//! the serial number continuity. The following changes are regarded as relevant:
+//!
+//! * Changes to nodes.
+//! * Cotos posted in the subscribed cotonomas, and the reposts of them posted in
+//!   the subscribed cotonomas.
+//! * Cotonomas posted in the subscribed cotonomas, the subscribed cotonomas themselves,
+//!   the cotonomas on the paths to them, and the root cotonomas, without which
+//!   the relevant cotos couldn't be imported.
+//! * Itos between relevant cotos.
+//!
+//! A change to an entity that no longer exists in the parent can't be related to any
+//! cotonoma. Such a deletion is sent as is since deleting a missing entity is no-op,
+//! and the others are sent as placeholders since the entity is to be deleted by
+//! a succeeding change anyway.
+
+use std::collections::HashSet;
+
+use anyhow::Result;
+
+use super::*;
+use crate::models::{changelog::ChangeSubscription, coto::Coto, cotonoma::Cotonoma, ito::Ito};
+
+/// Cotonomas relevant to a [ChangeSubscription]
+struct Scope {
+    /// Cotonomas whose posts are to be replicated
+    subscribed: HashSet<Id<Cotonoma>>,
+
+    /// Cotonomas on the paths from the root cotonomas to the subscribed ones
+    ancestors: HashSet<Id<Cotonoma>>,
+}
+
+impl Scope {
+    fn is_relevant<Conn: ReadConn>(
+        &self,
+        change: &Change,
+        ctx: &mut Context<'_, Conn>,
+    ) -> Result<bool> {
+        Ok(match change {
+            Change::None
+            | Change::CreateNode { .. }
+            | Change::UpsertNode(_)
+            | Change::RenameNode { .. }
+            | Change::SetNodeIcon { .. }
+            | Change::SetRootCotonoma { .. } => true,
+            Change::CreateCoto(coto) => self.is_relevant_coto(coto, ctx)?,
+            Change::EditCoto { coto_id, .. }
+            | Change::Promote { coto_id, .. }
+            | Change::PromoteCoto { coto_id, .. } => {
+                self.is_relevant_coto_id(coto_id, ctx)?.unwrap_or(false)
+            }
+            Change::DeleteCoto { coto_id, .. } => {
+                self.is_relevant_coto_id(coto_id, ctx)?.unwrap_or(true)
+            }
+            Change::CreateCotonoma(cotonoma, coto) => self.is_relevant_cotonoma(cotonoma, coto),
+            Change::RenameCotonoma { cotonoma_id, .. } => {
+                match cotonoma_ops::pair(cotonoma_id).run(ctx)? {
+                    Some((cotonoma, coto)) => self.is_relevant_cotonoma(&cotonoma, &coto),
+                    None => false,
+                }
+            }
+            Change::CreateIto(ito) => self.is_relevant_ito(ito, ctx)?,
+            Change::EditIto { ito_id, .. } | Change::ChangeItoOrder { ito_id, .. } => {
+                match ito_ops::get(ito_id).run(ctx)? {
+                    Some(ito) => self.is_relevant_ito(&ito, ctx)?,
+                    None => false,
+                }
+            }
+            Change::DeleteIto { ito_id } => match ito_ops::get(ito_id).run(ctx)? {
+                Some(ito) => self.is_relevant_ito(&ito, ctx)?,
+                None => true,
+            },
+            Change::MediaOmitted { change, .. } => self.is_relevant(change, ctx)?,
+        })
+    }
+
+    fn is_subscribed(&self, cotonoma_id: Option<Id<Cotonoma>>) -> bool {
+        cotonoma_id.is_some_and(|id| self.subscribed.contains(&id))
+    }
+
+    fn is_relevant_cotonoma(&self, cotonoma: &Cotonoma, coto: &Coto) -> bool {
+        self.subscribed.contains(&cotonoma.uuid)
+            || self.ancestors.contains(&cotonoma.uuid)
+            || coto.posted_in_id.is_none() // root cotonoma
+            || self.is_subscribed(coto.posted_in_id)
+    }
+
+    fn is_relevant_coto<Conn: ReadConn>(
+        &self,
+        coto: &Coto,
+        ctx: &mut Context<'_, Conn>,
+    ) -> Result<bool> {
+        if let Some(ref repost_of_id) = coto.repost_of_id {
+            // A repost can't be imported without the original coto.
+            return Ok(self.is_subscribed(coto.posted_in_id)
+                && self
+                    .is_relevant_coto_id(repost_of_id, ctx)?
+                    .unwrap_or(false));
+        }
+        if coto.is_cotonoma {
+            if let Some((cotonoma, _)) = cotonoma_ops::get_by_coto_id(&coto.uuid).run(ctx)? {
+                return Ok(self.is_relevant_cotonoma(&cotonoma, coto));
+            }
+        }
+        Ok(self.is_subscribed(coto.posted_in_id))
+    }
+
+    /// Returns `None` if the coto doesn't exist.
+    fn is_relevant_coto_id<Conn: ReadConn>(
+        &self,
+        coto_id: &Id<Coto>,
+        ctx: &mut Context<'_, Conn>,
+    ) -> Result<Option<bool>> {
+        match coto_ops::get(coto_id).run(ctx)? {
+            Some(coto) => Ok(Some(self.is_relevant_coto(&coto, ctx)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn is_relevant_ito<Conn: ReadConn>(
+        &self,
+        ito: &Ito,
+        ctx: &mut Context<'_, Conn>,
+    ) -> Result<bool> {
+        Ok(self
+            .is_relevant_coto_id(&ito.source_coto_id, ctx)?
+            .unwrap_or(false)
+            && self
+                .is_relevant_coto_id(&ito.target_coto_id, ctx)?
+                .unwrap_or(false))
+    }
+}
+
+/// Resolves the cotonomas relevant to a [ChangeSubscription] in the current state.
+fn scope<Conn: ReadConn>(subscription: &ChangeSubscription) -> impl Operation<Conn, Scope> + '_ {
+    composite_op::<Conn, _, _>(move |ctx| {
+        let mut subscribed: HashSet<Id<Cotonoma>> =
+            subscription.cotonoma_ids.iter().copied().collect();
+        if subscription.recursive {
+            for id in subscription.cotonoma_ids.iter() {
+                subscribed.extend(cotonoma_ops::sub_ids_recursive(id, None).run(ctx)?);
+            }
+        }
+
+        let mut ancestors = HashSet::new();
+        for id in subscription.cotonoma_ids.iter() {
+            let mut current = *id;
+            while let Some((_, coto)) = cotonoma_ops::pair(&current).run(ctx)? {
+                match coto.posted_in_id {
+                    Some(posted_in_id) if ancestors.insert(posted_in_id) => current = posted_in_id,
+                    _ => break,
+                }
+            }
+        }
+
+        Ok(Scope {
+            subscribed,
+            ancestors,
+        })
+    })
+}
+
+/// Replaces the changes irrelevant to a [ChangeSubscription] with placeholders.
+pub(crate) fn filter<Conn: ReadConn>(
+    logs: Vec<ChangelogEntry>,
+    subscription: &ChangeSubscription,
+) -> impl Operation<Conn, Vec<ChangelogEntry>> + '_ {
+    composite_op::<Conn, _, _>(move |ctx| {
+        let scope = scope(subscription).run(ctx)?;
+        logs.into_iter()
+            .map(|log| {
+                if scope.is_relevant(&log.change, ctx)? {
+                    Ok(log)
+                } else {
+                    Ok(log.into_placeholder())
+                }
+            })
+            .collect()
+    })
+}
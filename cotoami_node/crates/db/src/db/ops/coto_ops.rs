@@ -23,7 +23,7 @@ use crate::{
     schema::cotos,
 };
 
-type ScopeFilter<'a> = Option<Either<&'a Id<Node>, &'a [Id<Cotonoma>]>>;
+pub(crate) type ScopeFilter<'a> = Option<Either<&'a Id<Node>, &'a [Id<Cotonoma>]>>;
 
 pub(crate) fn get<Conn: ReadConn>(id: &Id<Coto>) -> impl Operation<Conn, Option<Coto>> + '_ {
     read_op(move |conn| {
@@ -1,57 +1,147 @@
 //! Graph (cotos, cotonomas, itos) related operations
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 use diesel::{dsl::sql_query, prelude::*};
+use either::Either;
 use indoc::indoc;
+use petgraph::{algo::tarjan_scc, prelude::Graph as Petgraph};
 
 use crate::{
-    db::op::*,
-    models::{coto::Coto, graph::Graph, ito::Ito, Id},
-    schema::{cotos, itos},
+    db::{
+        op::*,
+        ops::{coto_ops::ScopeFilter, MAX_IDS_IN_CLAUSE},
+    },
+    models::{
+        coto::Coto,
+        graph::{Graph, GraphHealth, TraversalFrontier, TraversalOptions},
+        ito::Ito,
+        node::Node,
+        Id,
+    },
+    schema::{cotos, itos, parent_nodes},
 };
 
 /// Breadth first traversal by iterating a query for [Coto]s in the same level.
 ///
 /// The traversal starts at a given [Coto] and only traverses [Coto]s reachable from it.
-/// It won't traverse beyond other [Cotonoma]s if `until_cotonoma` is set to `true`.
+/// It will be bounded by the given [TraversalOptions], and when it has been cut off
+/// by the limits, [Graph::frontier] will be set so that the traversal can be resumed
+/// by passing it as `frontier`.
+///
+/// Itos in each level are traversed in a predictable order (by source coto ID and
+/// [Ito::order]), which allows the traversal to be cut off at a certain ito
+/// when the number of cotos has reached [TraversalOptions::max_cotos].
 pub(crate) fn traverse_by_level_queries<Conn: ReadConn>(
     root: Coto,
-    until_cotonoma: bool,
+    options: TraversalOptions,
+    frontier: Option<TraversalFrontier>,
 ) -> impl Operation<Conn, Graph> {
     read_op(move |conn| {
+        let root_node_id = root.node_id;
         let mut graph = Graph::new(root);
-        let mut next: HashSet<Id<Coto>> = [graph.root().uuid].into();
-        loop {
-            // Next itos
-            let itos = itos::table
-                .filter(itos::source_coto_id.eq_any(&next))
-                .load::<Ito>(conn)?;
 
-            // Next coto IDs
-            next.clear();
-            for ito in itos.into_iter() {
-                if !graph.contains(&ito.target_coto_id) {
-                    next.insert(ito.target_coto_id); // unvisited
+        // `sources`: cotos to be traversed in the current level, each paired with
+        //            the order of the last ito traversed from it (zero means none).
+        // `next`: cotos to be traversed in the next level.
+        let (mut depth, mut sources, mut next) = match frontier {
+            Some(frontier) => {
+                // The cotos at the frontier are needed as the ends of itos.
+                let coto_ids: Vec<Id<Coto>> = frontier
+                    .sources
+                    .iter()
+                    .map(|(coto_id, _)| *coto_id)
+                    .chain(frontier.next.iter().copied())
+                    .collect();
+                let cotos = cotos::table
+                    .filter(cotos::uuid.eq_any(&coto_ids))
+                    .load::<Coto>(conn)?;
+                for coto in cotos.into_iter() {
+                    graph.add_coto(coto);
                 }
-                graph.add_ito(ito);
+                (frontier.depth, frontier.sources, frontier.next)
             }
-            if next.is_empty() {
+            None => (0, vec![(graph.root_id, 0)], Vec::new()),
+        };
+        let mut count_cotos = 0;
+        loop {
+            if sources.is_empty() {
+                if next.is_empty() {
+                    break;
+                }
+                depth += 1;
+                sources = next.drain(..).map(|coto_id| (coto_id, 0)).collect();
+            }
+            if options.max_depth.is_some_and(|max| depth >= max) {
+                graph.frontier = Some(TraversalFrontier {
+                    depth,
+                    sources,
+                    next,
+                });
                 break;
             }
 
+            // Next itos
+            let traversed: HashMap<Id<Coto>, i32> =
+                std::mem::take(&mut sources).into_iter().collect();
+            let mut itos: Vec<Ito> = itos::table
+                .filter(itos::source_coto_id.eq_any(traversed.keys()))
+                .load::<Ito>(conn)?
+                .into_iter()
+                .filter(|ito| ito.order > traversed[&ito.source_coto_id])
+                .collect();
+            itos.sort_by_key(|ito| (ito.source_coto_id.as_uuid(), ito.order));
+
+            // Next coto IDs (unvisited targets) until reaching the max
+            let mut targets: HashSet<Id<Coto>> = HashSet::new();
+            let mut cut_off_at: Option<usize> = None;
+            for (i, ito) in itos.iter().enumerate() {
+                if graph.contains(&ito.target_coto_id) || targets.contains(&ito.target_coto_id) {
+                    continue;
+                }
+                if options
+                    .max_cotos
+                    .is_some_and(|max| count_cotos + targets.len() >= max)
+                {
+                    cut_off_at = Some(i);
+                    break;
+                }
+                targets.insert(ito.target_coto_id);
+            }
+
             // Next cotos
             let cotos = cotos::table
-                .filter(cotos::uuid.eq_any(&next))
+                .filter(cotos::uuid.eq_any(&targets))
                 .load::<Coto>(conn)?;
+            count_cotos += cotos.len();
             for coto in cotos.into_iter() {
-                // Stop traversing upon finding a cotonoma
-                if until_cotonoma && coto.is_cotonoma {
-                    next.remove(&coto.uuid);
+                // Stop traversing upon finding a cotonoma or a coto in another node
+                let stop = (options.until_cotonoma && coto.is_cotonoma)
+                    || (options.until_foreign_node && coto.node_id != root_node_id);
+                if !stop {
+                    next.push(coto.uuid);
                 }
                 graph.add_coto(coto);
             }
-            if next.is_empty() {
+
+            // Add the itos except for the ones cut off
+            let cut_off = cut_off_at.map(|i| itos.split_off(i));
+            for ito in itos.into_iter() {
+                graph.add_ito(ito);
+            }
+            if let Some(cut_off) = cut_off {
+                // Resume from the first ito cut off in each source
+                let mut sources: Vec<(Id<Coto>, i32)> = Vec::new();
+                for ito in cut_off.iter() {
+                    if !sources.iter().any(|(id, _)| *id == ito.source_coto_id) {
+                        sources.push((ito.source_coto_id, ito.order - 1));
+                    }
+                }
+                graph.frontier = Some(TraversalFrontier {
+                    depth,
+                    sources,
+                    next,
+                });
                 break;
             }
         }
@@ -61,6 +151,12 @@ pub(crate) fn traverse_by_level_queries<Conn: ReadConn>(
 }
 
 /// Experimental implementation of graph traversal by recursive CTE (Common Table Expression).
+///
+/// It is not used as the default traversal because it doesn't support the limits of
+/// [TraversalOptions]. Although a depth limit could be added to the recursion,
+/// cutting off a graph at a certain number of cotos and resuming it from
+/// a [TraversalFrontier] require the level-by-level control that
+/// [traverse_by_level_queries()] has.
 pub(crate) fn traverse_by_recursive_cte<Conn: ReadConn>(
     root: Coto,
     until_cotonoma: bool,
@@ -159,3 +255,296 @@ pub(crate) fn ancestors_of<Conn: ReadConn>(
         Ok(ancestors)
     })
 }
+
+/// Returns the descendants of the given coto level by level, each of which consists of
+/// the outgoing itos from the previous level and their target cotos.
+///
+/// The itos in each level are sorted by the source coto ID and [Ito::order].
+pub(crate) fn descendants_of<Conn: ReadConn>(
+    coto_id: &Id<Coto>,
+    max_depth: Option<usize>,
+) -> impl Operation<Conn, Vec<(Vec<Ito>, Vec<Coto>)>> + '_ {
+    read_op(move |conn| {
+        let mut descendants = Vec::new();
+        let mut traversed: HashSet<Id<Coto>> = [*coto_id].into();
+        let mut next: HashSet<Id<Coto>> = [*coto_id].into();
+        while max_depth.is_none_or(|max| descendants.len() < max) {
+            // Next itos
+            let mut itos: Vec<Ito> = itos::table
+                .filter(itos::source_coto_id.eq_any(&next))
+                .load::<Ito>(conn)?;
+            if itos.is_empty() {
+                break;
+            }
+            itos.sort_by_key(|ito| (ito.source_coto_id.as_uuid(), ito.order));
+
+            // Next coto IDs
+            next = itos
+                .iter()
+                .filter_map(|ito| {
+                    if !traversed.contains(&ito.target_coto_id) {
+                        Some(ito.target_coto_id) // unvisited
+                    } else {
+                        None
+                    }
+                })
+                .collect();
+            traversed.extend(&next);
+
+            // Next cotos
+            let cotos = cotos::table
+                .filter(cotos::uuid.eq_any(&next))
+                .load::<Coto>(conn)?;
+            descendants.push((itos, cotos));
+
+            if next.is_empty() {
+                break;
+            }
+        }
+        Ok(descendants)
+    })
+}
+
+/// Finds the shortest paths between two [Coto]s by breadth first search
+/// with a query for each level (so it won't load the entire graph).
+///
+/// Each path is returned as a chain of [Ito]s ordered from `from` to `to`.
+/// If `directed` is `false`, itos can be followed in the reverse direction
+/// (from target to source). The search will give up after `max_depth` levels
+/// and return an empty vec if the two cotos are not connected within the depth.
+pub(crate) fn shortest_paths<'a, Conn: ReadConn>(
+    from: &'a Id<Coto>,
+    to: &'a Id<Coto>,
+    max_depth: Option<usize>,
+    directed: bool,
+) -> impl Operation<Conn, Vec<Vec<Ito>>> + 'a {
+    read_op(move |conn| {
+        if from == to {
+            return Ok(vec![Vec::new()]);
+        }
+
+        // Itos leading to each visited coto on the shortest paths from `from`.
+        let mut predecessors: HashMap<Id<Coto>, Vec<Ito>> = HashMap::new();
+        let mut visited: HashSet<Id<Coto>> = [*from].into();
+        let mut frontier: HashSet<Id<Coto>> = [*from].into();
+        let mut depth = 0;
+        while !frontier.is_empty() && max_depth.is_none_or(|max| depth < max) {
+            // Next itos
+            let itos: Vec<Ito> = if directed {
+                itos::table
+                    .filter(itos::source_coto_id.eq_any(&frontier))
+                    .load::<Ito>(conn)?
+            } else {
+                itos::table
+                    .filter(
+                        itos::source_coto_id
+                            .eq_any(&frontier)
+                            .or(itos::target_coto_id.eq_any(&frontier)),
+                    )
+                    .load::<Ito>(conn)?
+            };
+
+            // Unvisited cotos in the next level and the itos leading to them
+            let mut level: HashMap<Id<Coto>, Vec<Ito>> = HashMap::new();
+            for ito in itos.into_iter() {
+                if frontier.contains(&ito.source_coto_id) && !visited.contains(&ito.target_coto_id)
+                {
+                    level
+                        .entry(ito.target_coto_id)
+                        .or_default()
+                        .push(ito.clone());
+                }
+                if !directed
+                    && frontier.contains(&ito.target_coto_id)
+                    && !visited.contains(&ito.source_coto_id)
+                {
+                    level.entry(ito.source_coto_id).or_default().push(ito);
+                }
+            }
+            depth += 1;
+
+            frontier = level.keys().copied().collect();
+            visited.extend(&frontier);
+            predecessors.extend(level);
+            if frontier.contains(to) {
+                return Ok(collect_paths(from, to, &predecessors));
+            }
+        }
+        Ok(Vec::new())
+    })
+}
+
+/// The maximum number of paths returned by [shortest_paths()] to avoid
+/// combinatorial explosion in densely connected graphs.
+const MAX_SHORTEST_PATHS: usize = 100;
+
+fn collect_paths(
+    from: &Id<Coto>,
+    to: &Id<Coto>,
+    predecessors: &HashMap<Id<Coto>, Vec<Ito>>,
+) -> Vec<Vec<Ito>> {
+    if to == from {
+        return vec![Vec::new()];
+    }
+    let mut paths = Vec::new();
+    for ito in predecessors.get(to).map(Vec::as_slice).unwrap_or_default() {
+        // The other end of the ito (it could be reversed in an undirected search)
+        let previous = if ito.target_coto_id == *to {
+            &ito.source_coto_id
+        } else {
+            &ito.target_coto_id
+        };
+        for mut path in collect_paths(from, previous, predecessors) {
+            if paths.len() >= MAX_SHORTEST_PATHS {
+                return paths;
+            }
+            path.push(ito.clone());
+            paths.push(path);
+        }
+    }
+    paths
+}
+
+/// (ito ID, node ID, source coto ID, target coto ID)
+type ItoEnds = (Id<Ito>, Id<Node>, Id<Coto>, Id<Coto>);
+
+/// Inspects the cotos and itos in the given scope for structural problems.
+///
+/// Itos are regarded as in the scope if either of their ends is in the scope,
+/// while a cycle has to be made up of cotos in the scope.
+pub(crate) fn health<Conn: ReadConn>(
+    scope: ScopeFilter<'_>,
+) -> impl Operation<Conn, GraphHealth> + '_ {
+    read_op(move |conn| {
+        let mut health = GraphHealth::default();
+
+        // Cotos in the scope
+        let scoped = || {
+            let mut query = cotos::table.into_boxed();
+            match scope {
+                Some(Either::Left(node_id)) => {
+                    query = query.filter(cotos::node_id.eq(node_id));
+                }
+                Some(Either::Right(posted_in_ids)) => {
+                    query = query.filter(cotos::posted_in_id.eq_any(posted_in_ids));
+                }
+                None => (),
+            }
+            query
+        };
+        let cotos: Vec<(Id<Coto>, bool, Option<Id<Coto>>)> = scoped()
+            .select((cotos::uuid, cotos::is_cotonoma, cotos::repost_of_id))
+            .order(cotos::rowid)
+            .load(conn)?;
+        let in_scope: HashSet<Id<Coto>> = cotos.iter().map(|(id, _, _)| *id).collect();
+
+        // Itos in the scope
+        let mut query = itos::table
+            .select((
+                itos::uuid,
+                itos::node_id,
+                itos::source_coto_id,
+                itos::target_coto_id,
+            ))
+            .order(itos::created_at)
+            .into_boxed();
+        if scope.is_some() {
+            query = query.filter(
+                itos::source_coto_id
+                    .eq_any(scoped().select(cotos::uuid))
+                    .or(itos::target_coto_id.eq_any(scoped().select(cotos::uuid))),
+            );
+        }
+        let itos: Vec<ItoEnds> = query.load::<ItoEnds>(conn)?;
+
+        // Orphans: cotos with no incoming itos (which means they are not pinned either)
+        let targets: HashSet<Id<Coto>> = itos.iter().map(|(_, _, _, target)| *target).collect();
+        health.orphans = cotos
+            .iter()
+            .filter(|(id, is_cotonoma, _)| !is_cotonoma && !targets.contains(id))
+            .map(|(id, _, _)| *id)
+            .collect();
+
+        // Cycles: strongly connected components made up of more than one coto
+        let mut petgraph = Petgraph::<Id<Coto>, ()>::new();
+        let mut node_indices = HashMap::new();
+        for (_, _, source, target) in itos.iter() {
+            if !in_scope.contains(source) || !in_scope.contains(target) {
+                continue;
+            }
+            let source = *node_indices
+                .entry(*source)
+                .or_insert_with(|| petgraph.add_node(*source));
+            let target = *node_indices
+                .entry(*target)
+                .or_insert_with(|| petgraph.add_node(*target));
+            petgraph.add_edge(source, target, ());
+        }
+        health.cycles = tarjan_scc(&petgraph)
+            .into_iter()
+            .filter(|component| component.len() > 1)
+            .map(|component| {
+                let mut coto_ids: Vec<Id<Coto>> =
+                    component.into_iter().map(|index| petgraph[index]).collect();
+                coto_ids.sort_by_key(Id::as_uuid);
+                coto_ids
+            })
+            .collect();
+        health.cycles.sort_by_key(|coto_ids| coto_ids[0].as_uuid());
+
+        // Itos connected to cotos in other nodes which are no longer replicated
+        // (the parent nodes from which the local node has been forked)
+        let forked_parent_ids: Vec<Id<Node>> = parent_nodes::table
+            .filter(parent_nodes::forked.eq(true))
+            .select(parent_nodes::node_id)
+            .load(conn)?;
+        if !forked_parent_ids.is_empty() {
+            let unreplicated: HashMap<Id<Coto>, Id<Node>> = cotos::table
+                .filter(cotos::node_id.eq_any(&forked_parent_ids))
+                .select((cotos::uuid, cotos::node_id))
+                .load::<(Id<Coto>, Id<Node>)>(conn)?
+                .into_iter()
+                .collect();
+            health.itos_to_unreplicated_nodes = itos
+                .iter()
+                .filter(|(_, node_id, source, target)| {
+                    [source, target].into_iter().any(|coto_id| {
+                        unreplicated
+                            .get(coto_id)
+                            .is_some_and(|coto_node_id| coto_node_id != node_id)
+                    })
+                })
+                .map(|(id, _, _, _)| *id)
+                .collect();
+        }
+
+        // Reposts whose originals are missing
+        let original_ids: HashSet<Id<Coto>> = cotos
+            .iter()
+            .filter_map(|(_, _, original)| *original)
+            .collect();
+        let mut existing: HashSet<Id<Coto>> = HashSet::new();
+        for chunk in original_ids
+            .into_iter()
+            .collect::<Vec<_>>()
+            .chunks(MAX_IDS_IN_CLAUSE)
+        {
+            existing.extend(
+                cotos::table
+                    .filter(cotos::uuid.eq_any(chunk))
+                    .select(cotos::uuid)
+                    .load::<Id<Coto>>(conn)?,
+            );
+        }
+        health.reposts_without_original = cotos
+            .iter()
+            .filter_map(|(id, _, original)| {
+                original
+                    .filter(|original| !existing.contains(original))
+                    .map(|_| *id)
+            })
+            .collect();
+
+        Ok(health)
+    })
+}
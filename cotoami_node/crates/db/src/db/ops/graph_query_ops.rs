@@ -0,0 +1,296 @@
+//! Execution of [GraphQuery]
+
+use std::collections::HashSet;
+
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use diesel::{prelude::*, sqlite::Sqlite};
+
+use super::{escape_like_pattern, MAX_IDS_IN_CLAUSE};
+use crate::{
+    db::op::*,
+    models::{
+        coto::Coto,
+        graph::{
+            query::{Condition, CotoField, Direction, GraphQuery, ItoField, Operator, Value},
+            Graph,
+        },
+        ito::Ito,
+        Id,
+    },
+    schema::{cotonomas, cotos, itos},
+};
+
+/// Applies a comparison to a boxed query. The value has been typed by the parser,
+/// so that it matches the column type.
+macro_rules! compare {
+    ($query:expr, $column:expr, $operator:expr, $value:expr) => {
+        match $operator {
+            Operator::Eq => $query.filter($column.eq($value)),
+            Operator::Ne => $query.filter($column.ne($value)),
+            Operator::Lt => $query.filter($column.lt($value)),
+            Operator::Le => $query.filter($column.le($value)),
+            Operator::Gt => $query.filter($column.gt($value)),
+            Operator::Ge => $query.filter($column.ge($value)),
+            Operator::Contains => $query.filter(
+                $column
+                    .like(format!("%{}%", escape_like_pattern(&$value, '\\')))
+                    .escape('\\'),
+            ),
+        }
+    };
+}
+
+/// Applies a condition with a nullable column, in which `null` is handled as `IS NULL`
+/// or `IS NOT NULL`.
+macro_rules! compare_nullable {
+    ($query:expr, $column:expr, $operator:expr, $value:expr) => {
+        match ($operator, $value) {
+            (Operator::Eq, Value::Null) => $query.filter($column.is_null()),
+            (Operator::Ne, Value::Null) => $query.filter($column.is_not_null()),
+            (operator, Value::String(s)) => compare!($query, $column, operator, s.clone()),
+            (_, value) => unreachable!("{value:?}"),
+        }
+    };
+}
+
+macro_rules! compare_datetime {
+    ($query:expr, $column:expr, $operator:expr, $value:expr) => {
+        match $value {
+            Value::DateTime(datetime) => match $operator {
+                Operator::Eq => $query.filter($column.eq(*datetime)),
+                Operator::Ne => $query.filter($column.ne(*datetime)),
+                Operator::Lt => $query.filter($column.lt(*datetime)),
+                Operator::Le => $query.filter($column.le(*datetime)),
+                Operator::Gt => $query.filter($column.gt(*datetime)),
+                Operator::Ge => $query.filter($column.ge(*datetime)),
+                Operator::Contains => unreachable!(),
+            },
+            value => unreachable!("{value:?}"),
+        }
+    };
+}
+
+/// An ito paired with its ends in the order of a path: (left, right, ito)
+type PathIto = (Id<Coto>, Id<Coto>, Ito);
+
+fn string_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        value => unreachable!("{value:?}"),
+    }
+}
+
+/// Returns a [Graph] consisting of the cotos and itos that match the path pattern
+/// of the given [GraphQuery], or `None` if there's no match.
+///
+/// Each node pattern is first evaluated into a set of candidate cotos and each edge
+/// pattern into a set of candidate itos. Then the candidates are pruned by propagating
+/// the connectivity forward and backward along the path, so that every remaining coto
+/// and ito is a part of at least one complete match of the path.
+///
+/// The root of the returned graph is the oldest coto matching the first node pattern.
+/// The other matched cotos are included up to `max_cotos` in the order of creation,
+/// along with the matched itos between the included cotos.
+pub(crate) fn query<Conn: ReadConn>(
+    query: GraphQuery,
+    max_cotos: usize,
+) -> impl Operation<Conn, Option<Graph>> {
+    read_op(move |conn| {
+        // Candidate cotos for each node pattern (`None` means any coto)
+        let mut coto_sets: Vec<Option<HashSet<Id<Coto>>>> = Vec::new();
+        for node in query.nodes.iter() {
+            if node.conditions.is_empty() {
+                coto_sets.push(None);
+            } else {
+                let mut coto_query = cotos::table.select(cotos::uuid).into_boxed();
+                for condition in node.conditions.iter() {
+                    coto_query = filter_cotos(conn, coto_query, condition)?;
+                }
+                coto_sets.push(Some(
+                    coto_query.load::<Id<Coto>>(conn)?.into_iter().collect(),
+                ));
+            }
+        }
+
+        // Candidate itos for each edge pattern
+        let mut ito_sets: Vec<Vec<PathIto>> = Vec::new();
+        for (i, edge) in query.edges.iter().enumerate() {
+            let (sources, targets) = match edge.direction {
+                Direction::Outgoing => (&coto_sets[i], &coto_sets[i + 1]),
+                Direction::Incoming => (&coto_sets[i + 1], &coto_sets[i]),
+            };
+            let mut ito_query = itos::table.into_boxed();
+            for condition in edge.conditions.iter() {
+                ito_query = filter_itos(ito_query, condition);
+            }
+            if let Some(ids) = sources.as_ref().filter(|s| s.len() <= MAX_IDS_IN_CLAUSE) {
+                ito_query = ito_query.filter(itos::source_coto_id.eq_any(ids.iter().copied()));
+            }
+            if let Some(ids) = targets.as_ref().filter(|s| s.len() <= MAX_IDS_IN_CLAUSE) {
+                ito_query = ito_query.filter(itos::target_coto_id.eq_any(ids.iter().copied()));
+            }
+            let itos = ito_query
+                .load::<Ito>(conn)?
+                .into_iter()
+                .map(|ito| match edge.direction {
+                    Direction::Outgoing => (ito.source_coto_id, ito.target_coto_id, ito),
+                    Direction::Incoming => (ito.target_coto_id, ito.source_coto_id, ito),
+                })
+                .collect();
+            ito_sets.push(itos);
+        }
+
+        // Forward pass: each ito must start from a candidate coto and
+        // each coto (except the first) must be reachable via a candidate ito.
+        for (i, itos) in ito_sets.iter_mut().enumerate() {
+            itos.retain(|(left, right, _)| {
+                contains(&coto_sets[i], left) && contains(&coto_sets[i + 1], right)
+            });
+            coto_sets[i + 1] = Some(itos.iter().map(|(_, right, _)| *right).collect());
+        }
+
+        // Backward pass: each ito must end with a coto that can continue the path.
+        for (i, itos) in ito_sets.iter_mut().enumerate().rev() {
+            itos.retain(|(_, right, _)| contains(&coto_sets[i + 1], right));
+            coto_sets[i] = Some(itos.iter().map(|(left, _, _)| *left).collect());
+        }
+
+        // Pick the matched cotos up to `max_cotos` in the order of creation
+        let mut keys: Vec<(Id<Coto>, NaiveDateTime)> = if coto_sets.iter().all(Option::is_some) {
+            let coto_ids: HashSet<Id<Coto>> =
+                coto_sets.iter().flatten().flatten().copied().collect();
+            let mut keys = Vec::with_capacity(coto_ids.len());
+            for chunk in coto_ids
+                .into_iter()
+                .collect::<Vec<_>>()
+                .chunks(MAX_IDS_IN_CLAUSE)
+            {
+                keys.extend(
+                    cotos::table
+                        .select((cotos::uuid, cotos::created_at))
+                        .filter(cotos::uuid.eq_any(chunk))
+                        .load::<(Id<Coto>, NaiveDateTime)>(conn)?,
+                );
+            }
+            keys
+        } else {
+            // Only a single node pattern without any conditions
+            cotos::table
+                .select((cotos::uuid, cotos::created_at))
+                .order((cotos::created_at, cotos::uuid))
+                .limit(max_cotos as i64 + 1)
+                .load::<(Id<Coto>, NaiveDateTime)>(conn)?
+        };
+        keys.sort_by(|(a_id, a_created_at), (b_id, b_created_at)| {
+            a_created_at
+                .cmp(b_created_at)
+                .then_with(|| a_id.as_uuid().cmp(&b_id.as_uuid()))
+        });
+        let Some(root_index) = keys.iter().position(|(id, _)| contains(&coto_sets[0], id)) else {
+            return Ok(None);
+        };
+        let (root_id, _) = keys.remove(root_index);
+        keys.truncate(max_cotos);
+
+        // Load the picked cotos
+        let mut picked: HashSet<Id<Coto>> = keys.iter().map(|(id, _)| *id).collect();
+        picked.insert(root_id);
+        let mut cotos = Vec::with_capacity(picked.len());
+        for chunk in picked
+            .iter()
+            .copied()
+            .collect::<Vec<_>>()
+            .chunks(MAX_IDS_IN_CLAUSE)
+        {
+            cotos.extend(
+                cotos::table
+                    .filter(cotos::uuid.eq_any(chunk))
+                    .load::<Coto>(conn)?,
+            );
+        }
+        let Some(root_index) = cotos.iter().position(|coto| coto.uuid == root_id) else {
+            return Ok(None);
+        };
+
+        // Build a graph
+        let mut graph = Graph::new(cotos.remove(root_index));
+        for coto in cotos {
+            graph.add_coto(coto);
+        }
+        let mut added_itos = HashSet::new();
+        for (left, right, ito) in ito_sets.into_iter().flatten() {
+            if picked.contains(&left) && picked.contains(&right) && added_itos.insert(ito.uuid) {
+                graph.add_ito(ito);
+            }
+        }
+        graph.sort_itos();
+        Ok(Some(graph))
+    })
+}
+
+fn contains(set: &Option<HashSet<Id<Coto>>>, id: &Id<Coto>) -> bool {
+    set.as_ref().is_none_or(|set| set.contains(id))
+}
+
+fn filter_cotos<'a>(
+    conn: &mut SqliteConnection,
+    query: cotos::BoxedQuery<'a, Sqlite, diesel::sql_types::Text>,
+    condition: &Condition<CotoField>,
+) -> Result<cotos::BoxedQuery<'a, Sqlite, diesel::sql_types::Text>> {
+    let Condition {
+        field,
+        operator,
+        value,
+    } = condition;
+    Ok(match field {
+        CotoField::Id => compare!(query, cotos::uuid, operator, string_value(value)),
+        CotoField::Node => compare!(query, cotos::node_id, operator, string_value(value)),
+        CotoField::PostedBy => compare!(query, cotos::posted_by_id, operator, string_value(value)),
+        CotoField::PostedIn => compare_nullable!(query, cotos::posted_in_id, operator, value),
+        CotoField::Content => compare_nullable!(query, cotos::content, operator, value),
+        CotoField::Summary => compare_nullable!(query, cotos::summary, operator, value),
+        CotoField::Cotonoma => {
+            if let Value::Null = value {
+                compare_nullable!(query, cotos::posted_in_id, operator, value)
+            } else {
+                // Resolve the cotonoma names into IDs
+                let cotonoma_ids: Vec<String> = compare!(
+                    cotonomas::table.select(cotonomas::uuid).into_boxed(),
+                    cotonomas::name,
+                    operator,
+                    string_value(value)
+                )
+                .load(conn)?;
+                query.filter(cotos::posted_in_id.eq_any(cotonoma_ids))
+            }
+        }
+        CotoField::IsCotonoma => match (operator, value) {
+            (Operator::Eq, Value::Bool(b)) => query.filter(cotos::is_cotonoma.eq(*b)),
+            (Operator::Ne, Value::Bool(b)) => query.filter(cotos::is_cotonoma.ne(*b)),
+            condition => unreachable!("{condition:?}"),
+        },
+        CotoField::CreatedAt => compare_datetime!(query, cotos::created_at, operator, value),
+        CotoField::UpdatedAt => compare_datetime!(query, cotos::updated_at, operator, value),
+    })
+}
+
+fn filter_itos<'a>(
+    query: itos::BoxedQuery<'a, Sqlite>,
+    condition: &Condition<ItoField>,
+) -> itos::BoxedQuery<'a, Sqlite> {
+    let Condition {
+        field,
+        operator,
+        value,
+    } = condition;
+    match field {
+        ItoField::Id => compare!(query, itos::uuid, operator, string_value(value)),
+        ItoField::Node => compare!(query, itos::node_id, operator, string_value(value)),
+        ItoField::CreatedBy => compare!(query, itos::created_by_id, operator, string_value(value)),
+        ItoField::Description => compare_nullable!(query, itos::description, operator, value),
+        ItoField::Details => compare_nullable!(query, itos::details, operator, value),
+        ItoField::CreatedAt => compare_datetime!(query, itos::created_at, operator, value),
+        ItoField::UpdatedAt => compare_datetime!(query, itos::updated_at, operator, value),
+    }
+}
@@ -25,6 +25,15 @@ pub(crate) fn get<Conn: ReadConn>(id: &Id<Ito>) -> impl Operation<Conn, Option<I
     })
 }
 
+pub(crate) fn all<Conn: ReadConn>() -> impl Operation<Conn, Vec<Ito>> {
+    read_op(move |conn| {
+        itos::table
+            .order(itos::created_at.asc())
+            .load::<Ito>(conn)
+            .map_err(anyhow::Error::from)
+    })
+}
+
 pub(crate) fn try_get<Conn: ReadConn>(
     id: &Id<Ito>,
 ) -> impl Operation<Conn, Result<Ito, DatabaseError>> + '_ {
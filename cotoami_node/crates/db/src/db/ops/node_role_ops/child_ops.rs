@@ -2,19 +2,22 @@
 
 use std::ops::DerefMut;
 
+use chrono::NaiveDateTime;
 use diesel::prelude::*;
 use validator::Validate;
 
 use crate::{
     db::{error::*, op::*, ops, ops::Page},
     models::{
+        changelog::ChangeSubscription,
         node::{
-            child::{ChildNode, ChildNodeInput, NewChildNode, UpdateChildNode},
+            child::{ChildNode, ChildNodeInput, NewChildNode, NewHandledRequest, UpdateChildNode},
+            parent::OutboxEntry,
             Node,
         },
         Id,
     },
-    schema::{child_nodes, nodes},
+    schema::{child_nodes, handled_requests, nodes},
 };
 
 /// Returns a [ChildNode] by its ID.
@@ -91,6 +94,64 @@ pub(crate) fn update<'a>(
     })
 }
 
+/// Records the serial number of the last change that a child has received.
+/// The number will never go back even if a smaller one is given.
+pub(crate) fn acknowledge_changes(
+    id: &Id<Node>,
+    serial_number: i64,
+) -> impl Operation<WriteConn, usize> + '_ {
+    write_op(move |conn| {
+        diesel::update(child_nodes::table)
+            .filter(child_nodes::node_id.eq(id))
+            .filter(child_nodes::changes_acknowledged.lt(serial_number))
+            .set(child_nodes::changes_acknowledged.eq(serial_number))
+            .execute(conn.deref_mut())
+            .map_err(anyhow::Error::from)
+    })
+}
+
+/// Returns the minimum number of the changes acknowledged by the child nodes,
+/// or `None` if there are no child nodes.
+pub(crate) fn min_changes_acknowledged<Conn: ReadConn>() -> impl Operation<Conn, Option<i64>> {
+    read_op(move |conn| {
+        child_nodes::table
+            .select(diesel::dsl::min(child_nodes::changes_acknowledged))
+            .first(conn)
+            .map_err(anyhow::Error::from)
+    })
+}
+
+/// Records the [ChangeSubscription] with which a child has started a session.
+pub(crate) fn set_subscription<'a>(
+    id: &'a Id<Node>,
+    subscription: Option<&'a ChangeSubscription>,
+) -> impl Operation<WriteConn, ChildNode> + 'a {
+    write_op(move |conn| {
+        let (cotonoma_ids, recursive) = ChangeSubscription::to_columns(subscription);
+        diesel::update(child_nodes::table.find(id))
+            .set((
+                child_nodes::subscribed_cotonoma_ids.eq(cotonoma_ids),
+                child_nodes::subscription_recursive.eq(recursive),
+            ))
+            .get_result(conn.deref_mut())
+            .map_err(anyhow::Error::from)
+    })
+}
+
+/// Records whether a child has requested to omit media content from the changes
+/// on starting a session.
+pub(crate) fn set_lazy_media(
+    id: &Id<Node>,
+    lazy_media: bool,
+) -> impl Operation<WriteConn, ChildNode> + '_ {
+    write_op(move |conn| {
+        diesel::update(child_nodes::table.find(id))
+            .set(child_nodes::lazy_media.eq(lazy_media))
+            .get_result(conn.deref_mut())
+            .map_err(anyhow::Error::from)
+    })
+}
+
 pub(crate) fn edit<'a>(
     id: &'a Id<Node>,
     input: &'a ChildNodeInput,
@@ -101,3 +162,40 @@ pub(crate) fn edit<'a>(
         Ok(child)
     })
 }
+
+/// Returns `true` if a request from a child with the given idempotency key
+/// has already been handled.
+pub(crate) fn is_request_handled<Conn: ReadConn>(
+    idempotency_key: &Id<OutboxEntry>,
+) -> impl Operation<Conn, bool> + '_ {
+    read_op(move |conn| {
+        diesel::select(diesel::dsl::exists(
+            handled_requests::table.find(idempotency_key),
+        ))
+        .get_result(conn)
+        .map_err(anyhow::Error::from)
+    })
+}
+
+/// Records the idempotency key of a request from a child after handling it,
+/// returning `false` if the key has already been recorded.
+pub(crate) fn record_handled_request<'a>(
+    idempotency_key: &'a Id<OutboxEntry>,
+    node_id: &'a Id<Node>,
+) -> impl Operation<WriteConn, bool> + 'a {
+    write_op(move |conn| {
+        let affected = diesel::insert_or_ignore_into(handled_requests::table)
+            .values(NewHandledRequest::new(idempotency_key, node_id))
+            .execute(conn.deref_mut())?;
+        Ok(affected > 0)
+    })
+}
+
+/// Deletes the idempotency keys of the requests handled before the given datetime.
+pub(crate) fn prune_handled_requests(before: NaiveDateTime) -> impl Operation<WriteConn, usize> {
+    write_op(move |conn| {
+        diesel::delete(handled_requests::table.filter(handled_requests::created_at.lt(before)))
+            .execute(conn.deref_mut())
+            .map_err(anyhow::Error::from)
+    })
+}
@@ -2,21 +2,22 @@
 
 use std::{collections::HashMap, ops::DerefMut};
 
-use anyhow::Context;
+use anyhow::{ensure, Context};
 use chrono::NaiveDateTime;
 use diesel::{dsl::max, prelude::*};
 use validator::Validate;
 
 use crate::{
-    db::op::*,
+    db::{error::DatabaseError, op::*},
     models::{
+        changelog::ChangeSubscription,
         node::{
-            parent::{NewParentNode, ParentNode, UpdateParentNode},
+            parent::{NewOutboxEntry, NewParentNode, OutboxEntry, ParentNode, UpdateParentNode},
             Node,
         },
         Id,
     },
-    schema::{cotos, parent_nodes},
+    schema::{cotos, outbox, parent_nodes},
 };
 
 /// Returns a [ParentNode] by its ID.
@@ -144,6 +145,71 @@ pub(crate) fn mark_as_read(
     })
 }
 
+pub(crate) fn set_subscription<'a>(
+    id: &'a Id<Node>,
+    subscription: Option<&'a ChangeSubscription>,
+) -> impl Operation<WriteConn, ParentNode> + 'a {
+    composite_op::<WriteConn, _, _>(move |ctx| {
+        if let Some(parent) = get(id).run(ctx)? {
+            ensure!(
+                parent.can_subscribe(subscription),
+                DatabaseError::SubscriptionWidened {
+                    parent_node_id: *id
+                }
+            );
+        }
+        let mut update_parent = UpdateParentNode::new(id);
+        update_parent.set_subscription(subscription);
+        let parent = update(&update_parent).run(ctx)?;
+        Ok(parent)
+    })
+}
+
+pub(crate) fn set_lazy_media(
+    id: &Id<Node>,
+    lazy_media: bool,
+) -> impl Operation<WriteConn, ParentNode> + '_ {
+    composite_op::<WriteConn, _, _>(move |ctx| {
+        let mut update_parent = UpdateParentNode::new(id);
+        update_parent.lazy_media = Some(lazy_media);
+        let parent = update(&update_parent).run(ctx)?;
+        Ok(parent)
+    })
+}
+
+/// Marks the local node as forked from a parent at the given serial number of
+/// the local changelog, or as re-attached to the parent if `None`.
+pub(crate) fn set_forked_at(
+    id: &Id<Node>,
+    serial_number: Option<i64>,
+) -> impl Operation<WriteConn, ParentNode> + '_ {
+    composite_op::<WriteConn, _, _>(move |ctx| {
+        let mut update_parent = UpdateParentNode::new(id);
+        update_parent.set_forked_at(serial_number);
+        let parent = update(&update_parent).run(ctx)?;
+        Ok(parent)
+    })
+}
+
+/// Updates [parent_nodes::changes_received] of a parent that has not sent any changes yet
+/// with the last serial number covered by a snapshot installed from the parent.
+pub(crate) fn skip_changes_by_snapshot(
+    id: &Id<Node>,
+    last_serial_number: i64,
+) -> impl Operation<WriteConn, ParentNode> + '_ {
+    write_op(move |conn| {
+        diesel::update(parent_nodes::table)
+            .filter(parent_nodes::node_id.eq(id))
+            .filter(parent_nodes::changes_received.eq(0))
+            .set((
+                parent_nodes::changes_received.eq(last_serial_number),
+                parent_nodes::last_change_received_at.eq(Some(crate::current_datetime())),
+            ))
+            .get_result(conn.deref_mut())
+            .with_context(|| format!("Changes have already been received from: {id}"))
+    })
+}
+
 /// Updates [parent_nodes::changes_received] with a number that must be the current value + 1.
 /// If the `incremented_number` is not an expected value, `Err(NotFound)` will be returned.
 pub(crate) fn increment_changes_received(
@@ -165,3 +231,52 @@ pub(crate) fn increment_changes_received(
             .with_context(|| format!("Invalid change number increment on: {id}"))
     })
 }
+
+/// Queues a request to a parent into the outbox with a new idempotency key.
+pub(crate) fn enqueue<'a>(
+    parent_node_id: &'a Id<Node>,
+    command: &'a [u8],
+) -> impl Operation<WriteConn, OutboxEntry> + 'a {
+    write_op(move |conn| {
+        diesel::insert_into(outbox::table)
+            .values(NewOutboxEntry::new(parent_node_id, command))
+            .get_result(conn.deref_mut())
+            .map_err(anyhow::Error::from)
+    })
+}
+
+/// Returns the requests queued to a parent in the order in which they have been queued.
+pub(crate) fn outbox<Conn: ReadConn>(
+    parent_node_id: &Id<Node>,
+) -> impl Operation<Conn, Vec<OutboxEntry>> + '_ {
+    read_op(move |conn| {
+        outbox::table
+            .filter(outbox::parent_node_id.eq(parent_node_id))
+            .order(outbox::serial_number.asc())
+            .load::<OutboxEntry>(conn)
+            .map_err(anyhow::Error::from)
+    })
+}
+
+/// Returns the request to be sent first to a parent, if any.
+pub(crate) fn next_in_outbox<Conn: ReadConn>(
+    parent_node_id: &Id<Node>,
+) -> impl Operation<Conn, Option<OutboxEntry>> + '_ {
+    read_op(move |conn| {
+        outbox::table
+            .filter(outbox::parent_node_id.eq(parent_node_id))
+            .order(outbox::serial_number.asc())
+            .first(conn)
+            .optional()
+            .map_err(anyhow::Error::from)
+    })
+}
+
+/// Deletes a request from the outbox, returning `false` if it has already been deleted.
+pub(crate) fn dequeue(serial_number: i64) -> impl Operation<WriteConn, bool> {
+    write_op(move |conn| {
+        let affected =
+            diesel::delete(outbox::table.find(serial_number)).execute(conn.deref_mut())?;
+        Ok(affected > 0)
+    })
+}
@@ -34,6 +34,17 @@ pub fn enable_wal(conn: &mut SqliteConnection) -> Result<()> {
     Ok(())
 }
 
+/// Writes a consistent copy of the database of the connection into a new file at `path`
+/// by `VACUUM INTO`, which works with a read-only connection without blocking writers.
+///
+/// <https://www.sqlite.org/lang_vacuum.html#vacuuminto>
+pub fn vacuum_into(conn: &mut SqliteConnection, path: &str) -> Result<()> {
+    diesel::sql_query("VACUUM INTO ?")
+        .bind::<diesel::sql_types::Text, _>(path)
+        .execute(conn)?;
+    Ok(())
+}
+
 /// Setting a busy handler that sleeps for a specified amount of time when a table is locked.
 /// (The default busy callback is NULL)
 ///
@@ -49,7 +49,7 @@ impl<'a> DatabaseSession<'a> {
     }
 
     /// Runs a read operation in snapshot isolation.
-    fn read_transaction<Op, T>(&mut self, op: Op) -> Result<T>
+    pub(crate) fn read_transaction<Op, T>(&mut self, op: Op) -> Result<T>
     where
         Op: Operation<SqliteConnection, T>,
     {
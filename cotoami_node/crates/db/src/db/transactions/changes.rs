@@ -1,7 +1,12 @@
 use anyhow::Result;
+use diesel::sqlite::SqliteConnection;
 
 use crate::{
-    db::{ops::changelog_ops, DatabaseSession},
+    db::{
+        op::*,
+        ops::{changelog_ops, node_role_ops::child_ops, Page},
+        DatabaseSession,
+    },
     models::prelude::*,
 };
 
@@ -20,12 +25,162 @@ impl DatabaseSession<'_> {
         ))
     }
 
+    /// Installs a snapshot of the parent, which must not have sent any changes yet,
+    /// and returns the changes logged in the local changelog by the installation.
+    pub fn install_snapshot(
+        &self,
+        snapshot: &ChangelogSnapshot,
+        parent_node_id: &Id<Node>,
+    ) -> Result<Vec<ChangelogEntry>> {
+        let mut parent_node = self.globals.try_write_parent_node(parent_node_id)?;
+        let local_node = self.globals.try_read_local_node()?;
+        self.write_transaction(changelog_ops::install_snapshot(
+            snapshot,
+            &mut parent_node,
+            &local_node,
+        ))
+    }
+
+    /// Returns the changes imported from the parent that failed to be applied to the
+    /// local node.
+    pub fn failed_imports(
+        &mut self,
+        parent_node_id: &Id<Node>,
+        page_size: i64,
+        page_index: i64,
+        operator: &Operator,
+    ) -> Result<Page<ChangelogEntry>> {
+        operator.requires_to_be_owner()?;
+        self.read_transaction(changelog_ops::failed_imports(
+            parent_node_id,
+            page_size,
+            page_index,
+        ))
+    }
+
+    /// Applies the changes that failed to be imported again (ex. after the entities
+    /// they depend on have arrived), and returns the entries updated with the results.
+    pub fn retry_failed_imports(
+        &self,
+        serial_numbers: &[i64],
+        operator: &Operator,
+    ) -> Result<Vec<ChangelogEntry>> {
+        operator.requires_to_be_owner()?;
+        let image_max_size = self.globals.try_read_local_node()?.image_max_size();
+        self.write_transaction(composite_op::<WriteConn, _, _>(move |ctx| {
+            serial_numbers
+                .iter()
+                .map(|serial_number| {
+                    changelog_ops::retry_import(*serial_number, image_max_size).run(ctx)
+                })
+                .collect()
+        }))
+    }
+
+    /// Returns up to `limit` changes from the serial number `from`, cut to fit in
+    /// the `byte_budget` if given, along with the last serial number in the changelog.
     pub fn chunk_of_changes(
         &mut self,
         from: i64,
         limit: i64,
+        byte_budget: Option<usize>,
     ) -> Result<(Vec<ChangelogEntry>, i64)> {
-        self.read_transaction(changelog_ops::chunk(from, limit))
+        self.read_transaction(changelog_ops::chunk(from, limit, byte_budget))
+    }
+
+    /// Returns a snapshot of the replicated entities, which is read in a single
+    /// transaction to be consistent with [ChangelogSnapshot::last_serial_number].
+    ///
+    /// The whole snapshot is loaded into memory, from which the media content of cotos
+    /// is omitted ([ChangelogSnapshot::omitted_media]).
+    pub fn changelog_snapshot(&mut self) -> Result<ChangelogSnapshot> {
+        self.read_transaction(changelog_ops::snapshot())
+    }
+
+    /// Replaces the changes irrelevant to the given subscription with placeholders.
+    ///
+    /// See [changelog_ops::subscription] for which changes are relevant.
+    pub fn filter_changes(
+        &mut self,
+        changes: Vec<ChangelogEntry>,
+        subscription: &ChangeSubscription,
+    ) -> Result<Vec<ChangelogEntry>> {
+        self.read_transaction(changelog_ops::subscription::filter(changes, subscription))
+    }
+
+    /// Returns the media content of the coto if its hash ([Coto::media_hash]) matches
+    /// the given one, which is the media omitted from a change ([Change::MediaOmitted]).
+    pub fn coto_media_by_hash(
+        &mut self,
+        coto_id: &Id<Coto>,
+        hash: &str,
+    ) -> Result<Option<CotoMedia>> {
+        self.read_transaction(changelog_ops::media::get_by_hash(coto_id, hash))
+    }
+
+    pub fn missing_media_of(&mut self, coto_id: &Id<Coto>) -> Result<Option<MissingMedia>> {
+        self.read_transaction(changelog_ops::media::get_missing(coto_id))
+    }
+
+    /// Returns the media omitted from the imported changes that have not yet been
+    /// fetched, in the order in which they have been found missing.
+    pub fn missing_media(&mut self) -> Result<Vec<MissingMedia>> {
+        self.read_transaction(changelog_ops::media::all_missing())
+    }
+
+    /// Returns the missing media to be fetched from the given parent (including the ones
+    /// whose parent is unknown), in the order in which they have been found missing.
+    pub fn missing_media_from(&mut self, parent_node_id: &Id<Node>) -> Result<Vec<MissingMedia>> {
+        self.read_transaction(changelog_ops::media::missing_from(parent_node_id))
+    }
+
+    /// Stores the fetched content of a missing media into the coto and returns the
+    /// updated coto, or `None` if the media is no longer missing.
+    ///
+    /// An image will be resized to fit within [LocalNode::image_max_size].
+    pub fn store_missing_media(&self, coto_id: &Id<Coto>, content: &[u8]) -> Result<Option<Coto>> {
+        let image_max_size = self.globals.try_read_local_node()?.image_max_size();
+        self.write_transaction(changelog_ops::media::store_missing(
+            coto_id,
+            content,
+            image_max_size,
+        ))
+    }
+
+    /// Records that a child node has received the changes up to `serial_number`,
+    /// which allows the changelog to be compacted up to the number.
+    pub fn acknowledge_changes(&self, child_id: &Id<Node>, serial_number: i64) -> Result<()> {
+        self.write_transaction(child_ops::acknowledge_changes(child_id, serial_number))?;
+        Ok(())
+    }
+
+    /// Compacts the changelog entries that have been acknowledged by all the child
+    /// nodes, except for the latest `margin` ones.
+    ///
+    /// See [changelog_ops::compaction] for what will be compacted.
+    ///
+    /// The changelog is scanned in a read transaction, and then each group of the entries
+    /// to be compacted is rewritten in its own write transaction so as not to block the
+    /// other writes during the whole compaction.
+    pub fn compact_changelog(&mut self, margin: i64) -> Result<ChangelogCompaction> {
+        let plan = self.read_transaction(composite_op::<SqliteConnection, _, _>(move |ctx| {
+            let watermark = changelog_ops::compaction::watermark(margin).run(ctx)?;
+            changelog_ops::compaction::plan(watermark).run(ctx)
+        }))?;
+        let mut compaction = ChangelogCompaction {
+            watermark: plan.watermark,
+            ..Default::default()
+        };
+        for serials in plan.cleared.iter() {
+            compaction.cleared +=
+                self.write_transaction(changelog_ops::compaction::clear(serials))?;
+        }
+        for run in plan.runs.iter() {
+            compaction.cleared +=
+                self.write_transaction(changelog_ops::compaction::merge_run(run))?;
+            compaction.merged += 1;
+        }
+        Ok(compaction)
     }
 
     pub fn last_change_number(&mut self) -> Result<Option<i64>> {
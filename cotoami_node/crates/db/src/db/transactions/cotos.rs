@@ -38,7 +38,7 @@ pub enum CotonomaScope {
     Depth(usize),
 }
 
-fn resolve_scope_filter(
+pub(crate) fn resolve_scope_filter(
     ctx: &mut Context<'_, SqliteConnection>,
     scope: Scope,
 ) -> Result<Option<Either<Id<Node>, Vec<Id<Cotonoma>>>>> {
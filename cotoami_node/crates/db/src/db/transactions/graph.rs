@@ -4,21 +4,60 @@ use diesel::sqlite::SqliteConnection;
 use crate::{
     db::{
         op::*,
-        ops::{coto_ops, graph_ops, ito_ops},
+        ops::{coto_ops, graph_ops, graph_query_ops, ito_ops},
+        transactions::cotos::{resolve_scope_filter, Scope},
         DatabaseSession,
     },
-    models::prelude::*,
+    models::{graph::query::GraphQuery, prelude::*},
 };
 
 impl DatabaseSession<'_> {
     pub fn graph(&mut self, root: Coto, until_cotonoma: bool) -> Result<Graph> {
-        self.read_transaction(graph_ops::traverse_by_level_queries(root, until_cotonoma))
+        let options = TraversalOptions {
+            until_cotonoma,
+            ..Default::default()
+        };
+        self.traverse_graph(root, options, None)
+    }
+
+    /// Traverses the graph from the `root` within the limits of the given `options`.
+    ///
+    /// If the traversal has been cut off by the limits, the returned graph will
+    /// have a [Graph::frontier], which can be passed as `frontier` to get the next page.
+    pub fn traverse_graph(
+        &mut self,
+        root: Coto,
+        options: TraversalOptions,
+        frontier: Option<TraversalFrontier>,
+    ) -> Result<Graph> {
+        self.read_transaction(graph_ops::traverse_by_level_queries(
+            root, options, frontier,
+        ))
     }
 
     pub fn graph_by_cte(&mut self, root: Coto, until_cotonoma: bool) -> Result<Graph> {
         self.read_transaction(graph_ops::traverse_by_recursive_cte(root, until_cotonoma))
     }
 
+    /// Inspects the graph in the given scope for structural problems
+    /// such as orphan cotos, ito cycles and dangling references.
+    pub fn graph_health(&mut self, scope: Scope) -> Result<GraphHealth> {
+        self.read_transaction(|ctx: &mut Context<'_, SqliteConnection>| {
+            let scope = resolve_scope_filter(ctx, scope)?;
+            graph_ops::health(scope.as_ref().map(|e| e.as_ref().map_right(Vec::as_slice))).run(ctx)
+        })
+    }
+
+    /// Returns a graph matching the given query written in the graph query language
+    /// (see [crate::models::graph::query]), or `None` if nothing matches.
+    ///
+    /// The graph contains at most `max_cotos` cotos (excluding the root), which are
+    /// the oldest ones of the matched cotos.
+    pub fn query_graph(&mut self, query: &str, max_cotos: usize) -> Result<Option<Graph>> {
+        let query = GraphQuery::parse(query)?;
+        self.read_transaction(graph_query_ops::query(query, max_cotos))
+    }
+
     pub fn incoming_neighbors(&mut self, coto_id: &Id<Coto>) -> Result<(Vec<Ito>, Vec<Coto>)> {
         self.read_transaction(|ctx: &mut Context<'_, SqliteConnection>| {
             let itos = ito_ops::incoming(coto_id).run(ctx)?;
@@ -32,4 +71,29 @@ impl DatabaseSession<'_> {
     pub fn ancestors_of(&mut self, coto_id: &Id<Coto>) -> Result<Vec<(Vec<Ito>, Vec<Coto>)>> {
         self.read_transaction(graph_ops::ancestors_of(coto_id))
     }
+
+    /// Returns the descendants of the given coto level by level (up to `max_depth` levels).
+    ///
+    /// Each level consists of the outgoing itos from the previous level and
+    /// the cotos newly reached by them.
+    pub fn descendants_of(
+        &mut self,
+        coto_id: &Id<Coto>,
+        max_depth: Option<usize>,
+    ) -> Result<Vec<(Vec<Ito>, Vec<Coto>)>> {
+        self.read_transaction(graph_ops::descendants_of(coto_id, max_depth))
+    }
+
+    /// Returns the shortest ito chains connecting the coto `from` to the coto `to`.
+    ///
+    /// If `directed` is `false`, itos will be followed in both directions.
+    pub fn paths_between(
+        &mut self,
+        from: &Id<Coto>,
+        to: &Id<Coto>,
+        max_depth: Option<usize>,
+        directed: bool,
+    ) -> Result<Vec<Vec<Ito>>> {
+        self.read_transaction(graph_ops::shortest_paths(from, to, max_depth, directed))
+    }
 }
@@ -24,6 +24,10 @@ impl DatabaseSession<'_> {
         self.read_transaction(ito_ops::outgoing(coto_ids))
     }
 
+    pub fn incoming_itos(&mut self, coto_id: &Id<Coto>) -> Result<Vec<Ito>> {
+        self.read_transaction(ito_ops::incoming(coto_id))
+    }
+
     pub fn sibling_itos(
         &mut self,
         source_coto_id: &Id<Coto>,
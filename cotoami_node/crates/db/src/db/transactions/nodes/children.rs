@@ -1,13 +1,19 @@
 use anyhow::Result;
+use chrono::Duration;
 
 use crate::{
     db::{
+        op::*,
         ops::{node_role_ops::child_ops, Page},
         DatabaseSession,
     },
     models::prelude::*,
 };
 
+/// Days to keep the idempotency keys of the requests handled for child nodes,
+/// after which a request sent again will be handled as a new one.
+const HANDLED_REQUESTS_RETENTION_DAYS: i64 = 30;
+
 impl DatabaseSession<'_> {
     pub fn recent_child_nodes(
         &mut self,
@@ -34,4 +40,41 @@ impl DatabaseSession<'_> {
         operator.requires_to_be_owner()?;
         self.write_transaction(child_ops::edit(id, input))
     }
+
+    /// Records the [ChangeSubscription] requested by a child node on starting a session.
+    pub fn set_child_subscription(
+        &self,
+        id: &Id<Node>,
+        subscription: Option<&ChangeSubscription>,
+    ) -> Result<ChildNode> {
+        self.write_transaction(child_ops::set_subscription(id, subscription))
+    }
+
+    /// Records whether a child node has requested to omit media content from the changes
+    /// on starting a session.
+    pub fn set_child_lazy_media(&self, id: &Id<Node>, lazy_media: bool) -> Result<ChildNode> {
+        self.write_transaction(child_ops::set_lazy_media(id, lazy_media))
+    }
+
+    /// Returns `true` if a request from a child node with the given idempotency key
+    /// has already been handled.
+    pub fn is_request_handled(&mut self, idempotency_key: &Id<OutboxEntry>) -> Result<bool> {
+        self.read_transaction(child_ops::is_request_handled(idempotency_key))
+    }
+
+    /// Records the idempotency key of a request from a child node after handling it,
+    /// returning `false` if the key has already been recorded.
+    ///
+    /// The keys older than [HANDLED_REQUESTS_RETENTION_DAYS] will be pruned at the same time.
+    pub fn record_handled_request(
+        &self,
+        idempotency_key: &Id<OutboxEntry>,
+        child_id: &Id<Node>,
+    ) -> Result<bool> {
+        let before = crate::current_datetime() - Duration::days(HANDLED_REQUESTS_RETENTION_DAYS);
+        self.write_transaction(composite_op::<WriteConn, _, _>(move |ctx| {
+            child_ops::prune_handled_requests(before).run(ctx)?;
+            child_ops::record_handled_request(idempotency_key, child_id).run(ctx)
+        }))
+    }
 }
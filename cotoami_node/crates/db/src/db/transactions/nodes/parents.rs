@@ -2,7 +2,11 @@ use anyhow::Result;
 use chrono::NaiveDateTime;
 
 use crate::{
-    db::{ops::node_role_ops::parent_ops, DatabaseSession},
+    db::{
+        error::*,
+        ops::{changelog_ops::fork, node_role_ops::parent_ops},
+        DatabaseSession,
+    },
     models::prelude::*,
 };
 
@@ -39,4 +43,108 @@ impl DatabaseSession<'_> {
         self.globals.cache_parent_node(parent);
         Ok(())
     }
+
+    /// Sets the [ChangeSubscription] to be requested to a parent node, which will
+    /// take effect on the next session with the parent.
+    ///
+    /// Once changes have been received from the parent, the subscription can only be
+    /// narrowed (See [ParentNode::can_subscribe]).
+    pub fn set_parent_subscription(
+        &self,
+        id: &Id<Node>,
+        subscription: Option<&ChangeSubscription>,
+        operator: &Operator,
+    ) -> Result<ParentNode> {
+        operator.requires_to_be_owner()?;
+        let parent = self.write_transaction(parent_ops::set_subscription(id, subscription))?;
+        self.globals.cache_parent_node(parent.clone());
+        Ok(parent)
+    }
+
+    /// Sets whether to request a parent node to omit media content from the changes,
+    /// which will take effect on the next session with the parent.
+    pub fn set_parent_lazy_media(
+        &self,
+        id: &Id<Node>,
+        lazy_media: bool,
+        operator: &Operator,
+    ) -> Result<ParentNode> {
+        operator.requires_to_be_owner()?;
+        let parent = self.write_transaction(parent_ops::set_lazy_media(id, lazy_media))?;
+        self.globals.cache_parent_node(parent.clone());
+        Ok(parent)
+    }
+
+    /// Forks the local node from a parent to stop replicating its changes, disabling
+    /// the connection with the parent (See [ParentNode::forked]).
+    pub fn fork_from_parent(&self, id: &Id<Node>, operator: &Operator) -> Result<ParentNode> {
+        operator.requires_to_be_owner()?;
+        let parent = self.write_transaction(fork::fork(id))?;
+        self.globals.cache_parent_node(parent.clone());
+        Ok(parent)
+    }
+
+    /// Re-attaches the local node to a parent from which it has been forked, enabling
+    /// the connection with the parent to import the changes made during the fork.
+    pub fn reattach_to_parent(&self, id: &Id<Node>, operator: &Operator) -> Result<ParentNode> {
+        operator.requires_to_be_owner()?;
+        let parent = self.write_transaction(fork::reattach(id))?;
+        self.globals.cache_parent_node(parent.clone());
+        Ok(parent)
+    }
+
+    /// Returns the local changes since the fork from a parent that involve the entities
+    /// owned by the parent.
+    pub fn diverged_changes(
+        &mut self,
+        id: &Id<Node>,
+        operator: &Operator,
+    ) -> Result<Vec<ChangelogEntry>> {
+        operator.requires_to_be_owner()?;
+        let parent = self
+            .globals
+            .parent_node(id)
+            .ok_or(DatabaseError::not_found(EntityKind::ParentNode, *id))?;
+        let local_node_id = self.globals.try_get_local_node_id()?;
+        self.read_transaction(fork::diverged_changes(&parent, &local_node_id))
+    }
+
+    /// Summarizes the changes made in a forked parent (not yet imported) and returns them
+    /// along with the ones conflicting with the local changes returned by
+    /// [DatabaseSession::diverged_changes].
+    ///
+    /// The parent changes can be given in chunks not to hold all of them at once.
+    pub fn fork_conflicts(
+        &mut self,
+        id: &Id<Node>,
+        parent_changes: &[ChangelogEntry],
+        local_changes: &[ChangelogEntry],
+        operator: &Operator,
+    ) -> Result<(Vec<ChangeSummary>, Vec<ForkConflict>)> {
+        operator.requires_to_be_owner()?;
+        self.read_transaction(fork::conflicts(id, parent_changes, local_changes))
+    }
+
+    /// Queues a request to a parent node into the outbox to be sent when reconnected.
+    pub fn enqueue_to_parent(&self, id: &Id<Node>, command: &[u8]) -> Result<OutboxEntry> {
+        self.globals
+            .parent_node(id)
+            .ok_or(DatabaseError::not_found(EntityKind::ParentNode, *id))?;
+        self.write_transaction(parent_ops::enqueue(id, command))
+    }
+
+    /// Returns the requests queued to a parent node in the order in which they will be sent.
+    pub fn outbox(&mut self, id: &Id<Node>) -> Result<Vec<OutboxEntry>> {
+        self.read_transaction(parent_ops::outbox(id))
+    }
+
+    /// Returns the request to be sent first to a parent node, if any.
+    pub fn next_in_outbox(&mut self, id: &Id<Node>) -> Result<Option<OutboxEntry>> {
+        self.read_transaction(parent_ops::next_in_outbox(id))
+    }
+
+    /// Deletes a request from the outbox after it has been sent or rejected.
+    pub fn dequeue_from_outbox(&self, serial_number: i64) -> Result<bool> {
+        self.write_transaction(parent_ops::dequeue(serial_number))
+    }
 }
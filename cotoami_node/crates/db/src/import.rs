@@ -0,0 +1,56 @@
+//! Importing data from external formats into the local node.
+
+use chrono::NaiveDateTime;
+
+use crate::models::{node::Node, Id};
+
+pub mod archive;
+pub mod cotoami;
+pub mod markdown;
+pub mod opml;
+
+/// Derives a deterministic ID from the local node ID and a key (such as a file path)
+/// so that importing the same source again yields the same IDs.
+///
+/// The ID is a version 8 UUID made from a 128-bit FNV-1a hash, which is stable
+/// across platforms and Rust versions unlike [std::hash::DefaultHasher].
+fn derive_id<T>(node_id: &Id<Node>, key: &str) -> Id<T> {
+    const OFFSET_BASIS: u128 = 0x6c62272e07bb014262b821756295c58d;
+    const PRIME: u128 = 0x0000000001000000000000000000013b;
+    let hash = node_id
+        .as_uuid()
+        .as_bytes()
+        .iter()
+        .chain(key.as_bytes())
+        .fold(OFFSET_BASIS, |hash, byte| {
+            (hash ^ *byte as u128).wrapping_mul(PRIME)
+        });
+    Id::new(uuid::Builder::from_custom_bytes(hash.to_be_bytes()).into_uuid())
+}
+
+fn system_time_to_datetime(time: std::time::SystemTime) -> NaiveDateTime {
+    chrono::DateTime::<chrono::Utc>::from(time).naive_utc()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use anyhow::Result;
+    use googletest::prelude::*;
+
+    use super::*;
+    use crate::models::coto::Coto;
+
+    #[test]
+    fn derive_id() -> Result<()> {
+        let node_id: Id<Node> = Id::from_str("00000000-0000-0000-0000-000000000001")?;
+        let id1: Id<Coto> = super::derive_id(&node_id, "foo.md");
+        let id2: Id<Coto> = super::derive_id(&node_id, "foo.md");
+        let id3: Id<Coto> = super::derive_id(&node_id, "bar.md");
+        assert_that!(id1, eq(id2));
+        assert_that!(id1, not(eq(id3)));
+        assert_that!(id1.as_uuid().get_version_num(), eq(8));
+        Ok(())
+    }
+}
@@ -0,0 +1,326 @@
+//! Importing an archive written by [DatabaseSession::export_archive].
+//!
+//! The archived entities keep their UUIDs and timestamps in the local node, while
+//! the following references will be replaced:
+//!
+//! * The archived node will be replaced with the local node.
+//! * The root cotonoma of the archived node and any other cotonomas missing from
+//!   the archive will be replaced with the destination cotonoma of the import.
+//!
+//! Since the IDs are preserved, importing the same archive again skips the entities
+//! that have already been imported, which makes an interrupted import resumable.
+//! The changes made by an import are passed to the `on_change` callback of
+//! [import_archive] so that they can be published like the other local changes.
+
+use std::{
+    collections::HashSet,
+    io::{Read, Seek},
+};
+
+use anyhow::{bail, Result};
+use validator::Validate;
+
+use crate::{
+    archive::{ArchiveReader, ArchivedCoto, ArchivedCotonoma},
+    db::transactions::DatabaseSession,
+    models::{
+        changelog::ChangelogEntry, coto::Coto, cotonoma::Cotonoma, ito::Ito, node::Node, Bytes, Id,
+    },
+};
+
+/// Result of an import (or a dry run of it).
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ArchiveImportReport {
+    /// Number of nodes imported as authors of the entities
+    pub nodes: usize,
+
+    /// Number of cotonomas imported
+    pub cotonomas: usize,
+
+    /// Number of cotos imported, including the ones of cotonomas
+    pub cotos: usize,
+
+    /// Number of itos imported
+    pub itos: usize,
+
+    /// Number of media files imported
+    pub media: usize,
+
+    /// Number of entities skipped since they had already been imported
+    pub already_imported: usize,
+
+    /// Entities that couldn't be imported: (entity ID, reason)
+    pub rejected: Vec<(String, String)>,
+}
+
+/// Imports an archive into the cotonoma specified by `cotonoma_id` in the local node.
+///
+/// If `dry_run` is `true`, nothing will be written to the database and the returned
+/// report tells what would be imported or rejected. Otherwise, each change made to
+/// the database will be passed to `on_change`.
+pub fn import_archive<R: Read + Seek>(
+    ds: &mut DatabaseSession<'_>,
+    reader: R,
+    cotonoma_id: &Id<Cotonoma>,
+    dry_run: bool,
+    on_change: impl FnMut(ChangelogEntry),
+) -> Result<ArchiveImportReport> {
+    let mut archive = ArchiveReader::new(reader)?;
+    let (cotonoma, cotonoma_coto) = ds.try_get_cotonoma_pair(cotonoma_id)?;
+    let local_node_id = ds.local_node()?.uuid;
+    if cotonoma.node_id != local_node_id {
+        bail!("The destination cotonoma must belong to the local node: {cotonoma_id}");
+    }
+
+    let cotonomas = archive.cotonomas()?;
+    let manifest = archive.manifest();
+    let mut importer = Importer {
+        ds,
+        dry_run,
+        local_node_id,
+        source_node_id: manifest.node.uuid,
+        source_root_coto_id: manifest.root_coto_id,
+        archived_cotonoma_ids: cotonomas
+            .iter()
+            .map(|archived| archived.cotonoma.uuid)
+            .collect(),
+        destination: cotonoma.uuid,
+        destination_coto: cotonoma_coto.uuid,
+        nodes: HashSet::new(),
+        cotos: HashSet::new(),
+        cotonomas: HashSet::new(),
+        cotonoma_names: HashSet::new(),
+        on_change,
+        report: ArchiveImportReport::default(),
+    };
+
+    for node in archive.nodes()? {
+        importer.import_node(node)?;
+    }
+    for ArchivedCotonoma {
+        cotonoma,
+        coto,
+        media,
+    } in cotonomas
+    {
+        importer.import_coto(&mut archive, coto, Some(cotonoma), media)?;
+    }
+    for ArchivedCoto { coto, media } in archive.cotos()? {
+        importer.import_coto(&mut archive, coto, None, media)?;
+    }
+    for ito in archive.itos()? {
+        importer.import_ito(ito)?;
+    }
+    Ok(importer.report)
+}
+
+struct Importer<'a, 'b, F: FnMut(ChangelogEntry)> {
+    ds: &'a mut DatabaseSession<'b>,
+    dry_run: bool,
+    local_node_id: Id<Node>,
+
+    source_node_id: Id<Node>,
+    source_root_coto_id: Id<Coto>,
+    archived_cotonoma_ids: HashSet<Id<Cotonoma>>,
+
+    destination: Id<Cotonoma>,
+    destination_coto: Id<Coto>,
+
+    /// Entities available in the local node or imported in this session
+    /// (also in a dry run)
+    nodes: HashSet<Id<Node>>,
+    cotos: HashSet<Id<Coto>>,
+    cotonomas: HashSet<Id<Cotonoma>>,
+    cotonoma_names: HashSet<String>,
+
+    on_change: F,
+    report: ArchiveImportReport,
+}
+
+impl<F: FnMut(ChangelogEntry)> Importer<'_, '_, F> {
+    fn import_node(&mut self, node: Node) -> Result<()> {
+        if node.uuid == self.local_node_id || node.uuid == self.source_node_id {
+            return Ok(());
+        }
+        if self.ds.node(&node.uuid)?.is_some() {
+            self.report.already_imported += 1;
+        } else {
+            self.report.nodes += 1;
+        }
+        if !self.dry_run {
+            // Update the node if the archived one is newer.
+            if let Some((_, log)) = self.ds.import_node(&node)? {
+                (self.on_change)(log);
+            }
+        }
+        self.nodes.insert(node.uuid);
+        Ok(())
+    }
+
+    fn import_coto<R: Read + Seek>(
+        &mut self,
+        archive: &mut ArchiveReader<R>,
+        mut coto: Coto,
+        mut cotonoma: Option<Cotonoma>,
+        media: Option<String>,
+    ) -> Result<()> {
+        let coto_id = coto.uuid;
+        if self.contains_coto(&coto_id)? {
+            self.report.already_imported += 1;
+            return Ok(());
+        }
+
+        coto.node_id = self.local_node_id;
+        coto.posted_by_id = self.map_node(coto.posted_by_id);
+        coto.posted_in_id = Some(self.map_cotonoma(coto.posted_in_id));
+        coto.reposted_in_ids = None;
+        if let Some(cotonoma) = cotonoma.as_mut() {
+            cotonoma.node_id = self.local_node_id;
+        }
+
+        let result = self
+            .check_dependencies(&coto, cotonoma.as_ref())
+            .and_then(|_| {
+                if let Some(path) = media.as_deref() {
+                    coto.media_content = Some(Bytes::from(archive.media(path)?));
+                }
+                self.insert_coto(coto, cotonoma)
+            });
+        match result {
+            Ok(()) => {
+                if media.is_some() {
+                    self.report.media += 1;
+                }
+            }
+            Err(e) => self.reject(format!("coto:{coto_id}"), e.to_string()),
+        }
+        Ok(())
+    }
+
+    fn check_dependencies(&mut self, coto: &Coto, cotonoma: Option<&Cotonoma>) -> Result<()> {
+        if !self.contains_node(&coto.posted_by_id)? {
+            bail!("missing node: {}", coto.posted_by_id);
+        }
+        if let Some(posted_in_id) = coto.posted_in_id {
+            if !self.contains_cotonoma(&posted_in_id)? {
+                bail!("missing cotonoma: {posted_in_id}");
+            }
+        }
+        if let Some(repost_of_id) = coto.repost_of_id {
+            if !self.contains_coto(&repost_of_id)? {
+                bail!("repost of a missing coto: {repost_of_id}");
+            }
+        }
+        if let Some(cotonoma) = cotonoma {
+            if self.cotonoma_names.contains(&cotonoma.name)
+                || self
+                    .ds
+                    .cotonoma_by_name(&cotonoma.name, &self.local_node_id)?
+                    .is_some()
+            {
+                bail!("duplicate cotonoma name: {}", cotonoma.name);
+            }
+        }
+        Ok(())
+    }
+
+    fn insert_coto(&mut self, coto: Coto, cotonoma: Option<Cotonoma>) -> Result<()> {
+        if self.dry_run {
+            coto.to_import(None)?.validate()?;
+            if let Some(cotonoma) = &cotonoma {
+                cotonoma.to_import().validate()?;
+            }
+        } else if let Some(cotonoma) = &cotonoma {
+            let (_, log) = self.ds.import_cotonoma(&coto, cotonoma)?;
+            (self.on_change)(log);
+        } else {
+            let (_, log) = self.ds.import_coto(&coto)?;
+            (self.on_change)(log);
+        }
+        self.cotos.insert(coto.uuid);
+        self.report.cotos += 1;
+        if let Some(cotonoma) = cotonoma {
+            self.cotonomas.insert(cotonoma.uuid);
+            self.cotonoma_names.insert(cotonoma.name);
+            self.report.cotonomas += 1;
+        }
+        Ok(())
+    }
+
+    fn import_ito(&mut self, mut ito: Ito) -> Result<()> {
+        let ito_id = ito.uuid;
+        if self.ds.ito(&ito_id)?.is_some() {
+            self.report.already_imported += 1;
+            return Ok(());
+        }
+
+        ito.node_id = self.local_node_id;
+        ito.created_by_id = self.map_node(ito.created_by_id);
+        ito.source_coto_id = self.map_coto(ito.source_coto_id);
+        ito.target_coto_id = self.map_coto(ito.target_coto_id);
+
+        let result = (|| {
+            if !self.contains_node(&ito.created_by_id)? {
+                bail!("missing node: {}", ito.created_by_id);
+            }
+            for coto_id in [&ito.source_coto_id, &ito.target_coto_id] {
+                if !self.contains_coto(coto_id)? {
+                    bail!("missing coto: {coto_id}");
+                }
+            }
+            if self.dry_run {
+                ito.to_import().validate()?;
+            } else {
+                let (_, log) = self.ds.import_ito(&ito)?;
+                (self.on_change)(log);
+            }
+            Ok(())
+        })();
+        match result {
+            Ok(()) => self.report.itos += 1,
+            Err(e) => self.reject(format!("ito:{ito_id}"), e.to_string()),
+        }
+        Ok(())
+    }
+
+    fn map_node(&self, id: Id<Node>) -> Id<Node> {
+        if id == self.source_node_id {
+            self.local_node_id
+        } else {
+            id
+        }
+    }
+
+    fn map_cotonoma(&self, id: Option<Id<Cotonoma>>) -> Id<Cotonoma> {
+        match id {
+            Some(id) if self.archived_cotonoma_ids.contains(&id) => id,
+            _ => self.destination,
+        }
+    }
+
+    fn map_coto(&self, id: Id<Coto>) -> Id<Coto> {
+        if id == self.source_root_coto_id {
+            self.destination_coto
+        } else {
+            id
+        }
+    }
+
+    fn contains_node(&mut self, id: &Id<Node>) -> Result<bool> {
+        Ok(*id == self.local_node_id || self.nodes.contains(id) || self.ds.node(id)?.is_some())
+    }
+
+    fn contains_cotonoma(&mut self, id: &Id<Cotonoma>) -> Result<bool> {
+        Ok(*id == self.destination
+            || self.cotonomas.contains(id)
+            || self.ds.contains_cotonoma(id)?)
+    }
+
+    fn contains_coto(&mut self, id: &Id<Coto>) -> Result<bool> {
+        Ok(self.cotos.contains(id) || self.ds.contains_coto(id)?)
+    }
+
+    fn reject(&mut self, entity: String, reason: String) {
+        self.report.rejected.push((entity, reason));
+    }
+}
@@ -0,0 +1,762 @@
+//! Importing a JSON dump exported from the original Cotoami.
+//!
+//! The export feature in the original Cotoami is implemented as:
+//! <https://github.com/cotoami/cotoami/blob/develop/lib/cotoami_web/controllers/database_controller.ex#L12>
+//!
+//! An import consists of two phases: [ImportPhase::Cotos] (including cotonomas) and
+//! then [ImportPhase::Connections] (which will be itos). The cotos keep their original
+//! IDs and the IDs of the itos are derived from their ends, so that an import
+//! interrupted by a crash can be resumed by running it again with the same dump:
+//! entities that have already been imported will be skipped.
+//!
+//! Note that the imported entities are not tracked persistently: a rerun decides what
+//! to skip only by whether the IDs exist in the database, so the entities that have been
+//! deleted after being imported will be imported again.
+//!
+//! With [CotoamiImportOptions::dry_run], the import only validates the dump and
+//! checks the dependencies between the entities without writing anything,
+//! and the returned report tells what would be imported or rejected.
+
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{BufReader, Read},
+    path::Path,
+};
+
+use anyhow::{anyhow, bail, Result};
+use chrono::{naive::NaiveDateTime, DateTime};
+use uuid::Uuid;
+use validator::Validate;
+
+use super::derive_id;
+use crate::{
+    db::transactions::DatabaseSession,
+    models::{changelog::ChangelogEntry, coto::Coto, cotonoma::Cotonoma, ito::Ito, node::Node, Id},
+};
+
+/// Number of entities to be processed between [ImportEvent::Progress] notifications
+const PROGRESS_INTERVAL: usize = 100;
+
+/// Options of [import_cotoami_json].
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct CotoamiImportOptions {
+    /// Cotonomas to be excluded from the import along with the cotos posted in them
+    #[serde(default)]
+    pub exclude_cotonomas: Vec<Id<Cotonoma>>,
+
+    /// If `true`, nothing will be written to the database.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum ImportPhase {
+    Cotos,
+    Connections,
+}
+
+/// An event notified while [import_cotoami_json] is running.
+#[derive(Debug)]
+pub enum ImportEvent {
+    /// `done` out of `total` entities in the `phase` have been processed.
+    Progress {
+        phase: ImportPhase,
+        done: usize,
+        total: usize,
+    },
+
+    /// A change has been made to the database.
+    Change(Box<ChangelogEntry>),
+}
+
+/// Result of an import (or a dry run of it).
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct CotoamiImportReport {
+    /// Number of cotos imported, including the ones of cotonomas
+    pub cotos: usize,
+
+    /// Number of cotonomas imported
+    pub cotonomas: usize,
+
+    /// Number of itos imported from connections
+    pub itos: usize,
+
+    /// Number of entities skipped since they had already been imported
+    pub already_imported: usize,
+
+    /// Cotos that couldn't be imported: (coto ID, reason)
+    pub rejected_cotos: Vec<(Id<Coto>, String)>,
+
+    /// Connections that couldn't be imported: (`start->end`, reason)
+    pub rejected_connections: Vec<(String, String)>,
+}
+
+/// Imports a [CotoamiExportJson] into the local node.
+///
+/// Cotos that don't belong to any cotonoma (which were displayed in "My Home" in the
+/// original Cotoami) will be imported in the root cotonoma of the local node.
+pub fn import_cotoami_json(
+    ds: &mut DatabaseSession<'_>,
+    json: CotoamiExportJson,
+    options: &CotoamiImportOptions,
+    on_event: impl FnMut(ImportEvent),
+) -> Result<CotoamiImportReport> {
+    let Some((root_cotonoma, _)) = ds.local_node_root()? else {
+        bail!("The root cotonoma is required for import.")
+    };
+    let mut importer = Importer {
+        ds,
+        options,
+        local_node_id: root_cotonoma.node_id,
+        root_cotonoma,
+        coto_waitlist: json.all_coto_ids(),
+        cotonoma_waitlist: json.all_cotonoma_ids(),
+        dry_run_cotos: HashSet::new(),
+        dry_run_cotonomas: HashSet::new(),
+        dry_run_itos: HashSet::new(),
+        on_event,
+        report: CotoamiImportReport::default(),
+    };
+    importer.import_cotos(json.cotos)?;
+    importer.import_connections(json.connections)?;
+    Ok(importer.report)
+}
+
+struct Importer<'a, 'b, F> {
+    ds: &'a mut DatabaseSession<'b>,
+    options: &'a CotoamiImportOptions,
+    local_node_id: Id<Node>,
+    root_cotonoma: Cotonoma,
+
+    /// Cotos and cotonomas in the dump that haven't been processed yet
+    coto_waitlist: HashSet<Id<Coto>>,
+    cotonoma_waitlist: HashSet<Id<Cotonoma>>,
+
+    /// Entities that would have been imported in a dry run
+    dry_run_cotos: HashSet<Id<Coto>>,
+    dry_run_cotonomas: HashSet<Id<Cotonoma>>,
+    dry_run_itos: HashSet<Id<Ito>>,
+
+    on_event: F,
+    report: CotoamiImportReport,
+}
+
+/// Result of a dependency check of a coto
+enum Dependency {
+    Ready,
+    Pending,
+    Missing(String),
+}
+
+impl<F: FnMut(ImportEvent)> Importer<'_, '_, F> {
+    fn import_cotos(&mut self, coto_jsons: Vec<CotoJson>) -> Result<()> {
+        let total = coto_jsons.len();
+        let mut done = 0;
+        let mut pendings = coto_jsons;
+        while !pendings.is_empty() {
+            let mut next_pendings: Vec<CotoJson> = Vec::new();
+            let pending_count = pendings.len();
+            for mut coto_json in pendings {
+                match self.check_dependencies(&mut coto_json)? {
+                    Dependency::Ready => self.import_coto(coto_json)?,
+                    Dependency::Pending => {
+                        next_pendings.push(coto_json);
+                        continue;
+                    }
+                    Dependency::Missing(reason) => self.reject_coto(&coto_json, reason),
+                }
+                done += 1;
+                self.progress(ImportPhase::Cotos, done, total);
+            }
+            if next_pendings.len() == pending_count {
+                // No progress in this round, which means the dependencies are circular.
+                for coto_json in next_pendings {
+                    self.reject_coto(&coto_json, "circular dependency".into());
+                }
+                self.progress(ImportPhase::Cotos, total, total);
+                break;
+            }
+            pendings = next_pendings;
+        }
+        Ok(())
+    }
+
+    fn check_dependencies(&mut self, coto_json: &mut CotoJson) -> Result<Dependency> {
+        // Exclude by cotonoma
+        if self.should_exclude(coto_json) {
+            return Ok(Dependency::Missing("excluded".into()));
+        }
+
+        // `posted_in_id`
+        if let Some(posted_in_id) = coto_json.posted_in_id {
+            if !self.contains_cotonoma(&posted_in_id)? {
+                if self.cotonoma_waitlist.contains(&posted_in_id) {
+                    // Wait until the cotonoma is imported
+                    return Ok(Dependency::Pending);
+                } else {
+                    return Ok(Dependency::Missing(format!(
+                        "missing cotonoma: {posted_in_id}"
+                    )));
+                }
+            }
+        }
+
+        // `repost_id`
+        if let Some(repost_id) = coto_json.repost_id {
+            if let Some(original) = self.ds.coto(&repost_id)? {
+                // Sync repost's update timestamp with the original
+                coto_json.updated_at = std::cmp::max(
+                    coto_json.updated_at,
+                    original.updated_at.and_utc().timestamp_millis(),
+                );
+            } else if !self.dry_run_cotos.contains(&repost_id) {
+                if self.coto_waitlist.contains(&repost_id) {
+                    // Wait until the original coto is imported
+                    return Ok(Dependency::Pending);
+                } else {
+                    return Ok(Dependency::Missing(format!(
+                        "repost of a missing coto: {repost_id}"
+                    )));
+                }
+            }
+        }
+
+        Ok(Dependency::Ready)
+    }
+
+    fn import_coto(&mut self, mut coto_json: CotoJson) -> Result<()> {
+        self.remove_from_waitlist(&coto_json);
+        if self.contains_coto(&coto_json.id)? {
+            self.report.already_imported += 1;
+            return Ok(());
+        }
+
+        let coto_id = coto_json.id;
+        let cotonoma_json = coto_json.cotonoma.take();
+        let result = coto_json
+            .into_coto(self.local_node_id)
+            .and_then(|mut coto| {
+                if coto.posted_in_id.is_none() {
+                    // A coto that doesn't belong to a cotonoma will be imported
+                    // in the root cotonoma.
+                    coto.posted_in_id = Some(self.root_cotonoma.uuid);
+                }
+                let cotonoma = cotonoma_json
+                    .map(|json| json.into_cotonoma(self.local_node_id, coto.uuid))
+                    .transpose()?;
+                self.insert_coto(coto, cotonoma)
+            });
+        if let Err(e) = result {
+            self.report.rejected_cotos.push((coto_id, e.to_string()));
+        }
+        Ok(())
+    }
+
+    fn insert_coto(&mut self, coto: Coto, cotonoma: Option<Cotonoma>) -> Result<()> {
+        let is_cotonoma = cotonoma.is_some();
+        if self.options.dry_run {
+            coto.to_import(None)?.validate()?;
+            if let Some(cotonoma) = cotonoma {
+                cotonoma.to_import().validate()?;
+                self.dry_run_cotonomas.insert(cotonoma.uuid);
+            }
+            self.dry_run_cotos.insert(coto.uuid);
+        } else if let Some(cotonoma) = cotonoma {
+            let (_, log) = self.ds.import_cotonoma(&coto, &cotonoma)?;
+            (self.on_event)(ImportEvent::Change(Box::new(log)));
+        } else {
+            let (_, log) = self.ds.import_coto(&coto)?;
+            (self.on_event)(ImportEvent::Change(Box::new(log)));
+        }
+        self.report.cotos += 1;
+        if is_cotonoma {
+            self.report.cotonomas += 1;
+        }
+        Ok(())
+    }
+
+    fn import_connections(&mut self, connection_jsons: Vec<ConnectionJson>) -> Result<()> {
+        let total = connection_jsons.len();
+        for (i, conn_json) in connection_jsons.into_iter().enumerate() {
+            self.import_connection(conn_json)?;
+            self.progress(ImportPhase::Connections, i + 1, total);
+        }
+        Ok(())
+    }
+
+    fn import_connection(&mut self, conn_json: ConnectionJson) -> Result<()> {
+        let label = format!("{}->{}", conn_json.start, conn_json.end);
+        if let Some(start_coto) = conn_json.start_as_coto() {
+            if !self.contains_coto(&start_coto)? {
+                let reason = format!("start coto is missing: {start_coto}");
+                self.report.rejected_connections.push((label, reason));
+                return Ok(());
+            }
+        }
+        if !self.contains_coto(&conn_json.end)? {
+            let reason = format!("end coto is missing: {}", conn_json.end);
+            self.report.rejected_connections.push((label, reason));
+            return Ok(());
+        }
+
+        let ito_id = conn_json.ito_id(&self.local_node_id);
+        if self.ds.ito(&ito_id)?.is_some() || self.dry_run_itos.contains(&ito_id) {
+            self.report.already_imported += 1;
+            return Ok(());
+        }
+        let result = conn_json
+            .into_ito(self.local_node_id, self.root_cotonoma.coto_id)
+            .and_then(|ito| {
+                if self.options.dry_run {
+                    ito.to_import().validate()?;
+                    self.dry_run_itos.insert(ito.uuid);
+                } else {
+                    let (_, log) = self.ds.import_ito(&ito)?;
+                    (self.on_event)(ImportEvent::Change(Box::new(log)));
+                }
+                Ok(())
+            });
+        match result {
+            Ok(()) => self.report.itos += 1,
+            Err(e) => self
+                .report
+                .rejected_connections
+                .push((label, e.to_string())),
+        }
+        Ok(())
+    }
+
+    fn should_exclude(&self, coto_json: &CotoJson) -> bool {
+        let excluded = |id: &Id<Cotonoma>| self.options.exclude_cotonomas.contains(id);
+        coto_json.posted_in_id.as_ref().is_some_and(excluded)
+            || coto_json
+                .cotonoma
+                .as_ref()
+                .is_some_and(|cotonoma| excluded(&cotonoma.id))
+    }
+
+    fn contains_coto(&mut self, id: &Id<Coto>) -> Result<bool> {
+        Ok(self.dry_run_cotos.contains(id) || self.ds.contains_coto(id)?)
+    }
+
+    fn contains_cotonoma(&mut self, id: &Id<Cotonoma>) -> Result<bool> {
+        Ok(self.dry_run_cotonomas.contains(id) || self.ds.contains_cotonoma(id)?)
+    }
+
+    fn remove_from_waitlist(&mut self, coto_json: &CotoJson) {
+        self.coto_waitlist.remove(&coto_json.id);
+        if let Some(cotonoma_json) = coto_json.cotonoma.as_ref() {
+            self.cotonoma_waitlist.remove(&cotonoma_json.id);
+        }
+    }
+
+    fn reject_coto(&mut self, coto_json: &CotoJson, reason: String) {
+        self.remove_from_waitlist(coto_json);
+        self.report.rejected_cotos.push((coto_json.id, reason));
+    }
+
+    fn progress(&mut self, phase: ImportPhase, done: usize, total: usize) {
+        if done.is_multiple_of(PROGRESS_INTERVAL) || done == total {
+            (self.on_event)(ImportEvent::Progress { phase, done, total });
+        }
+    }
+}
+
+fn from_timestamp_millis(millis: i64) -> Result<NaiveDateTime> {
+    DateTime::from_timestamp_millis(millis)
+        .ok_or(anyhow!("The timestamp is out of range: {millis}"))
+        .map(|dt| dt.naive_utc())
+}
+
+/////////////////////////////////////////////////////////////////////////////
+// CotoamiExportJson
+/////////////////////////////////////////////////////////////////////////////
+
+/// Exported JSON from the original Cotoami.
+/// <https://github.com/cotoami/cotoami/blob/develop/lib/cotoami_web/controllers/database_controller.ex#L12>
+#[derive(Debug, serde::Deserialize)]
+pub struct CotoamiExportJson {
+    cotos: Vec<CotoJson>,
+    connections: Vec<ConnectionJson>,
+}
+
+impl CotoamiExportJson {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::from_reader(BufReader::new(File::open(path)?))
+    }
+
+    pub fn from_reader<R: Read>(reader: R) -> Result<Self> { Ok(serde_json::from_reader(reader)?) }
+
+    pub fn coto_count(&self) -> usize { self.cotos.len() }
+
+    pub fn connection_count(&self) -> usize { self.connections.len() }
+
+    fn all_coto_ids(&self) -> HashSet<Id<Coto>> { self.cotos.iter().map(|coto| coto.id).collect() }
+
+    fn all_cotonoma_ids(&self) -> HashSet<Id<Cotonoma>> {
+        self.cotos
+            .iter()
+            .filter_map(|coto| coto.cotonoma.as_ref().map(|cotonoma| cotonoma.id))
+            .collect()
+    }
+}
+
+impl std::str::FromStr for CotoamiExportJson {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> { Ok(serde_json::from_str(s)?) }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+// CotoJson
+/////////////////////////////////////////////////////////////////////////////
+
+/// Exported coto JSON from the original Cotoami.
+/// <https://github.com/cotoami/cotoami/blob/develop/lib/cotoami_web/views/coto_view.ex#L48-L61>
+#[derive(Debug, serde::Deserialize)]
+struct CotoJson {
+    id: Id<Coto>,
+
+    content: Option<String>,
+    summary: Option<String>,
+
+    // `None` if this coto doesn't belong to any cotonoma, which is displayed only in "My Home".
+    // In the original Cotoami, "My Home" means "no cotonoma specified".
+    // On the other hand, in Cotoami Remake, that means being posted in the root cotonoma.
+    posted_in_id: Option<Id<Cotonoma>>,
+
+    as_cotonoma: bool,
+    cotonoma: Option<CotonomaJson>,
+
+    repost_id: Option<Id<Coto>>,
+
+    inserted_at: i64, // epoch milliseconds
+    updated_at: i64,  // epoch milliseconds
+}
+
+impl CotoJson {
+    fn into_coto(self, node_id: Id<Node>) -> Result<Coto> {
+        let (summary, content) = if self.as_cotonoma {
+            // The original version uses the `content` field as a cotonoma name
+            // while the new version uses the `summary` field.
+            (self.content, None)
+        } else {
+            (self.summary, self.content)
+        };
+        Ok(Coto {
+            uuid: self.id,
+            rowid: 0,
+            node_id,
+            posted_in_id: self.posted_in_id,
+            posted_by_id: node_id,
+            content,
+            media_content: None,
+            media_type: None,
+            summary,
+            is_cotonoma: self.as_cotonoma,
+            longitude: None,
+            latitude: None,
+            datetime_start: None,
+            datetime_end: None,
+            repost_of_id: self.repost_id,
+            reposted_in_ids: None, // will be restored during inserts
+            created_at: from_timestamp_millis(self.inserted_at)?,
+            updated_at: from_timestamp_millis(self.updated_at)?,
+        })
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+// CotonomaJson
+/////////////////////////////////////////////////////////////////////////////
+
+/// Exported cotonoma JSON from the original Cotoami.
+/// <https://github.com/cotoami/cotoami/blob/develop/lib/cotoami_web/views/cotonoma_view.ex#L61-L74>
+#[derive(Debug, serde::Deserialize)]
+#[allow(unused)]
+struct CotonomaJson {
+    id: Id<Cotonoma>,
+
+    key: String,
+    name: String,
+
+    shared: bool,
+
+    // Perhaps this property isn't used in the original Cotoami,
+    // I couldn't find usages in the source code :-(
+    pinned: bool,
+
+    // The revisions seem to be used only for detecting changes since the cotonoma is created:
+    // <https://github.com/cotoami/cotoami/blob/develop/assets/elm/src/App/Types/Coto.elm#L244>
+    timeline_revision: u32,
+    graph_revision: u32,
+
+    inserted_at: i64, // epoch milliseconds
+    updated_at: i64,  // epoch milliseconds
+
+    // This property is used for read/unread status management with the watchlist table:
+    // <https://github.com/cotoami/cotoami/blob/develop/priv/repo/migrations/20181101015822_create_watchlist.exs>
+    //
+    // Read/unread status management in the client-side:
+    // <https://github.com/cotoami/cotoami/blob/develop/assets/elm/src/App/Update/Watch.elm>
+    // <https://github.com/cotoami/cotoami/blob/develop/assets/elm/src/App/Views/Flow.elm#L480-L486>
+    last_post_timestamp: Option<i64>, // epoch milliseconds
+}
+
+impl CotonomaJson {
+    fn into_cotonoma(self, node_id: Id<Node>, coto_id: Id<Coto>) -> Result<Cotonoma> {
+        Ok(Cotonoma {
+            uuid: self.id,
+            node_id,
+            coto_id,
+            name: self.name,
+            created_at: from_timestamp_millis(self.inserted_at)?,
+            updated_at: from_timestamp_millis(self.updated_at)?,
+        })
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+// ConnectionJson
+/////////////////////////////////////////////////////////////////////////////
+
+/// Exported connection JSON from the original Cotoami.
+/// <https://github.com/cotoami/cotoami/blob/develop/lib/cotoami/services/coto_graph_service.ex#L222>
+#[derive(Debug, serde::Deserialize)]
+#[allow(unused)]
+struct ConnectionJson {
+    /// The `start` node could be a coto or an amishi.
+    ///
+    /// If the `start` is an amishi, this connection is one of the "root connections" of the
+    /// entire amishi's graph and which will be translated as an ito from root cotonoma
+    /// during import.
+    start: Uuid,
+    end: Id<Coto>,
+
+    created_by: Uuid, // amishi_id
+    created_in: Option<Id<Cotonoma>>,
+
+    linking_phrase: Option<String>,
+
+    order: i32,
+
+    created_at: i64, // epoch milliseconds
+}
+
+impl ConnectionJson {
+    /// Returns true if this connection is one of the root connections of the entire amishi's graph.
+    ///
+    /// Because only an amishi themself can create a connection from their amishi node,
+    /// it should be a root connection if `start` and `created_by` are the same value.
+    fn is_root(&self) -> bool { self.start == self.created_by }
+
+    fn start_as_coto(&self) -> Option<Id<Coto>> {
+        if self.is_root() {
+            None
+        } else {
+            Some(Id::new(self.start))
+        }
+    }
+
+    /// The ID of the ito to be imported from this connection, which is derived from
+    /// the ends of the connection so that it'll be the same in a resumed import.
+    fn ito_id(&self, node_id: &Id<Node>) -> Id<Ito> {
+        derive_id(node_id, &format!("connection:{}->{}", self.start, self.end))
+    }
+
+    fn into_ito(self, node_id: Id<Node>, root_coto_id: Id<Coto>) -> Result<Ito> {
+        let uuid = self.ito_id(&node_id);
+        let source_coto_id = if let Some(start_coto_id) = self.start_as_coto() {
+            start_coto_id
+        } else {
+            root_coto_id
+        };
+        Ok(Ito {
+            uuid,
+            node_id,
+            created_by_id: node_id,
+            source_coto_id,
+            target_coto_id: self.end,
+            description: self.linking_phrase,
+            details: None,
+            order: self.order,
+            created_at: from_timestamp_millis(self.created_at)?,
+            updated_at: from_timestamp_millis(self.created_at)?,
+        })
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+// tests
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use anyhow::Result;
+    use indoc::indoc;
+
+    use super::*;
+
+    #[test]
+    fn deserialize_coto_json() -> Result<()> {
+        let node_id: Id<Node> = Id::from_str("00000000-0000-0000-0000-000000000001")?;
+        let json = indoc! {r#"
+            {
+                "updated_at": 1507106650888,
+                "summary": null,
+                "reposted_in_ids": [],
+                "repost_id": null,
+                "posted_in_id": null,
+                "inserted_at": 1507106650888,
+                "id": "f05c0f03-8bb0-430e-a4d2-714c2922e0cd",
+                "cotonoma": null,
+                "content": "Nginx Ingress Controller",
+                "as_cotonoma": false
+            }
+        "#};
+        let coto: CotoJson = serde_json::from_str(json)?;
+        let coto = coto.into_coto(node_id)?;
+
+        assert_eq!(
+            coto.uuid,
+            Id::from_str("f05c0f03-8bb0-430e-a4d2-714c2922e0cd")?
+        );
+        assert_eq!(coto.content, Some("Nginx Ingress Controller".into()));
+        assert_eq!(coto.summary, None);
+        assert_eq!(coto.repost_of_id, None);
+        assert_eq!(coto.reposted_in_ids, None);
+        assert_eq!(coto.created_at.to_string(), "2017-10-04 08:44:10.888");
+        assert_eq!(coto.updated_at.to_string(), "2017-10-04 08:44:10.888");
+
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_coto_json2() -> Result<()> {
+        let node_id: Id<Node> = Id::from_str("00000000-0000-0000-0000-000000000001")?;
+        let json = indoc! {r#"
+            {
+                "updated_at": 1561866119804,
+                "summary": null,
+                "reposted_in_ids": [
+                    "a09327c2-d3d8-400e-b7ae-6af110fa982e",
+                    "e0871a62-33d0-407c-bdc0-9d72c6c84de4"
+                ],
+                "repost_id": null,
+                "posted_in_id": null,
+                "inserted_at": 1530275609616,
+                "id": "4d993f1c-270e-457a-9d12-92201e998691",
+                "cotonoma": null,
+                "content": "「最大多数の最小不幸」原則",
+                "as_cotonoma": false
+            }
+        "#};
+        let coto: CotoJson = serde_json::from_str(json)?;
+        let coto = coto.into_coto(node_id)?;
+
+        assert_eq!(
+            coto.uuid,
+            Id::from_str("4d993f1c-270e-457a-9d12-92201e998691")?
+        );
+        assert_eq!(coto.content, Some("「最大多数の最小不幸」原則".into()));
+        assert_eq!(coto.summary, None);
+        assert_eq!(coto.repost_of_id, None);
+        assert_eq!(coto.reposted_in_ids, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_cotonoma_json() -> Result<()> {
+        let node_id: Id<Node> = Id::from_str("00000000-0000-0000-0000-000000000001")?;
+        let coto_id: Id<Coto> = Id::from_str("00000000-0000-0000-0000-000000000002")?;
+        let json = indoc! {r#"
+            {
+                "updated_at": 1681253566558,
+                "timeline_revision": 102,
+                "shared": false,
+                "pinned": true,
+                "name": "Cotoami",
+                "last_post_timestamp": 1561866275044,
+                "key": "2al3mr9qljoslr23",
+                "inserted_at": 1507132300379,
+                "id": "43dea0e3-f19b-4837-8587-7ed55296c265",
+                "graph_revision": 0
+            }
+        "#};
+        let cotonoma: CotonomaJson = serde_json::from_str(json)?;
+        let cotonoma = cotonoma.into_cotonoma(node_id, coto_id)?;
+
+        assert_eq!(
+            cotonoma.uuid,
+            Id::from_str("43dea0e3-f19b-4837-8587-7ed55296c265")?
+        );
+        assert_eq!(cotonoma.name, "Cotoami");
+        assert_eq!(cotonoma.node_id, node_id);
+        assert_eq!(cotonoma.coto_id, coto_id);
+        assert_eq!(cotonoma.created_at.to_string(), "2017-10-04 15:51:40.379");
+        assert_eq!(cotonoma.updated_at.to_string(), "2023-04-11 22:52:46.558");
+
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_connection_json() -> Result<()> {
+        let node_id: Id<Node> = Id::from_str("00000000-0000-0000-0000-000000000001")?;
+        let root_coto_id: Id<Coto> = Id::from_str("00000000-0000-0000-0000-000000000002")?;
+        let json = indoc! {r#"
+            {
+                "start": "f05c0f03-8bb0-430e-a4d2-714c2922e0cd",
+                "order": 1,
+                "end": "72972fe8-695c-4086-86ff-29a12c8a98a4",
+                "created_by": "55111bd3-92e2-4b02-bc1a-15b74a945fd0",
+                "created_at": 1507106701180
+            }
+        "#};
+        let conn: ConnectionJson = serde_json::from_str(json)?;
+        let ito_id = conn.ito_id(&node_id);
+        let ito = conn.into_ito(node_id, root_coto_id)?;
+
+        assert_eq!(ito.uuid, ito_id);
+        assert_eq!(
+            ito.source_coto_id,
+            Id::from_str("f05c0f03-8bb0-430e-a4d2-714c2922e0cd")?
+        );
+        assert_eq!(
+            ito.target_coto_id,
+            Id::from_str("72972fe8-695c-4086-86ff-29a12c8a98a4")?
+        );
+        assert_eq!(ito.order, 1);
+        assert_eq!(ito.description, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_connection_json2() -> Result<()> {
+        let node_id: Id<Node> = Id::from_str("00000000-0000-0000-0000-000000000001")?;
+        let root_coto_id: Id<Coto> = Id::from_str("00000000-0000-0000-0000-000000000002")?;
+        let json = indoc! {r#"
+            {
+                "start": "55111bd3-92e2-4b02-bc1a-15b74a945fd0",
+                "order": 8,
+                "end": "d1b71c83-9eca-41c2-96ae-ee63bc31696c",
+                "created_by": "55111bd3-92e2-4b02-bc1a-15b74a945fd0",
+                "created_at": 1576546971349
+            }
+        "#};
+        let conn: ConnectionJson = serde_json::from_str(json)?;
+        let ito = conn.into_ito(node_id, root_coto_id)?;
+
+        assert_eq!(
+            ito.source_coto_id,
+            Id::from_str("00000000-0000-0000-0000-000000000002")?,
+            "The source coto should be the root coto."
+        );
+
+        Ok(())
+    }
+}
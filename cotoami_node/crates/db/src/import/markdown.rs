@@ -0,0 +1,724 @@
+//! Importing a folder of Markdown files such as an [Obsidian](https://obsidian.md/) vault.
+//!
+//! * The folder itself maps to the target cotonoma, and each subfolder to a cotonoma
+//!   posted in the cotonoma of its parent folder. Since cotonoma names are unique
+//!   in a node, folders with the same name will be merged into one cotonoma.
+//! * Each Markdown file (`*.md`) maps to a coto posted in the cotonoma of its folder.
+//!   The file name (or `title` in the front matter) becomes the summary and
+//!   the rest of the file the content.
+//! * `[[wikilinks]]` to other Markdown files map to itos from the linking coto.
+//! * Dates in the front matter (`date` or `start`, and `end`) map to a [DateTimeRange],
+//!   and `created` to the creation timestamp of the coto.
+//! * Embedded images (`![[image.png]]` or `![alt](image.png)`) map to media content.
+//!   The first image in a file goes into the coto of the file, and each of the rest
+//!   into a separate coto connected from it.
+//!
+//! The IDs of the imported entities are derived from the paths of the source files
+//! relative to the folder, which makes re-importing idempotent: files that have been
+//! imported before will be skipped (existing cotos won't be updated).
+//! The changes made by an import are passed to the `on_change` callback of
+//! [import_markdown] so that they can be published like the other local changes.
+//! Hidden files and folders (such as `.obsidian`) are ignored.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use super::{derive_id, system_time_to_datetime};
+use crate::{
+    current_datetime,
+    db::transactions::DatabaseSession,
+    models::{
+        changelog::ChangelogEntry, coto::Coto, cotonoma::Cotonoma, ito::Ito, node::Node, Bytes,
+        DateTimeRange, Id,
+    },
+};
+
+/// Result of a Markdown import (or a dry run of it).
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct MarkdownImportReport {
+    /// Number of cotonomas imported (or to be imported in a dry run)
+    pub cotonomas: usize,
+
+    /// Number of cotos imported (or to be imported in a dry run)
+    pub cotos: usize,
+
+    /// Number of itos imported (or to be imported in a dry run)
+    pub itos: usize,
+
+    /// Number of images imported as media content
+    pub media: usize,
+
+    /// Number of entities skipped since they already exist in the database
+    pub already_imported: usize,
+
+    /// Wikilinks that couldn't be resolved into Markdown files: (source file, link)
+    pub unresolved_links: Vec<(PathBuf, String)>,
+
+    /// Problems that didn't prevent the files from being imported: (file, message)
+    pub warnings: Vec<(PathBuf, String)>,
+
+    /// Files that couldn't be imported: (file, reason)
+    pub rejected: Vec<(PathBuf, String)>,
+}
+
+/// Imports the Markdown files in `dir` into the cotonoma `cotonoma_id`.
+///
+/// If `dry_run` is `true`, nothing will be written to the database, and the returned
+/// report tells what would be imported. Otherwise, each change made to the database
+/// will be passed to `on_change`.
+pub fn import_markdown(
+    ds: &mut DatabaseSession<'_>,
+    dir: impl AsRef<Path>,
+    cotonoma_id: &Id<Cotonoma>,
+    dry_run: bool,
+    on_change: impl FnMut(ChangelogEntry),
+) -> Result<MarkdownImportReport> {
+    let dir = dir.as_ref();
+    let target = ds.try_get_cotonoma(cotonoma_id)?;
+    let vault = Vault::scan(dir)?;
+    let mut importer = Importer {
+        ds,
+        dir,
+        node_id: target.node_id,
+        dry_run,
+        cotonomas: HashMap::from([(PathBuf::new(), target.uuid)]),
+        names: HashMap::new(),
+        on_change,
+        report: MarkdownImportReport::default(),
+    };
+    for folder in vault.folders.iter() {
+        importer.import_folder(folder)?;
+    }
+    let mut links = Vec::new();
+    for note in vault.notes.iter() {
+        importer.import_note(note, &vault, &mut links)?;
+    }
+    for (source, targets) in links {
+        importer.import_links(&source, targets)?;
+    }
+    Ok(importer.report)
+}
+
+/////////////////////////////////////////////////////////////////////////////
+// Vault
+/////////////////////////////////////////////////////////////////////////////
+
+/// Files in a folder to import. All the paths are relative to the folder.
+#[derive(Debug, Default)]
+struct Vault {
+    /// Subfolders (parents come first)
+    folders: Vec<PathBuf>,
+
+    /// Markdown files
+    notes: Vec<PathBuf>,
+
+    /// All the files including Markdown files
+    files: HashSet<PathBuf>,
+}
+
+impl Vault {
+    fn scan(dir: &Path) -> Result<Self> {
+        let mut vault = Self::default();
+        vault.scan_dir(dir, Path::new(""))?;
+        vault
+            .folders
+            .sort_by_key(|path| (path.components().count(), path.clone()));
+        vault.notes.sort();
+        Ok(vault)
+    }
+
+    fn scan_dir(&mut self, root: &Path, relative: &Path) -> Result<()> {
+        for entry in fs::read_dir(root.join(relative))? {
+            let entry = entry?;
+            let name = entry.file_name();
+            if name.to_string_lossy().starts_with('.') {
+                continue;
+            }
+            let path = relative.join(&name);
+            if entry.file_type()?.is_dir() {
+                self.scan_dir(root, &path)?;
+                self.folders.push(path);
+            } else {
+                if path.extension().is_some_and(|ext| ext == "md") {
+                    self.notes.push(path.clone());
+                }
+                self.files.insert(path);
+            }
+        }
+        Ok(())
+    }
+
+    /// Resolves a wikilink target (`Note`, `Note.md` or `folder/Note`) into a note.
+    /// A name without a folder has to be unique in the vault.
+    fn resolve_note(&self, target: &str) -> Option<&PathBuf> {
+        let target = target.trim();
+        let target = target.strip_suffix(".md").unwrap_or(target);
+        let by_path = self
+            .notes
+            .iter()
+            .find(|note| slash_path(&note.with_extension("")) == target);
+        by_path.or_else(|| {
+            let mut by_name = self
+                .notes
+                .iter()
+                .filter(|note| note.file_stem().is_some_and(|stem| stem == target));
+            match (by_name.next(), by_name.next()) {
+                (Some(note), None) => Some(note),
+                _ => None,
+            }
+        })
+    }
+
+    /// Resolves an image reference relative to the note, to the vault, or by its name.
+    fn resolve_file(&self, note: &Path, target: &str) -> Option<PathBuf> {
+        let target = target.trim().replace("%20", " ");
+        let note_dir = note.parent().unwrap_or(Path::new(""));
+        [note_dir.join(&target), PathBuf::from(&target)]
+            .into_iter()
+            .map(|path| normalize(&path))
+            .find(|path| self.files.contains(path))
+            .or_else(|| {
+                let mut by_name = self
+                    .files
+                    .iter()
+                    .filter(|file| file.file_name().is_some_and(|name| *name == *target));
+                match (by_name.next(), by_name.next()) {
+                    (Some(file), None) => Some(file.clone()),
+                    _ => None,
+                }
+            })
+    }
+}
+
+/// Returns a path string separated by slashes regardless of the platform,
+/// which is used as a key to derive IDs.
+fn slash_path(path: &Path) -> String {
+    path.components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Resolves `.` and `..` in a relative path.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            std::path::Component::CurDir => (),
+            std::path::Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/////////////////////////////////////////////////////////////////////////////
+// Importer
+/////////////////////////////////////////////////////////////////////////////
+
+struct Importer<'a, 'b, F: FnMut(ChangelogEntry)> {
+    ds: &'a mut DatabaseSession<'b>,
+    dir: &'a Path,
+    node_id: Id<Node>,
+    dry_run: bool,
+
+    /// Cotonomas mapped by folder paths
+    cotonomas: HashMap<PathBuf, Id<Cotonoma>>,
+
+    /// Cotonomas imported in this run mapped by names
+    names: HashMap<String, Id<Cotonoma>>,
+
+    on_change: F,
+    report: MarkdownImportReport,
+}
+
+impl<F: FnMut(ChangelogEntry)> Importer<'_, '_, F> {
+    fn import_folder(&mut self, folder: &Path) -> Result<()> {
+        let parent = folder.parent().unwrap_or(Path::new(""));
+        let posted_in_id = self.cotonomas[parent];
+        let name = folder
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let name = truncate(&name, Cotonoma::NAME_MAX_LENGTH as usize);
+
+        // Merge into a cotonoma with the same name imported in this run
+        // or existing in the database.
+        if let Some(id) = self.names.get(&name) {
+            self.cotonomas.insert(folder.to_owned(), *id);
+            return Ok(());
+        }
+        if let Some((cotonoma, _)) = self.ds.cotonoma_by_name(&name, &self.node_id)? {
+            self.report.already_imported += 1;
+            self.cotonomas.insert(folder.to_owned(), cotonoma.uuid);
+            return Ok(());
+        }
+
+        let key = format!("folder:{}", slash_path(folder));
+        let timestamp = self.modified_at(folder);
+        let coto = Coto {
+            summary: Some(name.clone()),
+            is_cotonoma: true,
+            ..self.new_coto(derive_id(&self.node_id, &key), posted_in_id, timestamp)
+        };
+        let cotonoma = Cotonoma {
+            uuid: derive_id(&self.node_id, &format!("{key}:cotonoma")),
+            node_id: self.node_id,
+            coto_id: coto.uuid,
+            name,
+            created_at: timestamp,
+            updated_at: timestamp,
+        };
+        if !self.dry_run {
+            let (_, log) = self.ds.import_cotonoma(&coto, &cotonoma)?;
+            (self.on_change)(log);
+        }
+        self.report.cotonomas += 1;
+        self.report.cotos += 1;
+        self.cotonomas.insert(folder.to_owned(), cotonoma.uuid);
+        self.names.insert(cotonoma.name, cotonoma.uuid);
+        Ok(())
+    }
+
+    fn import_note(
+        &mut self,
+        note: &Path,
+        vault: &Vault,
+        links: &mut Vec<(PathBuf, Vec<PathBuf>)>,
+    ) -> Result<()> {
+        let text = match fs::read_to_string(self.dir.join(note)) {
+            Ok(text) => text,
+            Err(e) => {
+                self.report.rejected.push((note.to_owned(), e.to_string()));
+                return Ok(());
+            }
+        };
+        let (front_matter, body) = split_front_matter(&text);
+        let field = |names: &[&str]| {
+            names
+                .iter()
+                .find_map(|name| front_matter.get(*name).map(String::as_str))
+        };
+        let datetime = |names: &[&str], report: &mut MarkdownImportReport| {
+            field(names).and_then(|value| {
+                let datetime = parse_datetime(value);
+                if datetime.is_none() {
+                    report
+                        .warnings
+                        .push((note.to_owned(), format!("invalid date: {value}")));
+                }
+                datetime
+            })
+        };
+
+        // Images and links
+        let mut images: Vec<(std::ops::Range<usize>, PathBuf)> = Vec::new();
+        let mut targets: Vec<PathBuf> = Vec::new();
+        let mut image = |range, target: &str, report: &mut MarkdownImportReport| match vault
+            .resolve_file(note, target)
+        {
+            Some(path) => images.push((range, path)),
+            None => report
+                .warnings
+                .push((note.to_owned(), format!("image not found: {target}"))),
+        };
+        for captures in WIKILINK.captures_iter(body) {
+            let (range, embed, target) = (
+                captures.get(0).unwrap_or_else(|| unreachable!()).range(),
+                !captures[1].is_empty(),
+                &captures[2],
+            );
+            if embed && media_type(target).is_some() {
+                image(range, target, &mut self.report);
+            } else {
+                match vault.resolve_note(target) {
+                    Some(target) if target != note => {
+                        if !targets.contains(target) {
+                            targets.push(target.clone());
+                        }
+                    }
+                    Some(_) => (), // a link to itself
+                    None => self
+                        .report
+                        .unresolved_links
+                        .push((note.to_owned(), target.to_owned())),
+                }
+            }
+        }
+        for captures in MARKDOWN_IMAGE.captures_iter(body) {
+            let target = &captures[1];
+            if !target.contains("://") && media_type(target).is_some() {
+                let range = captures.get(0).unwrap_or_else(|| unreachable!()).range();
+                image(range, target, &mut self.report);
+            }
+        }
+
+        // Strip the embedded images from the content
+        images.sort_by_key(|(range, _)| range.start);
+        let mut content = String::with_capacity(body.len());
+        let mut offset = 0;
+        for (range, _) in images.iter() {
+            content.push_str(&body[offset..range.start]);
+            offset = range.end;
+        }
+        content.push_str(&body[offset..]);
+
+        // Coto
+        let posted_in_id = self.cotonomas[note.parent().unwrap_or(Path::new(""))];
+        let created_at = datetime(&["created", "created_at"], &mut self.report)
+            .unwrap_or_else(|| self.modified_at(note));
+        let mut coto = self.new_coto(
+            derive_id(&self.node_id, &format!("note:{}", slash_path(note))),
+            posted_in_id,
+            created_at,
+        );
+        coto.updated_at = std::cmp::max(created_at, self.modified_at(note));
+        let title = field(&["title"])
+            .map(str::to_owned)
+            .or_else(|| {
+                note.file_stem()
+                    .map(|stem| stem.to_string_lossy().to_string())
+            })
+            .unwrap_or_default();
+        coto.summary = Some(truncate(&title, Coto::SUMMARY_MAX_LENGTH as usize));
+        let content = content.trim();
+        coto.content = (!content.is_empty()).then(|| content.to_owned());
+        if let Some(start) = datetime(&["date", "start"], &mut self.report) {
+            let range = DateTimeRange {
+                start,
+                end: datetime(&["end"], &mut self.report),
+            };
+            coto.datetime_start = Some(range.start);
+            coto.datetime_end = range.end;
+        }
+
+        // Images
+        let mut images = images.into_iter().map(|(_, path)| path);
+        if let Some(image) = images.next() {
+            self.set_media(&mut coto, note, &image);
+        }
+        let source_coto_id = coto.uuid;
+        let created_at = coto.created_at;
+        if !self.import_coto(coto, note)? {
+            return Ok(());
+        }
+        let mut image_cotos = Vec::new();
+        for image in images {
+            let key = format!("note:{}#{}", slash_path(note), slash_path(&image));
+            let mut image_coto =
+                self.new_coto(derive_id(&self.node_id, &key), posted_in_id, created_at);
+            image_coto.summary = image
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string());
+            let image_coto_id = image_coto.uuid;
+            if self.set_media(&mut image_coto, note, &image)
+                && self.import_coto(image_coto, &image)?
+            {
+                image_cotos.push((image_coto_id, key));
+            }
+        }
+        for (order, (image_coto_id, key)) in image_cotos.into_iter().enumerate() {
+            self.import_ito(&key, source_coto_id, image_coto_id, order as i32 + 1)?;
+        }
+
+        if !targets.is_empty() {
+            links.push((note.to_owned(), targets));
+        }
+        Ok(())
+    }
+
+    /// Imports itos from a note to the notes linked from it.
+    fn import_links(&mut self, source: &Path, targets: Vec<PathBuf>) -> Result<()> {
+        let node_id = self.node_id;
+        let note_id = |path: &Path| derive_id(&node_id, &format!("note:{}", slash_path(path)));
+        let source_coto_id = note_id(source);
+        let existing = self.ds.outgoing_itos(&[source_coto_id])?;
+        let mut order = existing.iter().map(|ito| ito.order).max().unwrap_or(0);
+        for target in targets {
+            let target_coto_id = note_id(&target);
+            if existing
+                .iter()
+                .any(|ito| ito.target_coto_id == target_coto_id)
+            {
+                self.report.already_imported += 1;
+                continue;
+            }
+            if !self.dry_run && !self.ds.contains_coto(&target_coto_id)? {
+                continue; // the target has been rejected
+            }
+            order += 1;
+            let key = format!("link:{}->{}", slash_path(source), slash_path(&target));
+            self.import_ito(&key, source_coto_id, target_coto_id, order)?;
+        }
+        Ok(())
+    }
+
+    fn new_coto(
+        &self,
+        uuid: Id<Coto>,
+        posted_in_id: Id<Cotonoma>,
+        timestamp: NaiveDateTime,
+    ) -> Coto {
+        Coto {
+            uuid,
+            rowid: 0,
+            node_id: self.node_id,
+            posted_in_id: Some(posted_in_id),
+            posted_by_id: self.node_id,
+            content: None,
+            summary: None,
+            media_content: None,
+            media_type: None,
+            is_cotonoma: false,
+            longitude: None,
+            latitude: None,
+            datetime_start: None,
+            datetime_end: None,
+            repost_of_id: None,
+            reposted_in_ids: None,
+            created_at: timestamp,
+            updated_at: timestamp,
+        }
+    }
+
+    /// Reads an image into the media content of the coto and returns `true` if succeeded.
+    fn set_media(&mut self, coto: &mut Coto, note: &Path, image: &Path) -> bool {
+        let media_type = image
+            .to_str()
+            .and_then(media_type)
+            .unwrap_or_else(|| unreachable!("{image:?}"));
+        match fs::read(self.dir.join(image)) {
+            Ok(bytes) => {
+                coto.media_content = Some(Bytes::from(bytes));
+                coto.media_type = Some(media_type.to_owned());
+                true
+            }
+            Err(e) => {
+                self.report.warnings.push((
+                    note.to_owned(),
+                    format!("couldn't read {}: {e}", image.display()),
+                ));
+                false
+            }
+        }
+    }
+
+    /// Imports a coto from the file `path` and returns `false` if it has been rejected.
+    fn import_coto(&mut self, coto: Coto, path: &Path) -> Result<bool> {
+        if self.ds.contains_coto(&coto.uuid)? {
+            self.report.already_imported += 1;
+            return Ok(true);
+        }
+        if !self.dry_run {
+            // Invalid media content will be rejected here.
+            match self.ds.import_coto(&coto) {
+                Ok((_, log)) => (self.on_change)(log),
+                Err(e) => {
+                    self.report.rejected.push((path.to_owned(), e.to_string()));
+                    return Ok(false);
+                }
+            }
+        }
+        self.report.cotos += 1;
+        if coto.media_content.is_some() {
+            self.report.media += 1;
+        }
+        Ok(true)
+    }
+
+    fn import_ito(
+        &mut self,
+        key: &str,
+        source_coto_id: Id<Coto>,
+        target_coto_id: Id<Coto>,
+        order: i32,
+    ) -> Result<()> {
+        let uuid = derive_id(&self.node_id, key);
+        if self.ds.ito(&uuid)?.is_some() {
+            self.report.already_imported += 1;
+            return Ok(());
+        }
+        let now = current_datetime();
+        let ito = Ito {
+            uuid,
+            node_id: self.node_id,
+            created_by_id: self.node_id,
+            source_coto_id,
+            target_coto_id,
+            description: None,
+            details: None,
+            order,
+            created_at: now,
+            updated_at: now,
+        };
+        if !self.dry_run {
+            let (_, log) = self.ds.import_ito(&ito)?;
+            (self.on_change)(log);
+        }
+        self.report.itos += 1;
+        Ok(())
+    }
+
+    fn modified_at(&self, path: &Path) -> NaiveDateTime {
+        fs::metadata(self.dir.join(path))
+            .and_then(|metadata| metadata.modified())
+            .map(system_time_to_datetime)
+            .unwrap_or_else(|_| current_datetime())
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+// Parsing
+/////////////////////////////////////////////////////////////////////////////
+
+/// `[[target]]`, `[[target#heading]]`, `[[target|alias]]` and their embeds (`![[...]]`)
+static WIKILINK: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(!?)\[\[([^\[\]|#]+)(?:#[^\[\]|]*)?(?:\|[^\[\]]*)?\]\]")
+        .unwrap_or_else(|e| unreachable!("{e:?}"))
+});
+
+/// `![alt](path)` or `![alt](path "title")`
+static MARKDOWN_IMAGE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"!\[[^\]]*\]\(([^)\s]+)(?:\s+"[^"]*")?\)"#)
+        .unwrap_or_else(|e| unreachable!("{e:?}"))
+});
+
+/// Splits a YAML front matter from a Markdown text.
+///
+/// Only top-level `key: value` pairs are parsed, which is enough to read
+/// titles and dates.
+fn split_front_matter(text: &str) -> (HashMap<String, String>, &str) {
+    let mut fields = HashMap::new();
+    let Some(rest) = text
+        .strip_prefix("---\n")
+        .or_else(|| text.strip_prefix("---\r\n"))
+    else {
+        return (fields, text);
+    };
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        offset += line.len();
+        let line = line.trim_end();
+        if line == "---" {
+            return (fields, &rest[offset..]);
+        }
+        if line.starts_with([' ', '\t', '-', '#']) {
+            continue;
+        }
+        if let Some((key, value)) = line.split_once(':') {
+            let value = value.trim();
+            let value = value
+                .strip_prefix('"')
+                .and_then(|v| v.strip_suffix('"'))
+                .or_else(|| value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')))
+                .unwrap_or(value);
+            if !value.is_empty() {
+                fields.insert(key.trim().to_owned(), value.to_owned());
+            }
+        }
+    }
+    // Not closed
+    (HashMap::new(), text)
+}
+
+/// Parses a date or datetime in the front matter (datetimes without offsets are in UTC).
+fn parse_datetime(s: &str) -> Option<NaiveDateTime> {
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(s) {
+        return Some(datetime.naive_utc());
+    }
+    for format in [
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%dT%H:%M",
+        "%Y-%m-%d %H:%M",
+    ] {
+        if let Ok(datetime) = NaiveDateTime::parse_from_str(s, format) {
+            return Some(datetime);
+        }
+    }
+    NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .ok()
+        .map(|date| date.and_time(Default::default()))
+}
+
+/// Returns a media type if the given path is an image supported as media content.
+fn media_type(path: &str) -> Option<&'static str> {
+    let extension = path.rsplit_once('.')?.1.to_lowercase();
+    match extension.as_str() {
+        "png" => Some("image/png"),
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "gif" => Some("image/gif"),
+        "webp" => Some("image/webp"),
+        _ => None,
+    }
+}
+
+fn truncate(s: &str, length: usize) -> String { s.chars().take(length).collect() }
+
+/////////////////////////////////////////////////////////////////////////////
+// tests
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use googletest::prelude::*;
+    use indoc::indoc;
+
+    use super::*;
+
+    #[test]
+    fn front_matter() -> Result<()> {
+        let (fields, body) = split_front_matter(indoc! {r#"
+            ---
+            title: "Hello: world"
+            date: 2026-01-31
+            tags:
+              - foo
+            ---
+            # Body
+        "#});
+        assert_that!(
+            fields,
+            unordered_elements_are![
+                (eq("title"), eq("Hello: world")),
+                (eq("date"), eq("2026-01-31"))
+            ]
+        );
+        assert_that!(body, eq("# Body\n"));
+
+        let (fields, body) = split_front_matter("---\nno end\n");
+        assert_that!(fields, is_empty());
+        assert_that!(body, eq("---\nno end\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn datetimes() -> Result<()> {
+        let date = NaiveDate::from_ymd_opt(2026, 1, 31).unwrap();
+        assert_that!(
+            parse_datetime("2026-01-31"),
+            some(eq(date.and_hms_opt(0, 0, 0).unwrap()))
+        );
+        assert_that!(
+            parse_datetime("2026-01-31 12:30"),
+            some(eq(date.and_hms_opt(12, 30, 0).unwrap()))
+        );
+        assert_that!(
+            parse_datetime("2026-01-31T12:30:00+09:00"),
+            some(eq(date.and_hms_opt(3, 30, 0).unwrap()))
+        );
+        assert_that!(parse_datetime("tomorrow"), none());
+        Ok(())
+    }
+}
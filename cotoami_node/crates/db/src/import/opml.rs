@@ -0,0 +1,338 @@
+//! Importing an OPML outline under a coto.
+//!
+//! Each `<outline>` maps to a coto connected from the coto of its parent outline
+//! (or the target coto for the top-level ones) with an ito ordered by its position.
+//! The attributes are read in the same way as [crate::models::graph::Graph::into_opml]
+//! writes them:
+//!
+//! * `text` becomes the summary if the outline has a `_note`, which becomes the
+//!   content. Otherwise, `text` becomes the content. An empty `_note` means a coto
+//!   that has only a summary.
+//! * `_cotonoma="true"` makes the outline a cotonoma named by `text`. If a cotonoma
+//!   with the same name already exists in the local node, the outline will be
+//!   connected to it instead.
+//! * `_description` becomes the description of the ito to the coto.
+//! * `created` (RFC 822) becomes the creation timestamp of the coto.
+//!
+//! The cotos (including the ones of cotonomas) will be posted in the target coto if it
+//! is a cotonoma, or in the cotonoma in which the target coto has been posted.
+//!
+//! The IDs of the imported entities are derived from the target coto and the positions
+//! and texts of the outlines, which makes re-importing the same outline idempotent.
+//! The changes made by an import are passed to the `on_change` callback of
+//! [import_opml] so that they can be published like the other local changes.
+
+use std::collections::HashSet;
+
+use anyhow::{anyhow, ensure, Result};
+use chrono::{DateTime, NaiveDateTime};
+use validator::Validate;
+
+use super::derive_id;
+use crate::{
+    current_datetime,
+    db::transactions::DatabaseSession,
+    models::{changelog::ChangelogEntry, coto::Coto, cotonoma::Cotonoma, ito::Ito, node::Node, Id},
+};
+
+/// Result of an OPML import (or a dry run of it).
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct OpmlImportReport {
+    /// Number of cotos imported (or to be imported in a dry run)
+    pub cotos: usize,
+
+    /// Number of itos imported (or to be imported in a dry run)
+    pub itos: usize,
+
+    /// Number of entities skipped since they already exist in the database
+    pub already_imported: usize,
+
+    /// Outlines that couldn't be imported along with their descendants:
+    /// (position such as `2.1`, reason)
+    pub rejected: Vec<(String, String)>,
+}
+
+/// Imports the outlines in an OPML document under the coto `coto_id`.
+///
+/// If `dry_run` is `true`, nothing will be written to the database, and the returned
+/// report tells what would be imported. Otherwise, each change made to the database
+/// will be passed to `on_change`.
+pub fn import_opml(
+    ds: &mut DatabaseSession<'_>,
+    opml: &str,
+    coto_id: &Id<Coto>,
+    dry_run: bool,
+    on_change: impl FnMut(ChangelogEntry),
+) -> Result<OpmlImportReport> {
+    let document = roxmltree::Document::parse(opml)?;
+    let root = document.root_element();
+    ensure!(root.has_tag_name("opml"), "Not an OPML document.");
+    let body = root
+        .children()
+        .find(|node| node.has_tag_name("body"))
+        .ok_or(anyhow!("Missing <body> in the OPML document."))?;
+
+    let target = ds.try_get_coto(coto_id)?;
+    let local_node_id = ds.local_node()?.uuid;
+    ensure!(
+        target.node_id == local_node_id,
+        "The target coto must belong to the local node: {coto_id}"
+    );
+    ensure!(
+        !target.is_repost(),
+        "A repost can't be the target: {coto_id}"
+    );
+    let posted_in_id = if target.is_cotonoma {
+        ds.try_get_cotonoma_by_coto_id(&target.uuid)?.0.uuid
+    } else {
+        target
+            .posted_in_id
+            .ok_or(anyhow!("The target coto doesn't belong to any cotonoma."))?
+    };
+
+    // Top-level outlines will be appended after the existing itos.
+    let last_order = ds
+        .outgoing_itos(&[target.uuid])?
+        .iter()
+        .map(|ito| ito.order)
+        .max()
+        .unwrap_or(0);
+
+    let mut importer = Importer {
+        ds,
+        dry_run,
+        node_id: local_node_id,
+        target_id: target.uuid,
+        posted_in_id,
+        dry_run_cotos: HashSet::new(),
+        on_change,
+        report: OpmlImportReport::default(),
+    };
+    importer.import_outlines(body, target.uuid, "", last_order)?;
+    Ok(importer.report)
+}
+
+struct Importer<'a, 'b, F: FnMut(ChangelogEntry)> {
+    ds: &'a mut DatabaseSession<'b>,
+    dry_run: bool,
+    node_id: Id<Node>,
+    target_id: Id<Coto>,
+    posted_in_id: Id<Cotonoma>,
+
+    /// Cotos that would have been imported in a dry run
+    dry_run_cotos: HashSet<Id<Coto>>,
+
+    on_change: F,
+    report: OpmlImportReport,
+}
+
+impl<F: FnMut(ChangelogEntry)> Importer<'_, '_, F> {
+    fn import_outlines(
+        &mut self,
+        parent: roxmltree::Node,
+        source_coto_id: Id<Coto>,
+        parent_position: &str,
+        last_order: i32,
+    ) -> Result<()> {
+        let outlines = parent
+            .children()
+            .filter(|node| node.has_tag_name("outline"));
+        for (i, outline) in outlines.enumerate() {
+            let position = if parent_position.is_empty() {
+                (i + 1).to_string()
+            } else {
+                format!("{parent_position}.{}", i + 1)
+            };
+            let key = format!(
+                "opml:{}/{position}:{}",
+                self.target_id,
+                outline.attribute("text").unwrap_or_default()
+            );
+            let order = last_order + i as i32 + 1;
+            match self.import_outline(outline, &key, source_coto_id, order) {
+                Ok(coto_id) => self.import_outlines(outline, coto_id, &position, 0)?,
+                Err(e) => self.report.rejected.push((position, e.to_string())),
+            }
+        }
+        Ok(())
+    }
+
+    /// Imports an outline as a coto connected from `source_coto_id` and returns the coto ID.
+    fn import_outline(
+        &mut self,
+        outline: roxmltree::Node,
+        key: &str,
+        source_coto_id: Id<Coto>,
+        order: i32,
+    ) -> Result<Id<Coto>> {
+        let mut coto = self.new_coto(outline, derive_id(&self.node_id, key))?;
+        let cotonoma = if outline.attribute("_cotonoma") == Some("true") {
+            match self.cotonoma(&mut coto, key)? {
+                Some(cotonoma) => Some(cotonoma),
+                None => {
+                    // Connect to the existing cotonoma with the same name
+                    self.report.already_imported += 1;
+                    return self.import_ito(outline, key, source_coto_id, &coto, order);
+                }
+            }
+        } else {
+            None
+        };
+        let coto_id = coto.uuid;
+        if self.ds.contains_coto(&coto_id)? || self.dry_run_cotos.contains(&coto_id) {
+            self.report.already_imported += 1;
+        } else if self.dry_run {
+            coto.to_import(None)?.validate()?;
+            if let Some(cotonoma) = &cotonoma {
+                cotonoma.to_import().validate()?;
+            }
+            self.dry_run_cotos.insert(coto_id);
+            self.report.cotos += 1;
+        } else {
+            let log = match &cotonoma {
+                Some(cotonoma) => self.ds.import_cotonoma(&coto, cotonoma)?.1,
+                None => self.ds.import_coto(&coto)?.1,
+            };
+            (self.on_change)(log);
+            self.report.cotos += 1;
+        }
+        self.import_ito(outline, key, source_coto_id, &coto, order)
+    }
+
+    /// Turns the `coto` into the one of a cotonoma named by its outline text, or
+    /// into the coto of the existing cotonoma with the same name (and returns `None`).
+    fn cotonoma(&mut self, coto: &mut Coto, key: &str) -> Result<Option<Cotonoma>> {
+        let name = coto
+            .summary
+            .take()
+            .or(coto.content.take())
+            .unwrap_or_default();
+        if let Some((_, existing)) = self.ds.cotonoma_by_name(&name, &self.node_id)? {
+            *coto = existing;
+            return Ok(None);
+        }
+        coto.summary = Some(name.clone());
+        coto.content = None;
+        coto.is_cotonoma = true;
+        Ok(Some(Cotonoma {
+            uuid: derive_id(&self.node_id, &format!("{key}:cotonoma")),
+            node_id: self.node_id,
+            coto_id: coto.uuid,
+            name,
+            created_at: coto.created_at,
+            updated_at: coto.created_at,
+        }))
+    }
+
+    /// Imports an ito from `source_coto_id` to the `coto` of an outline and returns
+    /// the coto ID.
+    fn import_ito(
+        &mut self,
+        outline: roxmltree::Node,
+        key: &str,
+        source_coto_id: Id<Coto>,
+        coto: &Coto,
+        order: i32,
+    ) -> Result<Id<Coto>> {
+        let coto_id = coto.uuid;
+
+        let uuid = derive_id(&self.node_id, &format!("{key}:ito"));
+        if self.ds.ito(&uuid)?.is_some() {
+            self.report.already_imported += 1;
+            return Ok(coto_id);
+        }
+        let ito = Ito {
+            uuid,
+            node_id: self.node_id,
+            created_by_id: self.node_id,
+            source_coto_id,
+            target_coto_id: coto_id,
+            description: outline
+                .attribute("_description")
+                .filter(|s| !s.is_empty())
+                .map(str::to_owned),
+            details: None,
+            order,
+            created_at: coto.created_at,
+            updated_at: coto.created_at,
+        };
+        if self.dry_run {
+            ito.to_import().validate()?;
+        } else {
+            let (_, log) = self.ds.import_ito(&ito)?;
+            (self.on_change)(log);
+        }
+        self.report.itos += 1;
+        Ok(coto_id)
+    }
+
+    fn new_coto(&self, outline: roxmltree::Node, uuid: Id<Coto>) -> Result<Coto> {
+        let text = outline.attribute("text").unwrap_or_default().trim();
+        let note = outline.attribute("_note").map(str::trim);
+        let summary_fits = text.chars().count() <= Coto::SUMMARY_MAX_LENGTH as usize;
+        let (summary, content) = match note {
+            Some(note) if text.is_empty() => (None, note.to_owned()),
+            Some(note) if !summary_fits => (None, format!("{text}\n\n{note}")),
+            Some(note) => (Some(text.to_owned()), note.to_owned()),
+            None => (None, text.to_owned()),
+        };
+        let content = content.trim_end().to_owned();
+        ensure!(!content.is_empty() || summary.is_some(), "empty outline");
+
+        let timestamp = outline
+            .attribute("created")
+            .and_then(parse_rfc822)
+            .unwrap_or_else(current_datetime);
+        Ok(Coto {
+            uuid,
+            rowid: 0,
+            node_id: self.node_id,
+            posted_in_id: Some(self.posted_in_id),
+            posted_by_id: self.node_id,
+            content: (!content.is_empty()).then_some(content),
+            summary,
+            media_content: None,
+            media_type: None,
+            is_cotonoma: false,
+            longitude: None,
+            latitude: None,
+            datetime_start: None,
+            datetime_end: None,
+            repost_of_id: None,
+            reposted_in_ids: None,
+            created_at: timestamp,
+            updated_at: timestamp,
+        })
+    }
+}
+
+fn parse_rfc822(s: &str) -> Option<NaiveDateTime> {
+    DateTime::parse_from_rfc2822(s.trim())
+        .ok()
+        .map(|datetime| datetime.naive_utc())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use googletest::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn parse_rfc822_dates() {
+        let expected = NaiveDate::from_ymd_opt(2026, 1, 31)
+            .unwrap()
+            .and_hms_opt(3, 4, 5)
+            .unwrap();
+        assert_that!(
+            parse_rfc822("Sat, 31 Jan 2026 03:04:05 +0000"),
+            some(eq(expected))
+        );
+        assert_that!(
+            parse_rfc822("Sat, 31 Jan 2026 12:04:05 +0900"),
+            some(eq(expected))
+        );
+        assert_that!(parse_rfc822("2026-01-31"), none());
+    }
+}
@@ -4,8 +4,10 @@ use base64::Engine;
 use rand::Rng;
 use serde::{Deserialize, Deserializer, Serializer};
 
+pub mod archive;
 pub mod db;
 mod image;
+pub mod import;
 pub mod models;
 mod schema;
 pub mod time;
@@ -145,11 +145,11 @@ impl<T> Clone for Id<T> {
     fn clone(&self) -> Self { *self }
 }
 
-impl<T: Eq> Ord for Id<T> {
+impl<T> Ord for Id<T> {
     fn cmp(&self, other: &Self) -> Ordering { self.value.cmp(&other.value) }
 }
 
-impl<T: Eq> PartialOrd for Id<T> {
+impl<T> PartialOrd for Id<T> {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> { Some(self.cmp(other)) }
 }
 
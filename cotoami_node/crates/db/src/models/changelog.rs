@@ -4,18 +4,21 @@
 //! When replicating a database to another node, that node must ensure to
 //! apply the changelog entries in the serial number order.
 
+use std::collections::HashMap;
+
 use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
 use diesel::{
     backend::Backend, deserialize::FromSql, expression::AsExpression, prelude::*, serialize::ToSql,
     sql_types::Binary, sqlite::Sqlite, FromSqlRow,
 };
+use uuid::Uuid;
 
 use super::{
-    coto::{Coto, CotoContentDiff},
+    coto::{Coto, CotoContentDiff, OmittedMedia},
     cotonoma::Cotonoma,
     ito::{Ito, ItoContentDiff},
     node::Node,
-    Bytes, Id,
+    Bytes, FieldDiff, Id, Ids,
 };
 use crate::schema::changelog;
 
@@ -26,7 +29,8 @@ use crate::schema::changelog;
 /// A row in `changelog` table
 ///
 /// - A `ChangelogEntry` must not be updated once it's inserted, so it
-///   shouldn't impl `AsChangeset`.
+///   shouldn't impl `AsChangeset`. The only exception is compaction, which rewrites
+///   the `change` of obsolete entries while keeping the other columns.
 #[derive(
     Debug, Clone, PartialEq, Identifiable, Queryable, serde::Serialize, serde::Deserialize,
 )]
@@ -52,18 +56,57 @@ pub struct ChangelogEntry {
 
     /// Registration date in this database.
     pub inserted_at: NaiveDateTime,
+
+    /// UUID of the parent node from which this change has been imported.
+    /// This field is not meant to be sent to other nodes.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub imported_from: Option<Id<Node>>,
 }
 
 impl ChangelogEntry {
     pub fn inserted_at(&self) -> DateTime<Local> { Local.from_utc_datetime(&self.inserted_at) }
 
-    pub(crate) fn to_import(&self) -> NewChangelogEntry<'_> {
+    pub fn origin(&self) -> ChangeOrigin {
+        ChangeOrigin {
+            node_id: self.origin_node_id,
+            serial_number: self.origin_serial_number,
+        }
+    }
+
+    pub(crate) fn to_import<'a>(&'a self, parent_node_id: &'a Id<Node>) -> NewChangelogEntry<'a> {
         NewChangelogEntry {
             origin_node_id: &self.origin_node_id,
             origin_serial_number: self.origin_serial_number,
             change: &self.change,
             import_error: None,
             inserted_at: crate::current_datetime(),
+            imported_from: Some(parent_node_id),
+        }
+    }
+
+    /// Converts this entry into a placeholder that keeps the serial number continuity
+    /// without the content of the change ([Change::None]).
+    ///
+    /// A placeholder has no origin serial number (`0`) so that it can be distinguished
+    /// from a compacted entry, which has been merged into a succeeding change, and
+    /// won't be recorded as the original change by a child.
+    pub fn into_placeholder(self) -> Self {
+        Self {
+            origin_serial_number: 0,
+            change: Change::None,
+            ..self
+        }
+    }
+
+    pub fn is_placeholder(&self) -> bool {
+        matches!(self.change, Change::None) && self.origin_serial_number == 0
+    }
+
+    /// Omits the media content from the change of this entry ([Change::omit_media]).
+    pub fn omit_media(self) -> Self {
+        Self {
+            change: self.change.omit_media(),
+            ..self
         }
     }
 }
@@ -77,6 +120,7 @@ pub(crate) struct NewChangelogEntry<'a> {
     change: &'a Change,
     import_error: Option<String>,
     inserted_at: NaiveDateTime,
+    imported_from: Option<&'a Id<Node>>,
 }
 
 impl<'a> NewChangelogEntry<'a> {
@@ -91,6 +135,10 @@ impl<'a> NewChangelogEntry<'a> {
 ///
 /// The default data layout should be designed so that adding a new field to
 /// one of variants won't break compatibility to the old version.
+///
+/// In the database, a change is stored in the versioned encoding described in
+/// [Change::ENCODING_VERSION], where struct fields are identified by name, so a new
+/// field with `#[serde(default)]` can be decoded from the data stored by older versions.
 #[derive(
     Debug, Clone, PartialEq, AsExpression, FromSqlRow, serde::Serialize, serde::Deserialize,
 )]
@@ -156,15 +204,100 @@ pub enum Change {
     //
     // Because rmp-serde doesn't handle enum variant field changes well and causes
     // invalid length errors, we had no choice but to add a new variant `PromoteCoto`.
-    // In the future, we should probably migrate to a serialization format that
-    // better supports schema evolution.
+    // Changes are now stored in a field-named encoding (`Change::ENCODING_VERSION`),
+    // so new fields should be added to existing variants with `#[serde(default)]`.
     Promote {
         coto_id: Id<Coto>,
         promoted_at: NaiveDateTime,
     },
+
+    /// A [Change::CreateCoto] or [Change::EditCoto] whose media content has been
+    /// omitted to be fetched lazily by the receiver (See [Change::omit_media]).
+    MediaOmitted {
+        change: Box<Change>,
+        media: OmittedMedia,
+    },
 }
 
 impl Change {
+    /// Omits the media content from a [Change::CreateCoto] or [Change::EditCoto] by
+    /// wrapping it in a [Change::MediaOmitted] with the hash of the media.
+    /// The other changes will be returned as they are.
+    ///
+    /// An edit whose media has been omitted deletes the old media of the coto,
+    /// so that the coto won't show the outdated media until the new one is fetched.
+    pub fn omit_media(self) -> Self {
+        match self {
+            Change::CreateCoto(mut coto) => match coto.omit_media() {
+                Some(media) => Change::MediaOmitted {
+                    change: Box::new(Change::CreateCoto(coto)),
+                    media,
+                },
+                None => Change::CreateCoto(coto),
+            },
+            Change::EditCoto {
+                coto_id,
+                mut diff,
+                updated_at,
+            } if matches!(diff.media_content, FieldDiff::Change(_)) => {
+                let FieldDiff::Change((content, media_type)) =
+                    std::mem::replace(&mut diff.media_content, FieldDiff::Delete)
+                else {
+                    unreachable!()
+                };
+                Change::MediaOmitted {
+                    change: Box::new(Change::EditCoto {
+                        coto_id,
+                        diff,
+                        updated_at,
+                    }),
+                    media: OmittedMedia {
+                        hash: Coto::media_hash(content.as_ref()),
+                        media_type: media_type.into_owned(),
+                    },
+                }
+            }
+            change => change,
+        }
+    }
+
+    /// Returns the name of the variant, or that of the wrapped change if the media
+    /// has been omitted.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Change::None => "None",
+            Change::CreateNode { .. } => "CreateNode",
+            Change::UpsertNode(_) => "UpsertNode",
+            Change::RenameNode { .. } => "RenameNode",
+            Change::SetNodeIcon { .. } => "SetNodeIcon",
+            Change::SetRootCotonoma { .. } => "SetRootCotonoma",
+            Change::CreateCoto(_) => "CreateCoto",
+            Change::EditCoto { .. } => "EditCoto",
+            Change::PromoteCoto { .. } => "PromoteCoto",
+            Change::DeleteCoto { .. } => "DeleteCoto",
+            Change::CreateCotonoma(..) => "CreateCotonoma",
+            Change::RenameCotonoma { .. } => "RenameCotonoma",
+            Change::CreateIto(_) => "CreateIto",
+            Change::EditIto { .. } => "EditIto",
+            Change::DeleteIto { .. } => "DeleteIto",
+            Change::ChangeItoOrder { .. } => "ChangeItoOrder",
+            Change::Promote { .. } => "Promote",
+            Change::MediaOmitted { change, .. } => change.kind(),
+        }
+    }
+
+    /// Returns the coto and its media omitted by [Change::omit_media], if any.
+    pub fn omitted_media(&self) -> Option<(&Id<Coto>, &OmittedMedia)> {
+        match self {
+            Change::MediaOmitted { change, media } => match change.as_ref() {
+                Change::CreateCoto(coto) => Some((&coto.uuid, media)),
+                Change::EditCoto { coto_id, .. } => Some((coto_id, media)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
     pub(crate) fn new_changelog_entry<'a>(
         &'a self,
         local_node_id: &'a Id<Node>,
@@ -176,6 +309,75 @@ impl Change {
             change: self,
             import_error: None,
             inserted_at: crate::current_datetime(),
+            imported_from: None,
+        }
+    }
+
+    /// Creates an entry of this change imported from the `parent_node_id` as
+    /// the change of the `origin`.
+    pub(crate) fn new_imported_entry<'a>(
+        &'a self,
+        origin: &'a ChangeOrigin,
+        parent_node_id: &'a Id<Node>,
+    ) -> NewChangelogEntry<'a> {
+        NewChangelogEntry {
+            origin_node_id: &origin.node_id,
+            origin_serial_number: origin.serial_number,
+            change: self,
+            import_error: None,
+            inserted_at: crate::current_datetime(),
+            imported_from: Some(parent_node_id),
+        }
+    }
+}
+
+impl Change {
+    /// Version of the encoding produced by [Change::to_msgpack].
+    ///
+    /// * `0`: Positional MessagePack (`rmp_serde::to_vec`) used by the versions before
+    ///   the encoding was versioned. A change in this encoding can't be decoded once
+    ///   a field has been added to its variant.
+    /// * `1`: A MessagePack array `[version, change]` where the structs in the change are
+    ///   encoded as maps keyed by field names.
+    ///
+    /// An encoding with a version is distinguished from the version `0` by the first byte
+    /// (an array of two elements), which never starts a change in the version `0`
+    /// (a string for unit variants or a map for the others).
+    pub const ENCODING_VERSION: u8 = 1;
+
+    /// The first byte of a versioned encoding (a MessagePack fixarray of two elements)
+    const VERSIONED_ENCODING_MARKER: u8 = 0x92;
+
+    /// Encodes this change in the current version of the encoding.
+    pub fn to_msgpack(&self) -> Result<Vec<u8>, rmp_serde::encode::Error> {
+        let mut bytes = Vec::new();
+        let mut serializer = rmp_serde::Serializer::new(&mut bytes).with_struct_map();
+        serde::Serialize::serialize(&(Self::ENCODING_VERSION, self), &mut serializer)?;
+        Ok(bytes)
+    }
+
+    /// Decodes a change encoded in any version up to [Change::ENCODING_VERSION].
+    pub fn from_msgpack(bytes: &[u8]) -> Result<Self, rmp_serde::decode::Error> {
+        let version = Self::encoding_version(bytes);
+        if version == 0 {
+            return rmp_serde::from_slice(bytes);
+        }
+        if version > Self::ENCODING_VERSION {
+            return Err(rmp_serde::decode::Error::Syntax(format!(
+                "Unsupported change encoding version: {version} (supported up to {})",
+                Self::ENCODING_VERSION
+            )));
+        }
+        let (_, change): (u8, Self) = rmp_serde::from_slice(bytes)?;
+        Ok(change)
+    }
+
+    /// Returns the version of the encoding in which the given bytes are encoded.
+    pub fn encoding_version(bytes: &[u8]) -> u8 {
+        match bytes {
+            // The version is encoded as a positive fixint.
+            [Self::VERSIONED_ENCODING_MARKER, version @ 0..=0x7f, ..] => *version,
+            _ => 0,
         }
     }
 }
@@ -185,7 +387,7 @@ impl ToSql<Binary, Sqlite> for Change {
         &'b self,
         out: &mut diesel::serialize::Output<'b, '_, Sqlite>,
     ) -> diesel::serialize::Result {
-        let msgpack_bytes = rmp_serde::to_vec(&self)?;
+        let msgpack_bytes = self.to_msgpack()?;
         // https://diesel.rs/guides/migration_guide.html#changed-tosql-implementations
         out.set_value(msgpack_bytes);
         Ok(diesel::serialize::IsNull::No)
@@ -195,8 +397,141 @@ impl ToSql<Binary, Sqlite> for Change {
 impl FromSql<Binary, Sqlite> for Change {
     fn from_sql(value: <Sqlite as Backend>::RawValue<'_>) -> diesel::deserialize::Result<Self> {
         let msgpack_bytes = <Vec<u8> as FromSql<Binary, Sqlite>>::from_sql(value)?;
-        Ok(rmp_serde::from_slice(&msgpack_bytes)?)
+        Ok(Self::from_msgpack(&msgpack_bytes)?)
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+// ChangelogSnapshot
+/////////////////////////////////////////////////////////////////////////////
+
+/// A consistent state of the replicated entities in a database covering the
+/// changelog up to [Self::last_serial_number].
+///
+/// A new child node can install a snapshot of its parent instead of replaying
+/// every changelog entry from the first one, and then continue to import changes
+/// from `last_serial_number + 1`.
+///
+/// The media content of cotos is omitted from a snapshot to keep it within the message
+/// size limits, and it is to be fetched lazily as [MissingMedia].
+///
+/// [MissingMedia]: super::coto::MissingMedia
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ChangelogSnapshot {
+    /// Serial number of the last changelog entry reflected in this snapshot
+    pub last_serial_number: i64,
+
+    pub nodes: Vec<Node>,
+
+    /// Cotonomas whose cotos are included in [Self::cotos]
+    pub cotonomas: Vec<Cotonoma>,
+
+    /// Cotos whose media content has been moved to [Self::omitted_media]
+    pub cotos: Vec<Coto>,
+
+    pub itos: Vec<Ito>,
+
+    /// Media omitted from [Self::cotos]
+    #[serde(default)]
+    pub omitted_media: HashMap<Id<Coto>, OmittedMedia>,
+
+    /// Origins of the changes covered by this snapshot in order of the serial number
+    ///
+    /// A child records the entities in this snapshot as the changes that have created
+    /// them ([Self::creations]) and the other changes as [Change::None], so that
+    /// the same changes received from other parents won't be imported twice.
+    #[serde(default)]
+    pub origins: Vec<ChangeOrigin>,
+
+    /// Origins of the changes that have created the cotos and itos in this snapshot,
+    /// keyed by the UUIDs of them
+    #[serde(default)]
+    pub creations: HashMap<Uuid, ChangeOrigin>,
+}
+
+/// The node in which a change has been originally created and the serial number
+/// of the change in that node, which identifies a change across nodes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub struct ChangeOrigin {
+    pub node_id: Id<Node>,
+    pub serial_number: i64,
+}
+
+/////////////////////////////////////////////////////////////////////////////
+// ChangeSubscription
+/////////////////////////////////////////////////////////////////////////////
+
+/// A set of cotonomas to which a child node subscribes for selective replication.
+///
+/// The parent node sends the changes outside of the subscribed cotonomas as
+/// placeholders ([ChangelogEntry::into_placeholder]), except for the changes
+/// of nodes and the cotonomas on the paths to the subscribed ones, which are
+/// needed for the replicated cotonomas to be consistent.
+///
+/// A subscription only affects the changes sent after it has been negotiated, so
+/// a child won't get the past changes of newly subscribed cotonomas.
+#[derive(Debug, Clone, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub struct ChangeSubscription {
+    pub cotonoma_ids: Vec<Id<Cotonoma>>,
+
+    /// TRUE if the sub cotonomas of [Self::cotonoma_ids] are subscribed, too.
+    #[serde(default)]
+    pub recursive: bool,
+}
+
+impl ChangeSubscription {
+    pub fn new(cotonoma_ids: Vec<Id<Cotonoma>>, recursive: bool) -> Self {
+        Self {
+            cotonoma_ids,
+            recursive,
+        }
     }
+
+    /// Returns true if this subscription covers all the cotonomas subscribed by `other`,
+    /// which means `other` is the same as or narrower than this.
+    pub fn covers(&self, other: &Self) -> bool {
+        (self.recursive || !other.recursive)
+            && other
+                .cotonoma_ids
+                .iter()
+                .all(|id| self.cotonoma_ids.contains(id))
+    }
+
+    /// Restores a subscription from the columns of `parent_nodes` or `child_nodes`.
+    pub(crate) fn from_columns(
+        cotonoma_ids: Option<&Ids<Cotonoma>>,
+        recursive: bool,
+    ) -> Option<Self> {
+        cotonoma_ids.map(|ids| Self::new(ids.0.clone(), recursive))
+    }
+
+    /// Returns the values of the columns of `parent_nodes` or `child_nodes`.
+    pub(crate) fn to_columns(subscription: Option<&Self>) -> (Option<Ids<Cotonoma>>, bool) {
+        match subscription {
+            Some(subscription) => (
+                Some(Ids(subscription.cotonoma_ids.clone())),
+                subscription.recursive,
+            ),
+            None => (None, false),
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+// ChangelogCompaction
+/////////////////////////////////////////////////////////////////////////////
+
+/// Result of a changelog compaction.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ChangelogCompaction {
+    /// Serial number of the last changelog entry that could be compacted
+    pub watermark: i64,
+
+    /// Number of entries rewritten into [Change::None]
+    pub cleared: usize,
+
+    /// Number of entries rewritten into a change merged with the preceding ones
+    pub merged: usize,
 }
 
 /////////////////////////////////////////////////////////////////////////////
@@ -223,6 +558,7 @@ mod tests {
             change,
             import_error: None,
             inserted_at: NaiveDateTime::parse_from_str("2023-01-02 03:04:05", "%Y-%m-%d %H:%M:%S")?,
+            imported_from: None,
         };
 
         // serialize
@@ -251,8 +587,9 @@ mod tests {
         let change = Change::DeleteIto {
             ito_id: Id::from_str("00000000-0000-0000-0000-000000000001")?,
         };
-        let msgpack_bytes = rmp_serde::to_vec(&change)?;
-        let deserialized: Change = rmp_serde::from_slice(&msgpack_bytes)?;
+        let msgpack_bytes = change.to_msgpack()?;
+        assert_eq!(msgpack_bytes[..2], [0x92, Change::ENCODING_VERSION]);
+        let deserialized = Change::from_msgpack(&msgpack_bytes)?;
         assert_eq!(deserialized, change);
         Ok(())
     }
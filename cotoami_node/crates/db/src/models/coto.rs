@@ -6,6 +6,7 @@ use anyhow::{bail, ensure, Result};
 use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
 use derive_new::new;
 use diesel::prelude::*;
+use sha2::{Digest, Sha256};
 use validator::Validate;
 
 use crate::{
@@ -14,7 +15,7 @@ use crate::{
         node::{BelongsToNode, Node},
         Bytes, DateTimeRange, FieldDiff, Geolocation, Id, Ids,
     },
-    schema::cotos,
+    schema::{cotos, missing_media},
 };
 
 /////////////////////////////////////////////////////////////////////////////
@@ -119,6 +120,26 @@ impl Coto {
         }
     }
 
+    /// Returns the content hash (SHA-256 in hex) of the given media content,
+    /// by which the media omitted from a change can be fetched ([OmittedMedia]).
+    pub fn media_hash(content: &[u8]) -> String { format!("{:x}", Sha256::digest(content)) }
+
+    /// Takes the media content out of this coto and returns the [OmittedMedia] by which
+    /// it can be fetched later, or `None` if this coto has no media.
+    pub(crate) fn omit_media(&mut self) -> Option<OmittedMedia> {
+        if self.media_content.is_none() || self.media_type.is_none() {
+            return None;
+        }
+        let (Some(content), Some(media_type)) = (self.media_content.take(), self.media_type.take())
+        else {
+            unreachable!()
+        };
+        Some(OmittedMedia {
+            hash: Self::media_hash(content.as_ref()),
+            media_type,
+        })
+    }
+
     pub fn posted_in(&self, cotonoma_id: &Id<Cotonoma>) -> bool {
         self.posted_in_id == Some(*cotonoma_id)
             || self
@@ -647,11 +668,88 @@ impl<'a> CotoContentDiff<'a> {
     }
 }
 
+/////////////////////////////////////////////////////////////////////////////
+// Lazy media
+/////////////////////////////////////////////////////////////////////////////
+
+/// Media content of a coto omitted from a change for lazy media replication
+/// (See [crate::models::changelog::Change::omit_media]).
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct OmittedMedia {
+    /// Content hash of the media ([Coto::media_hash])
+    pub hash: String,
+
+    /// MIME type of the media
+    pub media_type: String,
+}
+
+/// A row in `missing_media` table
+///
+/// A node that has imported a change without media content ([OmittedMedia]) keeps
+/// the coto's media as missing until it is fetched from a parent by the hash.
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    Identifiable,
+    Queryable,
+    Selectable,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+#[diesel(table_name = missing_media, primary_key(coto_id))]
+pub struct MissingMedia {
+    /// UUID of the coto whose media content is missing.
+    pub coto_id: Id<Coto>,
+
+    /// Content hash of the missing media ([Coto::media_hash])
+    pub hash: String,
+
+    /// MIME type of the missing media
+    pub media_type: String,
+
+    pub created_at: NaiveDateTime,
+
+    /// UUID of the parent node from which the change omitting the media has been
+    /// imported, or `None` if it is unknown.
+    pub parent_node_id: Option<Id<Node>>,
+}
+
+/// An `Insertable` missing media
+#[derive(Insertable)]
+#[diesel(table_name = missing_media)]
+pub(crate) struct NewMissingMedia<'a> {
+    coto_id: &'a Id<Coto>,
+    hash: &'a str,
+    media_type: &'a str,
+    created_at: NaiveDateTime,
+}
+
+impl<'a> NewMissingMedia<'a> {
+    pub fn new(coto_id: &'a Id<Coto>, media: &'a OmittedMedia) -> Self {
+        Self {
+            coto_id,
+            hash: &media.hash,
+            media_type: &media.media_type,
+            created_at: crate::current_datetime(),
+        }
+    }
+}
+
+/// Media content of a coto
+#[derive(derive_more::Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct CotoMedia {
+    #[debug(skip)]
+    pub content: Bytes,
+    pub media_type: String,
+}
+
 /////////////////////////////////////////////////////////////////////////////
 // Internal functions
 /////////////////////////////////////////////////////////////////////////////
 
-fn process_media_content<'a>(
+pub(crate) fn process_media_content<'a>(
     media_content: Cow<'a, [u8]>,
     media_type: &'a str,
     image_max_size: Option<u32>,
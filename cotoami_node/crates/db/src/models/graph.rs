@@ -6,6 +6,13 @@ use petgraph::prelude::{Graph as Petgraph, NodeIndex};
 
 use super::{coto::Coto, ito::Ito, Id};
 
+mod export;
+mod markdown;
+mod opml;
+pub mod query;
+
+pub use markdown::{coto_title, media_path, MarkdownExport, MarkdownLayout};
+
 /// A graph is a set of cotos that are connected with itos
 #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct Graph {
@@ -17,6 +24,10 @@ pub struct Graph {
 
     /// All the itos in this graph, each of which is mapped by the ID of the source coto
     pub itos: HashMap<Id<Coto>, Vec<Ito>>,
+
+    /// Where to resume the traversal if it has been cut off by [TraversalOptions].
+    #[serde(default)]
+    pub frontier: Option<TraversalFrontier>,
 }
 
 impl Graph {
@@ -26,6 +37,7 @@ impl Graph {
             root_id: root.uuid,
             cotos: HashMap::new(),
             itos: HashMap::new(),
+            frontier: None,
         };
         graph.add_coto(root);
         graph
@@ -101,3 +113,99 @@ impl Graph {
         petgraph
     }
 }
+
+/////////////////////////////////////////////////////////////////////////////
+// GraphHealth
+/////////////////////////////////////////////////////////////////////////////
+
+/// A report on structural problems of the graph in a scope
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct GraphHealth {
+    /// Cotos (other than cotonomas) that have no incoming itos, which means they are
+    /// not pinned to any cotonomas nor reachable from any cotonoma stocks.
+    pub orphans: Vec<Id<Coto>>,
+
+    /// Sets of cotos connected in cycles by itos
+    /// (each of which is a strongly connected component).
+    pub cycles: Vec<Vec<Id<Coto>>>,
+
+    /// Itos connected to cotos that belong to other nodes no longer replicated
+    /// (parent nodes from which the local node has been forked).
+    pub itos_to_unreplicated_nodes: Vec<Id<Ito>>,
+
+    /// Reposts whose original cotos are missing.
+    pub reposts_without_original: Vec<Id<Coto>>,
+}
+
+impl GraphHealth {
+    pub fn is_healthy(&self) -> bool {
+        self.orphans.is_empty()
+            && self.cycles.is_empty()
+            && self.itos_to_unreplicated_nodes.is_empty()
+            && self.reposts_without_original.is_empty()
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+// TraversalOptions
+/////////////////////////////////////////////////////////////////////////////
+
+/// Options to bound a graph traversal
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct TraversalOptions {
+    /// Don't traverse beyond cotonomas (other than the root).
+    #[serde(default)]
+    pub until_cotonoma: bool,
+
+    /// Don't traverse beyond cotos that belong to a node other than the root's one.
+    #[serde(default)]
+    pub until_foreign_node: bool,
+
+    /// The maximum number of levels (ito hops) from the root.
+    #[serde(default)]
+    pub max_depth: Option<usize>,
+
+    /// The maximum number of cotos (excluding the root) in a resulting graph.
+    #[serde(default)]
+    pub max_cotos: Option<usize>,
+}
+
+impl TraversalOptions {
+    pub fn until_cotonoma() -> Self {
+        Self {
+            until_cotonoma: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    pub fn max_cotos(mut self, max_cotos: usize) -> Self {
+        self.max_cotos = Some(max_cotos);
+        self
+    }
+}
+
+/// A continuation token to resume a traversal that has been cut off by
+/// the limits in [TraversalOptions].
+///
+/// A traversal resumed from a frontier doesn't know which cotos have been
+/// visited in the previous graphs, so the same cotos could appear again
+/// when there are multiple paths to them.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct TraversalFrontier {
+    /// The depth of `sources` from the root.
+    pub depth: usize,
+
+    /// Cotos at `depth` whose outgoing itos have not been traversed completely,
+    /// each paired with the order of the last ito that has been traversed
+    /// (itos with a larger order are yet to be traversed).
+    pub sources: Vec<(Id<Coto>, i32)>,
+
+    /// Cotos at `depth + 1` that have been included in a graph,
+    /// but whose outgoing itos have not been traversed yet.
+    pub next: Vec<Id<Coto>>,
+}
@@ -0,0 +1,165 @@
+//! Exporting a [Graph] into text formats for external tools.
+//!
+//! All the exporters use [Graph::into_petgraph()] with sorting so that
+//! the same graph always produces the same output (diffable).
+
+use petgraph::visit::EdgeRef;
+
+use super::Graph;
+use crate::models::coto::Coto;
+
+/// The maximum number of characters in a coto label.
+const LABEL_MAX_LENGTH: usize = 50;
+
+impl Graph {
+    /// Exports this graph as a Graphviz DOT digraph.
+    ///
+    /// Cotonomas are drawn as bold boxes, and ito descriptions become edge labels.
+    pub fn into_dot(self) -> String {
+        let petgraph = self.into_petgraph(true);
+        let mut lines = vec!["digraph {".to_owned()];
+        for index in petgraph.node_indices() {
+            let coto = &petgraph[index];
+            let label = escape_dot(&coto_label(coto));
+            let style = if coto.is_cotonoma {
+                " shape = box style = bold"
+            } else {
+                ""
+            };
+            lines.push(format!(
+                "    {} [ label = \"{label}\"{style} ]",
+                index.index()
+            ));
+        }
+        for edge in petgraph.edge_references() {
+            let description = edge.weight().description.as_deref().unwrap_or_default();
+            lines.push(format!(
+                "    {} -> {} [ label = \"{}\" ]",
+                edge.source().index(),
+                edge.target().index(),
+                escape_dot(description)
+            ));
+        }
+        lines.push("}\n".to_owned());
+        lines.join("\n")
+    }
+
+    /// Exports this graph as a GraphML document.
+    ///
+    /// Nodes and edges are identified by the IDs of cotos and itos, and cotonomas
+    /// are distinguished by the `cotonoma` attribute.
+    pub fn into_graphml(self) -> String {
+        let petgraph = self.into_petgraph(true);
+        let mut lines: Vec<String> = [
+            r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+            r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#,
+            r#"  <key id="label" for="node" attr.name="label" attr.type="string"/>"#,
+            r#"  <key id="cotonoma" for="node" attr.name="cotonoma" attr.type="boolean">"#,
+            r#"    <default>false</default>"#,
+            r#"  </key>"#,
+            r#"  <key id="description" for="edge" attr.name="label" attr.type="string"/>"#,
+            r#"  <graph id="G" edgedefault="directed">"#,
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect();
+        for coto in petgraph.node_weights() {
+            lines.push(format!(r#"    <node id="{}">"#, coto.uuid));
+            lines.push(format!(
+                r#"      <data key="label">{}</data>"#,
+                escape_xml(&coto_label(coto))
+            ));
+            if coto.is_cotonoma {
+                lines.push(r#"      <data key="cotonoma">true</data>"#.to_owned());
+            }
+            lines.push("    </node>".to_owned());
+        }
+        for ito in petgraph.edge_weights() {
+            let edge = format!(
+                r#"    <edge id="{}" source="{}" target="{}""#,
+                ito.uuid, ito.source_coto_id, ito.target_coto_id
+            );
+            if let Some(description) = ito.description.as_deref() {
+                lines.push(format!("{edge}>"));
+                lines.push(format!(
+                    r#"      <data key="description">{}</data>"#,
+                    escape_xml(description)
+                ));
+                lines.push("    </edge>".to_owned());
+            } else {
+                lines.push(format!("{edge}/>"));
+            }
+        }
+        lines.push("  </graph>".to_owned());
+        lines.push("</graphml>\n".to_owned());
+        lines.join("\n")
+    }
+
+    /// Exports this graph as a Mermaid flowchart.
+    ///
+    /// Cotonomas are drawn in the subroutine shape with the `cotonoma` class,
+    /// and ito descriptions become edge labels.
+    pub fn into_mermaid(self) -> String {
+        let petgraph = self.into_petgraph(true);
+        let mut lines = vec!["flowchart TD".to_owned()];
+        let mut cotonomas = Vec::new();
+        for index in petgraph.node_indices() {
+            let coto = &petgraph[index];
+            let label = escape_mermaid(&coto_label(coto));
+            if coto.is_cotonoma {
+                lines.push(format!("    n{}[[\"{label}\"]]", index.index()));
+                cotonomas.push(format!("n{}", index.index()));
+            } else {
+                lines.push(format!("    n{}[\"{label}\"]", index.index()));
+            }
+        }
+        for edge in petgraph.edge_references() {
+            let (source, target) = (edge.source().index(), edge.target().index());
+            match edge.weight().description.as_deref() {
+                Some(description) => lines.push(format!(
+                    "    n{source} -->|\"{}\"| n{target}",
+                    escape_mermaid(description)
+                )),
+                None => lines.push(format!("    n{source} --> n{target}")),
+            }
+        }
+        if !cotonomas.is_empty() {
+            lines.push("    classDef cotonoma font-weight:bold".to_owned());
+            lines.push(format!("    class {} cotonoma", cotonomas.join(",")));
+        }
+        lines.push(String::new());
+        lines.join("\n")
+    }
+}
+
+/// Returns a single-line label of a coto: the name of a cotonoma, the summary, or
+/// the first line of the content (abbreviated if it's too long).
+pub(super) fn coto_label(coto: &Coto) -> String {
+    let text = if coto.is_cotonoma {
+        coto.name_as_cotonoma().unwrap_or_default()
+    } else if let Some(summary) = coto.summary.as_deref() {
+        summary
+    } else {
+        coto.content
+            .as_deref()
+            .and_then(|content| content.lines().next())
+            .unwrap_or_default()
+    };
+    crate::abbreviate_str(text, LABEL_MAX_LENGTH, "…").unwrap_or_else(|| text.to_owned())
+}
+
+fn escape_dot(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+pub(super) fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn escape_mermaid(s: &str) -> String { s.replace('"', "#quot;").replace('\n', " ") }
@@ -0,0 +1,289 @@
+//! Exporting the stock of a cotonoma (a [Graph] from a cotonoma) as Markdown.
+//!
+//! The stock is read as an outline: the children of each coto are the targets of
+//! its outgoing itos ordered by [Ito::order]. A coto reachable via more than one
+//! ito appears in every place, but its children are expanded only at the first one
+//! so that a cycle won't make the output infinite.
+//!
+//! ```ignore
+//! ds.graph(cotonoma_coto, true)?
+//!     .into_markdown(MarkdownLayout::Files)
+//!     .write_to(dir)?;
+//! ```
+
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use chrono::NaiveDateTime;
+
+use super::{export::coto_label, Graph};
+use crate::models::{coto::Coto, ito::Ito, Bytes, Id};
+
+/// Directory in which media content will be written
+const MEDIA_DIR: &str = "media";
+
+/// Layout of the Markdown files to export
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarkdownLayout {
+    /// A single document (`index.md`) in which the stock is written as a nested list
+    Document,
+
+    /// A Markdown file for each coto (`{coto_id}.md`) with its metadata in the front
+    /// matter, in which the children are listed as wikilinks (`[[{coto_id}|label]]`).
+    Files,
+}
+
+/// Files to be written as a result of a Markdown export.
+#[derive(Debug, Default)]
+pub struct MarkdownExport {
+    /// Markdown documents, each of which is paired with a relative path to write it.
+    pub documents: Vec<(PathBuf, String)>,
+
+    /// Media content of the cotos, each of which is paired with a relative path
+    /// (under the `media` directory) to write it.
+    pub media: Vec<(PathBuf, Bytes)>,
+}
+
+impl MarkdownExport {
+    /// Writes all the files in the given directory.
+    pub fn write_to<P: AsRef<Path>>(&self, dir: P) -> Result<()> {
+        let dir = dir.as_ref();
+        let files = self
+            .documents
+            .iter()
+            .map(|(path, document)| (path, document.as_bytes()))
+            .chain(
+                self.media
+                    .iter()
+                    .map(|(path, bytes)| (path, bytes.as_ref())),
+            );
+        for (path, bytes) in files {
+            let path = dir.join(path);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(path, bytes)?;
+        }
+        Ok(())
+    }
+}
+
+impl Graph {
+    /// Exports this graph as Markdown in the given layout.
+    ///
+    /// Ito descriptions are preserved as emphasized prefixes of the list items
+    /// (`- *description*: ...`).
+    pub fn into_markdown(mut self, layout: MarkdownLayout) -> MarkdownExport {
+        self.sort_itos();
+        let mut export = MarkdownExport::default();
+        for coto in self.cotos.values() {
+            if let Some((bytes, media_type)) = coto.media_content() {
+                export
+                    .media
+                    .push((PathBuf::from(media_path(coto.uuid, &media_type)), bytes));
+            }
+        }
+        export
+            .media
+            .sort_by(|(path1, _), (path2, _)| path1.cmp(path2));
+
+        match layout {
+            MarkdownLayout::Document => {
+                let root = self.root();
+                let mut lines = vec![format!("# {}", coto_title(root)), String::new()];
+                lines.extend(coto_body(root));
+                if lines.last().is_some_and(|line| !line.is_empty()) {
+                    lines.push(String::new());
+                }
+                let mut visited = HashSet::from([self.root_id]);
+                self.write_list(&self.root_id, 0, &mut visited, &mut lines);
+                lines.push(String::new());
+                export
+                    .documents
+                    .push((PathBuf::from("index.md"), lines.join("\n")));
+            }
+            MarkdownLayout::Files => {
+                let mut cotos: Vec<&Coto> = self.cotos.values().collect();
+                cotos.sort_by_key(|coto| (coto.created_at, coto.uuid.as_uuid()));
+                for coto in cotos {
+                    let path = PathBuf::from(format!("{}.md", coto.uuid));
+                    export.documents.push((path, self.coto_file(coto)));
+                }
+            }
+        }
+        export
+    }
+
+    fn children(&self, coto_id: &Id<Coto>) -> impl Iterator<Item = (&Ito, &Coto)> {
+        self.itos
+            .get(coto_id)
+            .into_iter()
+            .flatten()
+            .filter_map(|ito| Some((ito, self.cotos.get(&ito.target_coto_id)?)))
+    }
+
+    fn write_list(
+        &self,
+        coto_id: &Id<Coto>,
+        depth: usize,
+        visited: &mut HashSet<Id<Coto>>,
+        lines: &mut Vec<String>,
+    ) {
+        let indent = "  ".repeat(depth);
+        for (ito, child) in self.children(coto_id) {
+            let mut body = if child.is_cotonoma {
+                vec![format!("[[{}]]", coto_title(child))]
+            } else {
+                coto_body(child)
+            };
+            if body.is_empty() {
+                body.push(String::new());
+            }
+            if let Some(description) = ito.description.as_deref() {
+                body[0] = format!("*{}*: {}", escape_markdown(description), body[0]);
+            }
+            for (i, line) in body.iter().enumerate() {
+                if i == 0 {
+                    lines.push(format!("{indent}- {line}").trim_end().to_owned());
+                } else if line.is_empty() {
+                    lines.push(String::new());
+                } else {
+                    lines.push(format!("{indent}  {line}"));
+                }
+            }
+            if visited.insert(child.uuid) {
+                self.write_list(&child.uuid, depth + 1, visited, lines);
+            }
+        }
+    }
+
+    fn coto_file(&self, coto: &Coto) -> String {
+        let mut lines = vec!["---".to_owned()];
+        let mut field = |name: &str, value: String| lines.push(format!("{name}: {value}"));
+        field("uuid", quote(&coto.uuid.to_string()));
+        field("node_id", quote(&coto.node_id.to_string()));
+        field("title", quote(&coto_title(coto)));
+        field("is_cotonoma", coto.is_cotonoma.to_string());
+        field("created_at", format_datetime(&coto.created_at));
+        field("updated_at", format_datetime(&coto.updated_at));
+        if let Some(start) = coto.datetime_start.as_ref() {
+            field("datetime_start", format_datetime(start));
+        }
+        if let Some(end) = coto.datetime_end.as_ref() {
+            field("datetime_end", format_datetime(end));
+        }
+        if let (Some(longitude), Some(latitude)) = (coto.longitude, coto.latitude) {
+            field("longitude", longitude.to_string());
+            field("latitude", latitude.to_string());
+        }
+        if let Some(media_type) = coto.media_type.as_deref() {
+            field("media_type", quote(media_type));
+        }
+        lines.push("---".to_owned());
+        lines.push(String::new());
+
+        let body = if coto.is_cotonoma {
+            vec![format!("# {}", coto_title(coto))]
+        } else {
+            coto_body(coto)
+        };
+        if !body.is_empty() {
+            lines.extend(body);
+            lines.push(String::new());
+        }
+
+        let children: Vec<String> = self
+            .children(&coto.uuid)
+            .map(|(ito, child)| {
+                let link = format!(
+                    "[[{}|{}]]",
+                    child.uuid,
+                    escape_link_label(&coto_label(child))
+                );
+                match ito.description.as_deref() {
+                    Some(description) => format!("- *{}*: {link}", escape_markdown(description)),
+                    None => format!("- {link}"),
+                }
+            })
+            .collect();
+        if !children.is_empty() {
+            lines.extend(children);
+            lines.push(String::new());
+        }
+        lines.join("\n")
+    }
+}
+
+/// Title of a coto: the cotonoma name, the summary or the first non-empty line of
+/// the content without heading marks.
+pub fn coto_title(coto: &Coto) -> String {
+    if let Some(name) = coto.name_as_cotonoma() {
+        name.to_owned()
+    } else if let Some(summary) = coto.summary.as_deref() {
+        summary.to_owned()
+    } else {
+        coto.content
+            .as_deref()
+            .and_then(|content| content.lines().find(|line| !line.trim().is_empty()))
+            .map(|line| line.trim_start_matches(['#', ' ']).to_owned())
+            .unwrap_or_default()
+    }
+}
+
+/// Lines of a (non-cotonoma) coto: the summary in bold, the content and the media.
+fn coto_body(coto: &Coto) -> Vec<String> {
+    let mut lines = Vec::new();
+    if coto.is_cotonoma {
+        return lines;
+    }
+    if let Some(summary) = coto.summary.as_deref() {
+        lines.push(format!("**{}**", escape_markdown(summary)));
+        lines.push(String::new());
+    }
+    if let Some(content) = coto.content.as_deref() {
+        lines.extend(content.trim_end().lines().map(str::to_owned));
+    }
+    if let Some(media_type) = coto.media_type.as_deref() {
+        if coto.media_content.is_some() {
+            let path = media_path(coto.uuid, media_type);
+            lines.push(format!("![]({path})"));
+        }
+    }
+    while lines.last().is_some_and(String::is_empty) {
+        lines.pop();
+    }
+    lines
+}
+
+/// Relative path (or URL) of the media file of a coto
+pub fn media_path(coto_id: Id<Coto>, media_type: &str) -> String {
+    let extension = match media_type {
+        "image/jpeg" => "jpg",
+        "image/png" => "png",
+        "image/gif" => "gif",
+        "image/webp" => "webp",
+        "image/svg+xml" => "svg",
+        _ => "bin",
+    };
+    format!("{MEDIA_DIR}/{coto_id}.{extension}")
+}
+
+fn format_datetime(datetime: &NaiveDateTime) -> String {
+    datetime.format("%Y-%m-%dT%H:%M:%SZ").to_string()
+}
+
+/// Quotes a string for the front matter. A JSON string is also a valid YAML string.
+fn quote(s: &str) -> String { serde_json::Value::from(s).to_string() }
+
+fn escape_markdown(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('*', "\\*")
+        .replace('_', "\\_")
+        .replace('\n', " ")
+}
+
+fn escape_link_label(s: &str) -> String { s.replace(['|', '[', ']'], " ").replace('\n', " ") }
@@ -0,0 +1,122 @@
+//! Exporting the stock of a cotonoma (a [Graph] from a cotonoma) as an OPML outline.
+//!
+//! Each coto maps to an `<outline>` element whose children are the targets of its
+//! outgoing itos ordered by [Ito::order]. Like the Markdown export, a coto reachable
+//! via more than one ito appears in every place, but its children are expanded only
+//! at the first one.
+//!
+//! The attributes of an outline are:
+//!
+//! * `text` - The summary of the coto, or the content if it has no summary
+//!   (the name for a cotonoma)
+//! * `_note` - The content of the coto if it has a summary (empty if it has only
+//!   the summary)
+//! * `_cotonoma` - `true` if the coto is a cotonoma
+//! * `_description` - The description of the ito to the coto
+//! * `created` - The creation date of the coto in RFC 822 format
+//!
+//! which can be imported back by [crate::import::opml::import_opml].
+
+use std::collections::HashSet;
+
+use super::{export::escape_xml, Graph};
+use crate::models::{coto::Coto, ito::Ito, Id};
+
+impl Graph {
+    /// Exports this graph as an OPML 2.0 document.
+    pub fn into_opml(mut self) -> String {
+        self.sort_itos();
+        let root = self.root();
+        let mut lines = vec![
+            r#"<?xml version="1.0" encoding="UTF-8"?>"#.to_owned(),
+            r#"<opml version="2.0">"#.to_owned(),
+            "  <head>".to_owned(),
+            format!("    <title>{}</title>", escape_xml(&outline_text(root))),
+            format!(
+                "    <dateCreated>{}</dateCreated>",
+                root.created_at.and_utc().to_rfc2822()
+            ),
+            "  </head>".to_owned(),
+            "  <body>".to_owned(),
+        ];
+        let mut visited = HashSet::from([self.root_id]);
+        self.write_outlines(&self.root_id, 2, &mut visited, &mut lines);
+        lines.push("  </body>".to_owned());
+        lines.push("</opml>\n".to_owned());
+        lines.join("\n")
+    }
+
+    fn write_outlines(
+        &self,
+        coto_id: &Id<Coto>,
+        depth: usize,
+        visited: &mut HashSet<Id<Coto>>,
+        lines: &mut Vec<String>,
+    ) {
+        let indent = "  ".repeat(depth);
+        let children: Vec<(&Ito, &Coto)> = self
+            .itos
+            .get(coto_id)
+            .into_iter()
+            .flatten()
+            .filter_map(|ito| Some((ito, self.cotos.get(&ito.target_coto_id)?)))
+            .collect();
+        for (ito, child) in children {
+            let mut attrs = vec![("text", outline_text(child))];
+            if let Some(note) = outline_note(child) {
+                attrs.push(("_note", note.to_owned()));
+            }
+            if child.is_cotonoma {
+                attrs.push(("_cotonoma", "true".to_owned()));
+            }
+            if let Some(description) = ito.description.as_deref() {
+                attrs.push(("_description", description.to_owned()));
+            }
+            attrs.push(("created", child.created_at.and_utc().to_rfc2822()));
+            let attrs: Vec<String> = attrs
+                .into_iter()
+                .map(|(name, value)| format!("{name}=\"{}\"", escape_attr(&value)))
+                .collect();
+
+            let expand = visited.insert(child.uuid)
+                && self
+                    .itos
+                    .get(&child.uuid)
+                    .is_some_and(|itos| !itos.is_empty());
+            if expand {
+                lines.push(format!("{indent}<outline {}>", attrs.join(" ")));
+                self.write_outlines(&child.uuid, depth + 1, visited, lines);
+                lines.push(format!("{indent}</outline>"));
+            } else {
+                lines.push(format!("{indent}<outline {}/>", attrs.join(" ")));
+            }
+        }
+    }
+}
+
+fn outline_text(coto: &Coto) -> String {
+    if let Some(name) = coto.name_as_cotonoma() {
+        name.to_owned()
+    } else if let Some(summary) = coto.summary.as_deref() {
+        summary.to_owned()
+    } else {
+        coto.content.as_deref().unwrap_or_default().to_owned()
+    }
+}
+
+fn outline_note(coto: &Coto) -> Option<&str> {
+    if coto.is_cotonoma || coto.summary.is_none() {
+        None
+    } else {
+        Some(coto.content.as_deref().unwrap_or_default())
+    }
+}
+
+/// Escapes an attribute value, in which line breaks have to be character references
+/// to survive the attribute-value normalization of XML parsers.
+fn escape_attr(s: &str) -> String {
+    escape_xml(s)
+        .replace('\r', "&#13;")
+        .replace('\n', "&#10;")
+        .replace('\t', "&#9;")
+}
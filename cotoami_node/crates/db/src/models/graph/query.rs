@@ -0,0 +1,766 @@
+//! A small declarative query language for pattern matching over cotos and itos.
+//!
+//! A query consists of a `MATCH` clause with a path pattern (in a Cypher-like syntax)
+//! and an optional `WHERE` clause with conditions joined by `AND`:
+//!
+//! ```text
+//! MATCH (a {cotonoma: "A"})-[i]->(b:Coto)
+//! WHERE i.description = "cause" AND b.posted_by = "<node-id>" AND b.created_at >= "30d"
+//! ```
+//!
+//! * A node pattern `(var:Label {field: value, ...})` matches cotos. All the parts are
+//!   optional, and the label `Cotonoma` restricts the matches to cotonoma cotos.
+//! * An edge pattern `-[var {field: value, ...}]->` or `<-[...]-` matches itos in
+//!   the given direction. `-->` and `<--` are shorthands for edges without any conditions.
+//! * Conditions compare a field of a variable with a value by one of the operators:
+//!   `=`, `!=` (or `<>`), `<`, `<=`, `>`, `>=` and `CONTAINS`. Inline properties in
+//!   patterns are equality conditions.
+//! * Values are double-quoted strings, `true`, `false` or `null`. A string compared with
+//!   a datetime field can be a date (`2026-01-31`), a datetime (`2026-01-31T12:00:00`,
+//!   in UTC) or a duration back from now (such as `30d`, `12h`).
+//! * A coto can match more than one node pattern in a path.
+//!
+//! Fields of cotos: `id`, `node`, `posted_in`, `cotonoma` (name of the cotonoma in which
+//! the coto was posted), `posted_by`, `content`, `summary`, `is_cotonoma`, `created_at`,
+//! `updated_at`.
+//!
+//! Fields of itos: `id`, `node`, `created_by`, `description`, `details`, `created_at`,
+//! `updated_at`.
+
+use std::{fmt::Display, str::FromStr};
+
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+
+use crate::{db::error::DatabaseError, time::current_datetime};
+
+/// A parsed graph query
+#[derive(Debug, Clone, PartialEq)]
+pub struct GraphQuery {
+    /// Node patterns in the path (at least one)
+    pub nodes: Vec<NodePattern>,
+
+    /// Edge patterns in the path, each of which connects `nodes[i]` and `nodes[i + 1]`
+    pub edges: Vec<EdgePattern>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NodePattern {
+    pub variable: Option<String>,
+    pub conditions: Vec<Condition<CotoField>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EdgePattern {
+    pub variable: Option<String>,
+    pub direction: Direction,
+    pub conditions: Vec<Condition<ItoField>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// `(a)-[]->(b)`: from the left node to the right node
+    Outgoing,
+
+    /// `(a)<-[]-(b)`: from the right node to the left node
+    Incoming,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Condition<F> {
+    pub field: F,
+    pub operator: Operator,
+    pub value: Value,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Contains,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    Bool(bool),
+    DateTime(NaiveDateTime),
+    Null,
+}
+
+/// Kind of a field, which determines the values and operators applicable to it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldKind {
+    Id,
+    NullableId,
+    NullableText,
+    Bool,
+    DateTime,
+}
+
+pub trait Field: FromStr<Err = String> {
+    fn kind(&self) -> FieldKind;
+}
+
+impl FieldKind {
+    /// Checks if the given operator and value are applicable to this kind of field
+    /// and converts the value into the type of the field.
+    fn typed_value(&self, operator: Operator, value: Value) -> Result<Value, String> {
+        let is_nullable = matches!(self, Self::NullableId | Self::NullableText);
+        let is_equality = matches!(operator, Operator::Eq | Operator::Ne);
+        match (self, value) {
+            (_, Value::Null) if is_nullable && is_equality => Ok(Value::Null),
+            (_, Value::Null) => Err("null is not applicable here".into()),
+            (Self::Id | Self::NullableId, Value::String(s)) if is_equality => {
+                uuid::Uuid::parse_str(&s)
+                    .map(|uuid| Value::String(uuid.to_string()))
+                    .map_err(|_| format!("invalid ID: {s}"))
+            }
+            (Self::NullableText, value @ Value::String(_)) => Ok(value),
+            (Self::Bool, value @ Value::Bool(_)) if is_equality => Ok(value),
+            (Self::DateTime, Value::String(s)) if operator != Operator::Contains => {
+                parse_datetime(&s).map(Value::DateTime)
+            }
+            _ => Err("operator or value is not applicable to the field".into()),
+        }
+    }
+}
+
+/// Parses a date, a datetime or a duration back from now.
+fn parse_datetime(s: &str) -> Result<NaiveDateTime, String> {
+    if let Ok(datetime) = NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S") {
+        return Ok(datetime);
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        return Ok(date.and_time(Default::default()));
+    }
+    s.char_indices()
+        .last()
+        .and_then(|(i, unit)| {
+            let n: i64 = s[..i].parse().ok()?;
+            match unit {
+                'm' => Duration::try_minutes(n),
+                'h' => Duration::try_hours(n),
+                'd' => Duration::try_days(n),
+                'w' => Duration::try_weeks(n),
+                _ => None,
+            }
+        })
+        .and_then(|duration| current_datetime().checked_sub_signed(duration))
+        .ok_or_else(|| format!("invalid datetime: {s}"))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CotoField {
+    Id,
+    Node,
+    PostedIn,
+    Cotonoma,
+    PostedBy,
+    Content,
+    Summary,
+    IsCotonoma,
+    CreatedAt,
+    UpdatedAt,
+}
+
+impl Field for CotoField {
+    fn kind(&self) -> FieldKind {
+        match self {
+            Self::Id | Self::Node | Self::PostedBy => FieldKind::Id,
+            Self::PostedIn => FieldKind::NullableId,
+            Self::Cotonoma | Self::Content | Self::Summary => FieldKind::NullableText,
+            Self::IsCotonoma => FieldKind::Bool,
+            Self::CreatedAt | Self::UpdatedAt => FieldKind::DateTime,
+        }
+    }
+}
+
+impl FromStr for CotoField {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "id" => Ok(Self::Id),
+            "node" => Ok(Self::Node),
+            "posted_in" => Ok(Self::PostedIn),
+            "cotonoma" => Ok(Self::Cotonoma),
+            "posted_by" => Ok(Self::PostedBy),
+            "content" => Ok(Self::Content),
+            "summary" => Ok(Self::Summary),
+            "is_cotonoma" => Ok(Self::IsCotonoma),
+            "created_at" => Ok(Self::CreatedAt),
+            "updated_at" => Ok(Self::UpdatedAt),
+            _ => Err(format!("unknown coto field: {s}")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ItoField {
+    Id,
+    Node,
+    CreatedBy,
+    Description,
+    Details,
+    CreatedAt,
+    UpdatedAt,
+}
+
+impl Field for ItoField {
+    fn kind(&self) -> FieldKind {
+        match self {
+            Self::Id | Self::Node | Self::CreatedBy => FieldKind::Id,
+            Self::Description | Self::Details => FieldKind::NullableText,
+            Self::CreatedAt | Self::UpdatedAt => FieldKind::DateTime,
+        }
+    }
+}
+
+impl FromStr for ItoField {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "id" => Ok(Self::Id),
+            "node" => Ok(Self::Node),
+            "created_by" => Ok(Self::CreatedBy),
+            "description" => Ok(Self::Description),
+            "details" => Ok(Self::Details),
+            "created_at" => Ok(Self::CreatedAt),
+            "updated_at" => Ok(Self::UpdatedAt),
+            _ => Err(format!("unknown ito field: {s}")),
+        }
+    }
+}
+
+impl FromStr for GraphQuery {
+    type Err = DatabaseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> { Parser::new(s)?.parse_query() }
+}
+
+impl GraphQuery {
+    pub fn parse(query: &str) -> Result<Self, DatabaseError> { query.parse() }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+// Tokenizer
+/////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    String(String),
+    Symbol(&'static str),
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Ident(ident) => write!(f, "{ident}"),
+            Token::String(s) => write!(f, "\"{s}\""),
+            Token::Symbol(symbol) => write!(f, "{symbol}"),
+        }
+    }
+}
+
+/// Symbols in the order of matching (longer ones first)
+const SYMBOLS: &[&str] = &[
+    "->", "<-", "<=", ">=", "!=", "<>", "(", ")", "[", "]", "{", "}", ":", ",", ".", "-", "<", ">",
+    "=",
+];
+
+/// Splits a query into tokens, each of which is paired with its position (in chars).
+fn tokenize(query: &str) -> Result<Vec<(usize, Token)>, DatabaseError> {
+    let chars: Vec<char> = query.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '"' {
+            let start = i;
+            let mut s = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    Some('"') => break,
+                    Some('\\') => {
+                        match chars.get(i + 1) {
+                            Some('n') => s.push('\n'),
+                            Some(c @ ('"' | '\\')) => s.push(*c),
+                            _ => return Err(invalid(i, "invalid escape sequence")),
+                        }
+                        i += 2;
+                    }
+                    Some(c) => {
+                        s.push(*c);
+                        i += 1;
+                    }
+                    None => return Err(invalid(start, "unterminated string")),
+                }
+            }
+            i += 1;
+            tokens.push((start, Token::String(s)));
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while chars
+                .get(i)
+                .is_some_and(|c| c.is_alphanumeric() || *c == '_')
+            {
+                i += 1;
+            }
+            tokens.push((start, Token::Ident(chars[start..i].iter().collect())));
+        } else {
+            let symbol = SYMBOLS
+                .iter()
+                .find(|symbol| {
+                    symbol
+                        .chars()
+                        .enumerate()
+                        .all(|(j, c)| chars.get(i + j) == Some(&c))
+                })
+                .ok_or_else(|| invalid(i, format!("unexpected character: {c}")))?;
+            tokens.push((i, Token::Symbol(symbol)));
+            i += symbol.len();
+        }
+    }
+    Ok(tokens)
+}
+
+fn invalid(position: usize, reason: impl Into<String>) -> DatabaseError {
+    DatabaseError::InvalidGraphQuery {
+        position,
+        reason: reason.into(),
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+// Parser
+/////////////////////////////////////////////////////////////////////////////
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    index: usize,
+    end: usize,
+}
+
+impl Parser {
+    fn new(query: &str) -> Result<Self, DatabaseError> {
+        Ok(Self {
+            tokens: tokenize(query)?,
+            index: 0,
+            end: query.chars().count(),
+        })
+    }
+
+    fn parse_query(mut self) -> Result<GraphQuery, DatabaseError> {
+        self.expect_keyword("MATCH")?;
+
+        // Path
+        let mut query = GraphQuery {
+            nodes: vec![self.parse_node()?],
+            edges: Vec::new(),
+        };
+        while self.peek_symbol("-") || self.peek_symbol("<-") {
+            query.edges.push(self.parse_edge()?);
+            query.nodes.push(self.parse_node()?);
+        }
+
+        // Variables must be unique in a path
+        let mut variables: Vec<&str> = query
+            .nodes
+            .iter()
+            .filter_map(|node| node.variable.as_deref())
+            .chain(
+                query
+                    .edges
+                    .iter()
+                    .filter_map(|edge| edge.variable.as_deref()),
+            )
+            .collect();
+        variables.sort();
+        if let Some(pair) = variables.windows(2).find(|pair| pair[0] == pair[1]) {
+            return Err(invalid(0, format!("duplicate variable: {}", pair[0])));
+        }
+
+        // Conditions
+        if self.peek_keyword("WHERE") {
+            self.index += 1;
+            loop {
+                self.parse_where_condition(&mut query)?;
+                if self.peek_keyword("AND") {
+                    self.index += 1;
+                } else {
+                    break;
+                }
+            }
+        }
+
+        if let Some((position, token)) = self.tokens.get(self.index) {
+            return Err(invalid(*position, format!("unexpected token: {token}")));
+        }
+        Ok(query)
+    }
+
+    // (var:Label {field: value, ...})
+    fn parse_node(&mut self) -> Result<NodePattern, DatabaseError> {
+        self.expect_symbol("(")?;
+        let mut node = NodePattern {
+            variable: self.parse_variable(),
+            ..Default::default()
+        };
+        if self.peek_symbol(":") {
+            self.index += 1;
+            let position = self.position();
+            match self.expect_ident()?.as_str() {
+                "Coto" => (),
+                "Cotonoma" => node.conditions.push(Condition {
+                    field: CotoField::IsCotonoma,
+                    operator: Operator::Eq,
+                    value: Value::Bool(true),
+                }),
+                label => return Err(invalid(position, format!("unknown label: {label}"))),
+            }
+        }
+        if self.peek_symbol("{") {
+            node.conditions.extend(self.parse_properties()?);
+        }
+        self.expect_symbol(")")?;
+        Ok(node)
+    }
+
+    // -[var {field: value, ...}]->, <-[...]-, -->, <--
+    fn parse_edge(&mut self) -> Result<EdgePattern, DatabaseError> {
+        let incoming = self.peek_symbol("<-");
+        self.index += 1; // "-" or "<-"
+        let mut edge = EdgePattern {
+            variable: None,
+            direction: if incoming {
+                Direction::Incoming
+            } else {
+                Direction::Outgoing
+            },
+            conditions: Vec::new(),
+        };
+        if self.peek_symbol("[") {
+            self.index += 1;
+            edge.variable = self.parse_variable();
+            if self.peek_symbol("{") {
+                edge.conditions = self.parse_properties()?;
+            }
+            self.expect_symbol("]")?;
+        }
+        self.expect_symbol(if incoming { "-" } else { "->" })?;
+        Ok(edge)
+    }
+
+    fn parse_variable(&mut self) -> Option<String> {
+        match self.tokens.get(self.index) {
+            Some((_, Token::Ident(ident))) => {
+                let ident = ident.clone();
+                self.index += 1;
+                Some(ident)
+            }
+            _ => None,
+        }
+    }
+
+    // {field: value, ...}
+    fn parse_properties<F: Field>(&mut self) -> Result<Vec<Condition<F>>, DatabaseError> {
+        self.expect_symbol("{")?;
+        let mut conditions = Vec::new();
+        while !self.peek_symbol("}") {
+            if !conditions.is_empty() {
+                self.expect_symbol(",")?;
+            }
+            let field = self.parse_field()?;
+            self.expect_symbol(":")?;
+            conditions.push(self.parse_condition_value(field, Operator::Eq)?);
+        }
+        self.expect_symbol("}")?;
+        Ok(conditions)
+    }
+
+    // var.field op value
+    fn parse_where_condition(&mut self, query: &mut GraphQuery) -> Result<(), DatabaseError> {
+        let position = self.position();
+        let variable = self.expect_ident()?;
+        self.expect_symbol(".")?;
+        let matches = |v: &Option<String>| v.as_ref() == Some(&variable);
+        if let Some(node) = query.nodes.iter_mut().find(|n| matches(&n.variable)) {
+            let field = self.parse_field()?;
+            let operator = self.parse_operator()?;
+            node.conditions
+                .push(self.parse_condition_value(field, operator)?);
+        } else if let Some(edge) = query.edges.iter_mut().find(|e| matches(&e.variable)) {
+            let field = self.parse_field()?;
+            let operator = self.parse_operator()?;
+            edge.conditions
+                .push(self.parse_condition_value(field, operator)?);
+        } else {
+            return Err(invalid(position, format!("unknown variable: {variable}")));
+        }
+        Ok(())
+    }
+
+    fn parse_field<F: Field>(&mut self) -> Result<F, DatabaseError> {
+        let position = self.position();
+        self.expect_ident()?
+            .parse()
+            .map_err(|e| invalid(position, e))
+    }
+
+    fn parse_condition_value<F: Field>(
+        &mut self,
+        field: F,
+        operator: Operator,
+    ) -> Result<Condition<F>, DatabaseError> {
+        let position = self.position();
+        let value = field
+            .kind()
+            .typed_value(operator, self.parse_value()?)
+            .map_err(|e| invalid(position, e))?;
+        Ok(Condition {
+            field,
+            operator,
+            value,
+        })
+    }
+
+    fn parse_operator(&mut self) -> Result<Operator, DatabaseError> {
+        let position = self.position();
+        let operator = match self.tokens.get(self.index) {
+            Some((_, Token::Symbol("="))) => Operator::Eq,
+            Some((_, Token::Symbol("!=" | "<>"))) => Operator::Ne,
+            Some((_, Token::Symbol("<"))) => Operator::Lt,
+            Some((_, Token::Symbol("<="))) => Operator::Le,
+            Some((_, Token::Symbol(">"))) => Operator::Gt,
+            Some((_, Token::Symbol(">="))) => Operator::Ge,
+            Some((_, Token::Ident(ident))) if ident.eq_ignore_ascii_case("CONTAINS") => {
+                Operator::Contains
+            }
+            _ => return Err(invalid(position, "operator expected")),
+        };
+        self.index += 1;
+        Ok(operator)
+    }
+
+    fn parse_value(&mut self) -> Result<Value, DatabaseError> {
+        let position = self.position();
+        let value = match self.tokens.get(self.index) {
+            Some((_, Token::String(s))) => Value::String(s.clone()),
+            Some((_, Token::Ident(ident))) => match ident.to_lowercase().as_str() {
+                "true" => Value::Bool(true),
+                "false" => Value::Bool(false),
+                "null" => Value::Null,
+                _ => return Err(invalid(position, "value expected")),
+            },
+            _ => return Err(invalid(position, "value expected")),
+        };
+        self.index += 1;
+        Ok(value)
+    }
+
+    fn position(&self) -> usize {
+        self.tokens
+            .get(self.index)
+            .map(|(position, _)| *position)
+            .unwrap_or(self.end)
+    }
+
+    fn peek_symbol(&self, symbol: &str) -> bool {
+        matches!(self.tokens.get(self.index), Some((_, Token::Symbol(s))) if *s == symbol)
+    }
+
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        matches!(
+            self.tokens.get(self.index),
+            Some((_, Token::Ident(ident))) if ident.eq_ignore_ascii_case(keyword)
+        )
+    }
+
+    fn expect_symbol(&mut self, symbol: &str) -> Result<(), DatabaseError> {
+        if self.peek_symbol(symbol) {
+            self.index += 1;
+            Ok(())
+        } else {
+            Err(invalid(self.position(), format!("`{symbol}` expected")))
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), DatabaseError> {
+        if self.peek_keyword(keyword) {
+            self.index += 1;
+            Ok(())
+        } else {
+            Err(invalid(self.position(), format!("`{keyword}` expected")))
+        }
+    }
+
+    fn expect_ident(&mut self) -> Result<String, DatabaseError> {
+        match self.tokens.get(self.index) {
+            Some((_, Token::Ident(ident))) => {
+                let ident = ident.clone();
+                self.index += 1;
+                Ok(ident)
+            }
+            _ => Err(invalid(self.position(), "identifier expected")),
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+// tests
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use googletest::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn parse_path() -> Result<()> {
+        let query = GraphQuery::parse(
+            r#"MATCH (a:Cotonoma {cotonoma: "x"})-[i {description: "cause"}]->(b)<--(c)"#,
+        )?;
+
+        assert_that!(query.nodes.len(), eq(3));
+        assert_that!(
+            query.nodes[0],
+            eq(&NodePattern {
+                variable: Some("a".into()),
+                conditions: vec![
+                    Condition {
+                        field: CotoField::IsCotonoma,
+                        operator: Operator::Eq,
+                        value: Value::Bool(true),
+                    },
+                    Condition {
+                        field: CotoField::Cotonoma,
+                        operator: Operator::Eq,
+                        value: Value::String("x".into()),
+                    }
+                ]
+            })
+        );
+        assert_that!(
+            query.edges,
+            elements_are![
+                eq(&EdgePattern {
+                    variable: Some("i".into()),
+                    direction: Direction::Outgoing,
+                    conditions: vec![Condition {
+                        field: ItoField::Description,
+                        operator: Operator::Eq,
+                        value: Value::String("cause".into()),
+                    }]
+                }),
+                eq(&EdgePattern {
+                    variable: None,
+                    direction: Direction::Incoming,
+                    conditions: vec![]
+                })
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn parse_where() -> Result<()> {
+        let now = crate::time::mock_time();
+        let query = GraphQuery::parse(
+            r#"match (a)-[i]->(b) where i.description != null and b.created_at >= "30d"
+               AND a.content CONTAINS "foo" AND b.updated_at < "2026-01-31""#,
+        )?;
+        assert_that!(
+            query.nodes[0].conditions,
+            elements_are![eq(&Condition {
+                field: CotoField::Content,
+                operator: Operator::Contains,
+                value: Value::String("foo".into()),
+            })]
+        );
+        assert_that!(
+            query.edges[0].conditions,
+            elements_are![eq(&Condition {
+                field: ItoField::Description,
+                operator: Operator::Ne,
+                value: Value::Null,
+            })]
+        );
+        assert_that!(
+            query.nodes[1].conditions,
+            elements_are![
+                eq(&Condition {
+                    field: CotoField::CreatedAt,
+                    operator: Operator::Ge,
+                    value: Value::DateTime(now - Duration::days(30)),
+                }),
+                eq(&Condition {
+                    field: CotoField::UpdatedAt,
+                    operator: Operator::Lt,
+                    value: Value::DateTime(
+                        NaiveDate::from_ymd_opt(2026, 1, 31)
+                            .unwrap()
+                            .and_time(Default::default())
+                    ),
+                })
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn parse_errors() -> Result<()> {
+        let error = |query: &str| GraphQuery::parse(query).unwrap_err().to_string();
+        assert_that!(
+            error("(a)"),
+            eq("Invalid graph query at 0: `MATCH` expected")
+        );
+        assert_that!(
+            error("MATCH (a)-(b)"),
+            eq("Invalid graph query at 10: `->` expected")
+        );
+        assert_that!(
+            error("MATCH (a)-->(a)"),
+            eq("Invalid graph query at 0: duplicate variable: a")
+        );
+        assert_that!(
+            error(r#"MATCH (a {name: "x"})"#),
+            eq("Invalid graph query at 10: unknown coto field: name")
+        );
+        assert_that!(
+            error(r#"MATCH (a) WHERE b.content = "x""#),
+            eq("Invalid graph query at 16: unknown variable: b")
+        );
+        assert_that!(
+            error(r#"MATCH (a) WHERE a.content = "x"#),
+            eq("Invalid graph query at 28: unterminated string")
+        );
+        assert_that!(
+            error(r#"MATCH (a) WHERE a.is_cotonoma > true"#),
+            eq("Invalid graph query at 32: operator or value is not applicable to the field")
+        );
+        assert_that!(
+            error(r#"MATCH (a {created_at: "yesterday"})"#),
+            eq("Invalid graph query at 22: invalid datetime: yesterday")
+        );
+        assert_that!(
+            error(r#"MATCH (a {created_at: "3é"})"#),
+            eq("Invalid graph query at 22: invalid datetime: 3é")
+        );
+        assert_that!(
+            error(r#"MATCH (a {created_at: "100000000d"})"#),
+            eq("Invalid graph query at 22: invalid datetime: 100000000d")
+        );
+        Ok(())
+    }
+}
@@ -4,7 +4,12 @@ use diesel::prelude::*;
 use validator::Validate;
 
 use super::Node;
-use crate::{models::Id, schema::child_nodes};
+use crate::{
+    models::{
+        changelog::ChangeSubscription, cotonoma::Cotonoma, node::parent::OutboxEntry, Id, Ids,
+    },
+    schema::{child_nodes, handled_requests},
+};
 
 /////////////////////////////////////////////////////////////////////////////
 // ChildNode
@@ -38,6 +43,34 @@ pub struct ChildNode {
 
     /// Permission to post cotonomas in this database.
     pub can_post_cotonomas: bool,
+
+    /// Serial number of the last change that this child has acknowledged to have received.
+    #[serde(default)]
+    pub changes_acknowledged: i64,
+
+    /// Cotonomas to which this child has subscribed in its current session
+    /// (See [ChildNode::subscription]).
+    #[serde(default)]
+    pub subscribed_cotonoma_ids: Option<Ids<Cotonoma>>,
+
+    #[serde(default)]
+    pub subscription_recursive: bool,
+
+    /// TRUE if this child has requested to omit media content from the changes
+    /// in its current session (See [crate::models::changelog::Change::omit_media]).
+    #[serde(default)]
+    pub lazy_media: bool,
+}
+
+impl ChildNode {
+    /// Returns the [ChangeSubscription] of this child, or `None` if
+    /// the child replicates the whole database of the local node.
+    pub fn subscription(&self) -> Option<ChangeSubscription> {
+        ChangeSubscription::from_columns(
+            self.subscribed_cotonoma_ids.as_ref(),
+            self.subscription_recursive,
+        )
+    }
 }
 
 /////////////////////////////////////////////////////////////////////////////
@@ -67,6 +100,30 @@ impl<'a> NewChildNode<'a> {
     }
 }
 
+/////////////////////////////////////////////////////////////////////////////
+// NewHandledRequest
+/////////////////////////////////////////////////////////////////////////////
+
+/// An `Insertable` idempotency key of a request from a child node, which is
+/// an [OutboxEntry] of the child.
+#[derive(Insertable)]
+#[diesel(table_name = handled_requests)]
+pub(crate) struct NewHandledRequest<'a> {
+    idempotency_key: &'a Id<OutboxEntry>,
+    node_id: &'a Id<Node>,
+    created_at: NaiveDateTime,
+}
+
+impl<'a> NewHandledRequest<'a> {
+    pub fn new(idempotency_key: &'a Id<OutboxEntry>, node_id: &'a Id<Node>) -> Self {
+        Self {
+            idempotency_key,
+            node_id,
+            created_at: crate::current_datetime(),
+        }
+    }
+}
+
 /////////////////////////////////////////////////////////////////////////////
 // ChildNodeInput
 /////////////////////////////////////////////////////////////////////////////
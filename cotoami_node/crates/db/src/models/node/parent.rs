@@ -5,7 +5,12 @@ use diesel::prelude::*;
 use validator::Validate;
 
 use super::Node;
-use crate::{models::Id, schema::parent_nodes};
+use crate::{
+    models::{
+        changelog::ChangeSubscription, coto::Coto, cotonoma::Cotonoma, ito::Ito, Bytes, Id, Ids,
+    },
+    schema::{outbox, parent_nodes},
+};
 
 /////////////////////////////////////////////////////////////////////////////
 // ParentNode
@@ -42,9 +47,145 @@ pub struct ParentNode {
 
     /// TRUE if the local node has been forked from this parent node.
     ///
-    /// A forked child can never connect to or accept changes
-    /// (directly or indirectly) from the parent again.
+    /// A forked child can't connect to or accept changes from the parent
+    /// until it re-attaches to the parent to reconcile with it.
     pub forked: bool,
+
+    /// Cotonomas to which the local node subscribes in this parent
+    /// (See [ParentNode::subscription]).
+    #[serde(default)]
+    pub subscribed_cotonoma_ids: Option<Ids<Cotonoma>>,
+
+    #[serde(default)]
+    pub subscription_recursive: bool,
+
+    /// TRUE if the local node requests this parent to omit media content from
+    /// the changes to be fetched lazily (See [crate::models::coto::MissingMedia]).
+    #[serde(default)]
+    pub lazy_media: bool,
+
+    /// The last serial number of the local changelog when the local node was forked
+    /// from this parent node, after which the local changes have diverged from it.
+    #[serde(default)]
+    pub forked_at_serial_number: Option<i64>,
+}
+
+impl ParentNode {
+    /// Returns the [ChangeSubscription] to be requested to this parent, or `None` if
+    /// the local node replicates the whole database of the parent.
+    pub fn subscription(&self) -> Option<ChangeSubscription> {
+        ChangeSubscription::from_columns(
+            self.subscribed_cotonoma_ids.as_ref(),
+            self.subscription_recursive,
+        )
+    }
+
+    /// Returns true if the subscription can be changed to the given one.
+    ///
+    /// Once the local node has received changes from this parent, the subscription
+    /// can only be narrowed since the changes that have been received as placeholders
+    /// won't be sent again.
+    pub fn can_subscribe(&self, subscription: Option<&ChangeSubscription>) -> bool {
+        if self.changes_received == 0 {
+            return true;
+        }
+        match (self.subscription(), subscription) {
+            (None, _) => true,
+            (Some(_), None) => false,
+            (Some(current), Some(new)) => current.covers(new),
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+// ForkConflict
+/////////////////////////////////////////////////////////////////////////////
+
+/// A change made in a forked parent that involves the same entities as some of
+/// the local changes since the fork (ex. deleting a coto to which a local ito is connected).
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ForkConflict {
+    /// The change in the parent, which has not yet been imported.
+    pub parent_change: ChangeSummary,
+
+    /// Serial numbers of the local changes conflicting with the `parent_change`.
+    pub local_changes: Vec<i64>,
+}
+
+/// A summary of a change made in a forked parent, which is reported instead of
+/// the change itself not to carry its content (ex. media) around.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ChangeSummary {
+    /// Serial number of the change in the parent
+    pub serial_number: i64,
+
+    /// Kind of the change ([crate::models::changelog::Change::kind])
+    pub kind: String,
+
+    /// Cotos involved in the change
+    pub cotos: Vec<Id<Coto>>,
+
+    /// Cotonomas involved in the change
+    pub cotonomas: Vec<Id<Cotonoma>>,
+
+    /// Itos involved in the change
+    pub itos: Vec<Id<Ito>>,
+}
+
+/////////////////////////////////////////////////////////////////////////////
+// OutboxEntry
+/////////////////////////////////////////////////////////////////////////////
+
+/// A row in `outbox` table
+///
+/// A request to a parent node that has been queued while disconnected from the parent,
+/// which will be sent in the order of [OutboxEntry::serial_number] when reconnected.
+#[derive(
+    derive_more::Debug,
+    Clone,
+    PartialEq,
+    Identifiable,
+    Queryable,
+    Selectable,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+#[diesel(table_name = outbox, primary_key(serial_number))]
+pub struct OutboxEntry {
+    pub serial_number: i64,
+
+    /// Key to be sent with the request so that the parent won't handle it twice
+    /// even if it is sent again after the response has been lost.
+    pub idempotency_key: Id<OutboxEntry>,
+
+    pub parent_node_id: Id<Node>,
+
+    /// Command of the request, which is serialized by and opaque to this crate.
+    #[debug(skip)]
+    pub command: Bytes,
+
+    pub created_at: NaiveDateTime,
+}
+
+/// An `Insertable` outbox entry
+#[derive(Insertable)]
+#[diesel(table_name = outbox)]
+pub(crate) struct NewOutboxEntry<'a> {
+    idempotency_key: Id<OutboxEntry>,
+    parent_node_id: &'a Id<Node>,
+    command: &'a [u8],
+    created_at: NaiveDateTime,
+}
+
+impl<'a> NewOutboxEntry<'a> {
+    pub fn new(parent_node_id: &'a Id<Node>, command: &'a [u8]) -> Self {
+        Self {
+            idempotency_key: Id::generate(),
+            parent_node_id,
+            command,
+            created_at: crate::current_datetime(),
+        }
+    }
 }
 
 /////////////////////////////////////////////////////////////////////////////
@@ -90,4 +231,29 @@ pub(crate) struct UpdateParentNode<'a> {
 
     #[new(default)]
     pub forked: Option<bool>,
+
+    #[new(default)]
+    pub subscribed_cotonoma_ids: Option<Option<Ids<Cotonoma>>>,
+
+    #[new(default)]
+    pub subscription_recursive: Option<bool>,
+
+    #[new(default)]
+    pub lazy_media: Option<bool>,
+
+    #[new(default)]
+    pub forked_at_serial_number: Option<Option<i64>>,
+}
+
+impl UpdateParentNode<'_> {
+    pub fn set_forked_at(&mut self, serial_number: Option<i64>) {
+        self.forked = Some(serial_number.is_some());
+        self.forked_at_serial_number = Some(serial_number);
+    }
+
+    pub fn set_subscription(&mut self, subscription: Option<&ChangeSubscription>) {
+        let (cotonoma_ids, recursive) = ChangeSubscription::to_columns(subscription);
+        self.subscribed_cotonoma_ids = Some(cotonoma_ids);
+        self.subscription_recursive = Some(recursive);
+    }
 }
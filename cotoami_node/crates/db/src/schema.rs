@@ -12,14 +12,18 @@ diesel::allow_tables_to_appear_in_same_query!(
     server_nodes,
     client_nodes,
     parent_nodes,
+    outbox,
     child_nodes,
+    handled_requests,
     cotos,
+    missing_media,
     cotos_fts,
     cotos_fts_trigram,
     cotos_fts_trigram_vocab,
     cotonomas,
     itos,
-    changelog
+    changelog,
+    changelog_reencoding
 );
 
 /////////////////////////////////////////////////////////////////////////////
@@ -84,10 +88,25 @@ diesel::table! {
         last_change_received_at -> Nullable<Timestamp>,
         last_read_at -> Nullable<Timestamp>,
         forked -> Bool,
+        subscribed_cotonoma_ids -> Nullable<Text>,
+        subscription_recursive -> Bool,
+        lazy_media -> Bool,
+        forked_at_serial_number -> Nullable<BigInt>,
     }
 }
 diesel::joinable!(parent_nodes -> nodes (node_id));
 
+diesel::table! {
+    outbox (serial_number) {
+        serial_number -> BigInt,
+        idempotency_key -> Text,
+        parent_node_id -> Text,
+        command -> Binary,
+        created_at -> Timestamp,
+    }
+}
+diesel::joinable!(outbox -> parent_nodes (parent_node_id));
+
 diesel::table! {
     child_nodes (node_id) {
         node_id -> Text,
@@ -95,10 +114,23 @@ diesel::table! {
         as_owner -> Bool,
         can_edit_itos -> Bool,
         can_post_cotonomas -> Bool,
+        changes_acknowledged -> BigInt,
+        subscribed_cotonoma_ids -> Nullable<Text>,
+        subscription_recursive -> Bool,
+        lazy_media -> Bool,
     }
 }
 diesel::joinable!(child_nodes -> nodes (node_id));
 
+diesel::table! {
+    handled_requests (idempotency_key) {
+        idempotency_key -> Text,
+        node_id -> Text,
+        created_at -> Timestamp,
+    }
+}
+diesel::joinable!(handled_requests -> child_nodes (node_id));
+
 /////////////////////////////////////////////////////////////////////////////
 // Coto (related structs are in `models::coto`)
 /////////////////////////////////////////////////////////////////////////////
@@ -127,6 +159,17 @@ diesel::table! {
 }
 diesel::joinable!(cotos -> nodes (node_id));
 
+diesel::table! {
+    missing_media (coto_id) {
+        coto_id -> Text,
+        hash -> Text,
+        media_type -> Text,
+        created_at -> Timestamp,
+        parent_node_id -> Nullable<Text>,
+    }
+}
+diesel::joinable!(missing_media -> cotos (coto_id));
+
 diesel::table! {
     cotos_fts (uuid) {
         uuid -> Text,
@@ -248,5 +291,12 @@ diesel::table! {
         change -> Binary,
         import_error -> Nullable<Text>,
         inserted_at -> Timestamp,
+        imported_from -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    changelog_reencoding (serial_number) {
+        serial_number -> BigInt,
     }
 }
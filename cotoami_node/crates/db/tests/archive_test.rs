@@ -0,0 +1,207 @@
+use std::io::Cursor;
+
+use anyhow::Result;
+use cotoami_db::{archive::*, import::archive::*, prelude::*};
+use googletest::prelude::*;
+
+pub mod common;
+
+fn png() -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    image::RgbImage::new(2, 2).write_to(&mut Cursor::new(&mut bytes), image::ImageFormat::Png)?;
+    Ok(bytes)
+}
+
+#[test]
+fn export_and_import_archive() -> Result<()> {
+    /////////////////////////////////////////////////////////////////////////////
+    // Setup: the source node
+    /////////////////////////////////////////////////////////////////////////////
+
+    let (_src_dir, src_db, src_node) = common::setup_db("Source")?;
+    let mut src_ds = src_db.new_session()?;
+    let src_opr = src_db.globals().local_node_as_operator()?;
+    let (src_root, src_root_coto) = src_ds.local_node_root()?.unwrap();
+
+    let ((rust, rust_coto), _) =
+        src_ds.post_cotonoma(&CotonomaInput::new("Rust"), &src_root, &src_opr)?;
+    let ((cargo, _), _) = src_ds.post_cotonoma(&CotonomaInput::new("Cargo"), &rust, &src_opr)?;
+    let (coto1, _) = src_ds.post_coto(
+        &CotoInput::new("coto1").media_content(Bytes::from(png()?), "image/png"),
+        &rust.uuid,
+        &src_opr,
+    )?;
+    let (coto2, _) = src_ds.post_coto(&CotoInput::new("coto2"), &cargo.uuid, &src_opr)?;
+    let ((repost, _), _) = src_ds.repost(&coto2.uuid, &src_root, &src_opr)?;
+    let (ito1, _) =
+        src_ds.create_ito(&ItoInput::new(src_root_coto.uuid, rust_coto.uuid), &src_opr)?;
+    let (ito2, _) = src_ds.create_ito(&ItoInput::new(coto1.uuid, coto2.uuid), &src_opr)?;
+
+    /////////////////////////////////////////////////////////////////////////////
+    // When: export all
+    /////////////////////////////////////////////////////////////////////////////
+
+    let mut archive = Cursor::new(Vec::new());
+    let manifest = src_ds.export_archive(Scope::All, &mut archive)?;
+    assert_that!(
+        manifest,
+        pat!(ArchiveManifest {
+            format: eq(ARCHIVE_FORMAT),
+            version: eq(&ARCHIVE_VERSION),
+            node: pat!(Node {
+                uuid: eq(&src_node.uuid),
+                ..
+            }),
+            root_cotonoma_id: eq(&src_root.uuid),
+            root_coto_id: eq(&src_root_coto.uuid),
+            nodes: eq(&0),
+            cotonomas: eq(&2),
+            cotos: eq(&3),
+            itos: eq(&2),
+            media: eq(&1),
+            ..
+        })
+    );
+
+    /////////////////////////////////////////////////////////////////////////////
+    // When: dry run of import into another node
+    /////////////////////////////////////////////////////////////////////////////
+
+    let (_dst_dir, dst_db, dst_node) = common::setup_db("Destination")?;
+    let mut dst_ds = dst_db.new_session()?;
+    let (dst_root, dst_root_coto) = dst_ds.local_node_root()?.unwrap();
+
+    let expected_report = ArchiveImportReport {
+        nodes: 0,
+        cotonomas: 2,
+        cotos: 5,
+        itos: 2,
+        media: 1,
+        already_imported: 0,
+        rejected: vec![],
+    };
+
+    archive.set_position(0);
+    let report = import_archive(&mut dst_ds, &mut archive, &dst_root.uuid, true, |_| {
+        panic!("No changes should be made in a dry run.")
+    })?;
+    assert_that!(report, eq(&expected_report));
+    assert_that!(dst_ds.all_cotos()?, len(eq(1))); // only the root cotonoma
+
+    /////////////////////////////////////////////////////////////////////////////
+    // When: import
+    /////////////////////////////////////////////////////////////////////////////
+
+    archive.set_position(0);
+    let mut changes = Vec::new();
+    let report = import_archive(&mut dst_ds, &mut archive, &dst_root.uuid, false, |log| {
+        changes.push(log)
+    })?;
+    assert_that!(report, eq(&expected_report));
+    // 2 cotonomas + 3 cotos + 2 itos
+    assert_that!(changes, len(eq(7)));
+
+    let imported_rust = dst_ds.try_get_cotonoma(&rust.uuid)?;
+    assert_that!(
+        imported_rust,
+        pat!(Cotonoma {
+            node_id: eq(&dst_node.uuid),
+            coto_id: eq(&rust.coto_id),
+            name: eq("Rust"),
+            created_at: eq(&rust.created_at),
+            ..
+        })
+    );
+    assert_that!(
+        dst_ds.try_get_coto(&rust_coto.uuid)?.posted_in_id,
+        some(eq(dst_root.uuid))
+    );
+    assert_that!(
+        dst_ds.try_get_coto(&coto1.uuid)?,
+        pat!(Coto {
+            node_id: eq(&dst_node.uuid),
+            posted_in_id: some(eq(&rust.uuid)),
+            posted_by_id: eq(&dst_node.uuid),
+            content: some(eq("coto1")),
+            media_content: eq(&coto1.media_content),
+            media_type: some(eq("image/png")),
+            created_at: eq(&coto1.created_at),
+            updated_at: eq(&coto1.updated_at),
+            ..
+        })
+    );
+    assert_that!(
+        dst_ds.try_get_coto(&coto2.uuid)?,
+        pat!(Coto {
+            posted_in_id: some(eq(&cargo.uuid)),
+            reposted_in_ids: some(eq(&Ids(vec![dst_root.uuid]))),
+            ..
+        })
+    );
+    assert_that!(
+        dst_ds.try_get_coto(&repost.uuid)?,
+        pat!(Coto {
+            posted_in_id: some(eq(&dst_root.uuid)),
+            repost_of_id: some(eq(&coto2.uuid)),
+            ..
+        })
+    );
+    assert_that!(
+        dst_ds.outgoing_itos(&[dst_root_coto.uuid])?,
+        elements_are![pat!(Ito {
+            uuid: eq(&ito1.uuid),
+            node_id: eq(&dst_node.uuid),
+            target_coto_id: eq(&rust_coto.uuid),
+            ..
+        })]
+    );
+    assert_that!(
+        dst_ds.try_get_ito(&ito2.uuid)?,
+        pat!(Ito {
+            source_coto_id: eq(&coto1.uuid),
+            target_coto_id: eq(&coto2.uuid),
+            created_at: eq(&ito2.created_at),
+            ..
+        })
+    );
+
+    /////////////////////////////////////////////////////////////////////////////
+    // When: import again (resume)
+    /////////////////////////////////////////////////////////////////////////////
+
+    archive.set_position(0);
+    let report = import_archive(&mut dst_ds, &mut archive, &dst_root.uuid, false, |_| ())?;
+    assert_that!(
+        &report,
+        pat!(ArchiveImportReport {
+            cotonomas: eq(&0),
+            cotos: eq(&0),
+            itos: eq(&0),
+            // 5 cotos + 2 itos
+            already_imported: eq(&7),
+            rejected: is_empty(),
+            ..
+        })
+    );
+    assert_that!(dst_ds.all_cotos()?, len(eq(6)));
+
+    /////////////////////////////////////////////////////////////////////////////
+    // When: export a cotonoma recursively
+    /////////////////////////////////////////////////////////////////////////////
+
+    let mut archive = Cursor::new(Vec::new());
+    let manifest = src_ds.export_archive(Scope::cotonoma_recursive(rust.uuid), &mut archive)?;
+    assert_that!(
+        manifest,
+        pat!(ArchiveManifest {
+            cotonomas: eq(&2),
+            // the repost in the root is out of the scope
+            cotos: eq(&2),
+            // the ito from the root to "Rust"
+            itos: eq(&2),
+            ..
+        })
+    );
+
+    Ok(())
+}
@@ -0,0 +1,40 @@
+use anyhow::Result;
+use cotoami_db::prelude::*;
+use googletest::prelude::*;
+use tempfile::tempdir;
+
+pub mod common;
+
+#[test]
+fn backup_to() -> Result<()> {
+    let (_root_dir, db, node) = common::setup_db("My Node")?;
+    let mut ds = db.new_session()?;
+    let opr = db.globals().local_node_as_operator()?;
+    let (root, _) = ds.try_get_local_node_root()?;
+    let (coto, _) = ds.post_coto(&CotoInput::new("hello"), &root.uuid, &opr)?;
+
+    // When: back up while the database is in use
+    let backup_dir = tempdir()?;
+    let backup_file = backup_dir.path().join("cotoami.db");
+    db.backup_to(&backup_file)?;
+
+    // Then: the backup can't be overwritten
+    assert_that!(
+        db.backup_to(&backup_file).map_err(|e| e.to_string()),
+        err(contains_substring("already exists"))
+    );
+
+    // Then: the backup can be opened as a database
+    let restored = Database::new(backup_dir.path())?;
+    let mut restored_ds = restored.new_session()?;
+    assert_that!(restored_ds.local_node()?.uuid, eq(node.uuid));
+    assert_that!(
+        restored_ds.try_get_coto(&coto.uuid)?,
+        pat!(Coto {
+            content: some(eq("hello")),
+            ..
+        })
+    );
+
+    Ok(())
+}
@@ -0,0 +1,184 @@
+//! Compatibility tests of the encoding of [Change] stored in the changelog.
+//!
+//! `tests/fixtures/changes/{version}/*.msgpack` are changes encoded by the versions of
+//! [Change::ENCODING_VERSION], and `tests/fixtures/changes/*.json` are the expected
+//! values of them. When introducing a new version, add the fixtures encoded by it
+//! and keep the existing ones untouched.
+
+use std::{fs, path::PathBuf};
+
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use cotoami_db::{prelude::*, rmp_serde};
+use diesel::{
+    sql_types::BigInt, sqlite::SqliteConnection, Connection, QueryableByName, RunQueryDsl,
+};
+use googletest::prelude::*;
+
+pub mod common;
+
+fn fixtures_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/changes")
+}
+
+/// Returns pairs of a fixture name and its expected value.
+fn expected_changes() -> Result<Vec<(String, Change)>> {
+    let mut changes = Vec::new();
+    for entry in fs::read_dir(fixtures_dir())? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "json") {
+            let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+            let change: Change = serde_json::from_str(&fs::read_to_string(&path)?)?;
+            changes.push((name, change));
+        }
+    }
+    changes.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(changes)
+}
+
+#[test]
+fn decode_fixtures_of_every_version() -> Result<()> {
+    let expected_changes = expected_changes()?;
+    assert_that!(expected_changes.len(), eq(20));
+
+    for version in 0..=Change::ENCODING_VERSION {
+        for (name, expected) in expected_changes.iter() {
+            let path = fixtures_dir().join(format!("v{version}/{name}.msgpack"));
+            let bytes = fs::read(&path)?;
+            assert_that!(Change::encoding_version(&bytes), eq(version), "{path:?}");
+            assert_that!(Change::from_msgpack(&bytes)?, eq(expected), "{path:?}");
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn encode_in_current_version() -> Result<()> {
+    for (_, change) in expected_changes()? {
+        let bytes = change.to_msgpack()?;
+        assert_that!(
+            Change::encoding_version(&bytes),
+            eq(Change::ENCODING_VERSION)
+        );
+        assert_that!(Change::from_msgpack(&bytes)?, eq(&change));
+    }
+    Ok(())
+}
+
+#[test]
+fn decode_change_with_unknown_field() -> Result<()> {
+    // A variant to which a future version has added a field
+    #[derive(serde::Serialize)]
+    enum FutureChange {
+        DeleteIto {
+            ito_id: Id<Ito>,
+            deleted_at: NaiveDateTime,
+        },
+    }
+
+    let ito_id = Id::generate();
+    let future_change = FutureChange::DeleteIto {
+        ito_id,
+        deleted_at: NaiveDateTime::default(),
+    };
+    let mut bytes = Vec::new();
+    let mut serializer = rmp_serde::Serializer::new(&mut bytes).with_struct_map();
+    serde::Serialize::serialize(&(Change::ENCODING_VERSION, future_change), &mut serializer)?;
+
+    assert_that!(
+        Change::from_msgpack(&bytes)?,
+        eq(&Change::DeleteIto { ito_id })
+    );
+
+    // Whereas the legacy positional encoding can't be decoded
+    let legacy_bytes = rmp_serde::to_vec(&FutureChange::DeleteIto {
+        ito_id,
+        deleted_at: NaiveDateTime::default(),
+    })?;
+    assert_that!(Change::from_msgpack(&legacy_bytes), err(anything()));
+
+    Ok(())
+}
+
+#[test]
+fn reject_unsupported_version() -> Result<()> {
+    let mut bytes = Change::None.to_msgpack()?;
+    bytes[1] = Change::ENCODING_VERSION + 1;
+    assert_that!(
+        Change::from_msgpack(&bytes).map_err(|e| e.to_string()),
+        err(contains_substring("Unsupported change encoding version"))
+    );
+    Ok(())
+}
+
+#[derive(QueryableByName)]
+struct Count {
+    #[diesel(sql_type = BigInt)]
+    count: i64,
+}
+
+#[test]
+fn reencode_legacy_changes_on_open() -> Result<()> {
+    /////////////////////////////////////////////////////////////////////////////
+    // Setup: a database whose changes are stored in the legacy encoding
+    /////////////////////////////////////////////////////////////////////////////
+
+    let (root_dir, db, _node) = common::setup_db("My Node")?;
+    let mut ds = db.new_session()?;
+    let opr = db.globals().local_node_as_operator()?;
+    let (root, _) = ds.try_get_local_node_root()?;
+    let (coto, _) = ds.post_coto(&CotoInput::new("hello"), &root.uuid, &opr)?;
+    let _ = ds.edit_coto(
+        &coto.uuid,
+        CotoContentDiff::default().content("hello!"),
+        &opr,
+    )?;
+    let (changes, _) = ds.chunk_of_changes(1, 100, None)?;
+    drop(ds);
+    drop(db);
+
+    let mut conn =
+        SqliteConnection::establish(root_dir.path().join("cotoami.db").to_str().unwrap())?;
+    for log in changes.iter() {
+        diesel::sql_query(format!(
+            "UPDATE changelog SET change = x'{}' WHERE serial_number = {}",
+            hex(&rmp_serde::to_vec(&log.change)?),
+            log.serial_number
+        ))
+        .execute(&mut conn)?;
+    }
+    // What the migration `008_change_encoding` does against existing entries
+    diesel::sql_query(
+        "INSERT INTO changelog_reencoding (serial_number) SELECT serial_number FROM changelog",
+    )
+    .execute(&mut conn)?;
+    let count_legacy = |conn: &mut SqliteConnection| -> Result<i64> {
+        let count: Count = diesel::sql_query(
+            "SELECT count(*) AS count FROM changelog WHERE substr(change, 1, 1) <> x'92'",
+        )
+        .get_result(conn)?;
+        Ok(count.count)
+    };
+    assert_that!(count_legacy(&mut conn)?, eq(changes.len() as i64));
+
+    /////////////////////////////////////////////////////////////////////////////
+    // When: open the database
+    /////////////////////////////////////////////////////////////////////////////
+
+    let db = Database::new(&root_dir)?;
+
+    // Then: all the changes have been re-encoded without changing their values
+    assert_that!(count_legacy(&mut conn)?, eq(0));
+    let queued: Count = diesel::sql_query("SELECT count(*) AS count FROM changelog_reencoding")
+        .get_result(&mut conn)?;
+    assert_that!(queued.count, eq(0));
+    assert_that!(
+        db.new_session()?.chunk_of_changes(1, 100, None)?.0,
+        eq(&changes)
+    );
+    assert!(db.fsck()?.is_ok());
+
+    Ok(())
+}
+
+fn hex(bytes: &[u8]) -> String { bytes.iter().map(|b| format!("{b:02x}")).collect() }
@@ -1,4 +1,4 @@
-use std::str::FromStr;
+use std::{collections::HashSet, str::FromStr};
 
 use anyhow::Result;
 use cotoami_db::prelude::*;
@@ -284,6 +284,7 @@ fn duplicate_changes_from_different_parents() -> Result<()> {
         change: Change::None,
         import_error: None,
         inserted_at: Utc::now().naive_utc(),
+        imported_from: None,
     };
 
     let imported_change1 = ds1.import_change(&src_change, &node2.uuid)?;
@@ -325,7 +326,9 @@ fn import_error() -> Result<()> {
     let (_dir1, db, _) = common::setup_db("Local")?;
     let (_dir2, _, parent_node) = common::setup_db("Parent")?;
 
-    let ds = db.new_session()?;
+    let (_dir3, other_db, other_node) = common::setup_db("Other")?;
+
+    let mut ds = db.new_session()?;
     let opr = db.globals().local_node_as_operator()?;
     ds.import_node(&parent_node)?;
     ds.register_server_node_as_parent(&parent_node.uuid, "https://parent", &opr)?;
@@ -336,12 +339,13 @@ fn import_error() -> Result<()> {
         origin_node_id: parent_node.uuid,
         origin_serial_number: 1,
         change: Change::RenameNode {
-            node_id: Id::generate(), // no such node
+            node_id: other_node.uuid, // not yet imported
             name: "hello".into(),
             updated_at: Utc::now().naive_utc(),
         },
         import_error: None,
         inserted_at: Utc::now().naive_utc(),
+        imported_from: None,
     };
 
     let inserted_log = ds.import_change(&log, &parent_node.uuid)?;
@@ -356,9 +360,734 @@ fn import_error() -> Result<()> {
                 ..
             }),
             import_error: some(eq("NotFound")),
+            imported_from: some(eq(&parent_node.uuid)),
+            ..
+        }))
+    );
+
+    // The failed import can be listed
+    let failed = ds.failed_imports(&parent_node.uuid, 10, 0, &opr)?;
+    assert_that!(failed.total_rows, eq(1));
+    assert_that!(
+        failed.rows,
+        elements_are![eq(inserted_log.as_ref().unwrap())]
+    );
+
+    // The failed import has not been applied, which is consistent with the changelog
+    assert!(db.fsck()?.is_ok());
+
+    // Retrying will fail until the missing node arrives
+    let retried = ds.retry_failed_imports(&[3], &opr)?;
+    assert_that!(
+        retried,
+        elements_are![pat!(ChangelogEntry {
+            import_error: some(contains_substring("NotFound")),
+            ..
+        })]
+    );
+
+    // Retrying is refused while the local node is forked from the parent
+    ds.fork_from_parent(&parent_node.uuid, &opr)?;
+    assert_that!(
+        ds.retry_failed_imports(&[3], &opr)
+            .unwrap_err()
+            .downcast_ref::<DatabaseError>(),
+        some(pat!(DatabaseError::AlreadyForkedFromParent { .. }))
+    );
+    ds.reattach_to_parent(&parent_node.uuid, &opr)?;
+
+    // The node arrives from the parent
+    let (other_changes, _) = other_db.new_session()?.chunk_of_changes(1, 1, None)?;
+    let mut log = other_changes[0].clone();
+    log.serial_number = 2;
+    assert_that!(log.change, pat!(Change::CreateNode { .. }));
+    assert_that!(
+        ds.import_change(&log, &parent_node.uuid)?,
+        some(pat!(ChangelogEntry {
+            import_error: none(),
+            ..
+        }))
+    );
+
+    let retried = ds.retry_failed_imports(&[3], &opr)?;
+    assert_that!(
+        retried,
+        elements_are![pat!(ChangelogEntry {
+            serial_number: eq(&3),
+            import_error: none(),
+            ..
+        })]
+    );
+    assert_that!(ds.node(&other_node.uuid)?.unwrap().name, eq("hello"));
+    assert_that!(
+        ds.failed_imports(&parent_node.uuid, 10, 0, &opr)?.rows,
+        is_empty()
+    );
+
+    // The retried change is replayed after the change it depends on
+    let report = db.fsck()?;
+    assert_that!(report.replay_errors, is_empty());
+    assert!(report.is_ok());
+
+    // A change imported without errors can't be retried
+    assert_that!(
+        ds.retry_failed_imports(&[3], &opr)
+            .map_err(|e| e.to_string()),
+        err(contains_substring("without errors"))
+    );
+
+    Ok(())
+}
+
+#[test]
+fn install_snapshot() -> Result<()> {
+    /////////////////////////////////////////////////////////////////////////////
+    // Setup: a parent with some history
+    /////////////////////////////////////////////////////////////////////////////
+
+    let (_parent_dir, parent_db, parent_node) = common::setup_db("Parent")?;
+    let mut parent_ds = parent_db.new_session()?;
+    let parent_opr = parent_db.globals().local_node_as_operator()?;
+    let (root, root_coto) = parent_ds.try_get_local_node_root()?;
+
+    let (coto1, _) = parent_ds.post_coto(&CotoInput::new("hello"), &root.uuid, &parent_opr)?;
+    let _ = parent_ds.edit_coto(
+        &coto1.uuid,
+        CotoContentDiff::default().content("hello!"),
+        &parent_opr,
+    )?;
+    let (coto2, _) = parent_ds.post_coto(&CotoInput::new("moon"), &root.uuid, &parent_opr)?;
+    let ((cotonoma, _), _) = parent_ds.promote(&coto2.uuid, &parent_opr)?;
+    let ((repost, _), _) = parent_ds.repost(&coto1.uuid, &cotonoma, &parent_opr)?;
+    let _ = parent_ds.create_ito(&ItoInput::new(root_coto.uuid, coto2.uuid), &parent_opr)?;
+    let _ = parent_ds.create_ito(
+        &ItoInput::new(root_coto.uuid, coto1.uuid).order(1),
+        &parent_opr,
+    )?;
+    let (coto3, _) = parent_ds.post_coto(&CotoInput::new("bye"), &root.uuid, &parent_opr)?;
+    let _ = parent_ds.delete_coto(&coto3.uuid, &parent_opr)?;
+    let media_type = "application/octet-stream";
+    let (photo, _) = parent_ds.post_coto(
+        &CotoInput::new("photo").media_content(vec![1, 2, 3].into(), media_type),
+        &root.uuid,
+        &parent_opr,
+    )?;
+
+    let (_child_dir, child_db, child_node) = common::setup_db("Child")?;
+    let mut child_ds = child_db.new_session()?;
+    let child_opr = child_db.globals().local_node_as_operator()?;
+    child_ds.import_node(&parent_node)?;
+    child_ds.register_server_node_as_parent(&parent_node.uuid, "https://parent", &child_opr)?;
+
+    /////////////////////////////////////////////////////////////////////////////
+    // When: install a snapshot of the parent
+    /////////////////////////////////////////////////////////////////////////////
+
+    let snapshot = parent_ds.changelog_snapshot()?;
+    let last_serial_number = parent_ds.last_change_number()?.unwrap();
+    assert_that!(snapshot.last_serial_number, eq(last_serial_number));
+
+    let logs = child_ds.install_snapshot(&snapshot, &parent_node.uuid)?;
+
+    // Then: the changes covered by the snapshot are logged with their origins
+    let (parent_changes, _) = parent_ds.chunk_of_changes(1, 100, None)?;
+    let origins: HashSet<ChangeOrigin> = logs.iter().map(ChangelogEntry::origin).collect();
+    assert!(parent_changes
+        .iter()
+        .all(|log| origins.contains(&log.origin())));
+    assert!(logs
+        .iter()
+        .filter(|log| log.origin_node_id == child_node.uuid)
+        .all(|log| matches!(log.change, Change::UpsertNode(_))));
+
+    // Then: the child has the same entities as the parent
+    let ids = |cotos: Vec<Coto>| {
+        let mut ids: Vec<(String, Option<String>)> = cotos
+            .into_iter()
+            .filter(|c| c.node_id == parent_node.uuid)
+            .map(|c| (c.uuid.to_string(), c.content))
+            .collect();
+        ids.sort();
+        ids
+    };
+    assert_that!(ids(child_ds.all_cotos()?), eq(&ids(parent_ds.all_cotos()?)));
+    assert_that!(
+        child_ds.try_get_coto(&coto1.uuid)?.reposted_in_ids,
+        some(eq(&Ids(vec![cotonoma.uuid])))
+    );
+    assert_that!(
+        child_ds.try_get_coto(&repost.uuid)?.repost_of_id,
+        some(eq(coto1.uuid))
+    );
+    assert_that!(
+        child_ds
+            .outgoing_itos(&[root_coto.uuid])?
+            .into_iter()
+            .map(|ito| (ito.target_coto_id, ito.order))
+            .collect::<Vec<_>>(),
+        unordered_elements_are![eq(&(coto1.uuid, 1)), eq(&(coto2.uuid, 2))]
+    );
+    assert_that!(child_ds.try_get_cotonoma(&cotonoma.uuid)?.name, eq("moon"));
+    assert_that!(
+        child_ds.parent_node(&parent_node.uuid, &child_opr)?,
+        some(pat!(ParentNode {
+            changes_received: eq(&last_serial_number),
+            ..
+        }))
+    );
+
+    // Then: the media has been omitted to be fetched later
+    assert_that!(child_ds.try_get_coto(&photo.uuid)?.media_content, none());
+    assert_that!(
+        child_ds
+            .missing_media_from(&parent_node.uuid)?
+            .into_iter()
+            .map(|missing| missing.coto_id)
+            .collect::<Vec<_>>(),
+        eq(&vec![photo.uuid])
+    );
+
+    // Then: the local changelog of the child reproduces the installed state
+    assert!(child_db.fsck()?.is_ok());
+
+    assert_that!(
+        child_ds.store_missing_media(&photo.uuid, &[1, 2, 3])?,
+        some(field!(Coto.media_type, some(eq(media_type))))
+    );
+
+    // Then: a grandchild can import the changes both from the child and the parent
+    let (_grandchild_dir, grandchild_db, _) = common::setup_db("Grandchild")?;
+    let mut grandchild_ds = grandchild_db.new_session()?;
+    let grandchild_opr = grandchild_db.globals().local_node_as_operator()?;
+    let (child_changes, _) = child_ds.chunk_of_changes(1, 100, None)?;
+    for (node, url, changes) in [
+        (&child_node, "https://child", child_changes),
+        (&parent_node, "https://parent", parent_changes),
+    ] {
+        grandchild_ds.import_node(node)?;
+        grandchild_ds.register_server_node_as_parent(&node.uuid, url, &grandchild_opr)?;
+        for change in changes.iter() {
+            let imported = grandchild_ds.import_change(change, &node.uuid)?;
+            assert_that!(imported.and_then(|log| log.import_error), none());
+        }
+    }
+    assert_that!(
+        ids(grandchild_ds.all_cotos()?),
+        eq(&ids(parent_ds.all_cotos()?))
+    );
+
+    // Then: the child continues to import changes after the snapshot
+    let (coto4, _) = parent_ds.post_coto(&CotoInput::new("again"), &root.uuid, &parent_opr)?;
+    let (changes, _) = parent_ds.chunk_of_changes(last_serial_number + 1, 100, None)?;
+    for change in changes {
+        child_ds.import_change(&change, &parent_node.uuid)?;
+    }
+    assert_that!(
+        child_ds.try_get_coto(&coto4.uuid)?.content,
+        some(eq("again"))
+    );
+
+    // Then: a snapshot can't be installed after receiving changes
+    assert_that!(
+        child_ds
+            .install_snapshot(&snapshot, &parent_node.uuid)
+            .map_err(|e| e.to_string()),
+        err(contains_substring("can't be installed"))
+    );
+
+    Ok(())
+}
+
+#[test]
+fn compact_changelog() -> Result<()> {
+    /////////////////////////////////////////////////////////////////////////////
+    // Setup: a parent with a child and some obsolete changes
+    /////////////////////////////////////////////////////////////////////////////
+
+    let (_parent_dir, parent_db, _parent_node) = common::setup_db("Parent")?;
+    let (_child_dir, child_db, child_node) = common::setup_db("Child")?;
+    common::connect_parent_child(
+        &parent_db,
+        &child_db,
+        "https://parent",
+        "password",
+        ChildNodeInput::default(),
+    )?;
+
+    let mut parent_ds = parent_db.new_session()?;
+    let parent_opr = parent_db.globals().local_node_as_operator()?;
+    let (root, _) = parent_ds.try_get_local_node_root()?;
+
+    let ((cotonoma, _), _) =
+        parent_ds.post_cotonoma(&CotonomaInput::new("Rust"), &root, &parent_opr)?;
+    let (coto1, _) = parent_ds.post_coto(&CotoInput::new("hello"), &root.uuid, &parent_opr)?;
+    for diff in [
+        CotoContentDiff::default().content("hello!"),
+        CotoContentDiff::default().summary(Some("greeting")),
+        CotoContentDiff::default().content("hello!!"),
+    ] {
+        let _ = parent_ds.edit_coto(&coto1.uuid, diff, &parent_opr)?;
+    }
+
+    // A coto created, edited and deleted
+    let (coto2, _) = parent_ds.post_coto(&CotoInput::new("draft"), &cotonoma.uuid, &parent_opr)?;
+    for content in ["draft 1", "draft 2"] {
+        let _ = parent_ds.edit_coto(
+            &coto2.uuid,
+            CotoContentDiff::default().content(content),
+            &parent_opr,
+        )?;
+    }
+    let _ = parent_ds.delete_coto(&coto2.uuid, &parent_opr)?;
+
+    // The timestamp of the cotonoma will be updated again
+    let (coto3, _) = parent_ds.post_coto(&CotoInput::new("bye"), &cotonoma.uuid, &parent_opr)?;
+    let _ = parent_ds.rename_cotonoma(&cotonoma.uuid, "Rust!", &parent_opr)?;
+    let _ = parent_ds.rename_cotonoma(&cotonoma.uuid, "Rust!!", &parent_opr)?;
+
+    let last_serial_number = parent_ds.last_change_number()?.unwrap();
+
+    /////////////////////////////////////////////////////////////////////////////
+    // When: the child hasn't acknowledged any changes
+    /////////////////////////////////////////////////////////////////////////////
+
+    let compaction = parent_ds.compact_changelog(0)?;
+    assert_that!(
+        compaction,
+        eq(&ChangelogCompaction {
+            watermark: 0,
+            cleared: 0,
+            merged: 0
+        })
+    );
+
+    /////////////////////////////////////////////////////////////////////////////
+    // When: the child has acknowledged all the changes
+    /////////////////////////////////////////////////////////////////////////////
+
+    parent_ds.acknowledge_changes(&child_node.uuid, last_serial_number)?;
+
+    // Then: the latest changes within the margin won't be compacted
+    let compaction = parent_ds.compact_changelog(2)?;
+    assert_that!(compaction.watermark, eq(last_serial_number - 2));
+    assert_that!(compaction.cleared, eq(6)); // 2 edits of coto1, 4 changes of coto2
+    assert_that!(compaction.merged, eq(1));
+
+    let compaction = parent_ds.compact_changelog(0)?;
+    assert_that!(compaction.cleared, eq(1)); // the first rename
+    assert_that!(compaction.merged, eq(1));
+
+    // Then: serial numbers are preserved
+    assert_that!(
+        parent_ds.last_change_number()?,
+        some(eq(last_serial_number))
+    );
+    let (changes, _) = parent_ds.chunk_of_changes(1, 100, None)?;
+    assert_that!(changes.len() as i64, eq(last_serial_number));
+    assert_that!(
+        changes
+            .iter()
+            .filter(|log| log.change == Change::None)
+            .count(),
+        eq(7)
+    );
+
+    // Then: replaying the compacted changelog results in the same state
+    assert!(parent_db.fsck()?.is_ok());
+    let coto1 = parent_ds.try_get_coto(&coto1.uuid)?;
+    assert_that!(coto1.content, some(eq("hello!!")));
+    assert_that!(coto1.summary, some(eq("greeting")));
+
+    // Then: a new child can import the compacted changelog
+    let (_child2_dir, child2_db, _) = common::setup_db("Child2")?;
+    common::connect_parent_child(
+        &parent_db,
+        &child2_db,
+        "https://parent",
+        "password",
+        ChildNodeInput::default(),
+    )?;
+    let mut child2_ds = child2_db.new_session()?;
+    assert_that!(
+        child2_ds.try_get_coto(&coto1.uuid)?,
+        pat!(Coto {
+            content: eq(&coto1.content),
+            summary: eq(&coto1.summary),
+            updated_at: eq(&coto1.updated_at),
+            ..
+        })
+    );
+    assert_that!(child2_ds.coto(&coto2.uuid)?, none());
+    assert_that!(
+        child2_ds.try_get_coto(&coto3.uuid)?.content,
+        some(eq("bye"))
+    );
+    let cotonoma = parent_ds.try_get_cotonoma(&cotonoma.uuid)?;
+    assert_that!(
+        child2_ds.try_get_cotonoma(&cotonoma.uuid)?,
+        pat!(Cotonoma {
+            name: eq("Rust!!"),
+            updated_at: eq(&cotonoma.updated_at),
+            ..
+        })
+    );
+
+    Ok(())
+}
+
+#[test]
+fn filter_changes_by_subscription() -> Result<()> {
+    /////////////////////////////////////////////////////////////////////////////
+    // Setup: a parent with cotonomas (root > a > b, root > c)
+    /////////////////////////////////////////////////////////////////////////////
+
+    let (_parent_dir, parent_db, parent_node) = common::setup_db("Parent")?;
+    let mut parent_ds = parent_db.new_session()?;
+    let parent_opr = parent_db.globals().local_node_as_operator()?;
+    let (root, _) = parent_ds.try_get_local_node_root()?;
+
+    let ((cotonoma_a, _), log_a) =
+        parent_ds.post_cotonoma(&CotonomaInput::new("a"), &root, &parent_opr)?;
+    let ((cotonoma_b, _), log_b) =
+        parent_ds.post_cotonoma(&CotonomaInput::new("b"), &cotonoma_a, &parent_opr)?;
+    let ((cotonoma_c, _), _) =
+        parent_ds.post_cotonoma(&CotonomaInput::new("c"), &root, &parent_opr)?;
+    let (coto_a, log_coto_a) =
+        parent_ds.post_coto(&CotoInput::new("in a"), &cotonoma_a.uuid, &parent_opr)?;
+    let (coto_b, log_coto_b) =
+        parent_ds.post_coto(&CotoInput::new("in b"), &cotonoma_b.uuid, &parent_opr)?;
+    let (coto_c, _) =
+        parent_ds.post_coto(&CotoInput::new("in c"), &cotonoma_c.uuid, &parent_opr)?;
+    let (coto_root, _) =
+        parent_ds.post_coto(&CotoInput::new("in root"), &root.uuid, &parent_opr)?;
+    let (_, log_ito_ab) =
+        parent_ds.create_ito(&ItoInput::new(coto_a.uuid, coto_b.uuid), &parent_opr)?;
+    let _ = parent_ds.create_ito(&ItoInput::new(coto_a.uuid, coto_c.uuid), &parent_opr)?;
+    let _ = parent_ds.edit_coto(
+        &coto_c.uuid,
+        CotoContentDiff::default().content("in c!"),
+        &parent_opr,
+    )?;
+    let log_delete = parent_ds.delete_coto(&coto_root.uuid, &parent_opr)?;
+
+    let (changes, _) = parent_ds.chunk_of_changes(1, 100, None)?;
+    let relevant_serials = |changes: &[ChangelogEntry]| -> Vec<i64> {
+        changes
+            .iter()
+            .filter(|log| !log.is_placeholder())
+            .map(|log| log.serial_number)
+            .collect()
+    };
+
+    /////////////////////////////////////////////////////////////////////////////
+    // When: subscribe to the cotonoma "a"
+    /////////////////////////////////////////////////////////////////////////////
+
+    let subscription = ChangeSubscription::new(vec![cotonoma_a.uuid], false);
+    let filtered = parent_ds.filter_changes(changes.clone(), &subscription)?;
+
+    // Then: serial numbers are preserved
+    assert_that!(
+        filtered
+            .iter()
+            .map(|log| log.serial_number)
+            .collect::<Vec<_>>(),
+        eq(&changes
+            .iter()
+            .map(|log| log.serial_number)
+            .collect::<Vec<_>>())
+    );
+
+    // Then: the deletion of a missing coto is sent as is
+    assert_that!(
+        relevant_serials(&filtered),
+        eq(&vec![
+            1, // CreateNode
+            log_a.serial_number,
+            log_b.serial_number, // a cotonoma posted in "a"
+            log_coto_a.serial_number,
+            log_delete.serial_number,
+        ])
+    );
+
+    /////////////////////////////////////////////////////////////////////////////
+    // When: subscribe to the cotonoma "a" recursively
+    /////////////////////////////////////////////////////////////////////////////
+
+    let subscription = ChangeSubscription::new(vec![cotonoma_a.uuid], true);
+    let filtered = parent_ds.filter_changes(changes.clone(), &subscription)?;
+    assert_that!(
+        relevant_serials(&filtered),
+        eq(&vec![
+            1,
+            log_a.serial_number,
+            log_b.serial_number,
+            log_coto_a.serial_number,
+            log_coto_b.serial_number,
+            log_ito_ab.serial_number,
+            log_delete.serial_number,
+        ])
+    );
+
+    /////////////////////////////////////////////////////////////////////////////
+    // When: subscribe to the cotonoma "b", whose ancestors are needed in the child
+    /////////////////////////////////////////////////////////////////////////////
+
+    let subscription = ChangeSubscription::new(vec![cotonoma_b.uuid], false);
+    let filtered = parent_ds.filter_changes(changes.clone(), &subscription)?;
+    assert_that!(
+        relevant_serials(&filtered),
+        eq(&vec![
+            1,
+            log_a.serial_number,
+            log_b.serial_number,
+            log_coto_b.serial_number,
+            log_delete.serial_number,
+        ])
+    );
+
+    // Then: a child can import the filtered changes without errors
+    let (_child_dir, child_db, _) = common::setup_db("Child")?;
+    let mut child_ds = child_db.new_session()?;
+    let child_opr = child_db.globals().local_node_as_operator()?;
+    child_ds.import_node(&parent_node)?;
+    child_ds.register_server_node_as_parent(&parent_node.uuid, "https://parent", &child_opr)?;
+    for change in filtered.iter() {
+        let imported = child_ds.import_change(change, &parent_node.uuid)?;
+        assert_that!(imported.and_then(|log| log.import_error), none());
+    }
+    assert_that!(child_ds.coto(&coto_b.uuid)?, some(anything()));
+    assert_that!(child_ds.coto(&coto_a.uuid)?, none());
+    assert_that!(child_ds.cotonoma(&cotonoma_a.uuid)?, some(anything()));
+    assert_that!(child_ds.cotonoma(&cotonoma_c.uuid)?, none());
+    assert_that!(
+        child_ds.parent_node(&parent_node.uuid, &child_opr)?,
+        some(field!(
+            ParentNode.changes_received,
+            eq(&(changes.len() as i64))
+        ))
+    );
+
+    /////////////////////////////////////////////////////////////////////////////
+    // When: the child receives all the changes from another parent
+    /////////////////////////////////////////////////////////////////////////////
+
+    let (_relay_dir, _, relay_node) = common::setup_db("Relay")?;
+    child_ds.import_node(&relay_node)?;
+    child_ds.register_server_node_as_parent(&relay_node.uuid, "https://relay", &child_opr)?;
+    for change in changes.iter() {
+        let imported = child_ds.import_change(change, &relay_node.uuid)?;
+        assert_that!(imported.and_then(|log| log.import_error), none());
+    }
+
+    // Then: the changes received as placeholders from the first parent have been imported
+    assert_that!(child_ds.coto(&coto_a.uuid)?, some(anything()));
+    assert_that!(child_ds.cotonoma(&cotonoma_c.uuid)?, some(anything()));
+    assert_that!(child_ds.coto(&coto_c.uuid)?, some(anything()));
+
+    Ok(())
+}
+
+#[test]
+fn import_changes_without_media() -> Result<()> {
+    /////////////////////////////////////////////////////////////////////////////
+    // Setup: a parent with a coto whose media has been replaced
+    /////////////////////////////////////////////////////////////////////////////
+
+    let (_parent_dir, parent_db, parent_node) = common::setup_db("Parent")?;
+    let mut parent_ds = parent_db.new_session()?;
+    let parent_opr = parent_db.globals().local_node_as_operator()?;
+    let (root, _) = parent_ds.try_get_local_node_root()?;
+
+    let media_type = "application/octet-stream";
+    let (coto, _) = parent_ds.post_coto(
+        &CotoInput::new("media").media_content(vec![1, 2, 3].into(), media_type),
+        &root.uuid,
+        &parent_opr,
+    )?;
+    let (coto, _) = parent_ds.edit_coto(
+        &coto.uuid,
+        CotoContentDiff::default().media_content(Some((vec![4, 5, 6].into(), media_type))),
+        &parent_opr,
+    )?;
+    let old_hash = Coto::media_hash(&[1, 2, 3]);
+    let new_hash = Coto::media_hash(&[4, 5, 6]);
+
+    /////////////////////////////////////////////////////////////////////////////
+    // When: a child imports the changes without media
+    /////////////////////////////////////////////////////////////////////////////
+
+    let (changes, _) = parent_ds.chunk_of_changes(1, 100, None)?;
+    let changes: Vec<ChangelogEntry> = changes
+        .into_iter()
+        .map(|log| via_serialization(&log.omit_media()))
+        .collect::<Result<_>>()?;
+    assert_that!(
+        changes
+            .iter()
+            .filter_map(|log| match &log.change {
+                Change::MediaOmitted { media, .. } => Some(media.hash.clone()),
+                _ => None,
+            })
+            .collect::<Vec<_>>(),
+        elements_are![eq(&old_hash), eq(&new_hash)]
+    );
+
+    let (_child_dir, child_db, _) = common::setup_db("Child")?;
+    let mut child_ds = child_db.new_session()?;
+    let child_opr = child_db.globals().local_node_as_operator()?;
+    child_ds.import_node(&parent_node)?;
+    child_ds.register_server_node_as_parent(&parent_node.uuid, "https://parent", &child_opr)?;
+    for change in changes.iter() {
+        let imported = child_ds.import_change(change, &parent_node.uuid)?;
+        assert_that!(imported.and_then(|log| log.import_error), none());
+    }
+
+    // Then: the coto has been imported without media, which is recorded as missing
+    assert_that!(
+        child_ds.coto(&coto.uuid)?,
+        some(pat!(Coto {
+            content: some(eq("media")),
+            media_content: none(),
+            media_type: none(),
+            updated_at: eq(&coto.updated_at),
+            ..
+        }))
+    );
+    assert_that!(
+        child_ds.missing_media()?,
+        elements_are![pat!(MissingMedia {
+            coto_id: eq(&coto.uuid),
+            hash: eq(&new_hash),
+            media_type: eq(media_type),
+            parent_node_id: some(eq(&parent_node.uuid)),
+            ..
+        })]
+    );
+    assert_that!(
+        child_ds.missing_media_from(&parent_node.uuid)?,
+        elements_are![pat!(MissingMedia {
+            coto_id: eq(&coto.uuid),
+            ..
+        })]
+    );
+    assert_that!(child_ds.missing_media_from(&Id::generate())?, is_empty());
+
+    /////////////////////////////////////////////////////////////////////////////
+    // When: the child fetches the media from the parent by the hash
+    /////////////////////////////////////////////////////////////////////////////
+
+    assert_that!(parent_ds.coto_media_by_hash(&coto.uuid, &old_hash)?, none());
+    let media = parent_ds
+        .coto_media_by_hash(&coto.uuid, &new_hash)?
+        .unwrap();
+    assert_that!(media.content.as_ref(), eq(&[4, 5, 6]));
+
+    // Then: only the content matching the hash can be stored
+    assert_that!(
+        child_ds.store_missing_media(&coto.uuid, &[1, 2, 3]),
+        err(anything())
+    );
+    assert_that!(
+        child_ds.store_missing_media(&coto.uuid, media.content.as_ref())?,
+        some(pat!(Coto {
+            media_content: some(eq(&media.content)),
+            media_type: some(eq(media_type)),
+            updated_at: eq(&coto.updated_at),
             ..
         }))
     );
+    assert_that!(child_ds.missing_media()?, is_empty());
+    assert_that!(
+        child_ds.store_missing_media(&coto.uuid, media.content.as_ref())?,
+        none()
+    );
+
+    // Then: the child can serve the media to its own children
+    assert_that!(
+        child_ds.coto_media_by_hash(&coto.uuid, &new_hash)?,
+        some(eq(&media))
+    );
+
+    // And: the fetched media is not regarded as divergence from the changelog
+    assert!(child_db.fsck()?.is_ok());
+
+    /////////////////////////////////////////////////////////////////////////////
+    // When: the child fetches an image larger than its image_max_size
+    /////////////////////////////////////////////////////////////////////////////
+
+    child_ds.set_image_max_size(Some(5), &child_opr)?;
+    let mut png = Vec::new();
+    image::RgbImage::new(20, 8)
+        .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)?;
+    let (image_coto, _) = parent_ds.post_coto(
+        &CotoInput::new("image").media_content(png.clone().into(), "image/png"),
+        &root.uuid,
+        &parent_opr,
+    )?;
+    let (changes, _) = parent_ds.chunk_of_changes(changes.len() as i64 + 1, 100, None)?;
+    for change in changes.into_iter() {
+        child_ds.import_change(&change.omit_media(), &parent_node.uuid)?;
+    }
+    let media = parent_ds
+        .coto_media_by_hash(&image_coto.uuid, &Coto::media_hash(&png))?
+        .unwrap();
+    let stored = child_ds
+        .store_missing_media(&image_coto.uuid, media.content.as_ref())?
+        .unwrap();
+
+    // Then: the image has been resized after the hash was verified
+    let image = image::load_from_memory(stored.media_content.unwrap().as_ref())?;
+    assert_that!((image.width(), image.height()), eq((5, 2)));
+    assert!(child_db.fsck()?.is_ok());
+
+    Ok(())
+}
+
+#[test]
+fn chunk_of_changes_by_byte_budget() -> Result<()> {
+    /////////////////////////////////////////////////////////////////////////////
+    // Setup: a node with large cotos
+    /////////////////////////////////////////////////////////////////////////////
+
+    let (_root_dir, db, _) = common::setup_db("My Node")?;
+    let mut ds = db.new_session()?;
+    let opr = db.globals().local_node_as_operator()?;
+    let (root, _) = ds.try_get_local_node_root()?;
+
+    let content = "x".repeat(10_000);
+    let mut serial_numbers = Vec::new();
+    for _ in 0..3 {
+        let (_, log) = ds.post_coto(&CotoInput::new(&content), &root.uuid, &opr)?;
+        serial_numbers.push(log.serial_number);
+    }
+    let (all, last) = ds.chunk_of_changes(serial_numbers[0], 100, None)?;
+    assert_that!(all.len(), eq(3));
+    assert_that!(last, eq(serial_numbers[2]));
+
+    // When: the budget allows two of them
+    let (chunk, _) = ds.chunk_of_changes(serial_numbers[0], 100, Some(25_000))?;
+
+    // Then: the chunk has been cut at the budget
+    assert_that!(
+        chunk
+            .iter()
+            .map(|log| log.serial_number)
+            .collect::<Vec<_>>(),
+        elements_are![eq(&serial_numbers[0]), eq(&serial_numbers[1])]
+    );
+
+    // When: the budget is smaller than a single change
+    let (chunk, _) = ds.chunk_of_changes(serial_numbers[0], 100, Some(1))?;
+
+    // Then: the first change is still returned
+    assert_that!(chunk, elements_are![eq(&all[0])]);
+
+    // When: the count limit is tighter than the budget
+    let (chunk, _) = ds.chunk_of_changes(serial_numbers[0], 1, Some(1024 * 1024))?;
+
+    // Then: the count limit is respected
+    assert_that!(chunk, elements_are![eq(&all[0])]);
 
     Ok(())
 }
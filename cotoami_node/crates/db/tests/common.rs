@@ -52,7 +52,7 @@ pub fn connect_parent_child(
     child_ds.import_node(&parent_node)?;
     child_ds.register_server_node_as_parent(&parent_node.uuid, url_prefix, &child_opr)?;
 
-    let (changes, _) = parent_ds.chunk_of_changes(1, 100)?;
+    let (changes, _) = parent_ds.chunk_of_changes(1, 100, None)?;
     for change in changes {
         child_ds.import_change(&change, &parent_node.uuid)?;
     }
@@ -0,0 +1,83 @@
+use anyhow::Result;
+use cotoami_db::{
+    db::fsck::{Mismatch, MismatchProblem},
+    prelude::*,
+};
+use diesel::{sqlite::SqliteConnection, Connection, RunQueryDsl};
+use googletest::prelude::*;
+
+pub mod common;
+
+#[test]
+fn fsck() -> Result<()> {
+    /////////////////////////////////////////////////////////////////////////////
+    // Setup
+    /////////////////////////////////////////////////////////////////////////////
+
+    let (root_dir, db, _node) = common::setup_db("My Node")?;
+    let mut ds = db.new_session()?;
+    let opr = db.globals().local_node_as_operator()?;
+    let (root, root_coto) = ds.try_get_local_node_root()?;
+
+    let ((cotonoma, cotonoma_coto), _) =
+        ds.post_cotonoma(&CotonomaInput::new("Rust"), &root, &opr)?;
+    let (coto1, _) = ds.post_coto(&CotoInput::new("hello"), &cotonoma.uuid, &opr)?;
+    let (coto2, _) = ds.post_coto(&CotoInput::new("bye"), &root.uuid, &opr)?;
+    let (ito1, _) = ds.create_ito(&ItoInput::new(root_coto.uuid, cotonoma_coto.uuid), &opr)?;
+    let (ito2, _) = ds.create_ito(&ItoInput::new(cotonoma_coto.uuid, coto1.uuid), &opr)?;
+    let _ = ds.edit_coto(
+        &coto1.uuid,
+        CotoContentDiff::default().content("hello!"),
+        &opr,
+    )?;
+    let _ = ds.edit_ito(
+        &ito2.uuid,
+        ItoContentDiff::default().description(Some("greeting")),
+        &opr,
+    )?;
+    let _ = ds.delete_coto(&coto2.uuid, &opr)?;
+
+    /////////////////////////////////////////////////////////////////////////////
+    // When: the database is consistent with the changelog
+    /////////////////////////////////////////////////////////////////////////////
+
+    let report = db.fsck()?;
+    assert_that!(report.changes_replayed, gt(0));
+    assert_that!(report.mismatches, is_empty());
+    assert!(report.is_ok());
+
+    /////////////////////////////////////////////////////////////////////////////
+    // When: the live tables have been modified behind the changelog
+    /////////////////////////////////////////////////////////////////////////////
+
+    let mut conn =
+        SqliteConnection::establish(root_dir.path().join("cotoami.db").to_str().unwrap())?;
+    diesel::sql_query(format!(
+        "UPDATE cotos SET content = 'tampered' WHERE uuid = '{}'",
+        coto1.uuid
+    ))
+    .execute(&mut conn)?;
+    diesel::sql_query(format!("DELETE FROM itos WHERE uuid = '{}'", ito1.uuid))
+        .execute(&mut conn)?;
+    drop(conn);
+
+    let report = db.fsck()?;
+    assert!(!report.is_ok());
+    assert_that!(
+        report.mismatches,
+        unordered_elements_are![
+            eq(&Mismatch {
+                kind: EntityKind::Coto,
+                id: coto1.uuid.to_string(),
+                problem: MismatchProblem::Fields(vec!["content".into()]),
+            }),
+            eq(&Mismatch {
+                kind: EntityKind::Ito,
+                id: ito1.uuid.to_string(),
+                problem: MismatchProblem::MissingInDatabase,
+            }),
+        ]
+    );
+
+    Ok(())
+}
@@ -1,7 +1,9 @@
+use std::path::PathBuf;
+
 use anyhow::Result;
 use cotoami_db::prelude::*;
 use googletest::prelude::*;
-use indoc::indoc;
+use indoc::{formatdoc, indoc};
 use petgraph::dot::Dot;
 
 pub mod common;
@@ -66,7 +68,7 @@ fn graph() -> Result<()> {
     let ito2 = connect(&ItoInput::new(coto1.uuid, coto2.uuid))?;
 
     let ((cotonoma1, _), _) = ds.post_cotonoma(&CotonomaInput::new("cotonoma1"), &root, &opr)?;
-    let _ito3 = connect(&ItoInput::new(coto1.uuid, cotonoma1.coto_id))?;
+    let ito3 = connect(&ItoInput::new(coto1.uuid, cotonoma1.coto_id))?;
 
     let graph = ds.graph(root_coto.clone(), true)?;
     let graph_by_cte = ds.graph_by_cte(root_coto.clone(), true)?;
@@ -183,6 +185,53 @@ fn graph() -> Result<()> {
         ]
     );
 
+    assert_that!(
+        ds.descendants_of(&coto1.uuid, None)?,
+        elements_are![
+            (
+                unordered_elements_are![
+                    pat!(Ito {
+                        uuid: eq(&ito2.uuid),
+                        ..
+                    }),
+                    pat!(Ito {
+                        uuid: eq(&ito3.uuid),
+                        ..
+                    })
+                ],
+                unordered_elements_are![
+                    pat!(Coto {
+                        uuid: eq(&coto2.uuid),
+                        ..
+                    }),
+                    pat!(Coto {
+                        uuid: eq(&cotonoma1.coto_id),
+                        ..
+                    })
+                ]
+            ),
+            (
+                elements_are![pat!(Ito {
+                    uuid: eq(&ito4.uuid),
+                    ..
+                })],
+                elements_are![pat!(Coto {
+                    uuid: eq(&coto3.uuid),
+                    ..
+                })]
+            ),
+            (
+                // coto1 has been already traversed
+                elements_are![pat!(Ito {
+                    uuid: eq(&ito5.uuid),
+                    ..
+                })],
+                is_empty()
+            )
+        ]
+    );
+    assert_that!(ds.descendants_of(&coto1.uuid, Some(1))?.len(), eq(1));
+
     /////////////////////////////////////////////////////////////////////////////
     // When: until cotonoma
     /////////////////////////////////////////////////////////////////////////////
@@ -231,6 +280,678 @@ fn graph() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn paths_between() -> Result<()> {
+    let (_root_dir, db, _node) = common::setup_db("My Node")?;
+    let mut ds = db.new_session()?;
+    let opr = db.globals().local_node_as_operator()?;
+    let (root, root_coto) = ds.local_node_root()?.unwrap();
+
+    // root -> coto1 -> coto2 -> coto4
+    //      -> coto3 -> coto4
+    let (coto1, _) = ds.post_coto(&CotoInput::new("coto1"), &root.uuid, &opr)?;
+    let (coto2, _) = ds.post_coto(&CotoInput::new("coto2"), &root.uuid, &opr)?;
+    let (coto3, _) = ds.post_coto(&CotoInput::new("coto3"), &root.uuid, &opr)?;
+    let (coto4, _) = ds.post_coto(&CotoInput::new("coto4"), &root.uuid, &opr)?;
+    let (ito1, _) = ds.create_ito(&ItoInput::new(root_coto.uuid, coto1.uuid), &opr)?;
+    let (ito2, _) = ds.create_ito(&ItoInput::new(coto1.uuid, coto2.uuid), &opr)?;
+    let (ito3, _) = ds.create_ito(&ItoInput::new(root_coto.uuid, coto3.uuid), &opr)?;
+    let (ito4, _) = ds.create_ito(&ItoInput::new(coto3.uuid, coto4.uuid), &opr)?;
+    let (ito5, _) = ds.create_ito(&ItoInput::new(coto2.uuid, coto4.uuid), &opr)?;
+
+    // The same coto
+    assert_that!(
+        ds.paths_between(&coto1.uuid, &coto1.uuid, None, true)?,
+        elements_are![is_empty()]
+    );
+
+    // Directed
+    assert_that!(
+        ds.paths_between(&root_coto.uuid, &coto2.uuid, None, true)?,
+        elements_are![elements_are![
+            pat!(Ito {
+                uuid: eq(&ito1.uuid),
+                ..
+            }),
+            pat!(Ito {
+                uuid: eq(&ito2.uuid),
+                ..
+            })
+        ]]
+    );
+    assert_that!(
+        ds.paths_between(&root_coto.uuid, &coto4.uuid, None, true)?,
+        elements_are![elements_are![
+            pat!(Ito {
+                uuid: eq(&ito3.uuid),
+                ..
+            }),
+            pat!(Ito {
+                uuid: eq(&ito4.uuid),
+                ..
+            })
+        ]]
+    );
+    assert_that!(
+        ds.paths_between(&coto2.uuid, &root_coto.uuid, None, true)?,
+        is_empty()
+    );
+
+    // Limited by depth
+    assert_that!(
+        ds.paths_between(&root_coto.uuid, &coto4.uuid, Some(1), true)?,
+        is_empty()
+    );
+
+    // Undirected
+    assert_that!(
+        ds.paths_between(&coto2.uuid, &coto3.uuid, None, false)?,
+        elements_are![elements_are![
+            pat!(Ito {
+                uuid: eq(&ito5.uuid),
+                ..
+            }),
+            pat!(Ito {
+                uuid: eq(&ito4.uuid),
+                ..
+            })
+        ]]
+    );
+
+    // Multiple shortest paths
+    let (ito6, _) = ds.create_ito(&ItoInput::new(coto1.uuid, coto4.uuid), &opr)?;
+    assert_that!(
+        ds.paths_between(&root_coto.uuid, &coto4.uuid, None, true)?,
+        unordered_elements_are![
+            elements_are![
+                pat!(Ito {
+                    uuid: eq(&ito1.uuid),
+                    ..
+                }),
+                pat!(Ito {
+                    uuid: eq(&ito6.uuid),
+                    ..
+                })
+            ],
+            elements_are![
+                pat!(Ito {
+                    uuid: eq(&ito3.uuid),
+                    ..
+                }),
+                pat!(Ito {
+                    uuid: eq(&ito4.uuid),
+                    ..
+                })
+            ]
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn bounded_traversal() -> Result<()> {
+    let (_root_dir, db, _node) = common::setup_db("My Node")?;
+    let mut ds = db.new_session()?;
+    let opr = db.globals().local_node_as_operator()?;
+    let (root, root_coto) = ds.local_node_root()?.unwrap();
+
+    // root -> coto1 -> coto4 -> coto5
+    //      -> coto2
+    //      -> coto3
+    let (coto1, _) = ds.post_coto(&CotoInput::new("coto1"), &root.uuid, &opr)?;
+    let (coto2, _) = ds.post_coto(&CotoInput::new("coto2"), &root.uuid, &opr)?;
+    let (coto3, _) = ds.post_coto(&CotoInput::new("coto3"), &root.uuid, &opr)?;
+    let (coto4, _) = ds.post_coto(&CotoInput::new("coto4"), &root.uuid, &opr)?;
+    let (coto5, _) = ds.post_coto(&CotoInput::new("coto5"), &root.uuid, &opr)?;
+    let (_ito1, _) = ds.create_ito(&ItoInput::new(root_coto.uuid, coto1.uuid), &opr)?;
+    let (_ito2, _) = ds.create_ito(&ItoInput::new(root_coto.uuid, coto2.uuid), &opr)?;
+    let (ito3, _) = ds.create_ito(&ItoInput::new(root_coto.uuid, coto3.uuid), &opr)?;
+    let (_ito4, _) = ds.create_ito(&ItoInput::new(coto1.uuid, coto4.uuid), &opr)?;
+    let (ito5, _) = ds.create_ito(&ItoInput::new(coto4.uuid, coto5.uuid), &opr)?;
+
+    // Unlimited
+    let graph = ds.traverse_graph(root_coto.clone(), TraversalOptions::default(), None)?;
+    assert_that!(graph.frontier, none());
+    assert_that!(graph.cotos.len(), eq(6));
+
+    /////////////////////////////////////////////////////////////////////////////
+    // Depth limit
+    /////////////////////////////////////////////////////////////////////////////
+
+    let options = TraversalOptions::default().max_depth(1);
+    let graph = ds.traverse_graph(root_coto.clone(), options, None)?;
+    let frontier = graph.frontier.clone().unwrap();
+    assert_that!(frontier.depth, eq(1));
+    assert_that!(
+        frontier.sources,
+        unordered_elements_are![
+            eq(&(coto1.uuid, 0)),
+            eq(&(coto2.uuid, 0)),
+            eq(&(coto3.uuid, 0))
+        ]
+    );
+    assert_that!(frontier.next, is_empty());
+    assert_graph(
+        graph,
+        indoc! {r#"
+            digraph {
+                0 [ label = "<My Node>" ]
+                1 [ label = "coto1" ]
+                2 [ label = "coto2" ]
+                3 [ label = "coto3" ]
+                0 -> 1 [ label = "" ]
+                0 -> 2 [ label = "" ]
+                0 -> 3 [ label = "" ]
+            }
+        "#},
+    );
+
+    // Resume from the frontier (the depth is counted from the root)
+    let options = TraversalOptions::default().max_depth(2);
+    let graph = ds.traverse_graph(root_coto.clone(), options, Some(frontier))?;
+    assert_that!(
+        graph.frontier,
+        some(pat!(TraversalFrontier {
+            depth: eq(&2),
+            sources: elements_are![eq(&(coto4.uuid, 0))],
+            next: is_empty(),
+        }))
+    );
+    assert_graph(
+        graph,
+        indoc! {r#"
+            digraph {
+                0 [ label = "<My Node>" ]
+                1 [ label = "coto1" ]
+                2 [ label = "coto2" ]
+                3 [ label = "coto3" ]
+                4 [ label = "coto4" ]
+                1 -> 4 [ label = "" ]
+            }
+        "#},
+    );
+
+    /////////////////////////////////////////////////////////////////////////////
+    // Node-count limit
+    /////////////////////////////////////////////////////////////////////////////
+
+    let options = TraversalOptions::default().max_cotos(2);
+    let graph = ds.traverse_graph(root_coto.clone(), options.clone(), None)?;
+    let frontier = graph.frontier.clone().unwrap();
+    assert_that!(frontier.depth, eq(0));
+    assert_that!(
+        frontier.sources,
+        elements_are![eq(&(root_coto.uuid, ito3.order - 1))]
+    );
+    assert_that!(
+        frontier.next,
+        unordered_elements_are![eq(&coto1.uuid), eq(&coto2.uuid)]
+    );
+    assert_graph(
+        graph,
+        indoc! {r#"
+            digraph {
+                0 [ label = "<My Node>" ]
+                1 [ label = "coto1" ]
+                2 [ label = "coto2" ]
+                0 -> 1 [ label = "" ]
+                0 -> 2 [ label = "" ]
+            }
+        "#},
+    );
+
+    // Next page
+    let graph = ds.traverse_graph(root_coto.clone(), options.clone(), Some(frontier))?;
+    let frontier = graph.frontier.clone().unwrap();
+    assert_that!(frontier.depth, eq(2));
+    assert_that!(
+        frontier.sources,
+        elements_are![eq(&(coto4.uuid, ito5.order - 1))]
+    );
+    assert_that!(frontier.next, is_empty());
+    assert_graph(
+        graph,
+        indoc! {r#"
+            digraph {
+                0 [ label = "<My Node>" ]
+                1 [ label = "coto1" ]
+                2 [ label = "coto2" ]
+                3 [ label = "coto3" ]
+                4 [ label = "coto4" ]
+                0 -> 3 [ label = "" ]
+                1 -> 4 [ label = "" ]
+            }
+        "#},
+    );
+
+    // Last page
+    let graph = ds.traverse_graph(root_coto.clone(), options, Some(frontier))?;
+    assert_that!(graph.frontier, none());
+    assert_graph(
+        graph,
+        indoc! {r#"
+            digraph {
+                0 [ label = "<My Node>" ]
+                1 [ label = "coto4" ]
+                2 [ label = "coto5" ]
+                1 -> 2 [ label = "" ]
+            }
+        "#},
+    );
+
+    Ok(())
+}
+
+#[test]
+fn export() -> Result<()> {
+    let (_root_dir, db, _node) = common::setup_db("My Node")?;
+    let mut ds = db.new_session()?;
+    let opr = db.globals().local_node_as_operator()?;
+    let (root, root_coto) = ds.local_node_root()?.unwrap();
+
+    let (coto1, _) = ds.post_coto(
+        &CotoInput::new("Tom & Jerry\nsecond line"),
+        &root.uuid,
+        &opr,
+    )?;
+    let ((cotonoma1, _), _) = ds.post_cotonoma(&CotonomaInput::new("cotonoma1"), &root, &opr)?;
+    let (ito1, _) = ds.create_ito(
+        &ItoInput::new(root_coto.uuid, coto1.uuid).description("say \"hi\""),
+        &opr,
+    )?;
+    let (ito2, _) = ds.create_ito(&ItoInput::new(coto1.uuid, cotonoma1.coto_id), &opr)?;
+
+    let mut graph = || ds.graph(root_coto.clone(), false);
+
+    assert_that!(
+        graph()?.into_dot(),
+        eq(indoc! {r#"
+            digraph {
+                0 [ label = "My Node" shape = box style = bold ]
+                1 [ label = "Tom & Jerry" ]
+                2 [ label = "cotonoma1" shape = box style = bold ]
+                0 -> 1 [ label = "say \"hi\"" ]
+                1 -> 2 [ label = "" ]
+            }
+        "#})
+    );
+
+    assert_that!(
+        graph()?.into_mermaid(),
+        eq(indoc! {r#"
+            flowchart TD
+                n0[["My Node"]]
+                n1["Tom & Jerry"]
+                n2[["cotonoma1"]]
+                n0 -->|"say #quot;hi#quot;"| n1
+                n1 --> n2
+                classDef cotonoma font-weight:bold
+                class n0,n2 cotonoma
+        "#})
+    );
+
+    assert_that!(
+        graph()?.into_graphml(),
+        eq(&format!(
+            indoc! {r#"
+                <?xml version="1.0" encoding="UTF-8"?>
+                <graphml xmlns="http://graphml.graphdrawing.org/xmlns">
+                  <key id="label" for="node" attr.name="label" attr.type="string"/>
+                  <key id="cotonoma" for="node" attr.name="cotonoma" attr.type="boolean">
+                    <default>false</default>
+                  </key>
+                  <key id="description" for="edge" attr.name="label" attr.type="string"/>
+                  <graph id="G" edgedefault="directed">
+                    <node id="{root}">
+                      <data key="label">My Node</data>
+                      <data key="cotonoma">true</data>
+                    </node>
+                    <node id="{coto1}">
+                      <data key="label">Tom &amp; Jerry</data>
+                    </node>
+                    <node id="{cotonoma1}">
+                      <data key="label">cotonoma1</data>
+                      <data key="cotonoma">true</data>
+                    </node>
+                    <edge id="{ito1}" source="{root}" target="{coto1}">
+                      <data key="description">say &quot;hi&quot;</data>
+                    </edge>
+                    <edge id="{ito2}" source="{coto1}" target="{cotonoma1}"/>
+                  </graph>
+                </graphml>
+            "#},
+            root = root_coto.uuid,
+            coto1 = coto1.uuid,
+            cotonoma1 = cotonoma1.coto_id,
+            ito1 = ito1.uuid,
+            ito2 = ito2.uuid,
+        ))
+    );
+
+    Ok(())
+}
+
+#[test]
+fn markdown_export() -> Result<()> {
+    let (root_dir, db, _node) = common::setup_db("My Node")?;
+    let mut ds = db.new_session()?;
+    let opr = db.globals().local_node_as_operator()?;
+    let (root, root_coto) = ds.local_node_root()?.unwrap();
+
+    // My Node -(cause)-> coto1 -> coto2
+    //         \-> cotonoma1
+    let (coto1, _) = ds.post_coto(
+        &CotoInput::new("line1\nline2")
+            .summary("Coto1")
+            .geolocation(Geolocation::from_lng_lat((135.0, 35.0))),
+        &root.uuid,
+        &opr,
+    )?;
+    let (coto2, _) = ds.post_coto(
+        &CotoInput::new("child").media_content(vec![1, 2, 3].into(), "application/octet-stream"),
+        &root.uuid,
+        &opr,
+    )?;
+    let ((cotonoma1, _), _) = ds.post_cotonoma(&CotonomaInput::new("cotonoma1"), &root, &opr)?;
+    let (_ito1, _) = ds.create_ito(
+        &ItoInput::new(root_coto.uuid, coto1.uuid).description("cause"),
+        &opr,
+    )?;
+    let (_ito2, _) = ds.create_ito(&ItoInput::new(coto1.uuid, coto2.uuid), &opr)?;
+    let (_ito3, _) = ds.create_ito(&ItoInput::new(root_coto.uuid, cotonoma1.coto_id), &opr)?;
+
+    let mut graph = || ds.graph(root_coto.clone(), true);
+
+    // Document
+    let export = graph()?.into_markdown(MarkdownLayout::Document);
+    assert_that!(
+        export.documents,
+        elements_are![(
+            eq(&PathBuf::from("index.md")),
+            eq(&formatdoc! {"
+                # My Node
+
+                - *cause*: **Coto1**
+
+                  line1
+                  line2
+                  - child
+                    ![](media/{coto2_id}.bin)
+                - [[cotonoma1]]
+                ",
+                coto2_id = coto2.uuid
+            })
+        )]
+    );
+    assert_that!(
+        export.media,
+        elements_are![(
+            eq(&PathBuf::from(format!("media/{}.bin", coto2.uuid))),
+            eq(&Bytes::from(vec![1, 2, 3]))
+        )]
+    );
+
+    // Files
+    let export = graph()?.into_markdown(MarkdownLayout::Files);
+    let datetime = |coto: &Coto| coto.created_at.format("%Y-%m-%dT%H:%M:%SZ").to_string();
+    assert_that!(export.documents.len(), eq(4));
+    assert_that!(
+        export.documents[0],
+        (
+            eq(&PathBuf::from(format!("{}.md", root_coto.uuid))),
+            eq(&formatdoc! {r#"
+                ---
+                uuid: "{uuid}"
+                node_id: "{node_id}"
+                title: "My Node"
+                is_cotonoma: true
+                created_at: {created_at}
+                updated_at: {created_at}
+                ---
+
+                # My Node
+
+                - *cause*: [[{coto1_id}|Coto1]]
+                - [[{cotonoma1_id}|cotonoma1]]
+                "#,
+                uuid = root_coto.uuid,
+                node_id = root_coto.node_id,
+                created_at = datetime(&root_coto),
+                coto1_id = coto1.uuid,
+                cotonoma1_id = cotonoma1.coto_id,
+            })
+        )
+    );
+    assert_that!(
+        export.documents[1].1,
+        eq(&formatdoc! {r#"
+            ---
+            uuid: "{uuid}"
+            node_id: "{node_id}"
+            title: "Coto1"
+            is_cotonoma: false
+            created_at: {created_at}
+            updated_at: {created_at}
+            longitude: 135
+            latitude: 35
+            ---
+
+            **Coto1**
+
+            line1
+            line2
+
+            - [[{coto2_id}|child]]
+            "#,
+            uuid = coto1.uuid,
+            node_id = coto1.node_id,
+            created_at = datetime(&coto1),
+            coto2_id = coto2.uuid,
+        })
+    );
+
+    // Write the files
+    let dir = root_dir.path().join("markdown");
+    export.write_to(&dir)?;
+    assert_that!(
+        std::fs::read(dir.join(format!("media/{}.bin", coto2.uuid)))?,
+        eq(&vec![1, 2, 3])
+    );
+    assert_that!(
+        std::fs::read_to_string(dir.join(format!("{}.md", coto1.uuid)))?,
+        eq(&export.documents[1].1)
+    );
+
+    Ok(())
+}
+
+#[test]
+fn opml_export() -> Result<()> {
+    let (_root_dir, db, _node) = common::setup_db("My Node")?;
+    let mut ds = db.new_session()?;
+    let opr = db.globals().local_node_as_operator()?;
+    let (root, root_coto) = ds.local_node_root()?.unwrap();
+
+    // My Node -(cause)-> coto1 -> coto2
+    //         \-> cotonoma1
+    let (coto1, _) = ds.post_coto(
+        &CotoInput::new("line1\nline2").summary("Coto1 & more"),
+        &root.uuid,
+        &opr,
+    )?;
+    let (coto2, _) = ds.post_coto(&CotoInput::new("<child>"), &root.uuid, &opr)?;
+    let ((cotonoma1, _), _) = ds.post_cotonoma(&CotonomaInput::new("cotonoma1"), &root, &opr)?;
+    let (_ito1, _) = ds.create_ito(
+        &ItoInput::new(root_coto.uuid, coto1.uuid).description("cause"),
+        &opr,
+    )?;
+    let (_ito2, _) = ds.create_ito(&ItoInput::new(coto1.uuid, coto2.uuid), &opr)?;
+    let (_ito3, _) = ds.create_ito(&ItoInput::new(root_coto.uuid, cotonoma1.coto_id), &opr)?;
+
+    let created = |coto: &Coto| coto.created_at.and_utc().to_rfc2822();
+    let cotonoma1_coto = ds.try_get_coto(&cotonoma1.coto_id)?;
+    assert_that!(
+        ds.graph(root_coto.clone(), true)?.into_opml(),
+        eq(&formatdoc! {r#"
+            <?xml version="1.0" encoding="UTF-8"?>
+            <opml version="2.0">
+              <head>
+                <title>My Node</title>
+                <dateCreated>{root}</dateCreated>
+              </head>
+              <body>
+                <outline text="Coto1 &amp; more" _note="line1&#10;line2" _description="cause" created="{coto1}">
+                  <outline text="&lt;child&gt;" created="{coto2}"/>
+                </outline>
+                <outline text="cotonoma1" _cotonoma="true" created="{cotonoma1}"/>
+              </body>
+            </opml>
+            "#,
+            root = created(&root_coto),
+            coto1 = created(&coto1),
+            coto2 = created(&coto2),
+            cotonoma1 = created(&cotonoma1_coto),
+        })
+    );
+
+    Ok(())
+}
+
+#[test]
+fn graph_health() -> Result<()> {
+    let (_root_dir, db, _node) = common::setup_db("My Node")?;
+    let mut ds = db.new_session()?;
+    let opr = db.globals().local_node_as_operator()?;
+    let (root, root_coto) = ds.local_node_root()?.unwrap();
+
+    // root -> coto2 <-> coto3
+    // coto1 (orphan)
+    let (coto1, _) = ds.post_coto(&CotoInput::new("coto1"), &root.uuid, &opr)?;
+    let (coto2, _) = ds.post_coto(&CotoInput::new("coto2"), &root.uuid, &opr)?;
+    let (coto3, _) = ds.post_coto(&CotoInput::new("coto3"), &root.uuid, &opr)?;
+    let ((cotonoma1, _), _) = ds.post_cotonoma(&CotonomaInput::new("cotonoma1"), &root, &opr)?;
+    let (coto4, _) = ds.post_coto(&CotoInput::new("coto4"), &cotonoma1.uuid, &opr)?;
+    let (_ito1, _) = ds.create_ito(&ItoInput::new(root_coto.uuid, coto2.uuid), &opr)?;
+    let (_ito2, _) = ds.create_ito(&ItoInput::new(coto2.uuid, coto3.uuid), &opr)?;
+    let (_ito3, _) = ds.create_ito(&ItoInput::new(coto3.uuid, coto2.uuid), &opr)?;
+
+    let mut cycle = vec![coto2.uuid, coto3.uuid];
+    cycle.sort_by_key(Id::as_uuid);
+
+    let health = ds.graph_health(Scope::All)?;
+    assert_that!(
+        health,
+        pat!(GraphHealth {
+            orphans: elements_are![eq(&coto1.uuid), eq(&coto4.uuid)],
+            cycles: elements_are![eq(&cycle)],
+            itos_to_unreplicated_nodes: is_empty(),
+            reposts_without_original: is_empty(),
+        })
+    );
+    assert_that!(health.is_healthy(), eq(false));
+
+    let health = ds.graph_health(Scope::cotonoma_local(root.uuid))?;
+    assert_that!(health.orphans, elements_are![eq(&coto1.uuid)]);
+    assert_that!(health.cycles, elements_are![eq(&cycle)]);
+
+    let health = ds.graph_health(Scope::cotonoma_local(cotonoma1.uuid))?;
+    assert_that!(health.orphans, elements_are![eq(&coto4.uuid)]);
+    assert_that!(health.cycles, is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn query_graph() -> Result<()> {
+    let (_root_dir, db, _node) = common::setup_db("My Node")?;
+    let mut ds = db.new_session()?;
+    let opr = db.globals().local_node_as_operator()?;
+    let (root, _) = ds.local_node_root()?.unwrap();
+
+    // coto1 -(cause)-> coto2 -> coto3
+    //   \___________________^
+    let ((cotonoma1, _), _) = ds.post_cotonoma(&CotonomaInput::new("cotonoma1"), &root, &opr)?;
+    let (coto1, _) = ds.post_coto(&CotoInput::new("apple"), &root.uuid, &opr)?;
+    let (coto2, _) = ds.post_coto(&CotoInput::new("banana"), &cotonoma1.uuid, &opr)?;
+    let (coto3, _) = ds.post_coto(&CotoInput::new("cherry"), &cotonoma1.uuid, &opr)?;
+    let (ito1, _) = ds.create_ito(
+        &ItoInput::new(coto1.uuid, coto2.uuid).description("cause"),
+        &opr,
+    )?;
+    let (ito2, _) = ds.create_ito(&ItoInput::new(coto2.uuid, coto3.uuid), &opr)?;
+    let (ito3, _) = ds.create_ito(&ItoInput::new(coto1.uuid, coto3.uuid), &opr)?;
+
+    // (root, cotos, itos)
+    type Matched = (Id<Coto>, Vec<Id<Coto>>, Vec<Id<Ito>>);
+    let mut query_with_limit = |query: &str, max_cotos: usize| -> Result<Matched> {
+        let graph = ds.query_graph(query, max_cotos)?.unwrap();
+        let mut coto_ids: Vec<_> = graph.cotos.keys().copied().collect();
+        coto_ids.sort_by_key(Id::as_uuid);
+        let mut ito_ids: Vec<_> = graph.itos.values().flatten().map(|i| i.uuid).collect();
+        ito_ids.sort_by_key(Id::as_uuid);
+        Ok((graph.root_id, coto_ids, ito_ids))
+    };
+    let mut query = |query: &str| query_with_limit(query, 100);
+    fn sorted<T>(mut ids: Vec<Id<T>>) -> Vec<Id<T>> {
+        ids.sort_by_key(Id::as_uuid);
+        ids
+    }
+
+    assert_that!(
+        query(r#"MATCH (a)-[i {description: "cause"}]->(b)"#)?,
+        eq(&(
+            coto1.uuid,
+            sorted(vec![coto1.uuid, coto2.uuid]),
+            vec![ito1.uuid]
+        ))
+    );
+    assert_that!(
+        query(r#"MATCH (a {cotonoma: "cotonoma1"})-->(b)"#)?,
+        eq(&(
+            coto2.uuid,
+            sorted(vec![coto2.uuid, coto3.uuid]),
+            vec![ito2.uuid]
+        ))
+    );
+    assert_that!(
+        query(r#"MATCH (a)-->(b)-->(c) WHERE a.content CONTAINS "app""#)?,
+        eq(&(
+            coto1.uuid,
+            sorted(vec![coto1.uuid, coto2.uuid, coto3.uuid]),
+            sorted(vec![ito1.uuid, ito2.uuid])
+        ))
+    );
+    assert_that!(
+        query(r#"MATCH (a)<-[i]-(b) WHERE a.content = "cherry" AND i.description = null"#)?,
+        eq(&(
+            coto3.uuid,
+            sorted(vec![coto1.uuid, coto2.uuid, coto3.uuid]),
+            sorted(vec![ito2.uuid, ito3.uuid])
+        ))
+    );
+
+    // The oldest cotos are included up to the limit with the itos between them
+    assert_that!(
+        query_with_limit(r#"MATCH (a)-->(b) WHERE a.content = "apple""#, 1)?,
+        eq(&(
+            coto1.uuid,
+            sorted(vec![coto1.uuid, coto2.uuid]),
+            vec![ito1.uuid]
+        ))
+    );
+
+    assert_that!(ds.query_graph("MATCH (a:Cotonoma)-->(b)", 100)?, none());
+    assert_that!(
+        ds.query_graph("MATCH (a)->(b)", 100)
+            .unwrap_err()
+            .to_string(),
+        eq("Invalid graph query at 9: unexpected token: ->")
+    );
+
+    Ok(())
+}
+
 fn assert_graph(graph: Graph, expected_dot: &str) {
     assert_that!(
         Dot::new(&graph.into_petgraph(true)).to_string(),
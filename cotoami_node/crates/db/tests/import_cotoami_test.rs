@@ -0,0 +1,217 @@
+use std::str::FromStr;
+
+use anyhow::Result;
+use cotoami_db::{import::cotoami::*, prelude::*};
+use googletest::prelude::*;
+use indoc::indoc;
+
+pub mod common;
+
+const AMISHI_ID: &str = "55111bd3-92e2-4b02-bc1a-15b74a945fd0";
+const COTONOMA_COTO_ID: &str = "00000000-0000-0000-0000-000000000001";
+const COTONOMA_ID: &str = "00000000-0000-0000-0000-000000000002";
+const COTO_ID: &str = "00000000-0000-0000-0000-000000000003";
+const REPOST_ID: &str = "00000000-0000-0000-0000-000000000004";
+const EXCLUDED_COTONOMA_COTO_ID: &str = "00000000-0000-0000-0000-000000000005";
+const EXCLUDED_COTONOMA_ID: &str = "00000000-0000-0000-0000-000000000006";
+const EXCLUDED_COTO_ID: &str = "00000000-0000-0000-0000-000000000007";
+const ORPHAN_COTO_ID: &str = "00000000-0000-0000-0000-000000000008";
+
+fn cotoami_json() -> Result<CotoamiExportJson> {
+    // The repost comes first to test pending dependencies.
+    let json = format!(
+        indoc! {r#"
+            {{
+                "cotos": [
+                    {{
+                        "id": "{repost}", "content": null, "summary": null,
+                        "posted_in_id": "{cotonoma}", "as_cotonoma": false, "cotonoma": null,
+                        "repost_id": "{coto}",
+                        "inserted_at": 1507106650888, "updated_at": 1507106650888
+                    }},
+                    {{
+                        "id": "{coto}", "content": "Hello", "summary": null,
+                        "posted_in_id": null, "as_cotonoma": false, "cotonoma": null,
+                        "repost_id": null,
+                        "inserted_at": 1507106650888, "updated_at": 1507106650888
+                    }},
+                    {{
+                        "id": "{cotonoma_coto}", "content": "Cotoami", "summary": null,
+                        "posted_in_id": null, "as_cotonoma": true,
+                        "cotonoma": {{
+                            "id": "{cotonoma}", "key": "2al3mr9qljoslr23", "name": "Cotoami",
+                            "shared": false, "pinned": false,
+                            "timeline_revision": 0, "graph_revision": 0,
+                            "inserted_at": 1507132300379, "updated_at": 1507132300379,
+                            "last_post_timestamp": null
+                        }},
+                        "repost_id": null,
+                        "inserted_at": 1507132300379, "updated_at": 1507132300379
+                    }},
+                    {{
+                        "id": "{excluded_cotonoma_coto}", "content": "Private", "summary": null,
+                        "posted_in_id": null, "as_cotonoma": true,
+                        "cotonoma": {{
+                            "id": "{excluded_cotonoma}", "key": "xxxx", "name": "Private",
+                            "shared": false, "pinned": false,
+                            "timeline_revision": 0, "graph_revision": 0,
+                            "inserted_at": 1507132300379, "updated_at": 1507132300379,
+                            "last_post_timestamp": null
+                        }},
+                        "repost_id": null,
+                        "inserted_at": 1507132300379, "updated_at": 1507132300379
+                    }},
+                    {{
+                        "id": "{excluded_coto}", "content": "Secret", "summary": null,
+                        "posted_in_id": "{excluded_cotonoma}", "as_cotonoma": false,
+                        "cotonoma": null, "repost_id": null,
+                        "inserted_at": 1507106650888, "updated_at": 1507106650888
+                    }},
+                    {{
+                        "id": "{orphan}", "content": "Orphan", "summary": null,
+                        "posted_in_id": "99999999-0000-0000-0000-000000000000",
+                        "as_cotonoma": false, "cotonoma": null, "repost_id": null,
+                        "inserted_at": 1507106650888, "updated_at": 1507106650888
+                    }}
+                ],
+                "connections": [
+                    {{
+                        "start": "{amishi}", "end": "{cotonoma_coto}", "created_by": "{amishi}",
+                        "order": 1, "created_at": 1507106701180
+                    }},
+                    {{
+                        "start": "{cotonoma_coto}", "end": "{coto}", "created_by": "{amishi}",
+                        "linking_phrase": "greeting", "order": 1, "created_at": 1507106701180
+                    }},
+                    {{
+                        "start": "{coto}", "end": "{excluded_coto}", "created_by": "{amishi}",
+                        "order": 1, "created_at": 1507106701180
+                    }}
+                ]
+            }}
+        "#},
+        amishi = AMISHI_ID,
+        cotonoma_coto = COTONOMA_COTO_ID,
+        cotonoma = COTONOMA_ID,
+        coto = COTO_ID,
+        repost = REPOST_ID,
+        excluded_cotonoma_coto = EXCLUDED_COTONOMA_COTO_ID,
+        excluded_cotonoma = EXCLUDED_COTONOMA_ID,
+        excluded_coto = EXCLUDED_COTO_ID,
+        orphan = ORPHAN_COTO_ID,
+    );
+    CotoamiExportJson::from_str(&json)
+}
+
+#[test]
+fn import_cotoami_json_dump() -> Result<()> {
+    /////////////////////////////////////////////////////////////////////////////
+    // Setup
+    /////////////////////////////////////////////////////////////////////////////
+
+    let (_root_dir, db, _node) = common::setup_db("My Node")?;
+    let mut ds = db.new_session()?;
+    let (root_cotonoma, root_coto) = ds.local_node_root()?.unwrap();
+
+    let mut options = CotoamiImportOptions {
+        exclude_cotonomas: vec![Id::from_str(EXCLUDED_COTONOMA_ID)?],
+        dry_run: true,
+    };
+    let excluded_coto_id: Id<Coto> = Id::from_str(EXCLUDED_COTO_ID)?;
+    let expected_report = CotoamiImportReport {
+        cotos: 3,
+        cotonomas: 1,
+        itos: 2,
+        already_imported: 0,
+        rejected_cotos: vec![
+            (Id::from_str(EXCLUDED_COTONOMA_COTO_ID)?, "excluded".into()),
+            (excluded_coto_id, "excluded".into()),
+            (
+                Id::from_str(ORPHAN_COTO_ID)?,
+                "missing cotonoma: 99999999-0000-0000-0000-000000000000".into(),
+            ),
+        ],
+        rejected_connections: vec![(
+            format!("{COTO_ID}->{EXCLUDED_COTO_ID}"),
+            format!("end coto is missing: {EXCLUDED_COTO_ID}"),
+        )],
+    };
+
+    /////////////////////////////////////////////////////////////////////////////
+    // When: dry run
+    /////////////////////////////////////////////////////////////////////////////
+
+    let mut changes = 0;
+    let report = import_cotoami_json(&mut ds, cotoami_json()?, &options, |event| {
+        if let ImportEvent::Change(_) = event {
+            changes += 1;
+        }
+    })?;
+    assert_that!(report, eq(&expected_report));
+    assert_that!(changes, eq(0));
+    assert_that!(ds.all_cotos()?, len(eq(1))); // only the root cotonoma
+
+    /////////////////////////////////////////////////////////////////////////////
+    // When: import
+    /////////////////////////////////////////////////////////////////////////////
+
+    options.dry_run = false;
+    let mut events = Vec::new();
+    let report = import_cotoami_json(&mut ds, cotoami_json()?, &options, |event| {
+        events.push(event);
+    })?;
+    assert_that!(report, eq(&expected_report));
+    assert_that!(
+        events,
+        elements_are![
+            pat!(ImportEvent::Change(_)), // coto
+            pat!(ImportEvent::Change(_)), // cotonoma
+            pat!(ImportEvent::Change(_)), // repost
+            pat!(ImportEvent::Progress {
+                phase: eq(&ImportPhase::Cotos),
+                done: eq(&6),
+                total: eq(&6)
+            }),
+            pat!(ImportEvent::Change(_)), // ito from the root
+            pat!(ImportEvent::Change(_)), // ito
+            pat!(ImportEvent::Progress {
+                phase: eq(&ImportPhase::Connections),
+                done: eq(&3),
+                total: eq(&3)
+            }),
+        ]
+    );
+
+    let coto = ds.coto(&Id::from_str(COTO_ID)?)?.unwrap();
+    assert_that!(coto.posted_in_id, some(eq(root_cotonoma.uuid)));
+    let repost = ds.coto(&Id::from_str(REPOST_ID)?)?.unwrap();
+    assert_that!(repost.repost_of_id, some(eq(coto.uuid)));
+    assert_that!(ds.contains_coto(&excluded_coto_id)?, eq(false));
+    assert_that!(
+        ds.outgoing_itos(&[root_coto.uuid])?,
+        elements_are![pat!(Ito {
+            target_coto_id: eq(&Id::from_str(COTONOMA_COTO_ID)?),
+            ..
+        })]
+    );
+
+    /////////////////////////////////////////////////////////////////////////////
+    // When: import again (resume)
+    /////////////////////////////////////////////////////////////////////////////
+
+    let report = import_cotoami_json(&mut ds, cotoami_json()?, &options, |_| ())?;
+    assert_that!(
+        &report,
+        pat!(CotoamiImportReport {
+            cotos: eq(&0),
+            cotonomas: eq(&0),
+            itos: eq(&0),
+            // 3 cotos + 2 itos
+            already_imported: eq(&5),
+            ..
+        })
+    );
+    assert_that!(ds.all_cotos()?, len(eq(4)));
+
+    Ok(())
+}
@@ -0,0 +1,194 @@
+use std::{fs, io::Cursor, path::Path};
+
+use anyhow::Result;
+use chrono::NaiveDate;
+use cotoami_db::{import::markdown::*, prelude::*};
+use googletest::prelude::*;
+use indoc::indoc;
+use tempfile::tempdir;
+
+pub mod common;
+
+fn write(dir: &Path, path: &str, content: impl AsRef<[u8]>) -> Result<()> {
+    let path = dir.join(path);
+    fs::create_dir_all(path.parent().unwrap())?;
+    fs::write(path, content)?;
+    Ok(())
+}
+
+fn png() -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    image::RgbImage::new(2, 2).write_to(&mut Cursor::new(&mut bytes), image::ImageFormat::Png)?;
+    Ok(bytes)
+}
+
+#[test]
+fn import_vault() -> Result<()> {
+    /////////////////////////////////////////////////////////////////////////////
+    // Setup
+    /////////////////////////////////////////////////////////////////////////////
+
+    let (_root_dir, db, node) = common::setup_db("My Node")?;
+    let mut ds = db.new_session()?;
+    let (root_cotonoma, _) = ds.local_node_root()?.unwrap();
+
+    let vault = tempdir()?;
+    write(
+        vault.path(),
+        "Welcome.md",
+        indoc! {"
+            ---
+            title: Hello
+            date: 2026-01-31
+            end: 2026-02-01
+            ---
+            See [[Rust|the language]] and [[Missing]].
+
+            ![[diagram.png]]
+        "},
+    )?;
+    write(
+        vault.path(),
+        "Programming/Rust.md",
+        indoc! {"
+            Back to [[Welcome]].
+
+            ![](../diagram.png)
+            ![[photo.png]]
+        "},
+    )?;
+    write(vault.path(), "diagram.png", png()?)?;
+    write(vault.path(), "Programming/assets/photo.png", png()?)?;
+    write(vault.path(), ".obsidian/Ignored.md", "ignored")?;
+
+    /////////////////////////////////////////////////////////////////////////////
+    // When: dry run
+    /////////////////////////////////////////////////////////////////////////////
+
+    let report = import_markdown(&mut ds, vault.path(), &root_cotonoma.uuid, true, |_| {
+        panic!("No changes should be made in a dry run.")
+    })?;
+
+    macro_rules! expected_report {
+        () => {
+            pat!(MarkdownImportReport {
+                // "Programming" and "assets"
+                cotonomas: eq(&2),
+                // 2 cotonomas + 2 notes + 1 image
+                cotos: eq(&5),
+                // Welcome -> Rust, Rust -> Welcome, Rust -> photo.png
+                itos: eq(&3),
+                media: eq(&3),
+                already_imported: eq(&0),
+                unresolved_links: elements_are![(eq(Path::new("Welcome.md")), eq("Missing"))],
+                warnings: is_empty(),
+                rejected: is_empty(),
+            })
+        };
+    }
+    assert_that!(&report, expected_report!());
+    assert_that!(ds.all_cotos()?, len(eq(1))); // only the root cotonoma
+
+    /////////////////////////////////////////////////////////////////////////////
+    // When: import
+    /////////////////////////////////////////////////////////////////////////////
+
+    let mut changes = Vec::new();
+    let report = import_markdown(&mut ds, vault.path(), &root_cotonoma.uuid, false, |log| {
+        changes.push(log)
+    })?;
+    assert_that!(&report, expected_report!());
+    // 2 cotonomas + 3 cotos + 3 itos
+    assert_that!(changes, len(eq(8)));
+
+    let (programming, _) = ds.cotonoma_by_name("Programming", &node.uuid)?.unwrap();
+    let (assets, _) = ds.cotonoma_by_name("assets", &node.uuid)?.unwrap();
+
+    let cotos = ds.all_cotos()?;
+    let welcome = cotos
+        .iter()
+        .find(|c| c.summary.as_deref() == Some("Hello"))
+        .unwrap();
+    let rust = cotos
+        .iter()
+        .find(|c| c.summary.as_deref() == Some("Rust"))
+        .unwrap();
+    let photo = cotos
+        .iter()
+        .find(|c| c.summary.as_deref() == Some("photo.png"))
+        .unwrap();
+
+    assert_that!(
+        *welcome,
+        pat!(Coto {
+            posted_in_id: some(eq(&root_cotonoma.uuid)),
+            content: some(eq("See [[Rust|the language]] and [[Missing]].")),
+            media_type: some(eq("image/png")),
+            datetime_start: some(eq(&NaiveDate::from_ymd_opt(2026, 1, 31)
+                .unwrap()
+                .and_hms_opt(0, 0, 0)
+                .unwrap())),
+            datetime_end: some(eq(&NaiveDate::from_ymd_opt(2026, 2, 1)
+                .unwrap()
+                .and_hms_opt(0, 0, 0)
+                .unwrap())),
+            ..
+        })
+    );
+    assert_that!(
+        *rust,
+        pat!(Coto {
+            posted_in_id: some(eq(&programming.uuid)),
+            content: some(eq("Back to [[Welcome]].")),
+            media_type: some(eq("image/png")),
+            ..
+        })
+    );
+    assert_that!(photo.posted_in_id, some(eq(programming.uuid)));
+    assert_that!(assets.name, eq("assets"));
+    assert_that!(assets.uuid.as_uuid(), not(eq(assets.coto_id.as_uuid())));
+
+    assert_that!(
+        ds.outgoing_itos(&[welcome.uuid])?,
+        elements_are![pat!(Ito {
+            target_coto_id: eq(&rust.uuid),
+            ..
+        })]
+    );
+    assert_that!(
+        ds.outgoing_itos(&[rust.uuid])?,
+        unordered_elements_are![
+            pat!(Ito {
+                target_coto_id: eq(&photo.uuid),
+                ..
+            }),
+            pat!(Ito {
+                target_coto_id: eq(&welcome.uuid),
+                ..
+            }),
+        ]
+    );
+
+    /////////////////////////////////////////////////////////////////////////////
+    // When: import again with a new file
+    /////////////////////////////////////////////////////////////////////////////
+
+    write(vault.path(), "Programming/Haskell.md", "Unlike [[Rust]]")?;
+
+    let report = import_markdown(&mut ds, vault.path(), &root_cotonoma.uuid, false, |_| ())?;
+    assert_that!(
+        &report,
+        pat!(MarkdownImportReport {
+            cotonomas: eq(&0),
+            cotos: eq(&1),
+            itos: eq(&1),
+            media: eq(&0),
+            // 2 cotonomas + 3 cotos + 3 itos
+            already_imported: eq(&8),
+            ..
+        })
+    );
+    assert_that!(ds.all_cotos()?, len(eq(7)));
+
+    Ok(())
+}
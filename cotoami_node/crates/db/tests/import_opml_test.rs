@@ -0,0 +1,200 @@
+use anyhow::Result;
+use cotoami_db::{import::opml::*, prelude::*};
+use googletest::prelude::*;
+use indoc::indoc;
+
+pub mod common;
+
+#[test]
+fn import_outline() -> Result<()> {
+    /////////////////////////////////////////////////////////////////////////////
+    // Setup
+    /////////////////////////////////////////////////////////////////////////////
+
+    let (_root_dir, db, node) = common::setup_db("My Node")?;
+    let mut ds = db.new_session()?;
+    let opr = db.globals().local_node_as_operator()?;
+    let (root, root_coto) = ds.local_node_root()?.unwrap();
+
+    // An existing ito from the root
+    let (coto, _) = ds.post_coto(&CotoInput::new("existing"), &root.uuid, &opr)?;
+    let (_ito, _) = ds.create_ito(&ItoInput::new(root_coto.uuid, coto.uuid), &opr)?;
+
+    let opml = indoc! {r#"
+        <?xml version="1.0" encoding="UTF-8"?>
+        <opml version="2.0">
+          <head><title>Plan</title></head>
+          <body>
+            <outline text="Goals" _note="line1&#10;line2" created="Sat, 31 Jan 2026 03:04:05 +0000">
+              <outline text="Learn Rust" _description="first"/>
+              <outline text=""/>
+              <outline text="Write a book"/>
+            </outline>
+            <outline text="Notes"/>
+            <outline text="Ideas" _cotonoma="true"/>
+          </body>
+        </opml>
+    "#};
+
+    /////////////////////////////////////////////////////////////////////////////
+    // When: dry run
+    /////////////////////////////////////////////////////////////////////////////
+
+    let expected_report = OpmlImportReport {
+        cotos: 5,
+        itos: 5,
+        already_imported: 0,
+        rejected: vec![("1.2".into(), "empty outline".into())],
+    };
+    let report = import_opml(&mut ds, opml, &root_coto.uuid, true, |_| {
+        panic!("No changes should be made in a dry run.")
+    })?;
+    assert_that!(report, eq(&expected_report));
+    assert_that!(ds.all_cotos()?, len(eq(2)));
+    assert_that!(ds.cotonoma_by_name("Ideas", &node.uuid)?, none());
+
+    /////////////////////////////////////////////////////////////////////////////
+    // When: import
+    /////////////////////////////////////////////////////////////////////////////
+
+    let mut changes = Vec::new();
+    let report = import_opml(&mut ds, opml, &root_coto.uuid, false, |log| {
+        changes.push(log)
+    })?;
+    assert_that!(report, eq(&expected_report));
+    // 5 cotos (including a cotonoma) + 5 itos
+    assert_that!(changes, len(eq(10)));
+
+    let graph = ds.graph(root_coto.clone(), true)?;
+    let children = |coto_id: &Id<Coto>| -> Vec<(Option<String>, Option<String>)> {
+        graph.itos[coto_id]
+            .iter()
+            .map(|ito| {
+                let coto = &graph.cotos[&ito.target_coto_id];
+                (coto.summary.clone(), coto.content.clone())
+            })
+            .collect()
+    };
+    let mut root_itos = graph.itos[&root_coto.uuid].clone();
+    root_itos.sort_by_key(|ito| ito.order);
+    assert_that!(
+        root_itos.iter().map(|ito| ito.order).collect::<Vec<_>>(),
+        elements_are![eq(&1), eq(&2), eq(&3), eq(&4)]
+    );
+    let (ideas, ideas_coto) = ds.cotonoma_by_name("Ideas", &node.uuid)?.unwrap();
+    assert_that!(root_itos[3].target_coto_id, eq(ideas.coto_id));
+    assert_that!(
+        ideas_coto,
+        pat!(Coto {
+            posted_in_id: some(eq(&root.uuid)),
+            summary: some(eq("Ideas")),
+            is_cotonoma: eq(&true),
+            ..
+        })
+    );
+    let goals = &graph.cotos[&root_itos[1].target_coto_id];
+    assert_that!(
+        *goals,
+        pat!(Coto {
+            node_id: eq(&node.uuid),
+            posted_in_id: some(eq(&root.uuid)),
+            summary: some(eq("Goals")),
+            content: some(eq("line1\nline2")),
+            created_at: eq(&chrono::NaiveDate::from_ymd_opt(2026, 1, 31)
+                .unwrap()
+                .and_hms_opt(3, 4, 5)
+                .unwrap()),
+            ..
+        })
+    );
+    assert_that!(
+        children(&goals.uuid),
+        unordered_elements_are![
+            (none(), some(eq("Learn Rust"))),
+            (none(), some(eq("Write a book"))),
+        ]
+    );
+    assert_that!(
+        graph.itos[&goals.uuid]
+            .iter()
+            .find(|ito| ito.description.is_some())
+            .and_then(|ito| ito.description.as_deref()),
+        some(eq("first"))
+    );
+
+    /////////////////////////////////////////////////////////////////////////////
+    // When: import again
+    /////////////////////////////////////////////////////////////////////////////
+
+    let report = import_opml(&mut ds, opml, &root_coto.uuid, false, |_| ())?;
+    assert_that!(
+        &report,
+        pat!(OpmlImportReport {
+            cotos: eq(&0),
+            itos: eq(&0),
+            // 5 cotos + 5 itos
+            already_imported: eq(&10),
+            ..
+        })
+    );
+
+    /////////////////////////////////////////////////////////////////////////////
+    // When: round-trip an exported outline
+    /////////////////////////////////////////////////////////////////////////////
+
+    // Goals -> a cotonoma and a coto with only a summary
+    let ((rust, _), _) = ds.post_cotonoma(&CotonomaInput::new("Rust"), &root, &opr)?;
+    let _ = ds.create_ito(&ItoInput::new(goals.uuid, rust.coto_id), &opr)?;
+    let (summary_only, _) = ds.import_coto(&Coto {
+        uuid: Id::generate(),
+        content: None,
+        summary: Some("Only summary".into()),
+        ..goals.clone()
+    })?;
+    let _ = ds.create_ito(&ItoInput::new(goals.uuid, summary_only.uuid), &opr)?;
+
+    let exported = ds.graph(goals.clone(), true)?.into_opml();
+    let (target, _) = ds.post_coto(&CotoInput::new("copy"), &root.uuid, &opr)?;
+    let report = import_opml(&mut ds, &exported, &target.uuid, false, |_| ())?;
+    assert_that!(
+        &report,
+        pat!(OpmlImportReport {
+            cotos: eq(&3),
+            itos: eq(&4),
+            // the cotonoma "Rust" that already exists
+            already_imported: eq(&1),
+            rejected: is_empty(),
+            ..
+        })
+    );
+    let copy = ds.graph(target, true)?;
+    let mut copied: Vec<&Ito> = copy.itos.values().flatten().collect();
+    copied.sort_by_key(|ito| ito.order);
+    assert_that!(
+        copied
+            .iter()
+            .map(|ito| (
+                ito.description.clone(),
+                copy.cotos[&ito.target_coto_id].content.clone()
+            ))
+            .collect::<Vec<_>>(),
+        elements_are![
+            (some(eq("first")), some(eq("Learn Rust"))),
+            (none(), some(eq("Write a book"))),
+            (none(), none()),
+            (none(), none()),
+        ]
+    );
+    // The cotonoma has been connected as it is
+    assert_that!(copied[2].target_coto_id, eq(rust.coto_id));
+    assert_that!(
+        copy.cotos[&copied[3].target_coto_id],
+        pat!(Coto {
+            summary: some(eq("Only summary")),
+            content: none(),
+            ..
+        })
+    );
+
+    Ok(())
+}
@@ -97,3 +97,312 @@ fn parents() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn set_parent_subscription() -> Result<()> {
+    /////////////////////////////////////////////////////////////////////////////
+    // Setup: a child that has received changes from a parent
+    /////////////////////////////////////////////////////////////////////////////
+
+    let (_parent_dir, parent_db, parent_node) = common::setup_db("Parent")?;
+    let (_child_dir, child_db, _) = common::setup_db("Child")?;
+
+    let mut parent_ds = parent_db.new_session()?;
+    let parent_opr = parent_db.globals().local_node_as_operator()?;
+    let (root, _) = parent_ds.try_get_local_node_root()?;
+    let ((cotonoma_a, _), _) =
+        parent_ds.post_cotonoma(&CotonomaInput::new("a"), &root, &parent_opr)?;
+    let ((cotonoma_b, _), _) =
+        parent_ds.post_cotonoma(&CotonomaInput::new("b"), &root, &parent_opr)?;
+
+    let child_ds = child_db.new_session()?;
+    let child_opr = child_db.globals().local_node_as_operator()?;
+    common::connect_parent_child(
+        &parent_db,
+        &child_db,
+        "http://parent",
+        "parent-child-password",
+        ChildNodeInput::default(),
+    )?;
+
+    /////////////////////////////////////////////////////////////////////////////
+    // When: narrow the subscription
+    /////////////////////////////////////////////////////////////////////////////
+
+    let subscription = ChangeSubscription::new(vec![cotonoma_a.uuid, cotonoma_b.uuid], true);
+    let parent =
+        child_ds.set_parent_subscription(&parent_node.uuid, Some(&subscription), &child_opr)?;
+    assert_that!(parent.subscription(), some(eq(&subscription)));
+
+    let subscription = ChangeSubscription::new(vec![cotonoma_a.uuid], false);
+    let parent =
+        child_ds.set_parent_subscription(&parent_node.uuid, Some(&subscription), &child_opr)?;
+    assert_that!(parent.subscription(), some(eq(&subscription)));
+
+    /////////////////////////////////////////////////////////////////////////////
+    // When: widen the subscription
+    /////////////////////////////////////////////////////////////////////////////
+
+    for widened in [
+        Some(ChangeSubscription::new(
+            vec![cotonoma_a.uuid, cotonoma_b.uuid],
+            false,
+        )),
+        Some(ChangeSubscription::new(vec![cotonoma_a.uuid], true)),
+        None,
+    ] {
+        // Then: it's rejected since the changes received as placeholders won't be sent again
+        assert_that!(
+            child_ds
+                .set_parent_subscription(&parent_node.uuid, widened.as_ref(), &child_opr)
+                .unwrap_err()
+                .downcast_ref::<DatabaseError>(),
+            some(pat!(DatabaseError::SubscriptionWidened {
+                parent_node_id: eq(&parent_node.uuid)
+            }))
+        );
+    }
+    assert_that!(
+        child_db
+            .globals()
+            .parent_node(&parent_node.uuid)
+            .and_then(|parent| parent.subscription()),
+        some(eq(&subscription))
+    );
+
+    Ok(())
+}
+
+#[test]
+fn fork_and_reattach() -> Result<()> {
+    /////////////////////////////////////////////////////////////////////////////
+    // Setup: a child that has replicated a coto of the parent
+    /////////////////////////////////////////////////////////////////////////////
+
+    let (_parent_dir, parent_db, parent_node) = common::setup_db("Parent")?;
+    let (_child_dir, child_db, _) = common::setup_db("Child")?;
+
+    let mut parent_ds = parent_db.new_session()?;
+    let parent_opr = parent_db.globals().local_node_as_operator()?;
+    let (parent_root, _) = parent_ds.try_get_local_node_root()?;
+    let (coto1, _) =
+        parent_ds.post_coto(&CotoInput::new("coto1"), &parent_root.uuid, &parent_opr)?;
+    let (coto2, _) =
+        parent_ds.post_coto(&CotoInput::new("coto2"), &parent_root.uuid, &parent_opr)?;
+
+    common::connect_parent_child(
+        &parent_db,
+        &child_db,
+        "http://parent",
+        "parent-child-password",
+        ChildNodeInput::default(),
+    )?;
+
+    let mut child_ds = child_db.new_session()?;
+    let child_opr = child_db.globals().local_node_as_operator()?;
+    let (_, child_root_coto) = child_ds.try_get_local_node_root()?;
+
+    /////////////////////////////////////////////////////////////////////////////
+    // When: the child forks from the parent
+    /////////////////////////////////////////////////////////////////////////////
+
+    let forked_at = child_ds.last_change_number()?;
+    let parent = child_ds.fork_from_parent(&parent_node.uuid, &child_opr)?;
+
+    // Then: the fork point has been recorded and the connection has been disabled
+    assert_that!(
+        parent,
+        pat!(ParentNode {
+            forked: eq(&true),
+            forked_at_serial_number: eq(&forked_at),
+            ..
+        })
+    );
+    assert_that!(
+        child_ds.try_get_server_node(&parent_node.uuid, &child_opr)?,
+        field!(ServerNode.disabled, eq(&true))
+    );
+    assert_that!(
+        child_ds
+            .fork_from_parent(&parent_node.uuid, &child_opr)
+            .unwrap_err()
+            .downcast_ref::<DatabaseError>(),
+        some(pat!(DatabaseError::AlreadyForkedFromParent { .. }))
+    );
+
+    /////////////////////////////////////////////////////////////////////////////
+    // When: both nodes make changes during the fork
+    /////////////////////////////////////////////////////////////////////////////
+
+    let (_, ito_log) =
+        child_ds.create_ito(&ItoInput::new(child_root_coto.uuid, coto1.uuid), &child_opr)?;
+    let (child_root, _) = child_ds.try_get_local_node_root()?;
+    let _ = child_ds.post_coto(&CotoInput::new("local"), &child_root.uuid, &child_opr)?;
+
+    let parent_changes_received = child_ds
+        .parent_node(&parent_node.uuid, &child_opr)?
+        .unwrap()
+        .changes_received;
+    let delete_log = parent_ds.delete_coto(&coto1.uuid, &parent_opr)?;
+    let _ = parent_ds.edit_coto(
+        &coto2.uuid,
+        CotoContentDiff::default().content("coto2 edited"),
+        &parent_opr,
+    )?;
+    let (parent_changes, _) = parent_ds.chunk_of_changes(parent_changes_received + 1, 100, None)?;
+
+    // Then: the local change involving the parent's coto has diverged
+    let local_changes = child_ds.diverged_changes(&parent_node.uuid, &child_opr)?;
+    assert_that!(local_changes, elements_are![eq(&ito_log)]);
+
+    // And: the deletion of the coto conflicts with it
+    let (summaries, conflicts) = child_ds.fork_conflicts(
+        &parent_node.uuid,
+        &parent_changes,
+        &local_changes,
+        &child_opr,
+    )?;
+    assert_that!(
+        summaries,
+        contains(pat!(ChangeSummary {
+            serial_number: eq(&delete_log.serial_number),
+            kind: eq("DeleteCoto"),
+            cotos: elements_are![eq(&coto1.uuid)],
+            ..
+        }))
+    );
+    assert_that!(
+        conflicts,
+        elements_are![pat!(ForkConflict {
+            parent_change: field!(ChangeSummary.serial_number, eq(&delete_log.serial_number)),
+            local_changes: elements_are![eq(&ito_log.serial_number)],
+        })]
+    );
+
+    /////////////////////////////////////////////////////////////////////////////
+    // When: the child re-attaches to the parent
+    /////////////////////////////////////////////////////////////////////////////
+
+    let parent = child_ds.reattach_to_parent(&parent_node.uuid, &child_opr)?;
+    assert_that!(
+        parent,
+        pat!(ParentNode {
+            forked: eq(&false),
+            forked_at_serial_number: none(),
+            ..
+        })
+    );
+    assert_that!(
+        child_ds.try_get_server_node(&parent_node.uuid, &child_opr)?,
+        field!(ServerNode.disabled, eq(&false))
+    );
+
+    // Then: the parent changes during the fork can be imported
+    for change in parent_changes.iter() {
+        child_ds.import_change(change, &parent_node.uuid)?;
+    }
+    assert_that!(child_ds.coto(&coto1.uuid)?, none());
+    assert_that!(
+        child_ds.coto(&coto2.uuid)?,
+        some(field!(Coto.content, some(eq("coto2 edited"))))
+    );
+
+    Ok(())
+}
+
+#[test]
+fn outbox() -> Result<()> {
+    /////////////////////////////////////////////////////////////////////////////
+    // Setup
+    /////////////////////////////////////////////////////////////////////////////
+
+    let (_parent_dir, parent_db, parent_node) = common::setup_db("Parent")?;
+    let (_child_dir, child_db, child_node) = common::setup_db("Child")?;
+
+    common::connect_parent_child(
+        &parent_db,
+        &child_db,
+        "http://parent",
+        "parent-child-password",
+        ChildNodeInput::default(),
+    )?;
+
+    let mut parent_ds = parent_db.new_session()?;
+    let mut child_ds = child_db.new_session()?;
+
+    /////////////////////////////////////////////////////////////////////////////
+    // When: the child queues requests to the parent
+    /////////////////////////////////////////////////////////////////////////////
+
+    let entry1 = child_ds.enqueue_to_parent(&parent_node.uuid, b"command1")?;
+    let entry2 = child_ds.enqueue_to_parent(&parent_node.uuid, b"command2")?;
+
+    // Then: they will be sent in the queued order
+    assert_that!(
+        child_ds.outbox(&parent_node.uuid)?,
+        elements_are![eq(&entry1), eq(&entry2)]
+    );
+    assert_that!(
+        child_ds.next_in_outbox(&parent_node.uuid)?,
+        some(eq(&entry1))
+    );
+    assert_that!(entry1.command.as_ref(), eq(b"command1"));
+    assert_that!(entry1.idempotency_key, not(eq(entry2.idempotency_key)));
+
+    // And: requests can't be queued to a node that is not a parent
+    assert_that!(
+        child_ds
+            .enqueue_to_parent(&child_node.uuid, b"command")
+            .unwrap_err()
+            .downcast_ref::<DatabaseError>(),
+        some(pat!(DatabaseError::EntityNotFound { .. }))
+    );
+
+    /////////////////////////////////////////////////////////////////////////////
+    // When: the parent handles the first request
+    /////////////////////////////////////////////////////////////////////////////
+
+    assert_that!(
+        parent_ds.is_request_handled(&entry1.idempotency_key)?,
+        eq(false)
+    );
+    assert_that!(
+        parent_ds.record_handled_request(&entry1.idempotency_key, &child_node.uuid)?,
+        eq(true)
+    );
+
+    // Then: the same request will be recognized as handled
+    assert_that!(
+        parent_ds.is_request_handled(&entry1.idempotency_key)?,
+        eq(true)
+    );
+    assert_that!(
+        parent_ds.is_request_handled(&entry2.idempotency_key)?,
+        eq(false)
+    );
+    assert_that!(
+        parent_ds.record_handled_request(&entry1.idempotency_key, &child_node.uuid)?,
+        eq(false)
+    );
+
+    /////////////////////////////////////////////////////////////////////////////
+    // When: the child dequeues the sent request
+    /////////////////////////////////////////////////////////////////////////////
+
+    assert_that!(
+        child_ds.dequeue_from_outbox(entry1.serial_number)?,
+        eq(true)
+    );
+    assert_that!(
+        child_ds.dequeue_from_outbox(entry1.serial_number)?,
+        eq(false)
+    );
+
+    // Then: the next request will be sent
+    assert_that!(
+        child_ds.next_in_outbox(&parent_node.uuid)?,
+        some(eq(&entry2))
+    );
+
+    Ok(())
+}
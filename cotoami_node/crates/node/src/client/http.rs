@@ -18,9 +18,13 @@ use reqwest::{
 };
 use uuid::Uuid;
 
-use crate::service::{
-    error::{InputErrors, RequestError},
-    NodeServiceFuture, *,
+use crate::{
+    remote::codec::compression,
+    service::{
+        error::{InputErrors, RequestError},
+        models::{Compression, GraphTraversal},
+        NodeServiceFuture, *,
+    },
 };
 
 /// [HttpClient] provides the featuers of the [RemoteNodeService] trait by
@@ -62,6 +66,15 @@ impl HttpClient {
 
     pub(crate) fn read_headers(&self) -> RwLockReadGuard<'_, HeaderMap> { self.headers.read() }
 
+    /// Returns the compression agreed on with the server in the session.
+    pub(crate) fn compression(&self) -> Option<Compression> {
+        self.headers
+            .read()
+            .get(crate::web::COMPRESSION_HEADER_NAME)
+            .and_then(|value| value.to_str().ok())
+            .and_then(Compression::from_name)
+    }
+
     fn url(&self, path: &str) -> Url {
         self.url_prefix
             .join(path)
@@ -105,6 +118,7 @@ impl HttpClient {
         let request_id = *request.id();
         let accept = request.accept();
         let as_owner = request.as_owner();
+        let idempotency_key = request.idempotency_key().copied();
 
         // Translate the request's body into an HTTP request (RequestBuilder).
         let http_req = match request.command() {
@@ -119,10 +133,28 @@ impl HttpClient {
             Command::EnableAnonymousRead { enable } => self
                 .put(&format!("{API_PATH_LOCAL}/enable-anonymous"))
                 .json(&enable),
+            Command::ImportCotoamiJson { json, options } => self
+                .post(&format!("{API_PATH_LOCAL}/import/cotoami"))
+                .json(&CotoamiJsonImport::new(json, options)),
+            Command::CreateBackup => self.post(&format!("{API_PATH_LOCAL}/backups")),
             Command::InitialDataset => self.get(API_PATH_DATA),
-            Command::ChunkOfChanges { from } => self
-                .get(API_PATH_CHANGES)
-                .query(&[("from", from.to_string())]),
+            Command::ChunkOfChanges { from, byte_budget } => {
+                let request = self
+                    .get(API_PATH_CHANGES)
+                    .query(&[("from", from.to_string())]);
+                if let Some(byte_budget) = byte_budget {
+                    request.query(&[("byte_budget", byte_budget)])
+                } else {
+                    request
+                }
+            }
+            Command::ChangelogSnapshot => self.get(&format!("{API_PATH_CHANGES}/snapshot")),
+            Command::FailedImports { parent, pagination } => self
+                .get(&format!("{API_PATH_CHANGES}/failed/{parent}"))
+                .query(&pagination),
+            Command::RetryFailedImports { serial_numbers } => self
+                .post(&format!("{API_PATH_CHANGES}/retry"))
+                .json(&serial_numbers),
             Command::NodeDetails { id } => self.get(&format!("{API_PATH_NODES}/{id}/details")),
             Command::CreateClientNodeSession(input) => {
                 self.put("/api/session/client-node").json(&input)
@@ -134,6 +166,16 @@ impl HttpClient {
             Command::EditServer { id, values } => {
                 self.put(&format!("{API_PATH_SERVERS}/{id}")).form(&values)
             }
+            Command::SetParentSubscription { id, subscription } => self
+                .put(&format!("{API_PATH_SERVERS}/{id}/subscription"))
+                .json(&subscription),
+            Command::ForkFromParent { id } => self.post(&format!("{API_PATH_PARENTS}/{id}/fork")),
+            Command::ForkDivergence { id } => {
+                self.get(&format!("{API_PATH_PARENTS}/{id}/divergence"))
+            }
+            Command::ReconcileWithParent { id } => {
+                self.post(&format!("{API_PATH_PARENTS}/{id}/reconcile"))
+            }
             Command::RecentClients { pagination } => self.get(API_PATH_CLIENTS).query(&pagination),
             Command::ClientNode { id } => self.get(&format!("{API_PATH_CLIENTS}/{id}")),
             Command::AddClient(input) => self.post(API_PATH_CLIENTS).json(&input),
@@ -271,9 +313,75 @@ impl HttpClient {
                 }
             }
             Command::CotoDetails { id } => self.get(&format!("{API_PATH_COTOS}/{id}/details")),
-            Command::GraphFromCoto { coto } => self.get(&format!("{API_PATH_COTOS}/{coto}/graph")),
-            Command::GraphFromCotonoma { cotonoma } => {
-                self.get(&format!("{API_PATH_COTONOMAS}/{cotonoma}/graph"))
+            Command::CotoMediaByHash { coto, hash } => {
+                self.get(&format!("{API_PATH_COTOS}/{coto}/media/{hash}"))
+            }
+            Command::GraphFromCoto {
+                coto,
+                options,
+                frontier,
+            } => {
+                let path = format!("{API_PATH_COTOS}/{coto}/graph");
+                if options.is_none() && frontier.is_none() {
+                    self.get(&path)
+                } else {
+                    self.post(&path)
+                        .json(&GraphTraversal::new(options, frontier))
+                }
+            }
+            Command::GraphFromCotonoma {
+                cotonoma,
+                options,
+                frontier,
+            } => {
+                let path = format!("{API_PATH_COTONOMAS}/{cotonoma}/graph");
+                if options.is_none() && frontier.is_none() {
+                    self.get(&path)
+                } else {
+                    self.post(&path)
+                        .json(&GraphTraversal::new(options, frontier))
+                }
+            }
+            Command::PathBetween {
+                from,
+                to,
+                max_depth,
+                directed,
+            } => {
+                let mut query = HashMap::new();
+                if let Some(max_depth) = max_depth {
+                    query.insert("max_depth", max_depth.to_string());
+                }
+                query.insert("directed", directed.to_string());
+                self.get(&format!("{API_PATH_COTOS}/{from}/paths/{to}"))
+                    .query(&query)
+            }
+            Command::GraphHealth { scope } => match scope {
+                Scope::All => self.get(&format!("{API_PATH_COTOS}/graph-health")),
+                Scope::Node(node_id) => {
+                    self.get(&format!("{API_PATH_NODES}/{node_id}/cotos/graph-health"))
+                }
+                Scope::Cotonoma((cotonoma_id, cotonoma_scope)) => {
+                    let request = self.get(&format!(
+                        "{API_PATH_COTONOMAS}/{cotonoma_id}/cotos/graph-health"
+                    ));
+                    match cotonoma_scope {
+                        CotonomaScope::Recursive => request.query(&[("recursive", true)]),
+                        CotonomaScope::Depth(depth) => request.query(&[("depth", depth)]),
+                        CotonomaScope::Local => request,
+                    }
+                }
+            },
+            Command::QueryGraph { query } => self
+                .get(&format!("{API_PATH_COTOS}/graph-query"))
+                .query(&[("query", query)]),
+            Command::Descendants { coto, max_depth } => {
+                let request = self.get(&format!("{API_PATH_COTOS}/{coto}/descendants"));
+                if let Some(max_depth) = max_depth {
+                    request.query(&[("max_depth", max_depth)])
+                } else {
+                    request
+                }
             }
             Command::PostCoto { input, post_to } => self
                 .post(&format!("{API_PATH_COTONOMAS}/{post_to}/cotos"))
@@ -359,6 +467,13 @@ impl HttpClient {
             http_req
         };
 
+        // Prevent the request from being handled twice.
+        let http_req = if let Some(key) = idempotency_key {
+            http_req.header(crate::web::IDEMPOTENCY_KEY_HEADER_NAME, key.to_string())
+        } else {
+            http_req
+        };
+
         match http_req.send().await {
             Ok(response) => Self::convert_response(request_id, response).await,
             Err(e) => {
@@ -374,11 +489,20 @@ impl HttpClient {
     async fn convert_response(id: Uuid, http_response: reqwest::Response) -> Result<Response> {
         let body_format = detect_response_body_format(&http_response);
         if http_response.status().is_success() {
-            return Ok(Response::new(
-                id,
-                body_format,
-                Ok(Bytes::from(http_response.bytes().await?)),
-            ));
+            let compression = http_response
+                .headers()
+                .get(header::CONTENT_ENCODING)
+                .and_then(|value| value.to_str().ok())
+                .and_then(Compression::from_name);
+            let mut bytes = http_response.bytes().await?;
+            if let Some(compression) = compression {
+                bytes = compression::run_blocking(move || {
+                    compression::decompress(&bytes, compression, None)
+                })
+                .await?
+                .into();
+            }
+            return Ok(Response::new(id, body_format, Ok(Bytes::from(bytes))));
         }
 
         let error = match http_response.status() {
@@ -404,6 +528,7 @@ const API_PATH_CHANGES: &str = concatcp!(API_PATH_DATA, "/changes");
 const API_PATH_NODES: &str = concatcp!(API_PATH_DATA, "/nodes");
 const API_PATH_LOCAL: &str = concatcp!(API_PATH_NODES, "/local");
 const API_PATH_SERVERS: &str = concatcp!(API_PATH_NODES, "/servers");
+const API_PATH_PARENTS: &str = concatcp!(API_PATH_NODES, "/parents");
 const API_PATH_CLIENTS: &str = concatcp!(API_PATH_NODES, "/clients");
 const API_PATH_CHILDREN: &str = concatcp!(API_PATH_NODES, "/children");
 const API_PATH_COTONOMAS: &str = concatcp!(API_PATH_DATA, "/cotonomas");
@@ -448,4 +573,12 @@ impl RemoteNodeService for HttpClient {
         self.set_header(crate::web::SESSION_HEADER_NAME, token);
         Ok(())
     }
+
+    fn set_compression(&mut self, compression: Compression) -> Result<()> {
+        self.set_header(
+            crate::web::COMPRESSION_HEADER_NAME,
+            HeaderValue::from_static(compression.name()),
+        );
+        Ok(())
+    }
 }
@@ -119,6 +119,11 @@ impl WebSocketClient {
         }
 
         let (sink, stream) = ws_stream.split();
+        let max_message_size = self
+            .state
+            .node_state
+            .read_config()
+            .max_message_size_as_client;
         if let Some(opr) = self.state.server_as_operator.as_ref() {
             tokio::spawn(communicate_with_operator(
                 self.state.node_state.clone(),
@@ -127,6 +132,8 @@ impl WebSocketClient {
                 stream,
                 on_abort,
                 Some(Self::PING_INTERVAL),
+                self.http_client.compression(),
+                max_message_size,
                 self.state.abortables.clone(),
             ));
         } else {
@@ -138,6 +145,8 @@ impl WebSocketClient {
                 stream,
                 on_abort,
                 Some(Self::PING_INTERVAL),
+                self.http_client.compression(),
+                max_message_size,
                 self.state.abortables.clone(),
             ));
         }
@@ -54,16 +54,43 @@ pub struct NodeConfig {
 
     /// `COTOAMI_CHANGES_CHUNK_SIZE`
     ///
-    /// The number of changes to be fetched at once when the node is asked to.
+    /// The maximum number of changes to be fetched at once when the node is asked to.
     /// The larger this value is, the less websocket messages will be sent when synchronizing
-    /// changes, but the more memory will be used to store the changes and you have to configure
-    /// the `max_message_size_as_client` and `max_message_size_as_server` accordingly.
+    /// changes, but the more memory will be used to store the changes. The size of a chunk
+    /// is also limited by `changes_chunk_bytes`.
     #[serde(default = "NodeConfig::default_changes_chunk_size")]
     pub changes_chunk_size: i64,
 
+    /// `COTOAMI_CHANGES_CHUNK_BYTES`
+    ///
+    /// The approximate maximum total size in bytes of the changes in a chunk. As a parent,
+    /// this node cuts the chunks to be sent within this size, and as a child, it requests
+    /// its parents to do the same, so that a chunk fits in the `max_message_size_as_client`
+    /// (or `max_message_size_as_server` if the parent connects to this node as a client).
+    /// The size is measured by the changes as stored in the changelog, which doesn't
+    /// include the other fields of the entries or the encoding of a message, so it should
+    /// be set with some margin below the message size limit. A chunk contains at least one
+    /// change even if it exceeds the size by itself. `None` means that chunks are limited
+    /// only by `changes_chunk_size`.
+    #[serde(default = "NodeConfig::default_changes_chunk_bytes")]
+    pub changes_chunk_bytes: Option<usize>,
+
+    /// `COTOAMI_SNAPSHOT_THRESHOLD`
+    ///
+    /// When this node has not received any changes from a parent yet and the parent has
+    /// more changes than this number, the node will install a snapshot of the parent instead
+    /// of replaying all the changes. `None` means that the changes are always replayed.
+    /// A snapshot is sent as a single message without media content, which will be
+    /// fetched afterwards, so it has to fit in the `max_message_size_as_client`
+    /// (or `max_message_size_as_server` if the parent connects to this node).
+    #[serde(default = "NodeConfig::default_snapshot_threshold")]
+    pub snapshot_threshold: Option<i64>,
+
     /// `COTOAMI_MAX_MESSAGE_SIZE_AS_CLIENT`
     ///
     /// The maximum size of an incoming message when the node acts as a client.
+    /// If compression has been agreed on with the server, this limit applies to
+    /// the compressed size ([crate::remote::codec::compression]).
     /// cf. https://docs.rs/tungstenite/latest/tungstenite/protocol/struct.WebSocketConfig.html#structfield.max_message_size
     #[serde(default = "NodeConfig::default_max_message_size_as_client")]
     pub max_message_size_as_client: Option<usize>,
@@ -77,12 +104,55 @@ pub struct NodeConfig {
 
     /// `COTOAMI_PLUGINS_DIR`
     pub plugins_dir: Option<String>,
+
+    /// `COTOAMI_BACKUP_DIR`
+    ///
+    /// The directory to store database backups, which defaults to `backups` in the
+    /// database directory.
+    pub backup_dir: Option<String>,
+
+    /// `COTOAMI_BACKUP_INTERVAL_HOURS`
+    ///
+    /// If configured, the database will be backed up when this many hours have passed
+    /// since the last backup. Scheduled backups are disabled if `None`.
+    #[validate(range(min = 1))]
+    pub backup_interval_hours: Option<u64>,
+
+    /// `COTOAMI_BACKUP_RETENTION`
+    ///
+    /// The number of backups to keep. Older backups will be deleted after a new backup
+    /// has been created. Zero means keeping all the backups.
+    #[serde(default = "NodeConfig::default_backup_retention")]
+    pub backup_retention: usize,
+
+    /// `COTOAMI_COMPACTION_INTERVAL_HOURS`
+    ///
+    /// If configured, the changelog will be compacted every this many hours (and once at
+    /// startup). Scheduled compaction is disabled if `None`.
+    #[validate(range(min = 1))]
+    pub compaction_interval_hours: Option<u64>,
+
+    /// `COTOAMI_COMPACTION_MARGIN`
+    ///
+    /// The number of the latest changes that will be left intact by compaction even if
+    /// all the child nodes have acknowledged them.
+    #[serde(default = "NodeConfig::default_compaction_margin")]
+    #[validate(range(min = 0))]
+    pub compaction_margin: i64,
+
+    /// `COTOAMI_GRAPH_QUERY_MAX_COTOS`
+    ///
+    /// The maximum number of cotos (excluding the root) in a graph returned by a graph
+    /// query. The oldest ones will be returned if more cotos match the query.
+    #[serde(default = "NodeConfig::default_graph_query_max_cotos")]
+    pub graph_query_max_cotos: usize,
 }
 
 impl NodeConfig {
     const ENV_PREFIX: &'static str = "COTOAMI_";
     const DEFAULT_DB_DIR_NAME: &'static str = "cotoami";
     const DEFAULT_PLUGINS_DIR_NAME: &'static str = "plugins";
+    const DEFAULT_BACKUP_DIR_NAME: &'static str = "backups";
 
     pub fn load_from_env() -> Result<Self, envy::Error> {
         dotenv().ok();
@@ -98,9 +168,17 @@ impl NodeConfig {
             owner_remote_node_password: None,
             session_minutes: Self::default_session_minutes(),
             changes_chunk_size: Self::default_changes_chunk_size(),
+            changes_chunk_bytes: Self::default_changes_chunk_bytes(),
+            snapshot_threshold: Self::default_snapshot_threshold(),
             max_message_size_as_client: Self::default_max_message_size_as_client(),
             max_message_size_as_server: Self::default_max_message_size_as_server(),
             plugins_dir: None,
+            backup_dir: None,
+            backup_interval_hours: None,
+            backup_retention: Self::default_backup_retention(),
+            compaction_interval_hours: None,
+            compaction_margin: Self::default_compaction_margin(),
+            graph_query_max_cotos: Self::default_graph_query_max_cotos(),
         }
     }
 
@@ -108,12 +186,19 @@ impl NodeConfig {
     // https://github.com/serde-rs/serde/issues/368
     fn default_session_minutes() -> u64 { 60 * 24 }
     fn default_changes_chunk_size() -> i64 { 30 }
+    fn default_changes_chunk_bytes() -> Option<usize> {
+        Some(16 << 20) // 16 MiB
+    }
+    fn default_snapshot_threshold() -> Option<i64> { Some(1000) }
     fn default_max_message_size_as_client() -> Option<usize> {
         Some(1 << 30) // 1 GiB
     }
     fn default_max_message_size_as_server() -> Option<usize> {
         Some(64 << 20) // 64 MiB
     }
+    fn default_backup_retention() -> usize { 7 }
+    fn default_compaction_margin() -> i64 { 1000 }
+    fn default_graph_query_max_cotos() -> usize { 1000 }
 
     pub fn db_dir(&self) -> PathBuf {
         self.db_dir.as_ref().map(PathBuf::from).unwrap_or_else(|| {
@@ -137,6 +222,17 @@ impl NodeConfig {
             })
     }
 
+    pub fn backup_dir(&self) -> PathBuf {
+        self.backup_dir
+            .as_ref()
+            .map(PathBuf::from)
+            .unwrap_or_else(|| {
+                let mut path = self.db_dir();
+                path.push(Self::DEFAULT_BACKUP_DIR_NAME);
+                path
+            })
+    }
+
     pub fn try_get_owner_password(&self) -> Result<&str> {
         self.owner_password.as_deref().ok_or(anyhow!(
             "Owner password is required to invoke this operation."
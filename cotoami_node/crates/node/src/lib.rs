@@ -10,6 +10,7 @@ mod config;
 mod pubsub;
 mod remote;
 mod service;
+mod site;
 mod state;
 mod web;
 
@@ -19,6 +20,7 @@ pub mod prelude {
     pub use crate::{
         config::*,
         service::{command::*, error::*, models::*, service_ext::*, *},
+        site::*,
         state::*,
     };
 }
@@ -37,12 +37,13 @@
 //! changes and additive fields can evolve more safely than with the default
 //! compact positional encoding.
 
-use cotoami_db::{prelude::*, rmp_serde};
+use cotoami_db::{import::cotoami::CotoamiImportOptions, prelude::*, rmp_serde};
 use serde::{ser::SerializeStruct, Deserialize, Deserializer, Serialize, Serializer};
 use uuid::Uuid;
 
 use crate::service::{models::*, Command, Request, SerializeFormat};
 
+pub(crate) mod compression;
 pub(crate) mod legacy;
 
 /// Serialize a value into MessagePack while preserving field names for structs.
@@ -74,6 +75,20 @@ pub fn to_msgpack_vec_named<T: Serialize>(value: &T) -> Result<Vec<u8>, rmp_serd
     Ok(buf)
 }
 
+/// Serialize a [Command] to be stored locally (ex. in an outbox) through
+/// `CommandSchema`, so that it can be restored after the node has been upgraded.
+///
+/// JSON is used here since binary fields (such as [Bytes]) in an internally tagged
+/// enum like `CommandSchema` can't be restored from MessagePack.
+pub(crate) fn command_to_json_vec(command: Command) -> Result<Vec<u8>, serde_json::Error> {
+    serde_json::to_vec(&CommandSchema::from(command))
+}
+
+/// Restore a [Command] serialized by [command_to_json_vec].
+pub(crate) fn command_from_json_slice(bytes: &[u8]) -> Result<Command, serde_json::Error> {
+    serde_json::from_slice::<CommandSchema>(bytes).map(Into::into)
+}
+
 impl Serialize for Request {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -82,10 +97,16 @@ impl Serialize for Request {
         // `Request` remains the internal/public envelope type, but its
         // `command` field is serialized through `CommandSchema` so the wire
         // protocol is insulated from internal `Command` enum refactors.
-        let mut state = serializer.serialize_struct("Request", 4)?;
+        let len = if self.idempotency_key.is_some() { 5 } else { 4 };
+        let mut state = serializer.serialize_struct("Request", len)?;
         state.serialize_field("id", &self.id)?;
         state.serialize_field("accept", &self.accept)?;
         state.serialize_field("as_owner", &self.as_owner)?;
+        if let Some(key) = &self.idempotency_key {
+            state.serialize_field("idempotency_key", key)?;
+        } else {
+            state.skip_field("idempotency_key")?;
+        }
         state.serialize_field("command", &CommandSchema::from(self.command.clone()))?;
         state.end()
     }
@@ -105,6 +126,8 @@ impl<'de> Deserialize<'de> for Request {
             accept: SerializeFormat,
             #[serde(default)]
             as_owner: bool,
+            #[serde(default)]
+            idempotency_key: Option<Id<OutboxEntry>>,
             command: CommandSchema,
         }
 
@@ -114,6 +137,7 @@ impl<'de> Deserialize<'de> for Request {
             from: None,
             accept: envelope.accept,
             as_owner: envelope.as_owner,
+            idempotency_key: envelope.idempotency_key,
             command: envelope.command.into(),
         })
     }
@@ -140,9 +164,24 @@ pub(crate) enum CommandSchema {
     EnableAnonymousRead {
         enable: bool,
     },
+    ImportCotoamiJson {
+        json: String,
+        options: CotoamiImportOptions,
+    },
+    CreateBackup,
     InitialDataset,
     ChunkOfChanges {
         from: i64,
+        #[serde(default)]
+        byte_budget: Option<usize>,
+    },
+    ChangelogSnapshot,
+    FailedImports {
+        parent: Id<Node>,
+        pagination: Pagination,
+    },
+    RetryFailedImports {
+        serial_numbers: Vec<i64>,
     },
     NodeDetails {
         id: Id<Node>,
@@ -160,6 +199,19 @@ pub(crate) enum CommandSchema {
         id: Id<Node>,
         values: EditServer,
     },
+    SetParentSubscription {
+        id: Id<Node>,
+        subscription: Option<ChangeSubscription>,
+    },
+    ForkFromParent {
+        id: Id<Node>,
+    },
+    ForkDivergence {
+        id: Id<Node>,
+    },
+    ReconcileWithParent {
+        id: Id<Node>,
+    },
     RecentClients {
         pagination: Pagination,
     },
@@ -236,11 +288,41 @@ pub(crate) enum CommandSchema {
     CotoDetails {
         id: Id<Coto>,
     },
+    CotoMediaByHash {
+        coto: Id<Coto>,
+        hash: String,
+    },
     GraphFromCoto {
         coto: Id<Coto>,
+        #[serde(default)]
+        options: Option<TraversalOptions>,
+        #[serde(default)]
+        frontier: Option<TraversalFrontier>,
     },
     GraphFromCotonoma {
         cotonoma: Id<Cotonoma>,
+        #[serde(default)]
+        options: Option<TraversalOptions>,
+        #[serde(default)]
+        frontier: Option<TraversalFrontier>,
+    },
+    PathBetween {
+        from: Id<Coto>,
+        to: Id<Coto>,
+        #[serde(default)]
+        max_depth: Option<usize>,
+        directed: bool,
+    },
+    Descendants {
+        coto: Id<Coto>,
+        #[serde(default)]
+        max_depth: Option<usize>,
+    },
+    GraphHealth {
+        scope: Scope,
+    },
+    QueryGraph {
+        query: String,
     },
     PostCoto {
         input: CotoInput<'static>,
@@ -313,13 +395,32 @@ impl From<Command> for CommandSchema {
             Command::SetLocalNodeIcon { icon } => Self::SetLocalNodeIcon { icon },
             Command::SetImageMaxSize(size) => Self::SetImageMaxSize { size },
             Command::EnableAnonymousRead { enable } => Self::EnableAnonymousRead { enable },
+            Command::ImportCotoamiJson { json, options } => {
+                Self::ImportCotoamiJson { json, options }
+            }
+            Command::CreateBackup => Self::CreateBackup,
             Command::InitialDataset => Self::InitialDataset,
-            Command::ChunkOfChanges { from } => Self::ChunkOfChanges { from },
+            Command::ChunkOfChanges { from, byte_budget } => {
+                Self::ChunkOfChanges { from, byte_budget }
+            }
+            Command::ChangelogSnapshot => Self::ChangelogSnapshot,
+            Command::FailedImports { parent, pagination } => {
+                Self::FailedImports { parent, pagination }
+            }
+            Command::RetryFailedImports { serial_numbers } => {
+                Self::RetryFailedImports { serial_numbers }
+            }
             Command::NodeDetails { id } => Self::NodeDetails { id },
             Command::CreateClientNodeSession(session) => Self::CreateClientNodeSession { session },
             Command::TryLogIntoServer(login) => Self::TryLogIntoServer { login },
             Command::AddServer(server) => Self::AddServer { server },
             Command::EditServer { id, values } => Self::EditServer { id, values },
+            Command::SetParentSubscription { id, subscription } => {
+                Self::SetParentSubscription { id, subscription }
+            }
+            Command::ForkFromParent { id } => Self::ForkFromParent { id },
+            Command::ForkDivergence { id } => Self::ForkDivergence { id },
+            Command::ReconcileWithParent { id } => Self::ReconcileWithParent { id },
             Command::RecentClients { pagination } => Self::RecentClients { pagination },
             Command::ClientNode { id } => Self::ClientNode { id },
             Command::AddClient(client) => Self::AddClient { client },
@@ -370,8 +471,39 @@ impl From<Command> for CommandSchema {
                 pagination,
             },
             Command::CotoDetails { id } => Self::CotoDetails { id },
-            Command::GraphFromCoto { coto } => Self::GraphFromCoto { coto },
-            Command::GraphFromCotonoma { cotonoma } => Self::GraphFromCotonoma { cotonoma },
+            Command::CotoMediaByHash { coto, hash } => Self::CotoMediaByHash { coto, hash },
+            Command::GraphFromCoto {
+                coto,
+                options,
+                frontier,
+            } => Self::GraphFromCoto {
+                coto,
+                options,
+                frontier,
+            },
+            Command::GraphFromCotonoma {
+                cotonoma,
+                options,
+                frontier,
+            } => Self::GraphFromCotonoma {
+                cotonoma,
+                options,
+                frontier,
+            },
+            Command::PathBetween {
+                from,
+                to,
+                max_depth,
+                directed,
+            } => Self::PathBetween {
+                from,
+                to,
+                max_depth,
+                directed,
+            },
+            Command::Descendants { coto, max_depth } => Self::Descendants { coto, max_depth },
+            Command::GraphHealth { scope } => Self::GraphHealth { scope },
+            Command::QueryGraph { query } => Self::QueryGraph { query },
             Command::PostCoto { input, post_to } => Self::PostCoto { input, post_to },
             Command::PostCotonoma { input, post_to } => Self::PostCotonoma { input, post_to },
             Command::EditCoto { id, diff } => Self::EditCoto { id, diff },
@@ -410,8 +542,21 @@ impl From<CommandSchema> for Command {
             CommandSchema::SetLocalNodeIcon { icon } => Self::SetLocalNodeIcon { icon },
             CommandSchema::SetImageMaxSize { size } => Self::SetImageMaxSize(size),
             CommandSchema::EnableAnonymousRead { enable } => Self::EnableAnonymousRead { enable },
+            CommandSchema::ImportCotoamiJson { json, options } => {
+                Self::ImportCotoamiJson { json, options }
+            }
+            CommandSchema::CreateBackup => Self::CreateBackup,
             CommandSchema::InitialDataset => Self::InitialDataset,
-            CommandSchema::ChunkOfChanges { from } => Self::ChunkOfChanges { from },
+            CommandSchema::ChunkOfChanges { from, byte_budget } => {
+                Self::ChunkOfChanges { from, byte_budget }
+            }
+            CommandSchema::ChangelogSnapshot => Self::ChangelogSnapshot,
+            CommandSchema::FailedImports { parent, pagination } => {
+                Self::FailedImports { parent, pagination }
+            }
+            CommandSchema::RetryFailedImports { serial_numbers } => {
+                Self::RetryFailedImports { serial_numbers }
+            }
             CommandSchema::NodeDetails { id } => Self::NodeDetails { id },
             CommandSchema::CreateClientNodeSession { session } => {
                 Self::CreateClientNodeSession(session)
@@ -419,6 +564,12 @@ impl From<CommandSchema> for Command {
             CommandSchema::TryLogIntoServer { login } => Self::TryLogIntoServer(login),
             CommandSchema::AddServer { server } => Self::AddServer(server),
             CommandSchema::EditServer { id, values } => Self::EditServer { id, values },
+            CommandSchema::SetParentSubscription { id, subscription } => {
+                Self::SetParentSubscription { id, subscription }
+            }
+            CommandSchema::ForkFromParent { id } => Self::ForkFromParent { id },
+            CommandSchema::ForkDivergence { id } => Self::ForkDivergence { id },
+            CommandSchema::ReconcileWithParent { id } => Self::ReconcileWithParent { id },
             CommandSchema::RecentClients { pagination } => Self::RecentClients { pagination },
             CommandSchema::ClientNode { id } => Self::ClientNode { id },
             CommandSchema::AddClient { client } => Self::AddClient(client),
@@ -469,8 +620,39 @@ impl From<CommandSchema> for Command {
                 pagination,
             },
             CommandSchema::CotoDetails { id } => Self::CotoDetails { id },
-            CommandSchema::GraphFromCoto { coto } => Self::GraphFromCoto { coto },
-            CommandSchema::GraphFromCotonoma { cotonoma } => Self::GraphFromCotonoma { cotonoma },
+            CommandSchema::CotoMediaByHash { coto, hash } => Self::CotoMediaByHash { coto, hash },
+            CommandSchema::GraphFromCoto {
+                coto,
+                options,
+                frontier,
+            } => Self::GraphFromCoto {
+                coto,
+                options,
+                frontier,
+            },
+            CommandSchema::GraphFromCotonoma {
+                cotonoma,
+                options,
+                frontier,
+            } => Self::GraphFromCotonoma {
+                cotonoma,
+                options,
+                frontier,
+            },
+            CommandSchema::PathBetween {
+                from,
+                to,
+                max_depth,
+                directed,
+            } => Self::PathBetween {
+                from,
+                to,
+                max_depth,
+                directed,
+            },
+            CommandSchema::Descendants { coto, max_depth } => Self::Descendants { coto, max_depth },
+            CommandSchema::GraphHealth { scope } => Self::GraphHealth { scope },
+            CommandSchema::QueryGraph { query } => Self::QueryGraph { query },
             CommandSchema::PostCoto { input, post_to } => Self::PostCoto { input, post_to },
             CommandSchema::PostCotonoma { input, post_to } => Self::PostCotonoma { input, post_to },
             CommandSchema::EditCoto { id, diff } => Self::EditCoto { id, diff },
@@ -584,6 +766,7 @@ mod tests {
             from: None,
             accept: SerializeFormat::Json,
             as_owner: false,
+            idempotency_key: None,
             command: Command::PostCoto {
                 input: CotoInput::new("hello").summary("summary"),
                 post_to: cotonoma_id,
@@ -623,6 +806,7 @@ mod tests {
             from: None,
             accept: SerializeFormat::MessagePack,
             as_owner: true,
+            idempotency_key: None,
             command: Command::SearchCotos {
                 query: "query".into(),
                 scope: Scope::Cotonoma((coto_id, CotonomaScope::Depth(3))),
@@ -663,11 +847,13 @@ mod tests {
     fn request_message_pack_roundtrip_restores_internal_command() -> Result<()> {
         let source_coto = Id::generate();
         let post_to = Id::generate();
+        let idempotency_key = Id::generate();
         let request = Request {
             id: Uuid::nil(),
             from: None,
             accept: SerializeFormat::MessagePack,
             as_owner: false,
+            idempotency_key: Some(idempotency_key),
             command: Command::PostSubcoto {
                 source_coto,
                 input: CotoInput::new("child"),
@@ -678,6 +864,7 @@ mod tests {
 
         let bytes = to_msgpack_vec_named(&request)?;
         let restored: Request = cotoami_db::rmp_serde::from_slice(&bytes)?;
+        assert_eq!(restored.idempotency_key(), Some(&idempotency_key));
 
         match restored.command() {
             Command::PostSubcoto {
@@ -696,4 +883,29 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn command_with_bytes_can_roundtrip_through_json() -> Result<()> {
+        let post_to = Id::generate();
+        let command = Command::PostCoto {
+            input: CotoInput::new("image").media_content(Bytes::from(vec![1, 2, 3]), "image/png"),
+            post_to,
+        };
+
+        let bytes = command_to_json_vec(command)?;
+        match command_from_json_slice(&bytes)? {
+            Command::PostCoto {
+                input,
+                post_to: actual_post_to,
+            } => {
+                let (content, media_type) = input.media_content.unwrap();
+                assert_eq!(content.as_ref(), &[1, 2, 3]);
+                assert_eq!(media_type, "image/png");
+                assert_eq!(actual_post_to, post_to);
+            }
+            other => panic!("unexpected command after roundtrip: {other:?}"),
+        }
+
+        Ok(())
+    }
 }
@@ -0,0 +1,224 @@
+//! Compression of the data transferred between nodes.
+//!
+//! Changes are replicated in MessagePack or JSON, which could be large enough to be
+//! a bottleneck on a slow link (a chunk of changes or a change with media content).
+//! To reduce the size, a compression algorithm can be negotiated between nodes:
+//!
+//! 1. A client advertises the algorithms it supports on creating a session
+//!    ([CreateClientNodeSession::compression]).
+//! 2. The server chooses one of them ([ClientNodeSession::compression]),
+//!    which will be `None` if the server is an older version.
+//! 3. The client requests compressed data by sending the chosen algorithm in
+//!    the [crate::web::COMPRESSION_HEADER_NAME] header, with which the server
+//!    compresses the WebSocket frames and chunks of changes to the client.
+//!
+//! A node sends compressed WebSocket frames only to a peer that has agreed on the
+//! compression, and rejects compressed frames from a peer that hasn't. A compressed frame
+//! can be distinguished from an uncompressed one by the magic number of the algorithm,
+//! so a peer that has agreed can still send small frames uncompressed.
+//!
+//! Decompression is always run in a blocking thread and capped by the max message size
+//! of the receiving side since the decompressed size can't be known in advance.
+//!
+//! [CreateClientNodeSession::compression]: crate::service::models::CreateClientNodeSession::compression
+//! [ClientNodeSession::compression]: crate::service::models::ClientNodeSession::compression
+
+use std::{borrow::Cow, io::Read};
+
+use anyhow::{bail, ensure, Result};
+use bytes::Bytes;
+use tokio::task::spawn_blocking;
+
+use crate::service::models::Compression;
+
+/// Frames smaller than this size will be sent uncompressed since the compression
+/// wouldn't pay off.
+pub(crate) const MIN_FRAME_SIZE_TO_COMPRESS: usize = 1024;
+
+/// Data of this size or larger will be compressed in a blocking thread so as not to
+/// stall the async runtime ([run]).
+pub(crate) const MIN_SIZE_TO_COMPRESS_IN_BLOCKING: usize = 1024 * 1024;
+
+/// The upper limit of decompressed data size to protect a node from a decompression bomb
+/// if no max message size has been configured, which is the same as the default
+/// [crate::config::NodeConfig::max_message_size_as_client].
+const MAX_DECOMPRESSED_SIZE: usize = 1024 * 1024 * 1024;
+
+/// The magic number at the beginning of a zstd frame.
+/// A MessagePack-encoded [crate::remote::NodeSentEvent] never starts with it.
+const ZSTD_MAGIC_NUMBER: [u8; 4] = [0x28, 0xB5, 0x2F, 0xFD];
+
+pub(crate) fn compress(bytes: &[u8], compression: Compression) -> Result<Vec<u8>> {
+    match compression {
+        Compression::Zstd => Ok(zstd::bulk::compress(
+            bytes,
+            zstd::DEFAULT_COMPRESSION_LEVEL,
+        )?),
+        Compression::Unknown => bail!("Unsupported compression."),
+    }
+}
+
+/// Decompresses the bytes into data no larger than `max_size`
+/// (or [MAX_DECOMPRESSED_SIZE] if `None`).
+pub(crate) fn decompress(
+    bytes: &[u8],
+    compression: Compression,
+    max_size: Option<usize>,
+) -> Result<Vec<u8>> {
+    let max_size = max_size.unwrap_or(MAX_DECOMPRESSED_SIZE);
+    match compression {
+        Compression::Zstd => {
+            let mut decompressed = Vec::new();
+            zstd::stream::read::Decoder::new(bytes)?
+                .take(max_size as u64 + 1)
+                .read_to_end(&mut decompressed)?;
+            ensure!(
+                decompressed.len() <= max_size,
+                "The decompressed data exceeds the limit: {max_size} bytes"
+            );
+            Ok(decompressed)
+        }
+        Compression::Unknown => bail!("Unsupported compression."),
+    }
+}
+
+/// Runs a compression `f` of `size` bytes, which will be run in a blocking thread
+/// if the size is [MIN_SIZE_TO_COMPRESS_IN_BLOCKING] or larger.
+pub(crate) async fn run<T, F>(size: usize, f: F) -> Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T> + Send + 'static,
+{
+    if size >= MIN_SIZE_TO_COMPRESS_IN_BLOCKING {
+        run_blocking(f).await
+    } else {
+        f()
+    }
+}
+
+/// Runs `f` in a blocking thread, which is used for decompression regardless of
+/// the compressed size.
+pub(crate) async fn run_blocking<T, F>(f: F) -> Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T> + Send + 'static,
+{
+    spawn_blocking(f).await?
+}
+
+/// Compresses the bytes of a WebSocket frame if a compression has been agreed on and
+/// the frame is large enough.
+pub(crate) fn compress_frame(bytes: Bytes, compression: Option<Compression>) -> Result<Bytes> {
+    match compression {
+        Some(compression) if bytes.len() >= MIN_FRAME_SIZE_TO_COMPRESS => {
+            compress(&bytes, compression).map(Bytes::from)
+        }
+        _ => Ok(bytes),
+    }
+}
+
+/// Returns `true` if the bytes of a WebSocket frame are compressed.
+pub(crate) fn is_compressed_frame(bytes: &[u8]) -> bool { bytes.starts_with(&ZSTD_MAGIC_NUMBER) }
+
+/// Decompresses the bytes of a WebSocket frame into data no larger than `max_size` if
+/// they are compressed with the agreed `compression`, otherwise returns them as they are.
+pub(crate) fn decompress_frame(
+    bytes: &[u8],
+    compression: Option<Compression>,
+    max_size: Option<usize>,
+) -> Result<Cow<'_, [u8]>> {
+    if is_compressed_frame(bytes) {
+        ensure!(
+            compression == Some(Compression::Zstd),
+            "Received a compressed frame without agreement on the compression."
+        );
+        decompress(bytes, Compression::Zstd, max_size).map(Cow::Owned)
+    } else {
+        Ok(Cow::Borrowed(bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use googletest::prelude::*;
+
+    use super::*;
+    use crate::{remote::NodeSentEvent, service::Command};
+
+    #[test]
+    fn small_frame_should_not_be_compressed() -> Result<()> {
+        let bytes = Bytes::from_static(b"small");
+        let frame = compress_frame(bytes.clone(), Some(Compression::Zstd))?;
+        assert_that!(frame, eq(&bytes));
+        assert_that!(
+            decompress_frame(&frame, Some(Compression::Zstd), None)?.as_ref(),
+            eq(bytes.as_ref())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn compressed_frame_can_be_detected_and_decompressed() -> Result<()> {
+        let event = NodeSentEvent::Request(Command::SetImageMaxSize(2048).into_request());
+        let bytes = Bytes::from(
+            [
+                super::super::to_msgpack_vec_named(&event)?,
+                vec![0; MIN_FRAME_SIZE_TO_COMPRESS],
+            ]
+            .concat(),
+        );
+        assert!(!bytes.starts_with(&ZSTD_MAGIC_NUMBER));
+
+        let frame = compress_frame(bytes.clone(), Some(Compression::Zstd))?;
+        assert_that!(frame.len(), lt(bytes.len()));
+        assert_that!(
+            decompress_frame(&frame, Some(Compression::Zstd), None)?.as_ref(),
+            eq(bytes.as_ref())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn compressed_frame_should_be_rejected_without_agreement() -> Result<()> {
+        let bytes = Bytes::from(vec![0; MIN_FRAME_SIZE_TO_COMPRESS]);
+        let frame = compress_frame(bytes, Some(Compression::Zstd))?;
+        assert!(decompress_frame(&frame, None, None).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn decompressed_frame_should_not_exceed_max_size() -> Result<()> {
+        let bytes = Bytes::from(vec![0; MIN_FRAME_SIZE_TO_COMPRESS * 4]);
+        let frame = compress_frame(bytes.clone(), Some(Compression::Zstd))?;
+        let max_size = Some(MIN_FRAME_SIZE_TO_COMPRESS * 4);
+        assert!(decompress_frame(&frame, Some(Compression::Zstd), max_size).is_ok());
+        let max_size = Some(MIN_FRAME_SIZE_TO_COMPRESS * 4 - 1);
+        assert!(decompress_frame(&frame, Some(Compression::Zstd), max_size).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn frame_should_not_be_compressed_without_agreement() -> Result<()> {
+        let bytes = Bytes::from(vec![0; MIN_FRAME_SIZE_TO_COMPRESS]);
+        assert_that!(compress_frame(bytes.clone(), None)?, eq(&bytes));
+        Ok(())
+    }
+
+    #[test]
+    fn negotiate_supported_compression() -> Result<()> {
+        // An algorithm unknown to this node shouldn't break the deserialization.
+        let advertised: Vec<Compression> = serde_json::from_str(r#"["Lz4", "Zstd"]"#)?;
+        assert_that!(
+            advertised,
+            elements_are![eq(&Compression::Unknown), eq(&Compression::Zstd)]
+        );
+        assert_that!(
+            Compression::negotiate(&advertised),
+            some(eq(Compression::Zstd))
+        );
+        assert_that!(Compression::negotiate(&[Compression::Unknown]), none());
+        assert_that!(Compression::negotiate(&[]), none());
+        Ok(())
+    }
+}
@@ -29,6 +29,9 @@ struct LegacyRequest {
     accept: SerializeFormat,
     as_owner: bool,
     command: Command,
+    // Appended only when it's set so that older nodes can decode the other requests.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    idempotency_key: Option<Id<OutboxEntry>>,
 }
 
 impl From<Request> for LegacyRequest {
@@ -38,6 +41,7 @@ impl From<Request> for LegacyRequest {
             accept: request.accept,
             as_owner: request.as_owner,
             command: request.command,
+            idempotency_key: request.idempotency_key,
         }
     }
 }
@@ -49,6 +53,7 @@ impl From<LegacyRequest> for Request {
             from: None,
             accept: request.accept,
             as_owner: request.as_owner,
+            idempotency_key: request.idempotency_key,
             command: request.command,
         }
     }
@@ -137,4 +142,22 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn idempotency_key_can_roundtrip_through_legacy_protocol() -> Result<()> {
+        let key = Id::generate();
+        let mut request = Command::SetImageMaxSize(2048).into_request();
+        request.set_idempotency_key(key);
+        let bytes = to_legacy_msgpack_vec(&NodeSentEvent::Request(request))?;
+        let restored = from_legacy_msgpack_slice(&bytes)?;
+
+        match restored {
+            NodeSentEvent::Request(request) => {
+                assert_eq!(request.idempotency_key(), Some(&key));
+            }
+            other => panic!("unexpected event after roundtrip: {other:?}"),
+        }
+
+        Ok(())
+    }
 }
@@ -16,8 +16,11 @@ use tokio_tungstenite::tungstenite::protocol::Message;
 use tracing::debug;
 
 use crate::{
-    remote::{codec::legacy, CommunicationError, NodeSentEvent},
-    service::PubsubService,
+    remote::{
+        codec::{compression, legacy},
+        CommunicationError, NodeSentEvent,
+    },
+    service::{models::Compression, PubsubService},
     state::NodeState,
     Abortables,
 };
@@ -32,6 +35,8 @@ pub(crate) async fn communicate_with_parent<MsgSink, MsgSinkErr, MsgStream, MsgS
     msg_stream: MsgStream,
     mut on_abort: OnAbort,
     ping_interval: Option<Duration>,
+    compression: Option<Compression>,
+    max_message_size: Option<usize>,
     abortables: Abortables,
 ) where
     MsgSink: Sink<Message, Error = MsgSinkErr> + Unpin + Send + 'static,
@@ -47,7 +52,8 @@ pub(crate) async fn communicate_with_parent<MsgSink, MsgSinkErr, MsgStream, MsgS
     let parent_service = PubsubService::new(description, node_state.pubsub().responses().clone());
 
     // A task sending messages received from the other tasks
-    let (handle, msg_sender) = task_sending_messages(&mut tasks, msg_sink, task_error.clone());
+    let (handle, msg_sender) =
+        task_sending_messages(&mut tasks, msg_sink, compression, task_error.clone());
     abortables.add(handle);
 
     // A task sending pings.
@@ -76,6 +82,8 @@ pub(crate) async fn communicate_with_parent<MsgSink, MsgSinkErr, MsgStream, MsgS
     abortables.add(tasks.spawn(handle_message_stream(
         msg_stream,
         Some(parent_id),
+        compression,
+        max_message_size,
         task_error.clone(),
         {
             let node_state = node_state.clone();
@@ -97,6 +105,7 @@ pub(crate) async fn communicate_with_parent<MsgSink, MsgSinkErr, MsgStream, MsgS
 }
 
 /// Spawn and join tasks to handle a WebSocket connection to a child node.
+#[allow(clippy::too_many_arguments)]
 pub(crate) async fn communicate_with_operator<
     MsgSink,
     MsgSinkErr,
@@ -110,6 +119,8 @@ pub(crate) async fn communicate_with_operator<
     msg_stream: MsgStream,
     mut on_abort: OnAbort,
     ping_interval: Option<Duration>,
+    compression: Option<Compression>,
+    max_message_size: Option<usize>,
     abortables: Abortables,
 ) where
     MsgSink: Sink<Message, Error = MsgSinkErr> + Unpin + Send + 'static,
@@ -124,7 +135,8 @@ pub(crate) async fn communicate_with_operator<
     let task_error = Arc::new(Mutex::new(None::<CommunicationError>));
 
     // A task sending messages received from the other tasks
-    let (handle, msg_sender) = task_sending_messages(&mut tasks, msg_sink, task_error.clone());
+    let (handle, msg_sender) =
+        task_sending_messages(&mut tasks, msg_sink, compression, task_error.clone());
     abortables.add(handle);
 
     // A task sending pings.
@@ -135,7 +147,7 @@ pub(crate) async fn communicate_with_operator<
     // A task publishing change events to the operator
     abortables.add(tasks.spawn({
         let msg_sender = msg_sender.clone();
-        let mut changes = node_state.pubsub().changes().subscribe(None::<()>);
+        let mut changes = node_state.changes_for(&opr);
         async move {
             while let Some(change) = changes.next().await {
                 if msg_sender
@@ -170,7 +182,12 @@ pub(crate) async fn communicate_with_operator<
 
     // A task receiving events from the child
     abortables.add(tasks.spawn({
-        handle_message_stream(msg_stream, node_id, task_error.clone(), {
+        handle_message_stream(
+            msg_stream,
+            node_id,
+            compression,
+            max_message_size,
+            task_error.clone(),
             move |event| {
                 super::handle_event_from_operator(
                     event,
@@ -178,8 +195,8 @@ pub(crate) async fn communicate_with_operator<
                     node_state.clone(),
                     Box::pin(as_event_sink(msg_sender.clone())),
                 )
-            }
-        })
+            },
+        )
     }));
 
     // If any one of the tasks exit, abort the others.
@@ -195,9 +212,12 @@ pub(crate) async fn communicate_with_operator<
 const SEND_BUFFER_SIZE: usize = 16;
 
 /// Start a task sending tungstenite [Message]s which are received via [mpsc::channel].
+///
+/// Binary messages will be compressed if a `compression` has been agreed on with the peer.
 fn task_sending_messages<MsgSink, MsgSinkErr>(
     tasks: &mut JoinSet<()>,
     mut msg_sink: MsgSink,
+    compression: Option<Compression>,
     task_error: Arc<Mutex<Option<CommunicationError>>>,
 ) -> (AbortHandle, Sender<Message>)
 where
@@ -209,6 +229,25 @@ where
         let task_error = task_error.clone();
         async move {
             while let Some(message) = receiver.recv().await {
+                let message = match message {
+                    Message::Binary(bytes) => {
+                        let size = bytes.len();
+                        match compression::run(size, move || {
+                            compression::compress_frame(bytes, compression)
+                        })
+                        .await
+                        {
+                            Ok(bytes) => Message::Binary(bytes),
+                            Err(e) => {
+                                task_error
+                                    .lock()
+                                    .replace(CommunicationError::EventHandling(e));
+                                break;
+                            }
+                        }
+                    }
+                    message => message,
+                };
                 if let Err(e) = msg_sink.send(message).await {
                     task_error
                         .lock()
@@ -258,9 +297,14 @@ impl From<NodeSentEvent> for Message {
 }
 
 /// Read WebSocket messages as [NodeSentEvent]s and handle them with the given `handler`.
+///
+/// Compressed messages are accepted only if a `compression` has been agreed on with
+/// the peer, and they will be decompressed up to the `max_message_size`.
 async fn handle_message_stream<MsgStream, MsgStreamErr, H, F>(
     mut msg_stream: MsgStream,
     peer_id: Option<Id<Node>>,
+    compression: Option<Compression>,
+    max_message_size: Option<usize>,
     error: Arc<Mutex<Option<CommunicationError>>>,
     handler: H,
 ) where
@@ -272,23 +316,37 @@ async fn handle_message_stream<MsgStream, MsgStreamErr, H, F>(
     loop {
         match msg_stream.next().await {
             Some(Ok(msg)) => match msg {
-                Message::Binary(vec) => match rmp_serde::from_slice::<NodeSentEvent>(&vec)
-                    .or_else(|_| legacy::from_legacy_msgpack_slice(&vec))
-                {
-                    Ok(event) => {
-                        if let ControlFlow::Break(e) = handler(event).await {
+                Message::Binary(vec) => {
+                    let compressed = compression::is_compressed_frame(&vec);
+                    let size = vec.len();
+                    let decode = move || {
+                        compression::decompress_frame(&vec, compression, max_message_size).and_then(
+                            |bytes| {
+                                rmp_serde::from_slice::<NodeSentEvent>(&bytes)
+                                    .or_else(|_| legacy::from_legacy_msgpack_slice(&bytes))
+                                    .map_err(anyhow::Error::from)
+                            },
+                        )
+                    };
+                    let event = if compressed {
+                        compression::run_blocking(decode).await
+                    } else {
+                        compression::run(size, decode).await
+                    };
+                    match event {
+                        Ok(event) => {
+                            if let ControlFlow::Break(e) = handler(event).await {
+                                error.lock().replace(CommunicationError::EventHandling(e));
+                                break;
+                            }
+                        }
+                        Err(e) => {
+                            debug!("The peer ({peer_id:?}) sent an invalid binary message: {e}");
                             error.lock().replace(CommunicationError::EventHandling(e));
                             break;
                         }
                     }
-                    Err(e) => {
-                        debug!("The peer ({peer_id:?}) sent an invalid binary message: {e}");
-                        error
-                            .lock()
-                            .replace(CommunicationError::EventHandling(e.into()));
-                        break;
-                    }
-                },
+                }
                 Message::Close(c) => {
                     debug!("The peer ({peer_id:?}) sent close with: {c:?}");
                     break;
@@ -81,6 +81,10 @@ pub trait NodeService:
 
 pub trait RemoteNodeService: NodeService {
     fn set_session_token(&mut self, token: &str) -> Result<()>;
+
+    /// Requests the data from the remote node to be compressed with the algorithm
+    /// agreed on in the session ([models::ClientNodeSession::compression]).
+    fn set_compression(&mut self, compression: models::Compression) -> Result<()>;
 }
 
 pub type NodeServiceFuture = BoxFuture<'static, Result<Response, anyhow::Error>>;
@@ -103,6 +107,10 @@ pub struct Request {
 
     pub(crate) as_owner: bool,
 
+    /// The key to prevent the request from being handled twice, which is set when
+    /// the request is sent from an outbox ([OutboxEntry]).
+    pub(crate) idempotency_key: Option<Id<OutboxEntry>>,
+
     pub(crate) command: Command,
 }
 
@@ -113,6 +121,7 @@ impl Request {
             from: None,
             accept: SerializeFormat::MessagePack,
             as_owner: false,
+            idempotency_key: None,
             command,
         }
     }
@@ -133,6 +142,12 @@ impl Request {
 
     pub fn operate_as_owner(&mut self) { self.as_owner = true }
 
+    pub fn idempotency_key(&self) -> Option<&Id<OutboxEntry>> { self.idempotency_key.as_ref() }
+
+    pub fn set_idempotency_key(&mut self, key: Id<OutboxEntry>) {
+        self.idempotency_key = Some(key);
+    }
+
     pub fn command(self) -> Command { self.command }
 }
 
@@ -167,6 +182,10 @@ impl Response {
 
     pub fn id(&self) -> &Uuid { &self.id }
 
+    /// Returns the body as is, which is useful when only the success or failure
+    /// of the request matters.
+    pub fn into_body(self) -> Result<Bytes, ServiceError> { self.body }
+
     pub fn content<T: DeserializeOwned>(self) -> Result<T> {
         let bytes = self.body.map_err(BackendServiceError)?;
         match self.body_format {
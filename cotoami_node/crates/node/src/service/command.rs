@@ -1,4 +1,4 @@
-use cotoami_db::prelude::*;
+use cotoami_db::{import::cotoami::CotoamiImportOptions, prelude::*};
 
 use crate::service::models::*;
 
@@ -40,11 +40,45 @@ pub enum Command {
     /// Request to enable/disable anonymous read and return the [LocalNode] if succeeded.
     EnableAnonymousRead { enable: bool },
 
+    /// Request to import a JSON dump exported from the original Cotoami into the local node
+    /// and return a [cotoami_db::import::cotoami::CotoamiImportReport] if succeeded.
+    /// The progress will be published as [crate::state::LocalNodeEvent::ImportProgress].
+    ImportCotoamiJson {
+        #[debug(skip)]
+        json: String,
+        options: CotoamiImportOptions,
+    },
+
+    /// Request to back up the database into [crate::config::NodeConfig::backup_dir]
+    /// and return a [Backup] if succeeded. Only the owner can request it.
+    CreateBackup,
+
     /// Request an [InitialDataset].
     InitialDataset,
 
     /// Request a [ChunkOfChanges] from a change number `from`.
-    ChunkOfChanges { from: i64 },
+    ///
+    /// A child can request the chunk to be cut within `byte_budget` bytes, which will be
+    /// capped by [crate::config::NodeConfig::changes_chunk_bytes] of the parent.
+    ChunkOfChanges {
+        from: i64,
+        byte_budget: Option<usize>,
+    },
+
+    /// Request a [ChangelogSnapshot] of the local node, from which a new child can
+    /// bootstrap without replaying every change.
+    ChangelogSnapshot,
+
+    /// Request a [Page<FailedImport>] that contains the changes imported from the parent
+    /// that failed to be applied to the local node. Only the owner can request it.
+    FailedImports {
+        parent: Id<Node>,
+        pagination: Pagination,
+    },
+
+    /// Request to apply the failed imports of the given serial numbers again and return
+    /// the results as [Vec<FailedImport>]. Only the owner can request it.
+    RetryFailedImports { serial_numbers: Vec<i64> },
 
     /// Request a [NodeDetails] of the given ID.
     NodeDetails { id: Id<Node> },
@@ -61,6 +95,25 @@ pub enum Command {
     /// Request to edit a server node and return the updated [ServerNode].
     EditServer { id: Id<Node>, values: EditServer },
 
+    /// Request to set the [ChangeSubscription] to a parent node and return
+    /// the updated [ParentNode]. `None` to replicate the whole database of the parent.
+    SetParentSubscription {
+        id: Id<Node>,
+        subscription: Option<ChangeSubscription>,
+    },
+
+    /// Request to fork the local node from a parent node and return the updated
+    /// [ParentNode]. The connection with the parent will be disabled.
+    ForkFromParent { id: Id<Node> },
+
+    /// Request a [ForkDivergence] between the local node and a forked parent node.
+    ForkDivergence { id: Id<Node> },
+
+    /// Request to re-attach the local node to a forked parent node and return
+    /// the [ForkDivergence] at the time as a conflict report. The changes made in
+    /// the parent during the fork will be imported on reconnecting to it.
+    ReconcileWithParent { id: Id<Node> },
+
     /// Request a [Page<ClientNode>] that contains recently registered clients.
     RecentClients { pagination: Pagination },
 
@@ -148,11 +201,59 @@ pub enum Command {
     /// Request a [CotoDetails] of the given ID.
     CotoDetails { id: Id<Coto> },
 
+    /// Request the [CotoMedia] of the given coto whose content hash ([Coto::media_hash])
+    /// matches the given `hash`, which is the media omitted from a change for lazy media
+    /// replication ([Change::MediaOmitted]). If the media is missing in the local node,
+    /// it will be fetched from the parents and cached locally.
+    CotoMediaByHash { coto: Id<Coto>, hash: String },
+
     /// Request a [CotoGraph] by traversing from the given coto.
-    GraphFromCoto { coto: Id<Coto> },
+    ///
+    /// The traversal is bounded by `options` (until cotonomas without limits if `None`),
+    /// and can be resumed from the [CotoGraph::frontier] of the previous page.
+    GraphFromCoto {
+        coto: Id<Coto>,
+        #[serde(default)]
+        options: Option<TraversalOptions>,
+        #[serde(default)]
+        frontier: Option<TraversalFrontier>,
+    },
 
     /// Request a [CotoGraph] by traversing from the given cotonoma.
-    GraphFromCotonoma { cotonoma: Id<Cotonoma> },
+    ///
+    /// `options` and `frontier` work in the same way as [Command::GraphFromCoto].
+    GraphFromCotonoma {
+        cotonoma: Id<Cotonoma>,
+        #[serde(default)]
+        options: Option<TraversalOptions>,
+        #[serde(default)]
+        frontier: Option<TraversalFrontier>,
+    },
+
+    /// Request [CotoPaths] that contains the shortest ito chains from the coto `from`
+    /// to the coto `to`. Itos will be followed in both directions unless `directed`.
+    PathBetween {
+        from: Id<Coto>,
+        to: Id<Coto>,
+        max_depth: Option<usize>,
+        directed: bool,
+    },
+
+    /// Request the descendants of the given coto level by level (up to `max_depth` levels)
+    /// as `Vec<(Vec<Ito>, Vec<Coto>)>`, each element of which consists of the outgoing itos
+    /// from the previous level and the cotos newly reached by them.
+    Descendants {
+        coto: Id<Coto>,
+        max_depth: Option<usize>,
+    },
+
+    /// Request a [GraphHealth] report on the cotos and itos in the given scope.
+    GraphHealth { scope: Scope },
+
+    /// Request a [CotoGraph] that consists of the cotos and itos matching the given query
+    /// written in the graph query language (see [cotoami_db::models::graph::query]).
+    /// The response will be `None` if nothing matches.
+    QueryGraph { query: String },
 
     /// Request to create a new [Coto] in the given cotonoma (`post_to`),
     /// and return the [Coto] if suceeded.
@@ -5,7 +5,7 @@
 
 use anyhow::Result;
 use chrono::NaiveDateTime;
-use cotoami_db::prelude::*;
+use cotoami_db::{import::cotoami::CotoamiImportOptions, prelude::*};
 use derive_new::new;
 use itertools::Itertools;
 use validator::Validate;
@@ -52,6 +52,20 @@ pub struct CreateClientNodeSession {
     pub client: Node,
     pub client_role: Option<NodeRole>,
     pub client_version: String,
+
+    /// Cotonomas to be replicated to the client as a child,
+    /// or `None` to replicate the whole database.
+    #[serde(default)]
+    pub subscription: Option<ChangeSubscription>,
+
+    /// TRUE if the client as a child requests to omit media content from the changes
+    /// to fetch it lazily by [super::Command::CotoMediaByHash].
+    #[serde(default)]
+    pub lazy_media: bool,
+
+    /// Compression algorithms supported by the client in order of preference.
+    #[serde(default)]
+    pub compression: Vec<Compression>,
 }
 
 impl CreateClientNodeSession {
@@ -70,6 +84,11 @@ pub struct ClientNodeSession {
     pub server: Node,
     pub server_root: Option<(Cotonoma, Coto)>,
     pub child_privileges: Option<ChildNode>,
+
+    /// Compression algorithm chosen by the server from the ones supported by the client,
+    /// or `None` if the server doesn't support any of them (or is an older version).
+    #[serde(default)]
+    pub compression: Option<Compression>,
 }
 
 impl ClientNodeSession {
@@ -82,6 +101,46 @@ impl ClientNodeSession {
     }
 }
 
+/// Compression algorithm for the data transferred between nodes
+/// (See [crate::remote::codec::compression]).
+#[derive(Debug, Copy, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Compression {
+    Zstd,
+
+    /// An algorithm unknown to this node, which could be advertised by a newer client.
+    #[serde(other)]
+    Unknown,
+}
+
+impl Compression {
+    /// Algorithms supported by this node in order of preference.
+    pub const SUPPORTED: &[Compression] = &[Compression::Zstd];
+
+    /// Chooses the most preferred algorithm supported by this node from
+    /// the ones advertised by a peer.
+    pub fn negotiate(advertised: &[Compression]) -> Option<Compression> {
+        advertised
+            .iter()
+            .find(|compression| Self::SUPPORTED.contains(compression))
+            .copied()
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Compression::Zstd => "zstd",
+            Compression::Unknown => "unknown",
+        }
+    }
+
+    /// Returns a supported algorithm of the given name.
+    pub fn from_name(name: &str) -> Option<Compression> {
+        Self::SUPPORTED
+            .iter()
+            .find(|compression| compression.name() == name)
+            .copied()
+    }
+}
+
 /////////////////////////////////////////////////////////////////////////////
 // Client
 /////////////////////////////////////////////////////////////////////////////
@@ -145,6 +204,53 @@ impl InitialDataset {
     }
 }
 
+/// A database backup file created by [super::Command::CreateBackup].
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Backup {
+    /// Path to the backup file
+    pub path: String,
+
+    /// Size of the backup file in bytes
+    pub size: u64,
+
+    /// UTC timestamp of the backup
+    pub created_at: NaiveDateTime,
+}
+
+/// A change imported from a parent that failed to be applied to the local node.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct FailedImport {
+    pub entry: ChangelogEntry,
+
+    /// The error that occurred in the last attempt to apply the change,
+    /// which will be `None` if it has been applied by a retry.
+    pub error: Option<String>,
+}
+
+impl From<ChangelogEntry> for FailedImport {
+    fn from(mut entry: ChangelogEntry) -> Self {
+        let error = entry.import_error.take();
+        Self { entry, error }
+    }
+}
+
+/// How the local node and a forked parent have diverged since the fork.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ForkDivergence {
+    pub parent: ParentNode,
+
+    /// Local changes since the fork that involve the entities owned by the parent.
+    pub local_changes: Vec<ChangelogEntry>,
+
+    /// Summaries of the changes made in the parent that have not yet been imported, which
+    /// will be `None` if the parent can't be reached (ex. the parent connects to the local
+    /// node as a client).
+    pub parent_changes: Option<Vec<ChangeSummary>>,
+
+    /// Parent changes conflicting with the `local_changes`.
+    pub conflicts: Vec<ForkConflict>,
+}
+
 /////////////////////////////////////////////////////////////////////////////
 // Node
 /////////////////////////////////////////////////////////////////////////////
@@ -175,6 +281,14 @@ pub struct LogIntoServer {
     pub new_password: Option<String>,
 
     pub client_role: Option<NodeRole>,
+
+    /// Cotonomas to subscribe to if the server will be a parent.
+    #[serde(default)]
+    pub subscription: Option<ChangeSubscription>,
+
+    /// TRUE to fetch media content lazily if the server will be a parent.
+    #[serde(default)]
+    pub lazy_media: bool,
 }
 
 impl LogIntoServer {
@@ -190,6 +304,9 @@ impl LogIntoServer {
             client,
             client_role: self.client_role,
             client_version,
+            subscription: self.subscription,
+            lazy_media: self.lazy_media,
+            compression: Compression::SUPPORTED.to_vec(),
         })
     }
 }
@@ -217,6 +334,10 @@ pub struct EditServer {
     pub disabled: Option<bool>,
     pub password: Option<String>,
     pub url_prefix: Option<String>,
+
+    /// Whether to fetch media content lazily from the server as a parent.
+    #[serde(default)]
+    pub lazy_media: Option<bool>,
 }
 
 /////////////////////////////////////////////////////////////////////////////
@@ -369,6 +490,36 @@ pub struct CotoGraph {
     pub cotos: Vec<Coto>,
     pub cotos_related_data: CotosRelatedData,
     pub itos: Vec<Ito>,
+
+    /// Where to resume the traversal if this graph has been cut off by the limits.
+    #[serde(default)]
+    pub frontier: Option<TraversalFrontier>,
+}
+
+/// Parameters of a bounded graph traversal (the body of `POST .../graph`).
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize, new)]
+pub struct GraphTraversal {
+    #[serde(default)]
+    pub options: Option<TraversalOptions>,
+    #[serde(default)]
+    pub frontier: Option<TraversalFrontier>,
+}
+
+/// A JSON dump of the original Cotoami to import (the body of
+/// `POST /api/data/nodes/local/import/cotoami`).
+#[derive(Debug, serde::Serialize, serde::Deserialize, new)]
+pub struct CotoamiJsonImport {
+    pub json: String,
+    #[serde(default)]
+    pub options: CotoamiImportOptions,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, new)]
+pub struct CotoPaths {
+    /// Ito chains each of which is ordered from the start coto to the end coto.
+    pub paths: Vec<Vec<Ito>>,
+    pub cotos: Vec<Coto>,
+    pub cotos_related_data: CotosRelatedData,
 }
 
 /////////////////////////////////////////////////////////////////////////////
@@ -26,12 +26,28 @@ pub trait NodeServiceExt: NodeService {
         response.content::<InitialDataset>()
     }
 
-    async fn chunk_of_changes(&self, from: i64) -> Result<ChunkOfChanges> {
-        let request = Command::ChunkOfChanges { from }.into_request();
+    async fn chunk_of_changes(
+        &self,
+        from: i64,
+        byte_budget: Option<usize>,
+    ) -> Result<ChunkOfChanges> {
+        let request = Command::ChunkOfChanges { from, byte_budget }.into_request();
         let response = self.call(request).await?;
         response.content::<ChunkOfChanges>()
     }
 
+    async fn changelog_snapshot(&self) -> Result<ChangelogSnapshot> {
+        let request = Command::ChangelogSnapshot.into_request();
+        let response = self.call(request).await?;
+        response.content::<ChangelogSnapshot>()
+    }
+
+    async fn coto_media_by_hash(&self, coto: Id<Coto>, hash: String) -> Result<CotoMedia> {
+        let request = Command::CotoMediaByHash { coto, hash }.into_request();
+        let response = self.call(request).await?;
+        response.content::<CotoMedia>()
+    }
+
     async fn post_coto(&self, input: CotoInput<'static>, post_to: Id<Cotonoma>) -> Result<Coto> {
         let request = Command::PostCoto { input, post_to }.into_request();
         let response = self.call(request).await?;
@@ -135,6 +151,9 @@ pub trait RemoteNodeServiceExt: RemoteNodeService {
         if let Some(ref token) = client_node_session.token {
             self.set_session_token(&token.token)?;
         }
+        if let Some(compression) = client_node_session.compression {
+            self.set_compression(compression)?;
+        }
         Ok(client_node_session)
     }
 }
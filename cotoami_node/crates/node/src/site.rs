@@ -0,0 +1,515 @@
+//! Static HTML site generation from a cotonoma.
+//!
+//! A site can be published on any static file host without running a node server.
+//! It consists of the following files in the output directory:
+//!
+//! * `index.html` - The cotonoma and its stock tree (pinned cotos and their itos)
+//! * `timeline.html` - The cotos in the [Scope] in reverse chronological order
+//! * `cotos/{coto_id}.html` - A page for each coto in the timeline or the stock tree,
+//!   in which the content is rendered from Markdown and the itos are links to other pages
+//! * `map.html` - Geolocated cotos in the scope on a map (optional)
+//! * `media/{coto_id}.{ext}` - Images of the cotos
+//! * `style.css`
+//!
+//! Raw HTML in the content of cotos is escaped rather than rendered since a coto may
+//! have been posted by others.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use cotoami_db::prelude::*;
+use pulldown_cmark::{CowStr, Event, Options, Parser, Tag};
+
+use crate::{service::ServiceError, state::NodeState};
+
+/// Number of cotos to be loaded at once for the timeline
+const TIMELINE_PAGE_SIZE: i64 = 100;
+
+/// The maximum number of cotos to be plotted on the map page
+const MAP_MAX_COTOS: i64 = 1000;
+
+const STYLE: &str = r#"body { max-width: 48rem; margin: 0 auto; padding: 1rem; font-family: sans-serif; line-height: 1.6; }
+nav a { margin-right: 1rem; }
+article.coto { border-bottom: 1px solid #ddd; padding: 0.5rem 0; }
+.meta { color: #777; font-size: 0.85rem; }
+.description { color: #777; font-style: italic; margin-right: 0.3rem; }
+.cotonoma { font-weight: bold; }
+img { max-width: 100%; }
+#map { height: 32rem; }
+"#;
+
+/// Body of the map page, in which `{markers}` will be replaced with a JSON array
+const MAP: &str = r#"<h1>Map</h1>
+<link rel="stylesheet" href="https://unpkg.com/leaflet@1.9.4/dist/leaflet.css">
+<script src="https://unpkg.com/leaflet@1.9.4/dist/leaflet.js"></script>
+<div id="map"></div>
+<script>
+const markers = {markers};
+const map = L.map("map");
+L.tileLayer("https://tile.openstreetmap.org/{z}/{x}/{y}.png", {
+  maxZoom: 19,
+  attribution: "&copy; OpenStreetMap contributors",
+}).addTo(map);
+const bounds = [];
+for (const marker of markers) {
+  const popup = document.createElement(marker.url ? "a" : "span");
+  popup.textContent = marker.title;
+  if (marker.url) popup.href = marker.url;
+  L.marker([marker.lat, marker.lng]).addTo(map).bindPopup(popup);
+  bounds.push([marker.lat, marker.lng]);
+}
+const hash = location.hash.slice(1).split(",").map(Number);
+if (hash.length == 2 && hash.every(Number.isFinite)) {
+  map.setView(hash, 15);
+} else if (bounds.length > 0) {
+  map.fitBounds(bounds, { maxZoom: 15 });
+} else {
+  map.setView([0, 0], 2);
+}
+</script>
+"#;
+
+/// Options of [generate_site].
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct SiteOptions {
+    /// Title of the site, which defaults to the name of the cotonoma
+    #[serde(default)]
+    pub title: Option<String>,
+
+    /// If `true`, a map page of the geolocated cotos will be generated.
+    #[serde(default)]
+    pub map: bool,
+}
+
+/// Summary of a generated site.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct SiteReport {
+    /// Number of HTML pages written
+    pub pages: usize,
+
+    /// Number of media files written
+    pub media: usize,
+}
+
+/// Generates a static site into `output_dir` from the cotos in the `scope`.
+///
+/// The home cotonoma of the site is the cotonoma of [Scope::Cotonoma], or the root
+/// cotonoma of the node for [Scope::Node] (the local node for [Scope::All]).
+pub fn generate_site(
+    ds: &mut DatabaseSession<'_>,
+    scope: Scope,
+    output_dir: impl AsRef<Path>,
+    options: &SiteOptions,
+) -> Result<SiteReport> {
+    let (cotonoma, cotonoma_coto) = match &scope {
+        Scope::Cotonoma((id, _)) => ds.try_get_cotonoma_pair(id)?,
+        Scope::Node(node_id) => {
+            let node = ds.try_get_node(node_id)?;
+            let root_id = node
+                .root_cotonoma_id
+                .ok_or(DatabaseError::RootCotonomaNotFound)?;
+            ds.try_get_cotonoma_pair(&root_id)?
+        }
+        Scope::All => ds.try_get_local_node_root()?,
+    };
+    let title = options.title.clone().unwrap_or(cotonoma.name.clone());
+
+    // Timeline
+    let mut timeline = Vec::new();
+    let mut page_index = 0;
+    loop {
+        let page = ds.recent_cotos(scope.clone(), false, TIMELINE_PAGE_SIZE, page_index)?;
+        timeline.extend(page.rows);
+        if (page_index + 1) * page.size >= page.total_rows {
+            break;
+        }
+        page_index += 1;
+    }
+    let originals: Vec<Id<Coto>> = timeline.iter().filter_map(|c| c.repost_of_id).collect();
+    let originals = ds.cotos_map(&originals)?;
+
+    // Stock tree
+    let stock = ds.graph(cotonoma_coto.clone(), true)?;
+
+    // Cotos to have their own pages
+    let mut cotos: HashMap<Id<Coto>, Coto> = stock.cotos.clone();
+    for coto in timeline.iter() {
+        let coto = original(coto, &originals);
+        cotos.entry(coto.uuid).or_insert_with(|| coto.clone());
+    }
+
+    let site = Site {
+        dir: output_dir.as_ref().to_owned(),
+        title,
+        map: options.map,
+        cotonoma_names: ds
+            .cotonomas_of(cotos.values())?
+            .into_iter()
+            .map(|cotonoma| (cotonoma.uuid, cotonoma.name))
+            .collect(),
+        pages: cotos.keys().copied().collect(),
+    };
+    let mut report = SiteReport::default();
+    fs::create_dir_all(site.dir.join("cotos"))?;
+    fs::write(site.dir.join("style.css"), STYLE)?;
+
+    // index.html
+    let mut body = format!(
+        "<h1 class=\"cotonoma\">{}</h1>\n",
+        escape_html(&cotonoma.name)
+    );
+    body.push_str(&site.coto_body(&cotonoma_coto, ""));
+    body.push_str("<h2>Stock</h2>\n");
+    let mut visited = HashSet::from([cotonoma_coto.uuid]);
+    site.write_stock(&stock, &cotonoma_coto.uuid, &mut visited, &mut body);
+    site.write_page("index.html", &site.title, "", &body)?;
+    report.pages += 1;
+
+    // timeline.html
+    let mut body = String::from("<h1>Timeline</h1>\n");
+    for coto in timeline.iter() {
+        let coto = original(coto, &originals);
+        body.push_str("<article class=\"coto\">\n");
+        body.push_str(&format!(
+            "<h3><a href=\"cotos/{}.html\">{}</a></h3>\n",
+            coto.uuid,
+            escape_html(&coto_title(coto))
+        ));
+        body.push_str(&site.coto_meta(coto, ""));
+        body.push_str(&site.coto_body(coto, ""));
+        body.push_str("</article>\n");
+    }
+    site.write_page("timeline.html", "Timeline", "", &body)?;
+    report.pages += 1;
+
+    // cotos/{coto_id}.html
+    let mut pages: Vec<&Coto> = cotos.values().collect();
+    pages.sort_by_key(|coto| (coto.created_at, coto.uuid.as_uuid()));
+    for coto in pages {
+        if let Some((bytes, media_type)) = coto.media_content() {
+            let path = site.dir.join(media_path(coto.uuid, &media_type));
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(path, bytes.as_ref())?;
+            report.media += 1;
+        }
+
+        let mut body = format!("<h1>{}</h1>\n", escape_html(&coto_title(coto)));
+        body.push_str(&site.coto_meta(coto, "../"));
+        body.push_str(&site.coto_body(coto, "../"));
+        // Itos to cotos without pages are left out not to leak cotos out of the scope.
+        let mut itos = ds.outgoing_itos(&[coto.uuid])?;
+        itos.retain(|ito| site.pages.contains(&ito.target_coto_id));
+        let mut incoming = ds.incoming_itos(&coto.uuid)?;
+        incoming.retain(|ito| site.pages.contains(&ito.source_coto_id));
+        site.write_itos(
+            "Connections",
+            &itos,
+            |ito| ito.target_coto_id,
+            &cotos,
+            &mut body,
+        );
+        site.write_itos(
+            "Linked from",
+            &incoming,
+            |ito| ito.source_coto_id,
+            &cotos,
+            &mut body,
+        );
+        let title = coto_title(coto);
+        site.write_page(&format!("cotos/{}.html", coto.uuid), &title, "../", &body)?;
+        report.pages += 1;
+    }
+
+    // map.html
+    if options.map {
+        let markers: Vec<serde_json::Value> = ds
+            .geolocated_cotos(scope, MAP_MAX_COTOS)?
+            .iter()
+            .filter_map(|coto| {
+                Some(serde_json::json!({
+                    "lat": coto.latitude?,
+                    "lng": coto.longitude?,
+                    "title": coto_title(coto),
+                    "url": site
+                        .pages
+                        .contains(&coto.uuid)
+                        .then(|| format!("cotos/{}.html", coto.uuid)),
+                }))
+            })
+            .collect();
+        let markers = serde_json::to_string(&markers)?.replace("</", "<\\/");
+        let body = MAP.replace("{markers}", &markers);
+        site.write_page("map.html", "Map", "", &body)?;
+        report.pages += 1;
+    }
+
+    Ok(report)
+}
+
+impl NodeState {
+    /// Generates a static site in a blocking thread (see [generate_site]).
+    pub async fn generate_site(
+        &self,
+        scope: Scope,
+        output_dir: PathBuf,
+        options: SiteOptions,
+    ) -> Result<SiteReport, ServiceError> {
+        self.get(move |ds| generate_site(ds, scope, output_dir, &options))
+            .await
+    }
+}
+
+struct Site {
+    dir: PathBuf,
+    title: String,
+    map: bool,
+    cotonoma_names: HashMap<Id<Cotonoma>, String>,
+
+    /// Cotos that have their own pages
+    pages: HashSet<Id<Coto>>,
+}
+
+impl Site {
+    /// Writes a page to `path` relative to the output directory, where `root` is
+    /// the relative path from the page to the output directory.
+    fn write_page(&self, path: &str, title: &str, root: &str, body: &str) -> Result<()> {
+        let title = if title == self.title {
+            escape_html(title)
+        } else {
+            format!("{} - {}", escape_html(title), escape_html(&self.title))
+        };
+        let map_link = if self.map {
+            format!("<a href=\"{root}map.html\">Map</a>")
+        } else {
+            String::new()
+        };
+        let html = format!(
+            "<!DOCTYPE html>\n\
+            <html>\n\
+            <head>\n\
+            <meta charset=\"utf-8\">\n\
+            <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+            <title>{title}</title>\n\
+            <link rel=\"stylesheet\" href=\"{root}style.css\">\n\
+            </head>\n\
+            <body>\n\
+            <nav><a href=\"{root}index.html\">{site}</a>\
+            <a href=\"{root}timeline.html\">Timeline</a>{map_link}</nav>\n\
+            <main>\n{body}</main>\n\
+            </body>\n\
+            </html>\n",
+            site = escape_html(&self.title)
+        );
+        fs::write(self.dir.join(path), html)?;
+        Ok(())
+    }
+
+    /// A link to the page of a coto, or just its title if it doesn't have a page.
+    fn coto_link(&self, coto: &Coto, root: &str) -> String {
+        let title = escape_html(&coto_title(coto));
+        let class = if coto.is_cotonoma {
+            " class=\"cotonoma\""
+        } else {
+            ""
+        };
+        if self.pages.contains(&coto.uuid) {
+            format!(
+                "<a href=\"{root}cotos/{}.html\"{class}>{title}</a>",
+                coto.uuid
+            )
+        } else {
+            format!("<span{class}>{title}</span>")
+        }
+    }
+
+    fn coto_meta(&self, coto: &Coto, root: &str) -> String {
+        let mut meta = vec![coto.created_at().format("%Y-%m-%d %H:%M").to_string()];
+        if let Some(name) = coto
+            .posted_in_id
+            .and_then(|id| self.cotonoma_names.get(&id))
+        {
+            meta.push(format!("in {}", escape_html(name)));
+        }
+        if let Some(start) = coto.datetime_start {
+            let mut range = start.format("%Y-%m-%d %H:%M").to_string();
+            if let Some(end) = coto.datetime_end {
+                range.push_str(&format!(" - {}", end.format("%Y-%m-%d %H:%M")));
+            }
+            meta.push(range);
+        }
+        if let (Some(latitude), Some(longitude)) = (coto.latitude, coto.longitude) {
+            if self.map {
+                meta.push(format!(
+                    "<a href=\"{root}map.html#{latitude},{longitude}\">{latitude}, {longitude}</a>"
+                ));
+            } else {
+                meta.push(format!("{latitude}, {longitude}"));
+            }
+        }
+        format!("<p class=\"meta\">{}</p>\n", meta.join(" · "))
+    }
+
+    fn coto_body(&self, coto: &Coto, root: &str) -> String {
+        let mut body = String::new();
+        if !coto.is_cotonoma {
+            if let Some(content) = coto.content.as_deref() {
+                body.push_str(&render_markdown(content));
+            }
+        }
+        if let Some(media_type) = coto.media_type.as_deref() {
+            if coto.media_content.is_some() && media_type.starts_with("image/") {
+                body.push_str(&format!(
+                    "<p><img src=\"{root}{}\" alt=\"\"></p>\n",
+                    media_path(coto.uuid, media_type)
+                ));
+            }
+        }
+        body
+    }
+
+    fn write_stock(
+        &self,
+        stock: &Graph,
+        coto_id: &Id<Coto>,
+        visited: &mut HashSet<Id<Coto>>,
+        body: &mut String,
+    ) {
+        let Some(itos) = stock.itos.get(coto_id).filter(|itos| !itos.is_empty()) else {
+            return;
+        };
+        let mut itos: Vec<&Ito> = itos.iter().collect();
+        itos.sort_by_key(|ito| ito.order);
+        body.push_str("<ul>\n");
+        for ito in itos {
+            let Some(child) = stock.cotos.get(&ito.target_coto_id) else {
+                continue;
+            };
+            body.push_str("<li>");
+            body.push_str(&ito_description(ito));
+            body.push_str(&self.coto_link(child, ""));
+            if visited.insert(child.uuid) {
+                body.push('\n');
+                self.write_stock(stock, &child.uuid, visited, body);
+            }
+            body.push_str("</li>\n");
+        }
+        body.push_str("</ul>\n");
+    }
+
+    fn write_itos(
+        &self,
+        heading: &str,
+        itos: &[Ito],
+        other_end: impl Fn(&Ito) -> Id<Coto>,
+        cotos: &HashMap<Id<Coto>, Coto>,
+        body: &mut String,
+    ) {
+        if itos.is_empty() {
+            return;
+        }
+        let mut itos: Vec<&Ito> = itos.iter().collect();
+        itos.sort_by_key(|ito| ito.order);
+        body.push_str(&format!("<h2>{heading}</h2>\n<ul>\n"));
+        for ito in itos {
+            if let Some(coto) = cotos.get(&other_end(ito)) {
+                body.push_str(&format!(
+                    "<li>{}{}</li>\n",
+                    ito_description(ito),
+                    self.coto_link(coto, "../")
+                ));
+            }
+        }
+        body.push_str("</ul>\n");
+    }
+}
+
+fn original<'a>(coto: &'a Coto, originals: &'a HashMap<Id<Coto>, Coto>) -> &'a Coto {
+    coto.repost_of_id
+        .and_then(|id| originals.get(&id))
+        .unwrap_or(coto)
+}
+
+fn ito_description(ito: &Ito) -> String {
+    ito.description
+        .as_deref()
+        .map(|description| {
+            format!(
+                "<span class=\"description\">{}</span>",
+                escape_html(description)
+            )
+        })
+        .unwrap_or_default()
+}
+
+/// Renders Markdown into HTML in which raw HTML and `javascript:` links are disabled.
+pub(crate) fn render_markdown(markdown: &str) -> String {
+    let options =
+        Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS;
+    let events = Parser::new_ext(markdown, options).map(|event| match event {
+        Event::Html(html) | Event::InlineHtml(html) => Event::Text(html),
+        Event::Start(Tag::Link {
+            link_type,
+            dest_url,
+            title,
+            id,
+        }) if is_unsafe_url(&dest_url) => Event::Start(Tag::Link {
+            link_type,
+            dest_url: CowStr::Borrowed("#"),
+            title,
+            id,
+        }),
+        Event::Start(Tag::Image {
+            link_type,
+            dest_url,
+            title,
+            id,
+        }) if is_unsafe_url(&dest_url) => Event::Start(Tag::Image {
+            link_type,
+            dest_url: CowStr::Borrowed("#"),
+            title,
+            id,
+        }),
+        event => event,
+    });
+    let mut html = String::new();
+    pulldown_cmark::html::push_html(&mut html, events);
+    html
+}
+
+fn is_unsafe_url(url: &str) -> bool {
+    let url = url.trim_start().to_ascii_lowercase();
+    url.starts_with("javascript:") || url.starts_with("vbscript:") || url.starts_with("data:")
+}
+
+pub(crate) fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+#[cfg(test)]
+mod tests {
+    use googletest::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn render_markdown_safely() {
+        assert_that!(
+            render_markdown("**Hello** <script>alert(1)</script>"),
+            eq("<p><strong>Hello</strong> &lt;script&gt;alert(1)&lt;/script&gt;</p>\n")
+        );
+        assert_that!(
+            render_markdown("[link](javascript:alert(1)) [ok](https://cotoa.me)"),
+            eq("<p><a href=\"#\">link</a> <a href=\"https://cotoa.me\">ok</a></p>\n")
+        );
+    }
+}
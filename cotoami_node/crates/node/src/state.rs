@@ -1,11 +1,14 @@
 //! This module defines the global state ([NodeState]) and functions dealing with it.
 
 use core::future::Future;
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
 use anyhow::{anyhow, Result};
 use cotoami_db::prelude::*;
-use parking_lot::{RwLock, RwLockReadGuard};
+use parking_lot::{Mutex, RwLock, RwLockReadGuard};
 use semver::{Version, VersionReq};
 use tokio::{
     sync::oneshot::Sender,
@@ -52,6 +55,9 @@ struct State {
     client_conns: ClientConnections,
     anonymous_conns: AnonymousConnections,
     parent_services: ParentServices,
+    outbox_locks: Mutex<HashMap<Id<Node>, Arc<tokio::sync::Mutex<()>>>>,
+    handling_requests: Mutex<HashSet<Id<OutboxEntry>>>,
+    media_fetchers: Mutex<HashMap<Id<Node>, bool>>,
     abortables: Abortables,
     local_server_config: RwLock<Option<Arc<ServerConfig>>>,
     plugins: RwLock<PluginSystem>,
@@ -86,6 +92,9 @@ impl NodeState {
             client_conns: ClientConnections::default(),
             anonymous_conns: AnonymousConnections::default(),
             parent_services: ParentServices::default(),
+            outbox_locks: Mutex::new(HashMap::new()),
+            handling_requests: Mutex::new(HashSet::new()),
+            media_fetchers: Mutex::new(HashMap::new()),
             abortables: Abortables::default(),
             local_server_config: RwLock::new(None),
             plugins: RwLock::new(plugins),
@@ -204,6 +213,25 @@ impl NodeState {
 
     pub fn parent_services(&self) -> &ParentServices { &self.inner.parent_services }
 
+    /// Returns the lock to be held while sending the requests in the outbox to a parent.
+    fn outbox_lock(&self, parent_id: Id<Node>) -> Arc<tokio::sync::Mutex<()>> {
+        self.inner
+            .outbox_locks
+            .lock()
+            .entry(parent_id)
+            .or_default()
+            .clone()
+    }
+
+    /// Returns the idempotency keys of the requests from child nodes being handled.
+    fn handling_requests(&self) -> &Mutex<HashSet<Id<OutboxEntry>>> {
+        &self.inner.handling_requests
+    }
+
+    /// Returns the parents from which missing media is being fetched, each with a flag
+    /// whether another round of fetching has been requested in the meantime.
+    fn media_fetchers(&self) -> &Mutex<HashMap<Id<Node>, bool>> { &self.inner.media_fetchers }
+
     pub fn child_privileges(&self, parent_id: &Id<Node>) -> Option<ChildNode> {
         self.server_conns()
             .get(parent_id)
@@ -264,6 +292,14 @@ impl ParentServices {
             .ok_or(anyhow!("Parent disconnected: {parent_id}"))
     }
 
+    pub fn all(&self) -> Vec<(Id<Node>, Box<dyn NodeService>)> {
+        self.0
+            .read()
+            .iter()
+            .map(|(id, s)| (*id, dyn_clone::clone_box(&**s)))
+            .collect()
+    }
+
     fn put(&self, parent_id: Id<Node>, service: Box<dyn NodeService>) {
         self.0.write().insert(parent_id, service);
     }
@@ -1,4 +1,4 @@
-use cotoami_db::prelude::*;
+use cotoami_db::{import::cotoami::ImportPhase, prelude::*};
 
 use crate::{
     service::models::{ActiveClient, NotConnected},
@@ -37,6 +37,44 @@ pub enum LocalNodeEvent {
     ParentDisconnected {
         node_id: Id<Node>,
     },
+    /// A change from a parent has been recorded in the local changelog, but
+    /// failed to be applied to the database. It can be retried later with
+    /// [crate::service::Command::RetryFailedImports].
+    ChangeImportFailed {
+        parent_node_id: Id<Node>,
+        serial_number: i64,
+        error: String,
+    },
+    /// A change that failed to be imported from a parent has been applied to the
+    /// database by [crate::service::Command::RetryFailedImports].
+    ChangeImportRecovered {
+        parent_node_id: Id<Node>,
+        serial_number: i64,
+    },
+    /// The media content omitted from a change from a parent has been fetched
+    /// and stored into the coto.
+    CotoMediaFetched {
+        coto_id: Id<Coto>,
+        parent_node_id: Id<Node>,
+    },
+    /// A request queued in the outbox has been sent to and accepted by the parent.
+    OutboxRequestSent {
+        parent_node_id: Id<Node>,
+        idempotency_key: Id<OutboxEntry>,
+    },
+    /// A request queued in the outbox has been rejected by the parent (ex. the target
+    /// has been deleted or the permission has been revoked in the meantime), and
+    /// removed from the outbox.
+    OutboxRequestRejected {
+        parent_node_id: Id<Node>,
+        idempotency_key: Id<OutboxEntry>,
+        error: String,
+    },
+    ImportProgress {
+        phase: ImportPhase,
+        done: usize,
+        total: usize,
+    },
     PluginEvent(PluginEvent),
 }
 
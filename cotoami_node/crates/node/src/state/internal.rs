@@ -7,10 +7,14 @@ use tokio::task::spawn_blocking;
 
 use crate::state::NodeState;
 
+mod backups;
 mod changes;
+mod compaction;
 mod events;
 mod init;
+mod media;
 mod nodes;
+mod outbox;
 mod parents;
 
 impl NodeState {
@@ -0,0 +1,154 @@
+use std::{fs, path::Path, time::Duration};
+
+use anyhow::{ensure, Result};
+use chrono::NaiveDateTime;
+use tokio::task::spawn_blocking;
+use tracing::{debug, error, info};
+
+use crate::{service::models::Backup, state::NodeState};
+
+const BACKUP_FILE_PREFIX: &str = "cotoami-";
+const BACKUP_FILE_EXTENSION: &str = ".db";
+const BACKUP_TIMESTAMP_FORMAT: &str = "%Y%m%d-%H%M%S%.3f";
+
+impl NodeState {
+    /// Backs up the database into [crate::config::NodeConfig::backup_dir] and deletes
+    /// old backups exceeding [crate::config::NodeConfig::backup_retention].
+    pub(crate) async fn backup(&self) -> Result<Backup> {
+        let (backup_dir, retention) = {
+            let config = self.read_config();
+            (config.backup_dir(), config.backup_retention)
+        };
+        let db = self.db().clone();
+        spawn_blocking(move || {
+            crate::create_dir_if_not_exist(&backup_dir)?;
+            let created_at = cotoami_db::current_datetime();
+            let path = backup_dir.join(backup_file_name(created_at));
+            db.backup_to(&path)?;
+            let backup = Backup {
+                path: path.to_string_lossy().into(),
+                size: fs::metadata(&path)?.len(),
+                created_at,
+            };
+            info!("The database has been backed up to {:?}", backup.path);
+
+            if retention > 0 {
+                let backups = list_backups(&backup_dir)?;
+                let expired = backups.len().saturating_sub(retention);
+                for old in backups.into_iter().take(expired) {
+                    fs::remove_file(&old.path)?;
+                    debug!("An old backup has been deleted: {:?}", old.path);
+                }
+            }
+            Ok(backup)
+        })
+        .await?
+    }
+
+    /// Starts a task to back up the database every
+    /// [crate::config::NodeConfig::backup_interval_hours] if configured.
+    ///
+    /// The interval is counted from the last backup in the backup directory so that
+    /// the schedule is kept across restarts.
+    pub(crate) fn start_scheduled_backups(&self) {
+        let Some(hours) = self.read_config().backup_interval_hours else {
+            debug!("Scheduled backups are disabled.");
+            return;
+        };
+        let interval = Duration::from_secs(hours * 60 * 60);
+        let this = self.clone();
+        self.spawn_task(async move {
+            loop {
+                let last_backup = list_backups(&this.read_config().backup_dir())
+                    .map(|backups| backups.last().map(|backup| backup.created_at))
+                    .unwrap_or_else(|e| {
+                        error!("Couldn't read the backup directory: {e}");
+                        None
+                    });
+                if let Some(last_backup) = last_backup {
+                    let elapsed = (cotoami_db::current_datetime() - last_backup)
+                        .to_std()
+                        .unwrap_or_default();
+                    tokio::time::sleep(interval.saturating_sub(elapsed)).await;
+                }
+                if let Err(e) = this.backup().await {
+                    error!("Scheduled backup failed: {e:?}");
+                    tokio::time::sleep(interval).await;
+                }
+            }
+        });
+    }
+}
+
+fn backup_file_name(created_at: NaiveDateTime) -> String {
+    format!(
+        "{BACKUP_FILE_PREFIX}{}{BACKUP_FILE_EXTENSION}",
+        created_at.format(BACKUP_TIMESTAMP_FORMAT)
+    )
+}
+
+/// Returns the backups in the directory in chronological order.
+fn list_backups(dir: &Path) -> Result<Vec<Backup>> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut backups = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_name = entry.file_name();
+        let Some(created_at) = file_name
+            .to_str()
+            .and_then(|name| name.strip_prefix(BACKUP_FILE_PREFIX))
+            .and_then(|name| name.strip_suffix(BACKUP_FILE_EXTENSION))
+            .and_then(|timestamp| {
+                NaiveDateTime::parse_from_str(timestamp, BACKUP_TIMESTAMP_FORMAT).ok()
+            })
+        else {
+            continue;
+        };
+        ensure!(entry.file_type()?.is_file(), "Not a file: {file_name:?}");
+        backups.push(Backup {
+            path: entry.path().to_string_lossy().into(),
+            size: entry.metadata()?.len(),
+            created_at,
+        });
+    }
+    backups.sort_by_key(|backup| backup.created_at);
+    Ok(backups)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use googletest::prelude::*;
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn list_backups_in_order() -> anyhow::Result<()> {
+        let dir = tempdir()?;
+        let timestamp = |day| {
+            NaiveDate::from_ymd_opt(2026, 1, day)
+                .unwrap()
+                .and_hms_opt(12, 0, 0)
+                .unwrap()
+        };
+        for day in [3, 1, 2] {
+            fs::write(dir.path().join(backup_file_name(timestamp(day))), "")?;
+        }
+        fs::write(dir.path().join("cotoami-broken.db"), "")?;
+        fs::write(dir.path().join("notes.txt"), "")?;
+
+        let backups = list_backups(dir.path())?;
+        assert_that!(
+            backups
+                .iter()
+                .map(|backup| backup.created_at)
+                .collect::<Vec<_>>(),
+            elements_are![eq(&timestamp(1)), eq(&timestamp(2)), eq(&timestamp(3))]
+        );
+        assert_that!(backups[0].path, ends_with("cotoami-20260101-120000.000.db"));
+        Ok(())
+    }
+}
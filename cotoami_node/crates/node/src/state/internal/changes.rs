@@ -2,8 +2,9 @@ use std::{sync::Arc, time::Duration};
 
 use anyhow::{anyhow, bail, Result};
 use cotoami_db::prelude::*;
+use futures::{stream::BoxStream, StreamExt};
 use tokio::task::spawn_blocking;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 
 use crate::{
     service::{
@@ -70,14 +71,33 @@ impl NodeState {
             parent_service.description()
         );
 
-        let import_from = parent_node.changes_received + 1;
+        // Bootstrap from a snapshot if no changes have been received from the parent yet
+        // (a snapshot contains the whole database, so it's not for a subscribing child).
+        // The media omitted from the snapshot will be fetched after the sync.
+        let mut changes_received = parent_node.changes_received;
+        let mut snapshot_installed = false;
+        if changes_received == 0 && parent_node.subscription().is_none() {
+            if let Some(last_serial_number) = self
+                .install_parent_snapshot(&parent_node, parent_service.as_ref())
+                .await?
+            {
+                changes_received = last_serial_number;
+                snapshot_installed = true;
+            }
+        }
+
+        let import_from = changes_received + 1;
         let mut from = import_from;
+        let byte_budget = self.read_config().changes_chunk_bytes;
         loop {
             // Get a chunk of changelog entries from the service
-            let changes = match parent_service.chunk_of_changes(from).await? {
+            let changes = match parent_service.chunk_of_changes(from, byte_budget).await? {
                 ChunkOfChanges::Fetched(changes) => changes,
                 ChunkOfChanges::OutOfRange { max } => {
-                    if from == import_from && parent_node.changes_received == max {
+                    if from == import_from && changes_received == max {
+                        if snapshot_installed {
+                            return Ok(Some((1, max)));
+                        }
                         // A case where the local has already synced with the parent
                         info!("Already synced with: {}", parent_service.description());
                         return Ok(None);
@@ -127,7 +147,8 @@ impl NodeState {
                     last_number_of_chunk,
                     parent_service.description()
                 );
-                return Ok(Some((import_from, last_number_of_chunk)));
+                let range_from = if snapshot_installed { 1 } else { import_from };
+                return Ok(Some((range_from, last_number_of_chunk)));
             } else {
                 from = last_number_of_chunk + 1;
 
@@ -139,14 +160,74 @@ impl NodeState {
         }
     }
 
+    /// Installs a snapshot of the parent if it has more changes than
+    /// [crate::config::NodeConfig::snapshot_threshold], and returns the last serial
+    /// number covered by the snapshot.
+    ///
+    /// If the parent fails to provide a snapshot (ex. an older version), it will
+    /// fall back to replaying the changes by returning `None`.
+    async fn install_parent_snapshot(
+        &self,
+        parent_node: &ParentNode,
+        parent_service: &dyn NodeService,
+    ) -> Result<Option<i64>> {
+        let Some(threshold) = self.read_config().snapshot_threshold else {
+            return Ok(None);
+        };
+        // Only the last serial number is needed, so the smallest chunk will do.
+        let last_serial_number = match parent_service.chunk_of_changes(1, Some(1)).await? {
+            ChunkOfChanges::Fetched(changes) => changes.last_serial_number,
+            ChunkOfChanges::OutOfRange { max } => max,
+        };
+        if last_serial_number <= threshold {
+            return Ok(None);
+        }
+
+        info!(
+            "Fetching a snapshot from {} ({last_serial_number} changes)",
+            parent_service.description()
+        );
+        let snapshot = match parent_service.changelog_snapshot().await {
+            Ok(snapshot) => snapshot,
+            Err(e) => {
+                warn!("Couldn't get a snapshot, falling back to replaying changes: {e}");
+                return Ok(None);
+            }
+        };
+
+        let db = self.db().clone();
+        let parent_node_id = parent_node.node_id;
+        let change_pubsub = self.pubsub().changes().clone();
+        let last_serial_number = snapshot.last_serial_number;
+        spawn_blocking(move || {
+            for log in db
+                .new_session()?
+                .install_snapshot(&snapshot, &parent_node_id)?
+            {
+                change_pubsub.publish(log, None);
+            }
+            Ok::<_, anyhow::Error>(())
+        })
+        .await??;
+        info!(
+            "Installed a snapshot covering changes 1-{last_serial_number} from {}",
+            parent_service.description()
+        );
+        Ok(Some(last_serial_number))
+    }
+
     async fn import_changes(&self, parent_node_id: Id<Node>, changes: Changes) -> Result<()> {
         let db = self.db().clone();
         let change_pubsub = self.pubsub().changes().clone();
+        let event_pubsub = self.pubsub().events().clone();
         spawn_blocking(move || {
             let db = db.new_session()?;
             for change in changes.chunk {
                 debug!("Importing number {} ...", change.serial_number);
                 if let Some(imported_change) = db.import_change(&change, &parent_node_id)? {
+                    if let Some(event) = import_failed_event(&imported_change, parent_node_id) {
+                        event_pubsub.publish(event, None);
+                    }
                     change_pubsub.publish(imported_change, None);
                 }
             }
@@ -200,6 +281,11 @@ impl NodeState {
                 }
             }
             Ok(Some(imported_change)) => {
+                if let Some(event) = import_failed_event(&imported_change, parent_node_id) {
+                    self.pubsub().publish_event(event);
+                } else if imported_change.change.omitted_media().is_some() {
+                    self.fetch_missing_media_in_background(parent_node_id, parent_service);
+                }
                 self.pubsub().publish_change(imported_change);
             }
             Ok(None) => (),
@@ -207,6 +293,55 @@ impl NodeState {
         Ok(())
     }
 
+    /// Returns a stream of the changes to be sent to the operator.
+    ///
+    /// If the operator is a child node with a [ChangeSubscription], the changes irrelevant
+    /// to it will be replaced with placeholders. The stream ends if it fails to filter
+    /// a change, then the child will catch up with the changelog on reconnecting.
+    /// Media content will be omitted from the changes if the child has requested lazy media.
+    pub(crate) fn changes_for(&self, operator: &Operator) -> BoxStream<'static, ChangelogEntry> {
+        let (subscription, lazy_media) = match operator {
+            Operator::ChildNode(child) => (child.subscription(), child.lazy_media),
+            _ => (None, false),
+        };
+        let changes = self.pubsub().changes().subscribe(None::<()>);
+        let changes = if lazy_media {
+            changes.map(ChangelogEntry::omit_media).boxed()
+        } else {
+            changes.boxed()
+        };
+        let Some(subscription) = subscription else {
+            return changes;
+        };
+
+        let db = self.db().clone();
+        let subscription = Arc::new(subscription);
+        async_stream::stream! {
+            for await change in changes {
+                let result = spawn_blocking({
+                    let db = db.clone();
+                    let subscription = subscription.clone();
+                    move || db.new_session()?.filter_changes(vec![change], &subscription)
+                })
+                .await
+                .map_err(anyhow::Error::from)
+                .and_then(|result| result);
+                match result {
+                    Ok(filtered) => {
+                        for change in filtered {
+                            yield change;
+                        }
+                    }
+                    Err(e) => {
+                        error!("Failed to filter a change by subscription: {e}");
+                        break;
+                    }
+                }
+            }
+        }
+        .boxed()
+    }
+
     fn run_sync_with_parent(&self, parent_node_id: Id<Node>, parent_service: Box<dyn NodeService>) {
         tokio::spawn({
             let this = self.clone();
@@ -226,3 +361,19 @@ impl NodeState {
         });
     }
 }
+
+fn import_failed_event(
+    imported_change: &ChangelogEntry,
+    parent_node_id: Id<Node>,
+) -> Option<LocalNodeEvent> {
+    let error = imported_change.import_error.as_ref()?;
+    warn!(
+        "Failed to import change {} from {parent_node_id}: {error}",
+        imported_change.serial_number
+    );
+    Some(LocalNodeEvent::ChangeImportFailed {
+        parent_node_id,
+        serial_number: imported_change.serial_number,
+        error: error.clone(),
+    })
+}
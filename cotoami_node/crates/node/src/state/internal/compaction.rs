@@ -0,0 +1,43 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use cotoami_db::prelude::*;
+use tokio::task::spawn_blocking;
+use tracing::{debug, error, info};
+
+use crate::state::NodeState;
+
+impl NodeState {
+    /// Compacts the changelog entries acknowledged by all the child nodes except for
+    /// the latest [crate::config::NodeConfig::compaction_margin] ones.
+    pub(crate) async fn compact_changelog(&self) -> Result<ChangelogCompaction> {
+        let margin = self.read_config().compaction_margin;
+        let db = self.db().clone();
+        let compaction =
+            spawn_blocking(move || db.new_session()?.compact_changelog(margin)).await??;
+        info!(
+            "The changelog has been compacted up to {} (cleared: {}, merged: {})",
+            compaction.watermark, compaction.cleared, compaction.merged
+        );
+        Ok(compaction)
+    }
+
+    /// Starts a task to compact the changelog every
+    /// [crate::config::NodeConfig::compaction_interval_hours] if configured.
+    pub(crate) fn start_scheduled_compaction(&self) {
+        let Some(hours) = self.read_config().compaction_interval_hours else {
+            debug!("Scheduled changelog compaction is disabled.");
+            return;
+        };
+        let interval = Duration::from_secs(hours * 60 * 60);
+        let this = self.clone();
+        self.spawn_task(async move {
+            loop {
+                if let Err(e) = this.compact_changelog().await {
+                    error!("Scheduled changelog compaction failed: {e:?}");
+                }
+                tokio::time::sleep(interval).await;
+            }
+        });
+    }
+}
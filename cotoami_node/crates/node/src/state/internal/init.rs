@@ -17,6 +17,8 @@ impl NodeState {
         self.start_handling_local_events();
         self.restore_server_conns().await?;
         self.clone().init_plugins();
+        self.start_scheduled_backups();
+        self.start_scheduled_compaction();
         Ok(())
     }
 
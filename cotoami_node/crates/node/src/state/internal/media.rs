@@ -0,0 +1,122 @@
+use anyhow::Result;
+use cotoami_db::prelude::*;
+use tokio::task::spawn_blocking;
+use tracing::{debug, error, info};
+
+use crate::{
+    service::{BackendServiceError, NodeService, NodeServiceExt, ServiceError},
+    state::{LocalNodeEvent, NodeState},
+};
+
+impl NodeState {
+    /// Starts a task to fetch all the missing media (omitted from the changes for
+    /// lazy media replication) from the given parent.
+    ///
+    /// The media from a parent is fetched one by one in a single task. If the task is
+    /// already running, it will fetch the media again after the current round instead
+    /// of starting another task.
+    pub(crate) fn fetch_missing_media_in_background(
+        &self,
+        parent_id: Id<Node>,
+        parent_service: Box<dyn NodeService>,
+    ) {
+        {
+            let mut fetchers = self.media_fetchers().lock();
+            if let Some(requested) = fetchers.get_mut(&parent_id) {
+                *requested = true;
+                return;
+            }
+            fetchers.insert(parent_id, false);
+        }
+        self.spawn_task({
+            let this = self.clone();
+            async move {
+                loop {
+                    if let Err(e) = this.fetch_missing_media(parent_id, &*parent_service).await {
+                        // The rest will be fetched after the next sync with the parent.
+                        error!("Error fetching missing media from {parent_id}: {e:?}");
+                        this.media_fetchers().lock().remove(&parent_id);
+                        return;
+                    }
+                    let mut fetchers = this.media_fetchers().lock();
+                    if fetchers.get(&parent_id) == Some(&true) {
+                        fetchers.insert(parent_id, false);
+                    } else {
+                        fetchers.remove(&parent_id);
+                        return;
+                    }
+                }
+            }
+        });
+    }
+
+    async fn fetch_missing_media(
+        &self,
+        parent_id: Id<Node>,
+        parent_service: &dyn NodeService,
+    ) -> Result<()> {
+        let db = self.db().clone();
+        let missing =
+            spawn_blocking(move || db.new_session()?.missing_media_from(&parent_id)).await??;
+        if missing.is_empty() {
+            return Ok(());
+        }
+        info!(
+            "Fetching {} missing media from {}",
+            missing.len(),
+            parent_service.description()
+        );
+        for media in missing {
+            self.fetch_media(parent_id, parent_service, media).await?;
+        }
+        Ok(())
+    }
+
+    /// Fetches a missing media from the given parent and stores it into the coto.
+    ///
+    /// It returns `None` if the parent doesn't have the media (e.g. the parent has
+    /// not yet fetched it from its own parent or the media has been replaced since)
+    /// or it is no longer missing in the local node. Any other error from the parent
+    /// will be returned so that the caller can stop fetching and retry later.
+    pub(crate) async fn fetch_media(
+        &self,
+        parent_id: Id<Node>,
+        parent_service: &dyn NodeService,
+        missing: MissingMedia,
+    ) -> Result<Option<Coto>> {
+        let coto_id = missing.coto_id;
+        let media = match parent_service
+            .coto_media_by_hash(coto_id, missing.hash.clone())
+            .await
+        {
+            Ok(media) => media,
+            Err(e)
+                if matches!(
+                    e.downcast_ref::<BackendServiceError>(),
+                    Some(BackendServiceError(ServiceError::NotFound(_)))
+                ) =>
+            {
+                debug!("Media of {coto_id} not available in {parent_id}: {e:?}");
+                return Ok(None);
+            }
+            // Failed to communicate with the parent, which should be retried later.
+            Err(e) => return Err(e),
+        };
+
+        let db = self.db().clone();
+        let coto = spawn_blocking(move || {
+            db.new_session()?
+                .store_missing_media(&coto_id, media.content.as_ref())
+        })
+        .await??;
+        if coto.is_some() {
+            debug!("Media of {coto_id} has been fetched from {parent_id}");
+            self.pubsub()
+                .publish_event(LocalNodeEvent::CotoMediaFetched {
+                    coto_id,
+                    parent_node_id: parent_id,
+                });
+        }
+        Ok(coto)
+    }
+}
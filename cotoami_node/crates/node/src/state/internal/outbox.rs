@@ -0,0 +1,247 @@
+//! Outbox of the requests to parent nodes
+//!
+//! A change to an entity owned by a parent is forwarded to the parent, which would fail
+//! while the parent is disconnected. Instead, such a request is queued into the outbox
+//! ([OutboxEntry]) and sent in the queued order after the parent has been reconnected
+//! and synced with.
+//!
+//! Each request in the outbox is sent with an idempotency key, which the parent records
+//! after handling it successfully so that a request sent again after the response has
+//! been lost won't be handled twice. A request rejected by the parent is removed from the outbox
+//! and reported as [LocalNodeEvent::OutboxRequestRejected].
+
+use anyhow::Result;
+use cotoami_db::prelude::*;
+use serde::de::DeserializeOwned;
+use serde_json::json;
+use tokio::task::spawn_blocking;
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    remote::codec::{command_from_json_slice, command_to_json_vec},
+    service::{
+        error::{IntoServiceResult, RequestError},
+        Command, NodeService, ServiceError,
+    },
+    state::{LocalNodeEvent, NodeState},
+};
+
+/// [RequestError] code returned by a parent for a request that has already been handled.
+pub(crate) const DUPLICATE_REQUEST_CODE: &str = "duplicate-request";
+
+/// [RequestError] code returned by a parent for a request that is still being handled,
+/// which should be sent again later.
+pub(crate) const REQUEST_IN_PROGRESS_CODE: &str = "request-in-progress";
+
+/// [RequestError] code returned for a request to a disconnected parent, which has been
+/// queued into the outbox instead.
+pub(crate) const QUEUED_IN_OUTBOX_CODE: &str = "queued-in-outbox";
+
+impl NodeState {
+    /// Forwards a command to a parent node, or queues it into the outbox if the parent
+    /// is disconnected or there are requests queued before it.
+    pub(crate) async fn forward_to_parent<Change>(
+        &self,
+        parent_id: Id<Node>,
+        command: Command,
+    ) -> Result<Change, ServiceError>
+    where
+        Change: DeserializeOwned,
+    {
+        // Forward the change to the remote node only if the node is direct parent
+        // (which means the change won't be forwarded to the grandparents or farther).
+        match self.db().globals().parent_node(&parent_id) {
+            Some(parent) if !parent.forked => (),
+            _ => return Err(ServiceError::Permission),
+        }
+
+        let parent_service = self.parent_services().get(&parent_id);
+        let queued = self
+            .get(move |ds| Ok(ds.next_in_outbox(&parent_id)?.is_some()))
+            .await?;
+        if let (Some(parent_service), false) = (&parent_service, queued) {
+            return parent_service
+                .call(command.into_request())
+                .await?
+                .content()
+                .map_err(ServiceError::from);
+        }
+
+        let bytes = command_to_json_vec(command)?;
+        let entry = self
+            .get(move |ds| ds.enqueue_to_parent(&parent_id, &bytes))
+            .await?;
+        info!(
+            "A request to {parent_id} has been queued into the outbox: {}",
+            entry.idempotency_key
+        );
+        if let Some(parent_service) = parent_service {
+            // Requests queued before this one are still being sent.
+            self.send_outbox_in_background(parent_id, parent_service);
+        }
+        RequestError::new(
+            QUEUED_IN_OUTBOX_CODE,
+            "The parent node is not connected. The request will be sent on reconnection.",
+        )
+        .with_param("idempotency_key", json!(entry.idempotency_key))
+        .into_result()
+    }
+
+    /// Starts a task to send the requests queued in the outbox to the given parent.
+    pub(crate) fn send_outbox_in_background(
+        &self,
+        parent_id: Id<Node>,
+        parent_service: Box<dyn NodeService>,
+    ) {
+        self.spawn_task({
+            let this = self.clone();
+            async move {
+                if let Err(e) = this.send_outbox(parent_id, &*parent_service).await {
+                    error!("Error sending the outbox to {parent_id}: {e:?}");
+                }
+            }
+        });
+    }
+
+    /// Sends the requests in the outbox to the given parent in the queued order.
+    ///
+    /// A request is removed from the outbox when it has been handled or rejected by
+    /// the parent. It stops at the first request that fails to be delivered or fails
+    /// with an error other than a rejection (such as [ServiceError::Server]), which will
+    /// be sent again with the same idempotency key on the next connection.
+    async fn send_outbox(
+        &self,
+        parent_id: Id<Node>,
+        parent_service: &dyn NodeService,
+    ) -> Result<()> {
+        let lock = self.outbox_lock(parent_id);
+        let _guard = lock.lock().await;
+        loop {
+            let db = self.db().clone();
+            let Some(entry) =
+                spawn_blocking(move || db.new_session()?.next_in_outbox(&parent_id)).await??
+            else {
+                return Ok(());
+            };
+            let key = entry.idempotency_key;
+            let result = match command_from_json_slice(entry.command.as_ref()) {
+                Ok(command) => {
+                    let mut request = command.into_request();
+                    request.set_idempotency_key(key);
+                    parent_service.call(request).await?.into_body().map(|_| ())
+                }
+                Err(e) => Err(ServiceError::request(
+                    "invalid-command",
+                    format!("Invalid command: {e}"),
+                )),
+            };
+
+            let event = match result {
+                Ok(()) => LocalNodeEvent::OutboxRequestSent {
+                    parent_node_id: parent_id,
+                    idempotency_key: key,
+                },
+                Err(ServiceError::Request(e)) if e.code == DUPLICATE_REQUEST_CODE => {
+                    debug!("The request {key} has already been handled by {parent_id}.");
+                    LocalNodeEvent::OutboxRequestSent {
+                        parent_node_id: parent_id,
+                        idempotency_key: key,
+                    }
+                }
+                Err(ServiceError::Request(e)) if e.code == REQUEST_IN_PROGRESS_CODE => {
+                    debug!("The request {key} is still being handled by {parent_id}.");
+                    return Ok(());
+                }
+                Err(
+                    e @ (ServiceError::Request(_)
+                    | ServiceError::Input(_)
+                    | ServiceError::NotFound(_)
+                    | ServiceError::Permission),
+                ) => {
+                    info!("The request {key} has been rejected by {parent_id}: {e:?}");
+                    LocalNodeEvent::OutboxRequestRejected {
+                        parent_node_id: parent_id,
+                        idempotency_key: key,
+                        error: format!("{e:?}"),
+                    }
+                }
+                Err(e) => {
+                    // The request may succeed later, so it won't be dequeued.
+                    warn!("Couldn't send the request {key} to {parent_id}: {e:?}");
+                    return Ok(());
+                }
+            };
+
+            let db = self.db().clone();
+            spawn_blocking(move || db.new_session()?.dequeue_from_outbox(entry.serial_number))
+                .await??;
+            self.pubsub().publish_event(event);
+        }
+    }
+
+    /// Starts handling a request with an idempotency key from a child node.
+    ///
+    /// It returns an [IdempotentRequest] if the request has to be handled idempotently,
+    /// or `None` if the request has no key or is not from a child node. If the request
+    /// has already been handled, a [RequestError] with [DUPLICATE_REQUEST_CODE] will be
+    /// returned, or with [REQUEST_IN_PROGRESS_CODE] if it is still being handled.
+    pub(crate) async fn begin_idempotent_request(
+        &self,
+        key: Option<Id<OutboxEntry>>,
+        operator: Option<&Operator>,
+    ) -> Result<Option<IdempotentRequest>, ServiceError> {
+        let (Some(key), Some(Operator::ChildNode(child))) = (key, operator) else {
+            return Ok(None);
+        };
+        if !self.handling_requests().lock().insert(key) {
+            return RequestError::new(
+                REQUEST_IN_PROGRESS_CODE,
+                format!("The request is being handled: {key}"),
+            )
+            .into_result();
+        }
+        let request = IdempotentRequest {
+            state: self.clone(),
+            key,
+            child_id: child.node_id,
+        };
+        if self.get(move |ds| ds.is_request_handled(&key)).await? {
+            RequestError::new(
+                DUPLICATE_REQUEST_CODE,
+                format!("The request has already been handled: {key}"),
+            )
+            .into_result()
+        } else {
+            Ok(Some(request))
+        }
+    }
+}
+
+/// A request from a child node being handled with an idempotency key.
+///
+/// The key will be recorded only when the request has been handled successfully
+/// ([IdempotentRequest::complete]), so that the child node can retry the request
+/// that has failed or been interrupted.
+pub(crate) struct IdempotentRequest {
+    state: NodeState,
+    key: Id<OutboxEntry>,
+    child_id: Id<Node>,
+}
+
+impl IdempotentRequest {
+    /// Records the request as handled.
+    pub(crate) async fn complete(self) {
+        let (key, child_id) = (self.key, self.child_id);
+        if let Err(e) = self
+            .state
+            .get(move |ds| ds.record_handled_request(&key, &child_id))
+            .await
+        {
+            warn!("Couldn't record the handled request {key}: {e:?}");
+        }
+    }
+}
+
+impl Drop for IdempotentRequest {
+    fn drop(&mut self) { self.state.handling_requests().lock().remove(&self.key); }
+}
@@ -25,6 +25,8 @@ impl NodeState {
             let this = self.clone();
             async move {
                 let description = service.description().to_string();
+                let media_service = dyn_clone::clone_box(&*service);
+                let outbox_service = dyn_clone::clone_box(&*service);
                 match this.sync_with_parent(parent_id, service).await {
                     Ok(Some((import_from, _))) => {
                         // Create an ito to the parent cotonoma after the first import.
@@ -34,8 +36,13 @@ impl NodeState {
                                 error!("Error creating an ito: {e:?}");
                             }
                         }
+                        this.fetch_missing_media_in_background(parent_id, media_service);
+                        this.send_outbox_in_background(parent_id, outbox_service);
+                    }
+                    Ok(None) => {
+                        this.fetch_missing_media_in_background(parent_id, media_service);
+                        this.send_outbox_in_background(parent_id, outbox_service);
                     }
-                    Ok(None) => (),
                     Err(e) => {
                         if let Ok(conn) = this.server_conns().try_get(&parent_id) {
                             error!("Error syncing with ({description}): {e:?}");
@@ -1,8 +1,11 @@
-use std::{collections::HashSet, str::FromStr};
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+};
 
 use anyhow::{anyhow, bail, Result};
 use cotoami_db::prelude::*;
-use cotoami_plugin_api::Ancestors;
+use cotoami_plugin_api::{Ancestors, Descendants};
 use extism::UserData;
 
 use crate::state::{
@@ -76,33 +79,26 @@ impl HostFnContext {
         let coto_id: Id<Coto> = Id::from_str(&coto_id)?;
         let mut ds = self.node_state.db().new_session()?;
         let ancestors = ds.ancestors_of(&coto_id)?;
-        let author_ids: HashSet<Id<Node>> = ancestors
-            .iter()
-            .map(|(itos, cotos)| {
-                itos.iter()
-                    .map(|ito| ito.created_by_id)
-                    .chain(cotos.iter().map(|coto| coto.posted_by_id))
-                    .collect::<Vec<Id<Node>>>()
-            })
-            .flatten()
-            .collect();
-        let authors = ds
-            .nodes_map(&author_ids)?
-            .into_iter()
-            .map(|(id, node)| (id.to_string(), into_plugin_node(node)))
-            .collect();
-        let ancestors = ancestors
-            .into_iter()
-            .map(|(itos, cotos)| {
-                (
-                    itos.into_iter().map(into_plugin_ito).collect(),
-                    cotos.into_iter().filter_map(into_plugin_coto).collect(),
-                )
-            })
-            .collect();
+        let (ancestors, authors) = into_plugin_levels(&mut ds, ancestors)?;
         Ok(Ancestors { ancestors, authors })
     }
 
+    #[allow(dead_code)]
+    pub fn descendants_of(
+        &mut self,
+        coto_id: String,
+        max_depth: Option<u32>,
+    ) -> Result<Descendants> {
+        let coto_id: Id<Coto> = Id::from_str(&coto_id)?;
+        let mut ds = self.node_state.db().new_session()?;
+        let descendants = ds.descendants_of(&coto_id, max_depth.map(|depth| depth as usize))?;
+        let (descendants, authors) = into_plugin_levels(&mut ds, descendants)?;
+        Ok(Descendants {
+            descendants,
+            authors,
+        })
+    }
+
     fn try_get_agent(&self) -> Result<Operator> {
         if let Some(config) = self
             .node_state
@@ -133,3 +129,36 @@ impl HostFnContext {
         Ok(cotonoma_id)
     }
 }
+
+type PluginLevels = Vec<(Vec<cotoami_plugin_api::Ito>, Vec<cotoami_plugin_api::Coto>)>;
+
+/// Converts graph levels (such as ancestors or descendants) into the plugin models
+/// along with the authors of the itos and cotos in them.
+fn into_plugin_levels(
+    ds: &mut DatabaseSession<'_>,
+    levels: Vec<(Vec<Ito>, Vec<Coto>)>,
+) -> Result<(PluginLevels, HashMap<String, cotoami_plugin_api::Node>)> {
+    let author_ids: HashSet<Id<Node>> = levels
+        .iter()
+        .flat_map(|(itos, cotos)| {
+            itos.iter()
+                .map(|ito| ito.created_by_id)
+                .chain(cotos.iter().map(|coto| coto.posted_by_id))
+        })
+        .collect();
+    let authors = ds
+        .nodes_map(&author_ids)?
+        .into_iter()
+        .map(|(id, node)| (id.to_string(), into_plugin_node(node)))
+        .collect();
+    let levels = levels
+        .into_iter()
+        .map(|(itos, cotos)| {
+            (
+                itos.into_iter().map(into_plugin_ito).collect(),
+                cotos.into_iter().filter_map(into_plugin_coto).collect(),
+            )
+        })
+        .collect();
+    Ok((levels, authors))
+}
@@ -96,6 +96,13 @@ impl Plugin {
                 ctx.new_user_data(),
                 ancestors_of,
             )
+            .with_function(
+                "descendants_of",
+                [PTR, PTR],
+                [PTR],
+                ctx.new_user_data(),
+                descendants_of,
+            )
             .build()
     }
 
@@ -182,3 +189,10 @@ host_fn!(ancestors_of(context: HostFnContext; coto_id: String) -> Ancestors {
     let mut context = context.lock().unwrap();
     context.ancestors_of(coto_id)
 });
+
+// fn descendants_of(coto_id: String, max_depth: Option<u32>) -> Descendants
+host_fn!(descendants_of(context: HostFnContext; coto_id: String, max_depth: Option<u32>) -> Descendants {
+    let context = context.get()?;
+    let mut context = context.lock().unwrap();
+    context.descendants_of(coto_id, max_depth)
+});
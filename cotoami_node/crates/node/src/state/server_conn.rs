@@ -9,7 +9,7 @@ use tracing::{debug, info};
 use crate::{
     client::{ClientState, HttpClient, SseClient, WebSocketClient},
     service::{
-        models::{CreateClientNodeSession, NodeRole, NotConnected},
+        models::{ClientNodeSession, Compression, CreateClientNodeSession, NodeRole, NotConnected},
         BackendServiceError, RemoteNodeServiceExt, ServiceError,
     },
     state::NodeState,
@@ -75,6 +75,14 @@ impl ServerConnection {
     }
 
     async fn try_connect(self) -> Result<()> {
+        let (http_client, session) = self.log_in().await?;
+        self.start_event_loop(http_client, session.child_privileges)
+            .await
+    }
+
+    /// Logs into the server node to create a session, which can also be used for
+    /// one-off requests without starting an event loop.
+    pub(crate) async fn log_in(&self) -> Result<(HttpClient, ClientNodeSession)> {
         let (_, local_node) = self.node_state.local_node_pair().await?;
         let mut http_client = HttpClient::new(&self.server.url_prefix)?;
 
@@ -82,6 +90,14 @@ impl ServerConnection {
         let password = self
             .server
             .password(self.node_state.read_config().try_get_owner_password()?)?;
+        let parent = if self.to_parent() {
+            self.node_state
+                .db()
+                .globals()
+                .parent_node(&self.server.node_id)
+        } else {
+            None
+        };
         let session = http_client
             .create_client_node_session(CreateClientNodeSession {
                 password,
@@ -93,6 +109,9 @@ impl ServerConnection {
                     Some(NodeRole::Parent)
                 },
                 client_version: self.node_state.version().to_owned(),
+                subscription: parent.as_ref().and_then(|parent| parent.subscription()),
+                lazy_media: parent.is_some_and(|parent| parent.lazy_media),
+                compression: Compression::SUPPORTED.to_vec(),
             })
             .await?;
         info!("Successfully logged in to {}", http_client.url_prefix());
@@ -101,9 +120,7 @@ impl ServerConnection {
             session.server.uuid == self.server.node_id,
             "The remote node ID does not match the registered server ID."
         );
-
-        self.start_event_loop(http_client, session.child_privileges)
-            .await
+        Ok((http_client, session))
     }
 
     /// Starts a event loop using an [HttpClient] that already has a session token.
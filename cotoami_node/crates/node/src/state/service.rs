@@ -4,7 +4,8 @@ use std::{borrow::Cow, sync::Arc};
 
 use anyhow::Result;
 use cotoami_db::prelude::*;
-use futures::future::{BoxFuture, FutureExt};
+use futures::future::FutureExt;
+use serde::de::DeserializeOwned;
 use tokio::task::spawn_blocking;
 
 use crate::{
@@ -12,10 +13,12 @@ use crate::{
     state::NodeState,
 };
 
+mod backups;
 mod changes;
 mod cotonomas;
 mod cotos;
 mod graph;
+mod import;
 mod itos;
 mod nodes;
 mod session;
@@ -40,8 +43,21 @@ impl NodeState {
             Command::EnableAnonymousRead { enable } => {
                 format.serialize(self.enable_anonymous_read(enable, opr?).await)
             }
+            Command::ImportCotoamiJson { json, options } => {
+                format.serialize(self.import_cotoami_json(json, options, opr?).await)
+            }
+            Command::CreateBackup => format.serialize(self.create_backup(opr?).await),
             Command::InitialDataset => format.serialize(self.initial_dataset(opr?).await),
-            Command::ChunkOfChanges { from } => format.serialize(self.chunk_of_changes(from).await),
+            Command::ChunkOfChanges { from, byte_budget } => {
+                format.serialize(self.chunk_of_changes(from, byte_budget, opr.ok()).await)
+            }
+            Command::ChangelogSnapshot => format.serialize(self.changelog_snapshot().await),
+            Command::FailedImports { parent, pagination } => {
+                format.serialize(self.failed_imports(parent, pagination, opr?).await)
+            }
+            Command::RetryFailedImports { serial_numbers } => {
+                format.serialize(self.retry_failed_imports(serial_numbers, opr?).await)
+            }
             Command::NodeDetails { id } => format.serialize(self.node_details(id).await),
             Command::CreateClientNodeSession(input) => {
                 format.serialize(self.create_client_node_session(input).await)
@@ -55,6 +71,18 @@ impl NodeState {
             Command::EditServer { id, values } => {
                 format.serialize(self.edit_server(id, values, opr?).await)
             }
+            Command::SetParentSubscription { id, subscription } => {
+                format.serialize(self.set_parent_subscription(id, subscription, opr?).await)
+            }
+            Command::ForkFromParent { id } => {
+                format.serialize(self.fork_from_parent(id, opr?).await)
+            }
+            Command::ForkDivergence { id } => {
+                format.serialize(self.fork_divergence(id, opr?).await)
+            }
+            Command::ReconcileWithParent { id } => {
+                format.serialize(self.reconcile_with_parent(id, opr?).await)
+            }
             Command::RecentClients { pagination } => {
                 format.serialize(self.recent_clients(pagination, opr?).await)
             }
@@ -112,10 +140,30 @@ impl NodeState {
                     .await,
             ),
             Command::CotoDetails { id } => format.serialize(self.coto_details(id).await),
-            Command::GraphFromCoto { coto } => format.serialize(self.graph_from_coto(coto).await),
-            Command::GraphFromCotonoma { cotonoma } => {
-                format.serialize(self.graph_from_cotonoma(cotonoma).await)
+            Command::CotoMediaByHash { coto, hash } => {
+                format.serialize(self.coto_media_by_hash(coto, hash).await)
+            }
+            Command::GraphFromCoto {
+                coto,
+                options,
+                frontier,
+            } => format.serialize(self.graph_from_coto(coto, options, frontier).await),
+            Command::GraphFromCotonoma {
+                cotonoma,
+                options,
+                frontier,
+            } => format.serialize(self.graph_from_cotonoma(cotonoma, options, frontier).await),
+            Command::PathBetween {
+                from,
+                to,
+                max_depth,
+                directed,
+            } => format.serialize(self.path_between(from, to, max_depth, directed).await),
+            Command::Descendants { coto, max_depth } => {
+                format.serialize(self.descendants(coto, max_depth).await)
             }
+            Command::GraphHealth { scope } => format.serialize(self.graph_health(scope).await),
+            Command::QueryGraph { query } => format.serialize(self.query_graph(query).await),
             Command::PostCoto { input, post_to } => {
                 format.serialize(self.post_coto(input, post_to, opr?).await)
             }
@@ -170,11 +218,23 @@ impl Service<Request> for NodeState {
     fn call(&self, request: Request) -> Self::Future {
         let this = self.clone();
         async move {
-            Ok(Response::new(
-                *request.id(),
-                request.accept(),
-                this.handle_request(request).await,
-            ))
+            let id = *request.id();
+            let accept = request.accept();
+            let idempotent = match this
+                .begin_idempotent_request(
+                    request.idempotency_key().copied(),
+                    request.from.as_deref(),
+                )
+                .await
+            {
+                Ok(idempotent) => idempotent,
+                Err(e) => return Ok(Response::new(id, accept, Err(e))),
+            };
+            let body = this.handle_request(request).await;
+            if let (Some(idempotent), Ok(_)) = (idempotent, &body) {
+                idempotent.complete().await;
+            }
+            Ok(Response::new(id, accept, body))
         }
         .boxed()
     }
@@ -208,6 +268,15 @@ where
                     format!("Couldn't attach the role to: {with}"),
                 );
             }
+            Some(e @ DatabaseError::InvalidGraphQuery { .. }) => {
+                return Self::request("invalid-graph-query", e.to_string());
+            }
+            Some(e @ DatabaseError::AlreadyForkedFromParent { .. }) => {
+                return Self::request("already-forked", e.to_string());
+            }
+            Some(e @ DatabaseError::SubscriptionWidened { .. }) => {
+                return Self::request("subscription-widened", e.to_string());
+            }
             _ => (),
         }
 
@@ -259,23 +328,20 @@ impl NodeState {
         .await?
     }
 
-    pub(crate) async fn change<Input, Change, Apply, Forward>(
+    pub(crate) async fn change<Input, Change, Apply, ToCommand>(
         self,
         target_node_id: Id<Node>,
         input: Input,
         apply: Apply,
-        forward: Forward,
+        to_command: ToCommand,
     ) -> Result<Change, ServiceError>
     where
         Input: Send + 'static,
-        Change: Send + 'static,
+        Change: DeserializeOwned + Send + 'static,
         Apply: FnOnce(&mut DatabaseSession<'_>, Input) -> Result<(Change, ChangelogEntry)>
             + Send
             + 'static,
-        Forward: for<'a> FnOnce(
-            &'a mut dyn NodeService,
-            Input,
-        ) -> BoxFuture<'a, Result<Change, anyhow::Error>>,
+        ToCommand: FnOnce(Input) -> Command,
     {
         let result = spawn_blocking({
             let this = self.clone();
@@ -295,15 +361,8 @@ impl NodeState {
         match result {
             ChangeResult::Changed(change) => Ok(change),
             ChangeResult::ToForward { input } => {
-                // Forward the change to the remote node only if the node is direct parent
-                // (which means the change won't be forwarded to the grandparents or farther).
-                if let Some(mut parent_service) = self.parent_services().get(&target_node_id) {
-                    forward(&mut *parent_service, input)
-                        .await
-                        .map_err(ServiceError::from)
-                } else {
-                    Err(ServiceError::Permission)
-                }
+                self.forward_to_parent(target_node_id, to_command(input))
+                    .await
             }
         }
     }
@@ -0,0 +1,15 @@
+use std::sync::Arc;
+
+use cotoami_db::prelude::*;
+
+use crate::{
+    service::{models::Backup, ServiceError},
+    state::NodeState,
+};
+
+impl NodeState {
+    pub async fn create_backup(&self, operator: Arc<Operator>) -> Result<Backup, ServiceError> {
+        operator.requires_to_be_owner()?;
+        self.backup().await.map_err(ServiceError::from)
+    }
+}
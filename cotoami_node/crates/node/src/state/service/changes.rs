@@ -1,38 +1,150 @@
+use std::sync::Arc;
+
 use anyhow::Result;
 use cotoami_db::prelude::*;
+use validator::Validate;
 
 use crate::{
     service::{
-        models::{Changes, ChunkOfChanges},
+        error::IntoServiceResult,
+        models::{Changes, ChunkOfChanges, FailedImport, Pagination},
         ServiceError,
     },
-    state::NodeState,
+    state::{LocalNodeEvent, NodeState},
 };
 
+const DEFAULT_FAILED_IMPORTS_PAGE_SIZE: i64 = 50;
+
 impl NodeState {
     pub async fn last_change_number(&self) -> Result<Option<i64>, ServiceError> {
         self.get(move |ds| ds.last_change_number()).await
     }
 
-    pub async fn chunk_of_changes(&self, from: i64) -> Result<ChunkOfChanges, ServiceError> {
-        let changes_chunk_size = self.read_config().changes_chunk_size;
-        self.get(
-            move |ds| match ds.chunk_of_changes(from, changes_chunk_size) {
-                Ok((chunk, last_serial_number)) => Ok(ChunkOfChanges::Fetched(Changes {
-                    chunk,
-                    last_serial_number,
-                })),
+    pub async fn changelog_snapshot(&self) -> Result<ChangelogSnapshot, ServiceError> {
+        self.get(move |ds| ds.changelog_snapshot()).await
+    }
+
+    pub async fn failed_imports(
+        &self,
+        parent: Id<Node>,
+        pagination: Pagination,
+        operator: Arc<Operator>,
+    ) -> Result<Page<FailedImport>, ServiceError> {
+        if let Err(errors) = pagination.validate() {
+            return errors.into_result();
+        }
+        self.get(move |ds| {
+            let page = ds
+                .failed_imports(
+                    &parent,
+                    pagination
+                        .page_size
+                        .unwrap_or(DEFAULT_FAILED_IMPORTS_PAGE_SIZE),
+                    pagination.page,
+                    &operator,
+                )?
+                .map(FailedImport::from)
+                .into();
+            Ok(page)
+        })
+        .await
+    }
+
+    /// Applies the changes that failed to be imported again, and notifies the results
+    /// as local events.
+    ///
+    /// The changes applied successfully won't be published to the child nodes, to which
+    /// they have already been published when imported (as the entries in the local
+    /// changelog with the same serial numbers).
+    pub async fn retry_failed_imports(
+        &self,
+        serial_numbers: Vec<i64>,
+        operator: Arc<Operator>,
+    ) -> Result<Vec<FailedImport>, ServiceError> {
+        let entries = self
+            .get(move |ds| ds.retry_failed_imports(&serial_numbers, &operator))
+            .await?;
+        for entry in entries.iter() {
+            let Some(parent_node_id) = entry.imported_from else {
+                continue;
+            };
+            let event = match &entry.import_error {
+                Some(error) => LocalNodeEvent::ChangeImportFailed {
+                    parent_node_id,
+                    serial_number: entry.serial_number,
+                    error: error.clone(),
+                },
+                None => LocalNodeEvent::ChangeImportRecovered {
+                    parent_node_id,
+                    serial_number: entry.serial_number,
+                },
+            };
+            self.pubsub().publish_event(event);
+        }
+        Ok(entries.into_iter().map(FailedImport::from).collect())
+    }
+
+    /// Returns a chunk of changes starting from `from`.
+    ///
+    /// If the operator is a child node, the request means that the child has received
+    /// the changes before `from`, which will be recorded for changelog compaction.
+    /// The changes irrelevant to its [ChangeSubscription] will be replaced with placeholders,
+    /// and media content will be omitted from the changes if it has requested lazy media.
+    ///
+    /// The chunk will be cut within the smaller of the requested `byte_budget` and
+    /// [crate::config::NodeConfig::changes_chunk_bytes].
+    pub async fn chunk_of_changes(
+        &self,
+        from: i64,
+        byte_budget: Option<usize>,
+        operator: Option<Arc<Operator>>,
+    ) -> Result<ChunkOfChanges, ServiceError> {
+        let (changes_chunk_size, byte_budget) = {
+            let config = self.read_config();
+            let byte_budget = match (byte_budget, config.changes_chunk_bytes) {
+                (Some(requested), Some(limit)) => Some(requested.min(limit)),
+                (requested, limit) => requested.or(limit),
+            };
+            (config.changes_chunk_size, byte_budget)
+        };
+        self.get(move |ds| {
+            // The acknowledged number is clamped to the last one in the changelog,
+            // which a child can't have received beyond.
+            let acknowledge = |ds: &mut DatabaseSession<'_>, last_serial_number: i64| {
+                if let Some(Operator::ChildNode(child)) = operator.as_deref() {
+                    ds.acknowledge_changes(&child.node_id, (from - 1).min(last_serial_number))?;
+                }
+                anyhow::Ok(())
+            };
+            match ds.chunk_of_changes(from, changes_chunk_size, byte_budget) {
+                Ok((mut chunk, last_serial_number)) => {
+                    acknowledge(ds, last_serial_number)?;
+                    if let Some(Operator::ChildNode(child)) = operator.as_deref() {
+                        if let Some(subscription) = child.subscription() {
+                            chunk = ds.filter_changes(chunk, &subscription)?;
+                        }
+                        if child.lazy_media {
+                            chunk = chunk.into_iter().map(ChangelogEntry::omit_media).collect();
+                        }
+                    }
+                    Ok(ChunkOfChanges::Fetched(Changes {
+                        chunk,
+                        last_serial_number,
+                    }))
+                }
                 Err(anyhow_err) => {
                     if let Some(DatabaseError::ChangeNumberOutOfRange { max, .. }) =
                         anyhow_err.downcast_ref::<DatabaseError>()
                     {
-                        Ok(ChunkOfChanges::OutOfRange { max: *max })
+                        let max = *max;
+                        acknowledge(ds, max)?;
+                        Ok(ChunkOfChanges::OutOfRange { max })
                     } else {
                         Err(anyhow_err)
                     }
                 }
-            },
-        )
+            }
+        })
         .await
     }
 }
@@ -8,7 +8,7 @@ use crate::{
     service::{
         error::IntoServiceResult,
         models::{CotonomaDetails, Pagination},
-        NodeServiceExt, ServiceError,
+        Command, ServiceError,
     },
     state::NodeState,
 };
@@ -139,7 +139,10 @@ impl NodeState {
             post_to.node_id,
             (input, post_to),
             move |ds, (input, post_to)| ds.post_cotonoma(&input, &post_to, operator.as_ref()),
-            |parent, (input, post_to)| parent.post_cotonoma(input, post_to.uuid),
+            |(input, post_to)| Command::PostCotonoma {
+                input,
+                post_to: post_to.uuid,
+            },
         )
         .await
     }
@@ -155,7 +158,7 @@ impl NodeState {
             cotonoma.node_id,
             (id, name),
             move |ds, (id, name)| ds.rename_cotonoma(&id, &name, operator.as_ref()),
-            |parent, (id, name)| parent.rename_cotonoma(id, name),
+            |(id, name)| Command::RenameCotonoma { id, name },
         )
         .await
     }
@@ -9,7 +9,7 @@ use crate::{
     service::{
         error::{IntoServiceResult, RequestError},
         models::{CotoDetails, CotosRelatedData, GeolocatedCotos, PaginatedCotos, Pagination},
-        NodeServiceExt, ServiceError,
+        Command, ServiceError,
     },
     state::NodeState,
 };
@@ -87,6 +87,55 @@ impl NodeState {
         self.get(move |ds| ds.try_get_coto(&id)).await
     }
 
+    pub async fn coto_media_by_hash(
+        &self,
+        coto_id: Id<Coto>,
+        hash: String,
+    ) -> Result<CotoMedia, ServiceError> {
+        let (media, missing) = self
+            .get({
+                let hash = hash.clone();
+                move |ds| {
+                    Ok((
+                        ds.coto_media_by_hash(&coto_id, &hash)?,
+                        ds.missing_media_of(&coto_id)?,
+                    ))
+                }
+            })
+            .await?;
+        if let Some(media) = media {
+            return Ok(media);
+        }
+
+        // Fetch the media from the parent if it has been omitted from the changes.
+        if let Some(missing) = missing.filter(|missing| missing.hash == hash) {
+            let parent_services = match missing.parent_node_id {
+                Some(parent_id) => self
+                    .parent_services()
+                    .get(&parent_id)
+                    .map(|service| vec![(parent_id, service)])
+                    .unwrap_or_default(),
+                None => self.parent_services().all(),
+            };
+            for (parent_id, parent_service) in parent_services {
+                let fetched = self
+                    .fetch_media(parent_id, &*parent_service, missing.clone())
+                    .await?;
+                if let Some((content, media_type)) =
+                    fetched.as_ref().and_then(Coto::media_content)
+                {
+                    return Ok(CotoMedia {
+                        content,
+                        media_type,
+                    });
+                }
+            }
+        }
+        Err(ServiceError::NotFound(Some(format!(
+            "Media of {coto_id} with the hash: {hash}"
+        ))))
+    }
+
     pub async fn coto_details(&self, id: Id<Coto>) -> Result<CotoDetails, ServiceError> {
         self.get(move |ds| {
             let coto = ds.try_get_coto(&id)?;
@@ -118,7 +167,7 @@ impl NodeState {
             cotonoma.node_id,
             input,
             move |ds, input| ds.post_coto(&input, &post_to, operator.as_ref()),
-            |parent, input| parent.post_coto(input, post_to),
+            |input| Command::PostCoto { input, post_to },
         )
         .await
     }
@@ -137,7 +186,7 @@ impl NodeState {
             coto.node_id,
             diff,
             move |ds, diff| ds.edit_coto(&id, diff, operator.as_ref()),
-            |parent, diff| parent.edit_coto(id, diff),
+            |diff| Command::EditCoto { id, diff },
         )
         .await
     }
@@ -152,7 +201,7 @@ impl NodeState {
             coto.node_id,
             id,
             move |ds, id| ds.promote(&id, operator.as_ref()),
-            |parent, id| parent.promote(id),
+            |id| Command::Promote { id },
         )
         .await
     }
@@ -170,7 +219,7 @@ impl NodeState {
                 let changelog = ds.delete_coto(&coto_id, operator.as_ref())?;
                 Ok((coto_id, changelog))
             },
-            |parent, coto_id| parent.delete_coto(coto_id),
+            |id| Command::DeleteCoto { id },
         )
         .await
     }
@@ -186,7 +235,10 @@ impl NodeState {
             cotonoma.node_id,
             (id, cotonoma),
             move |ds, (id, cotonoma)| ds.repost(&id, &cotonoma, operator.as_ref()),
-            |parent, (id, cotonoma)| parent.repost(id, cotonoma.uuid),
+            |(id, cotonoma)| Command::Repost {
+                id,
+                dest: cotonoma.uuid,
+            },
         )
         .await
     }
@@ -241,14 +293,16 @@ impl NodeState {
             .await?
         } else {
             // Send the change to a remote node.
-            if let Some(parent_service) = self.parent_services().get(&post_to.node_id) {
-                parent_service
-                    .post_subcoto(source_coto_id, input, Some(post_to.uuid), order)
-                    .await
-                    .map_err(ServiceError::from)
-            } else {
-                Err(ServiceError::Permission)
-            }
+            self.forward_to_parent(
+                post_to.node_id,
+                Command::PostSubcoto {
+                    source_coto: source_coto_id,
+                    input,
+                    post_to: Some(post_to.uuid),
+                    order,
+                },
+            )
+            .await
         }
     }
 
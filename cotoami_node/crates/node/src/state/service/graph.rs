@@ -1,16 +1,22 @@
 use anyhow::Result;
 use cotoami_db::prelude::*;
+use itertools::Itertools;
 
 use crate::{
     service::{
+        models::{CotoGraph, CotoPaths, CotosRelatedData},
         ServiceError,
-        models::{CotoGraph, CotosRelatedData},
     },
     state::NodeState,
 };
 
 impl NodeState {
-    pub async fn graph_from_coto(&self, coto_id: Id<Coto>) -> Result<CotoGraph, ServiceError> {
+    pub async fn graph_from_coto(
+        &self,
+        coto_id: Id<Coto>,
+        options: Option<TraversalOptions>,
+        frontier: Option<TraversalFrontier>,
+    ) -> Result<CotoGraph, ServiceError> {
         self.get(move |ds| {
             let root_coto = ds.try_get_coto(&coto_id)?;
             let root_cotonoma = if root_coto.is_cotonoma {
@@ -19,7 +25,7 @@ impl NodeState {
             } else {
                 None
             };
-            graph(ds, root_coto, root_cotonoma)
+            graph(ds, root_coto, root_cotonoma, options, frontier)
         })
         .await
     }
@@ -27,10 +33,83 @@ impl NodeState {
     pub async fn graph_from_cotonoma(
         &self,
         cotonoma_id: Id<Cotonoma>,
+        options: Option<TraversalOptions>,
+        frontier: Option<TraversalFrontier>,
     ) -> Result<CotoGraph, ServiceError> {
         self.get(move |ds| {
             let (root_cotonoma, root_coto) = ds.try_get_cotonoma_pair(&cotonoma_id)?;
-            graph(ds, root_coto, Some(root_cotonoma))
+            graph(ds, root_coto, Some(root_cotonoma), options, frontier)
+        })
+        .await
+    }
+
+    pub async fn path_between(
+        &self,
+        from: Id<Coto>,
+        to: Id<Coto>,
+        max_depth: Option<usize>,
+        directed: bool,
+    ) -> Result<CotoPaths, ServiceError> {
+        self.get(move |ds| {
+            // Ensure both of the cotos exist to distinguish "not found" from "not connected".
+            ds.try_get_coto(&from)?;
+            ds.try_get_coto(&to)?;
+
+            let paths = ds.paths_between(&from, &to, max_depth, directed)?;
+            let coto_ids: Vec<Id<Coto>> = paths
+                .iter()
+                .flatten()
+                .flat_map(|ito| [ito.source_coto_id, ito.target_coto_id])
+                .chain([from, to])
+                .unique()
+                .collect();
+            let cotos = ds.cotos(&coto_ids)?;
+            let related_data = CotosRelatedData::fetch(ds, &cotos)?;
+            Ok(CotoPaths::new(paths, cotos, related_data))
+        })
+        .await
+    }
+
+    pub async fn descendants(
+        &self,
+        coto_id: Id<Coto>,
+        max_depth: Option<usize>,
+    ) -> Result<Vec<(Vec<Ito>, Vec<Coto>)>, ServiceError> {
+        self.get(move |ds| {
+            ds.try_get_coto(&coto_id)?;
+            ds.descendants_of(&coto_id, max_depth)
+        })
+        .await
+    }
+
+    pub async fn graph_health(&self, scope: Scope) -> Result<GraphHealth, ServiceError> {
+        self.get(move |ds| ds.graph_health(scope)).await
+    }
+
+    pub async fn query_graph(&self, query: String) -> Result<Option<CotoGraph>, ServiceError> {
+        let max_cotos = self.read_config().graph_query_max_cotos;
+        self.get(move |ds| {
+            let Some(graph) = ds.query_graph(&query, max_cotos)? else {
+                return Ok(None);
+            };
+            let root_cotonoma = if graph.root().is_cotonoma {
+                let (cotonoma, _) = ds.try_get_cotonoma_by_coto_id(&graph.root_id)?;
+                Some(cotonoma)
+            } else {
+                None
+            };
+            let root_coto_id = graph.root_id;
+            let cotos: Vec<Coto> = graph.cotos.into_values().collect();
+            let related_data = CotosRelatedData::fetch(ds, &cotos)?;
+            let itos: Vec<Ito> = graph.itos.into_values().flatten().collect();
+            Ok(Some(CotoGraph::new(
+                root_coto_id,
+                root_cotonoma,
+                cotos,
+                related_data,
+                itos,
+                None,
+            )))
         })
         .await
     }
@@ -40,9 +119,13 @@ fn graph(
     ds: &mut DatabaseSession<'_>,
     root_coto: Coto,
     root_cotonoma: Option<Cotonoma>,
+    options: Option<TraversalOptions>,
+    frontier: Option<TraversalFrontier>,
 ) -> Result<CotoGraph> {
     let root_coto_id = root_coto.uuid;
-    let graph = ds.graph(root_coto, true)?; // traverse until cotonomas
+    // Traverse until cotonomas by default
+    let options = options.unwrap_or_else(TraversalOptions::until_cotonoma);
+    let graph = ds.traverse_graph(root_coto, options, frontier)?;
     let cotos: Vec<Coto> = graph.cotos.into_values().collect();
     let related_data = CotosRelatedData::fetch(ds, &cotos)?;
     let itos: Vec<Ito> = graph.itos.into_values().flatten().collect();
@@ -52,5 +135,6 @@ fn graph(
         cotos,
         related_data,
         itos,
+        graph.frontier,
     ))
 }
@@ -0,0 +1,42 @@
+use std::{str::FromStr, sync::Arc};
+
+use anyhow::Result;
+use cotoami_db::{
+    import::cotoami::{self, CotoamiExportJson, CotoamiImportOptions, CotoamiImportReport},
+    prelude::*,
+};
+use tokio::task::spawn_blocking;
+
+use crate::{
+    service::ServiceError,
+    state::{LocalNodeEvent, NodeState},
+};
+
+impl NodeState {
+    pub async fn import_cotoami_json(
+        &self,
+        json: String,
+        options: CotoamiImportOptions,
+        operator: Arc<Operator>,
+    ) -> Result<CotoamiImportReport, ServiceError> {
+        operator.requires_to_be_owner()?;
+        let json = CotoamiExportJson::from_str(&json)
+            .map_err(|e| ServiceError::request("invalid-cotoami-json", e.to_string()))?;
+        let this = self.clone();
+        spawn_blocking(move || {
+            let mut ds = this.db().new_session()?;
+            cotoami::import_cotoami_json(&mut ds, json, &options, |event| match event {
+                cotoami::ImportEvent::Progress { phase, done, total } => {
+                    this.pubsub().publish_event(LocalNodeEvent::ImportProgress {
+                        phase,
+                        done,
+                        total,
+                    });
+                }
+                cotoami::ImportEvent::Change(log) => this.pubsub().publish_change(*log),
+            })
+            .map_err(ServiceError::from)
+        })
+        .await?
+    }
+}
@@ -3,9 +3,8 @@ use std::sync::Arc;
 use cotoami_db::prelude::*;
 use validator::Validate;
 
-use super::NodeServiceExt;
 use crate::{
-    service::{error::IntoServiceResult, ServiceError},
+    service::{error::IntoServiceResult, Command, ServiceError},
     state::NodeState,
 };
 
@@ -39,7 +38,7 @@ impl NodeState {
             target_node_id,
             input,
             move |ds, input| ds.create_ito(&input, operator.as_ref()),
-            |parent, input| parent.create_ito(input),
+            Command::CreateIto,
         )
         .await
     }
@@ -68,7 +67,7 @@ impl NodeState {
             ito.node_id,
             diff,
             move |ds, diff| ds.edit_ito(&id, diff, operator.as_ref()),
-            |parent, diff| parent.edit_ito(id, diff),
+            |diff| Command::EditIto { id, diff },
         )
         .await
     }
@@ -84,7 +83,7 @@ impl NodeState {
             ito.node_id,
             new_order,
             move |ds, new_order| ds.change_ito_order(&id, new_order, operator.as_ref()),
-            |parent, new_order| parent.change_ito_order(id, new_order),
+            |new_order| Command::ChangeItoOrder { id, new_order },
         )
         .await
     }
@@ -102,7 +101,7 @@ impl NodeState {
                 let changelog = ds.delete_ito(&ito_id, operator.as_ref())?;
                 Ok((ito_id, changelog))
             },
-            |parent, ito_id| parent.delete_ito(ito_id),
+            |id| Command::DeleteIto { id },
         )
         .await
     }
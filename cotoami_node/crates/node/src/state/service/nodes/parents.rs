@@ -2,8 +2,15 @@ use std::sync::Arc;
 
 use anyhow::Result;
 use cotoami_db::prelude::*;
+use tracing::{debug, info};
 
-use crate::{service::ServiceError, state::NodeState};
+use crate::{
+    service::{
+        models::{ChunkOfChanges, ForkDivergence},
+        BackendServiceError, NodeService, NodeServiceExt, ServiceError,
+    },
+    state::{NodeState, ServerConnection},
+};
 
 impl NodeState {
     pub async fn parent_nodes(
@@ -12,4 +19,175 @@ impl NodeState {
     ) -> Result<Vec<ParentNode>, ServiceError> {
         self.get(move |ds| ds.parent_nodes(&operator)).await
     }
+
+    /// Sets the [ChangeSubscription] to be requested to a parent node and reconnects
+    /// to the parent to start a session with it.
+    pub async fn set_parent_subscription(
+        &self,
+        parent_id: Id<Node>,
+        subscription: Option<ChangeSubscription>,
+        operator: Arc<Operator>,
+    ) -> Result<ParentNode, ServiceError> {
+        let parent = self
+            .get({
+                let operator = operator.clone();
+                move |ds| ds.set_parent_subscription(&parent_id, subscription.as_ref(), &operator)
+            })
+            .await?;
+        if self.server_conns().contains(&parent_id) {
+            self.reconnect_to_server(parent_id, operator).await?;
+        }
+        Ok(parent)
+    }
+
+    /// Forks the local node from a parent node and disconnects from the parent.
+    pub async fn fork_from_parent(
+        &self,
+        parent_id: Id<Node>,
+        operator: Arc<Operator>,
+    ) -> Result<ParentNode, ServiceError> {
+        let parent = self
+            .get({
+                let operator = operator.clone();
+                move |ds| ds.fork_from_parent(&parent_id, &operator)
+            })
+            .await?;
+        if let Some(conn) = self.server_conns().get(&parent_id) {
+            // Replace the connection with a disabled one.
+            conn.disable().await;
+            let server = self.server_node(parent_id, operator).await?;
+            self.server_conns()
+                .put(parent_id, ServerConnection::new(server, self.clone()));
+        }
+        self.client_conns().disconnect(&parent_id);
+        Ok(parent)
+    }
+
+    /// Returns a [ForkDivergence] between the local node and a forked parent node.
+    ///
+    /// The changes made in the parent can be fetched only if the parent is a server
+    /// of the local node, to which a one-off session will be created.
+    pub async fn fork_divergence(
+        &self,
+        parent_id: Id<Node>,
+        operator: Arc<Operator>,
+    ) -> Result<ForkDivergence, ServiceError> {
+        let (parent, local_changes) = self
+            .get({
+                let operator = operator.clone();
+                move |ds| {
+                    let parent = ds
+                        .parent_node(&parent_id, &operator)?
+                        .ok_or(DatabaseError::not_found(EntityKind::ParentNode, parent_id))?;
+                    let local_changes = ds.diverged_changes(&parent_id, &operator)?;
+                    Ok((parent, local_changes))
+                }
+            })
+            .await?;
+        let local_changes = Arc::new(local_changes);
+        let (parent_changes, conflicts) = match self
+            .summarize_parent_changes(&parent, &local_changes, &operator)
+            .await
+        {
+            Some((summaries, conflicts)) => (Some(summaries), conflicts),
+            None => (None, Vec::new()),
+        };
+        Ok(ForkDivergence {
+            parent,
+            local_changes: Arc::unwrap_or_clone(local_changes),
+            parent_changes,
+            conflicts,
+        })
+    }
+
+    /// Re-attaches the local node to a forked parent node and reconnects to the parent
+    /// to import the changes made during the fork. The [ForkDivergence] before the
+    /// re-attachment will be returned as a report of the conflicts.
+    pub async fn reconcile_with_parent(
+        &self,
+        parent_id: Id<Node>,
+        operator: Arc<Operator>,
+    ) -> Result<ForkDivergence, ServiceError> {
+        let divergence = self.fork_divergence(parent_id, operator.clone()).await?;
+        let parent = self
+            .get({
+                let operator = operator.clone();
+                move |ds| ds.reattach_to_parent(&parent_id, &operator)
+            })
+            .await?;
+        if self.server_conns().contains(&parent_id) {
+            self.reconnect_to_server(parent_id, operator).await?;
+        }
+        Ok(ForkDivergence {
+            parent,
+            ..divergence
+        })
+    }
+
+    /// Summarizes the changes made in a forked parent that have not yet been imported
+    /// along with the conflicts with the `local_changes`, or returns `None` if the parent
+    /// is not a server or can't be reached.
+    async fn summarize_parent_changes(
+        &self,
+        parent: &ParentNode,
+        local_changes: &Arc<Vec<ChangelogEntry>>,
+        operator: &Arc<Operator>,
+    ) -> Option<(Vec<ChangeSummary>, Vec<ForkConflict>)> {
+        let conn = self.server_conns().get(&parent.node_id)?;
+        let result = async {
+            let (http_client, _) = conn.log_in().await?;
+            let summaries = self
+                .summarize_changes(&http_client, parent, local_changes, operator)
+                .await;
+            if let Err(e) = http_client.delete_session().await {
+                debug!("Couldn't delete the session with {}: {e:?}", parent.node_id);
+            }
+            summaries
+        };
+        match result.await {
+            Ok(summaries) => Some(summaries),
+            Err(e) => {
+                info!(
+                    "Couldn't fetch the changes from the forked parent {}: {e:?}",
+                    parent.node_id
+                );
+                None
+            }
+        }
+    }
+
+    /// Fetches the unimported changes from the `service` of a parent chunk by chunk and
+    /// summarizes each chunk not to hold all the changes at once.
+    async fn summarize_changes(
+        &self,
+        service: &dyn NodeService,
+        parent: &ParentNode,
+        local_changes: &Arc<Vec<ChangelogEntry>>,
+        operator: &Arc<Operator>,
+    ) -> Result<(Vec<ChangeSummary>, Vec<ForkConflict>)> {
+        let parent_id = parent.node_id;
+        let byte_budget = self.read_config().changes_chunk_bytes;
+        let mut from = parent.changes_received + 1;
+        let (mut summaries, mut conflicts) = (Vec::new(), Vec::new());
+        while let ChunkOfChanges::Fetched(chunk) =
+            service.chunk_of_changes(from, byte_budget).await?
+        {
+            let Some(last) = chunk.chunk.last() else { break };
+            from = last.serial_number + 1;
+            let (chunk_summaries, chunk_conflicts) = self
+                .get({
+                    let local_changes = local_changes.clone();
+                    let operator = operator.clone();
+                    move |ds| ds.fork_conflicts(&parent_id, &chunk.chunk, &local_changes, &operator)
+                })
+                .await
+                .map_err(BackendServiceError)?;
+            summaries.extend(chunk_summaries);
+            conflicts.extend(chunk_conflicts);
+            if from > chunk.last_serial_number {
+                break;
+            }
+        }
+        Ok((summaries, conflicts))
+    }
 }
@@ -94,6 +94,8 @@ impl NodeState {
         };
 
         // Log into the server to create a session
+        let subscription = input.subscription.clone();
+        let lazy_media = input.lazy_media;
         let (client_session, http_client) = self.log_into_server(input).await?;
         let server_id = client_session.server.uuid;
 
@@ -118,6 +120,15 @@ impl NodeState {
                     &operator,
                 )?;
 
+                // Save the subscription to be requested on reconnecting to the parent
+                if let (Some(subscription), DatabaseRole::Parent(_)) = (&subscription, &server_role)
+                {
+                    ds.set_parent_subscription(&server_id, Some(subscription), &operator)?;
+                }
+                if let (true, DatabaseRole::Parent(_)) = (lazy_media, &server_role) {
+                    ds.set_parent_lazy_media(&server_id, true, &operator)?;
+                }
+
                 // Save the password in the ServerNode for auto-login
                 if let Some(password) = password {
                     server = ds.save_server_password(
@@ -198,6 +209,13 @@ impl NodeState {
                 .await?;
         }
 
+        // Set lazy media (only for a parent)
+        if let Some(lazy_media) = values.lazy_media {
+            let operator = operator.clone();
+            self.get(move |ds| ds.set_parent_lazy_media(&server_id, lazy_media, &operator))
+                .await?;
+        }
+
         // Recreate a connection with the new settings
         let server = self.reconnect_to_server(server_id, operator).await?;
 
@@ -7,7 +7,7 @@ use tracing::debug;
 
 use crate::{
     service::{
-        models::{ClientNodeSession, CreateClientNodeSession, NodeRole, SessionToken},
+        models::{ClientNodeSession, Compression, CreateClientNodeSession, NodeRole, SessionToken},
         ServiceError,
     },
     state::NodeState,
@@ -78,6 +78,17 @@ impl NodeState {
                 debug!("Password changed.");
             }
 
+            // Record the replication options of a child
+            let db_role = match db_role {
+                Some(DatabaseRole::Child(_)) => {
+                    ds.set_child_subscription(&client.node_id, input.subscription.as_ref())?;
+                    Some(DatabaseRole::Child(
+                        ds.set_child_lazy_media(&client.node_id, input.lazy_media)?,
+                    ))
+                }
+                db_role => db_role,
+            };
+
             // Import the client node
             if let Some((_, changelog)) = ds.import_node(&input.client)? {
                 change_pubsub.publish(changelog, None);
@@ -99,6 +110,7 @@ impl NodeState {
                 } else {
                     None
                 },
+                compression: Compression::negotiate(&input.compression),
             })
         })
         .await?
@@ -121,6 +133,7 @@ impl NodeState {
             server: local_node,
             server_root: self.local_node_root().await?,
             child_privileges: None,
+            compression: Compression::negotiate(&input.compression),
         })
     }
 }
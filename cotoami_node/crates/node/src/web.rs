@@ -4,10 +4,11 @@ use std::{future::IntoFuture, net::SocketAddr, sync::Arc, time::Duration};
 
 use anyhow::Result;
 use axum::{
+    body::Body,
     extract::{OriginalUri, Request},
     http::{
         header::{self, HeaderName, HeaderValue},
-        StatusCode, Uri,
+        HeaderMap, StatusCode, Uri,
     },
     middleware,
     middleware::Next,
@@ -20,7 +21,10 @@ use axum_extra::{
     headers,
 };
 use bytes::Bytes;
-use cotoami_db::{prelude::ClientSession, rmp_serde};
+use cotoami_db::{
+    prelude::{ClientSession, Operator},
+    rmp_serde,
+};
 use futures::TryFutureExt;
 use mime::Mime;
 use tokio::{
@@ -31,11 +35,17 @@ use tokio::{
 use tower_http::{timeout::TimeoutLayer, trace::TraceLayer};
 use tracing::{debug, error, info};
 
-use crate::{config::ServerConfig, service::ServiceError, state::NodeState};
+use crate::{
+    config::ServerConfig,
+    remote::codec::compression,
+    service::{models::Compression, ServiceError},
+    state::NodeState,
+};
 
 mod csrf;
 mod data;
 mod events;
+mod feeds;
 mod session;
 mod ws;
 
@@ -102,6 +112,7 @@ fn routes(enable_websocket: bool) -> Router<NodeState> {
         .route("/", get(|| async { "Cotoami Node API" }))
         .nest("/session", session::routes())
         .nest("/events", events::routes())
+        .nest("/feeds", feeds::routes())
         .nest("/data", data::routes());
 
     if enable_websocket {
@@ -191,6 +202,79 @@ impl headers::Header for Accept {
     }
 }
 
+/////////////////////////////////////////////////////////////////////////////
+// Compression
+/////////////////////////////////////////////////////////////////////////////
+
+// https://github.com/rust-lang/rust-clippy/issues/9776
+#[allow(clippy::declare_interior_mutable_const)]
+pub(crate) const COMPRESSION_HEADER_NAME: HeaderName =
+    HeaderName::from_static("x-cotoami-compression");
+
+/// Returns the [Compression] requested by a client that has agreed on it in the session
+/// (See [crate::remote::codec::compression]).
+fn requested_compression(headers: &HeaderMap) -> Option<Compression> {
+    headers
+        .get(COMPRESSION_HEADER_NAME)
+        .and_then(|value| value.to_str().ok())
+        .and_then(Compression::from_name)
+}
+
+/// A [Content] to be compressed with the requested [Compression] if any.
+struct Compressed<T>(Content<T>, Option<Compression>);
+
+impl<T> IntoResponse for Compressed<T>
+where
+    T: serde::Serialize,
+{
+    fn into_response(self) -> Response {
+        let Compressed(Content(content, accept), Some(compression)) = self else {
+            return self.0.into_response();
+        };
+        let (content_type, bytes) = if accept.contains(mime::APPLICATION_MSGPACK) {
+            (
+                HeaderValue::from_static(mime::APPLICATION_MSGPACK.as_ref()),
+                rmp_serde::to_vec(&content).map_err(anyhow::Error::from),
+            )
+        } else {
+            (
+                HeaderValue::from_static(mime::APPLICATION_JSON.as_ref()),
+                serde_json::to_vec(&content).map_err(anyhow::Error::from),
+            )
+        };
+        let headers = [
+            (header::CONTENT_TYPE, content_type),
+            (
+                header::CONTENT_ENCODING,
+                HeaderValue::from_static(compression.name()),
+            ),
+        ];
+        let bytes = match bytes {
+            Ok(bytes) if bytes.len() >= compression::MIN_SIZE_TO_COMPRESS_IN_BLOCKING => {
+                // A large body will be compressed in a blocking thread while being sent.
+                let compressed = compression::run(bytes.len(), move || {
+                    compression::compress(&bytes, compression)
+                });
+                let body = Body::from_stream(futures::stream::once(compressed));
+                return (headers, body).into_response();
+            }
+            bytes => bytes,
+        };
+        match bytes.and_then(|bytes| compression::compress(&bytes, compression)) {
+            Ok(bytes) => (headers, bytes).into_response(),
+            Err(err) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                [(
+                    header::CONTENT_TYPE,
+                    HeaderValue::from_static(mime::TEXT_PLAIN_UTF_8.as_ref()),
+                )],
+                err.to_string(),
+            )
+                .into_response(),
+        }
+    }
+}
+
 /////////////////////////////////////////////////////////////////////////////
 // Error
 /////////////////////////////////////////////////////////////////////////////
@@ -310,3 +394,37 @@ async fn require_operator(
         Err(ServiceError::Permission)
     }
 }
+
+/////////////////////////////////////////////////////////////////////////////
+// Idempotency
+/////////////////////////////////////////////////////////////////////////////
+
+// https://github.com/rust-lang/rust-clippy/issues/9776
+#[allow(clippy::declare_interior_mutable_const)]
+pub(crate) const IDEMPOTENCY_KEY_HEADER_NAME: HeaderName =
+    HeaderName::from_static("idempotency-key");
+
+/// A middleware function to handle a request with an idempotency key from a child node
+/// only once (See [NodeState::begin_idempotent_request]).
+///
+/// This middleware has to be placed after the [require_operator] middleware.
+async fn handle_idempotently(
+    Extension(state): Extension<NodeState>,
+    Extension(operator): Extension<Operator>,
+    request: Request,
+    next: Next,
+) -> Result<Response, ServiceError> {
+    let key = request
+        .headers()
+        .get(IDEMPOTENCY_KEY_HEADER_NAME)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok());
+    let Some(idempotent) = state.begin_idempotent_request(key, Some(&operator)).await? else {
+        return Ok(next.run(request).await);
+    };
+    let response = next.run(request).await;
+    if response.status().is_success() {
+        idempotent.complete().await;
+    }
+    Ok(response)
+}
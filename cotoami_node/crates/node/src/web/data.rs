@@ -30,6 +30,7 @@ pub(super) fn routes() -> Router<NodeState> {
         .nest("/cotos", cotos::routes())
         .nest("/cotonomas", cotonomas::routes())
         .nest("/itos", itos::routes())
+        .layer(middleware::from_fn(super::handle_idempotently))
         .layer(middleware::from_fn(super::require_operator))
         .layer(middleware::from_fn(super::require_session))
 }
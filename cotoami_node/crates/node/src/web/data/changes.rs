@@ -1,18 +1,32 @@
+use std::sync::Arc;
+
 use axum::{
-    extract::{Query, State},
-    routing::get,
-    Router,
+    extract::{Json, Path, Query, State},
+    http::HeaderMap,
+    routing::{get, post},
+    Extension, Router,
 };
 use axum_extra::TypedHeader;
+use cotoami_db::prelude::*;
 use validator::Validate;
 
 use crate::{
-    service::{error::IntoServiceResult, models::ChunkOfChanges, ServiceError},
+    service::{
+        error::IntoServiceResult,
+        models::{ChunkOfChanges, FailedImport, Pagination},
+        ServiceError,
+    },
     state::NodeState,
-    web::{Accept, Content},
+    web::{requested_compression, Accept, Compressed, Content},
 };
 
-pub(super) fn routes() -> Router<NodeState> { Router::new().route("/", get(chunk_of_changes)) }
+pub(super) fn routes() -> Router<NodeState> {
+    Router::new()
+        .route("/", get(chunk_of_changes))
+        .route("/snapshot", get(changelog_snapshot))
+        .route("/failed/{parent_id}", get(failed_imports))
+        .route("/retry", post(retry_failed_imports))
+}
 
 /////////////////////////////////////////////////////////////////////////////
 // GET /api/data/changes
@@ -22,19 +36,71 @@ pub(super) fn routes() -> Router<NodeState> { Router::new().route("/", get(chunk
 struct Position {
     #[validate(required, range(min = 1))]
     pub from: Option<i64>,
+
+    #[validate(range(min = 1))]
+    pub byte_budget: Option<usize>,
 }
 
 async fn chunk_of_changes(
     State(state): State<NodeState>,
+    Extension(operator): Extension<Operator>,
     TypedHeader(accept): TypedHeader<Accept>,
+    headers: HeaderMap,
     Query(position): Query<Position>,
-) -> Result<Content<ChunkOfChanges>, ServiceError> {
+) -> Result<Compressed<ChunkOfChanges>, ServiceError> {
     if let Err(errors) = position.validate() {
         return errors.into_result();
     }
     let from = position.from.unwrap_or_else(|| unreachable!());
     state
-        .chunk_of_changes(from)
+        .chunk_of_changes(from, position.byte_budget, Some(Arc::new(operator)))
+        .await
+        .map(|changes| Compressed(Content(changes, accept), requested_compression(&headers)))
+}
+
+/////////////////////////////////////////////////////////////////////////////
+// GET /api/data/changes/snapshot
+/////////////////////////////////////////////////////////////////////////////
+
+async fn changelog_snapshot(
+    State(state): State<NodeState>,
+    TypedHeader(accept): TypedHeader<Accept>,
+) -> Result<Content<ChangelogSnapshot>, ServiceError> {
+    state
+        .changelog_snapshot()
+        .await
+        .map(|snapshot| Content(snapshot, accept))
+}
+
+/////////////////////////////////////////////////////////////////////////////
+// GET /api/data/changes/failed/{parent_id}
+/////////////////////////////////////////////////////////////////////////////
+
+async fn failed_imports(
+    State(state): State<NodeState>,
+    Extension(operator): Extension<Operator>,
+    TypedHeader(accept): TypedHeader<Accept>,
+    Path(parent_id): Path<Id<Node>>,
+    Query(pagination): Query<Pagination>,
+) -> Result<Content<Page<FailedImport>>, ServiceError> {
+    state
+        .failed_imports(parent_id, pagination, Arc::new(operator))
+        .await
+        .map(|page| Content(page, accept))
+}
+
+/////////////////////////////////////////////////////////////////////////////
+// POST /api/data/changes/retry
+/////////////////////////////////////////////////////////////////////////////
+
+async fn retry_failed_imports(
+    State(state): State<NodeState>,
+    Extension(operator): Extension<Operator>,
+    TypedHeader(accept): TypedHeader<Accept>,
+    Json(serial_numbers): Json<Vec<i64>>,
+) -> Result<Content<Vec<FailedImport>>, ServiceError> {
+    state
+        .retry_failed_imports(serial_numbers, Arc::new(operator))
         .await
-        .map(|changes| Content(changes, accept))
+        .map(|results| Content(results, accept))
 }
@@ -11,7 +11,7 @@ use cotoami_db::prelude::*;
 
 use crate::{
     service::{
-        models::{CotoGraph, CotonomaDetails, Pagination},
+        models::{CotoGraph, CotonomaDetails, GraphTraversal, Pagination},
         ServiceError,
     },
     state::NodeState,
@@ -28,7 +28,7 @@ pub(super) fn routes() -> Router<NodeState> {
         .route("/partial/{partial}", get(cotonomas_by_partial))
         .route("/{cotonoma_id}", get(cotonoma))
         .route("/{cotonoma_id}/details", get(cotonoma_details))
-        .route("/{cotonoma_id}/graph", get(graph))
+        .route("/{cotonoma_id}/graph", get(graph).post(traverse_graph))
         .route("/{cotonoma_id}/rename", put(rename_cotonoma))
         .nest("/{cotonoma_id}/subs", subs::routes())
         .nest("/{cotonoma_id}/cotos", cotos::routes())
@@ -143,7 +143,23 @@ async fn graph(
     Path(cotonoma_id): Path<Id<Cotonoma>>,
 ) -> Result<Content<CotoGraph>, ServiceError> {
     state
-        .graph_from_cotonoma(cotonoma_id)
+        .graph_from_cotonoma(cotonoma_id, None, None)
+        .await
+        .map(|graph| Content(graph, accept))
+}
+
+/////////////////////////////////////////////////////////////////////////////
+// POST /api/data/cotonomas/{cotonoma_id}/graph
+/////////////////////////////////////////////////////////////////////////////
+
+async fn traverse_graph(
+    State(state): State<NodeState>,
+    TypedHeader(accept): TypedHeader<Accept>,
+    Path(cotonoma_id): Path<Id<Cotonoma>>,
+    Json(traversal): Json<GraphTraversal>,
+) -> Result<Content<CotoGraph>, ServiceError> {
+    state
+        .graph_from_cotonoma(cotonoma_id, traversal.options, traversal.frontier)
         .await
         .map(|graph| Content(graph, accept))
 }
@@ -27,6 +27,7 @@ pub(super) fn routes() -> Router<NodeState> {
         .route("/cotonomas", get(recent_cotonoma_cotos))
         .route("/repost", post(repost))
         .route("/geolocated", get(geolocated_cotos))
+        .route("/graph-health", get(graph_health))
         .route("/search/{query}", get(search_cotos))
         .route("/search/cotonomas/{query}", get(search_cotonoma_cotos))
 }
@@ -193,6 +194,22 @@ async fn geolocated_cotos(
         .map(|cotos| Content(cotos, accept))
 }
 
+/////////////////////////////////////////////////////////////////////////////
+// GET /api/data/cotonomas/:cotonoma_id/cotos/graph-health
+/////////////////////////////////////////////////////////////////////////////
+
+async fn graph_health(
+    State(state): State<NodeState>,
+    TypedHeader(accept): TypedHeader<Accept>,
+    Path(cotonoma_id): Path<Id<Cotonoma>>,
+    Query(cotos_query): Query<CotosQuery>,
+) -> Result<Content<GraphHealth>, ServiceError> {
+    state
+        .graph_health(cotos_query.scope(cotonoma_id))
+        .await
+        .map(|health| Content(health, accept))
+}
+
 /////////////////////////////////////////////////////////////////////////////
 // GET /api/data/cotonomas/:cotonoma_id/cotos/search/:query
 /////////////////////////////////////////////////////////////////////////////
@@ -12,7 +12,10 @@ use cotoami_db::prelude::*;
 
 use crate::{
     service::{
-        models::{CotoDetails, CotoGraph, GeolocatedCotos, PaginatedCotos, Pagination},
+        models::{
+            CotoDetails, CotoGraph, CotoPaths, GeolocatedCotos, GraphTraversal, PaginatedCotos,
+            Pagination,
+        },
         ServiceError,
     },
     state::NodeState,
@@ -24,6 +27,8 @@ pub(super) fn routes() -> Router<NodeState> {
         .route("/", get(recent_cotos))
         .route("/cotonomas", get(recent_cotonoma_cotos))
         .route("/geolocated", get(geolocated_cotos))
+        .route("/graph-health", get(graph_health))
+        .route("/graph-query", get(query_graph))
         .route(
             "/geo/{sw_lng}/{sw_lat}/{ne_lng}/{ne_lat}",
             get(cotos_in_geo_bounds),
@@ -31,11 +36,14 @@ pub(super) fn routes() -> Router<NodeState> {
         .route("/search/{query}", get(search_cotos))
         .route("/search/cotonomas/{query}", get(search_cotonoma_cotos))
         .route("/{coto_id}/details", get(coto_details))
+        .route("/{coto_id}/media/{hash}", get(coto_media_by_hash))
         .route("/{coto_id}/cotonoma", get(cotonoma))
         .route("/{coto_id}", put(edit_coto).delete(delete_coto))
         .route("/{coto_id}/promote", put(promote))
         .route("/{coto_id}/itos", get(sibling_itos))
-        .route("/{coto_id}/graph", get(graph))
+        .route("/{coto_id}/graph", get(graph).post(traverse_graph))
+        .route("/{coto_id}/paths/{to_coto_id}", get(paths))
+        .route("/{coto_id}/descendants", get(descendants))
         .route("/{coto_id}/subcotos", post(post_subcoto))
 }
 
@@ -83,6 +91,40 @@ async fn geolocated_cotos(
         .map(|cotos| Content(cotos, accept))
 }
 
+/////////////////////////////////////////////////////////////////////////////
+// GET /api/data/cotos/graph-health
+/////////////////////////////////////////////////////////////////////////////
+
+async fn graph_health(
+    State(state): State<NodeState>,
+    TypedHeader(accept): TypedHeader<Accept>,
+) -> Result<Content<GraphHealth>, ServiceError> {
+    state
+        .graph_health(Scope::All)
+        .await
+        .map(|health| Content(health, accept))
+}
+
+/////////////////////////////////////////////////////////////////////////////
+// GET /api/data/cotos/graph-query
+/////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, serde::Deserialize)]
+pub struct GraphQueryParams {
+    pub query: String,
+}
+
+async fn query_graph(
+    State(state): State<NodeState>,
+    TypedHeader(accept): TypedHeader<Accept>,
+    Query(params): Query<GraphQueryParams>,
+) -> Result<Content<Option<CotoGraph>>, ServiceError> {
+    state
+        .query_graph(params.query)
+        .await
+        .map(|graph| Content(graph, accept))
+}
+
 /////////////////////////////////////////////////////////////////////////////
 // GET /api/data/cotos/geo/{sw_lng}/{sw_lat}/{ne_lng}/{ne_lat}
 /////////////////////////////////////////////////////////////////////////////
@@ -148,6 +190,21 @@ async fn coto_details(
         .map(|details| Content(details, accept))
 }
 
+/////////////////////////////////////////////////////////////////////////////
+// GET /api/data/cotos/{coto_id}/media/{hash}
+/////////////////////////////////////////////////////////////////////////////
+
+async fn coto_media_by_hash(
+    State(state): State<NodeState>,
+    TypedHeader(accept): TypedHeader<Accept>,
+    Path((coto_id, hash)): Path<(Id<Coto>, String)>,
+) -> Result<Content<CotoMedia>, ServiceError> {
+    state
+        .coto_media_by_hash(coto_id, hash)
+        .await
+        .map(|media| Content(media, accept))
+}
+
 /////////////////////////////////////////////////////////////////////////////
 // GET /api/data/cotos/{coto_id}/cotonoma
 /////////////////////////////////////////////////////////////////////////////
@@ -244,11 +301,73 @@ async fn graph(
     Path(coto_id): Path<Id<Coto>>,
 ) -> Result<Content<CotoGraph>, ServiceError> {
     state
-        .graph_from_coto(coto_id)
+        .graph_from_coto(coto_id, None, None)
         .await
         .map(|graph| Content(graph, accept))
 }
 
+/////////////////////////////////////////////////////////////////////////////
+// POST /api/data/cotos/{coto_id}/graph
+/////////////////////////////////////////////////////////////////////////////
+
+async fn traverse_graph(
+    State(state): State<NodeState>,
+    TypedHeader(accept): TypedHeader<Accept>,
+    Path(coto_id): Path<Id<Coto>>,
+    Json(traversal): Json<GraphTraversal>,
+) -> Result<Content<CotoGraph>, ServiceError> {
+    state
+        .graph_from_coto(coto_id, traversal.options, traversal.frontier)
+        .await
+        .map(|graph| Content(graph, accept))
+}
+
+/////////////////////////////////////////////////////////////////////////////
+// GET /api/data/cotos/{coto_id}/paths/{to_coto_id}?max_depth=xxx&directed=xxx
+/////////////////////////////////////////////////////////////////////////////
+
+async fn paths(
+    State(state): State<NodeState>,
+    TypedHeader(accept): TypedHeader<Accept>,
+    Path((coto_id, to_coto_id)): Path<(Id<Coto>, Id<Coto>)>,
+    Query(params): Query<PathsParams>,
+) -> Result<Content<CotoPaths>, ServiceError> {
+    state
+        .path_between(coto_id, to_coto_id, params.max_depth, params.directed)
+        .await
+        .map(|paths| Content(paths, accept))
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct PathsParams {
+    #[serde(default)]
+    pub max_depth: Option<usize>,
+    #[serde(default)]
+    pub directed: bool,
+}
+
+/////////////////////////////////////////////////////////////////////////////
+// GET /api/data/cotos/{coto_id}/descendants?max_depth=xxx
+/////////////////////////////////////////////////////////////////////////////
+
+async fn descendants(
+    State(state): State<NodeState>,
+    TypedHeader(accept): TypedHeader<Accept>,
+    Path(coto_id): Path<Id<Coto>>,
+    Query(params): Query<DescendantsParams>,
+) -> Result<Content<Vec<(Vec<Ito>, Vec<Coto>)>>, ServiceError> {
+    state
+        .descendants(coto_id, params.max_depth)
+        .await
+        .map(|descendants| Content(descendants, accept))
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct DescendantsParams {
+    #[serde(default)]
+    pub max_depth: Option<usize>,
+}
+
 /////////////////////////////////////////////////////////////////////////////
 // POST /api/data/cotos/{coto_id}/subcotos?post_to=xxx
 /////////////////////////////////////////////////////////////////////////////
@@ -20,6 +20,7 @@ mod clients;
 mod cotonomas;
 mod cotos;
 mod local;
+mod parents;
 mod servers;
 
 pub(super) fn routes() -> Router<NodeState> {
@@ -32,6 +33,7 @@ pub(super) fn routes() -> Router<NodeState> {
         .nest("/{node_id}/cotos", cotos::routes())
         .nest("/local", local::routes())
         .nest("/servers", servers::routes())
+        .nest("/parents", parents::routes())
         .nest("/clients", clients::routes())
         .nest("/children", children::routes())
 }
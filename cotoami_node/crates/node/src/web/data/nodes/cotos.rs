@@ -20,6 +20,7 @@ pub(super) fn routes() -> Router<NodeState> {
         .route("/", get(recent_cotos))
         .route("/cotonomas", get(recent_cotonoma_cotos))
         .route("/geolocated", get(geolocated_cotos))
+        .route("/graph-health", get(graph_health))
         .route("/search/{query}", get(search_cotos))
         .route("/search/cotonomas/{query}", get(search_cotonoma_cotos))
 }
@@ -71,6 +72,21 @@ async fn geolocated_cotos(
         .map(|cotos| Content(cotos, accept))
 }
 
+/////////////////////////////////////////////////////////////////////////////
+// GET /api/data/nodes/:node_id/cotos/graph-health
+/////////////////////////////////////////////////////////////////////////////
+
+async fn graph_health(
+    State(state): State<NodeState>,
+    TypedHeader(accept): TypedHeader<Accept>,
+    Path(node_id): Path<Id<Node>>,
+) -> Result<Content<GraphHealth>, ServiceError> {
+    state
+        .graph_health(Scope::Node(node_id))
+        .await
+        .map(|health| Content(health, accept))
+}
+
 /////////////////////////////////////////////////////////////////////////////
 // GET /api/data/nodes/:node_id/cotos/search/:query
 /////////////////////////////////////////////////////////////////////////////
@@ -1,15 +1,19 @@
 use std::sync::Arc;
 
 use axum::{
-    extract::{Json, State},
-    routing::{get, put},
+    extract::{DefaultBodyLimit, Json, State},
+    http::StatusCode,
+    routing::{get, post, put},
     Extension, Router,
 };
 use axum_extra::TypedHeader;
-use cotoami_db::prelude::*;
+use cotoami_db::{import::cotoami::CotoamiImportReport, prelude::*};
 
 use crate::{
-    service::{models::LocalServer, ServiceError},
+    service::{
+        models::{Backup, CotoamiJsonImport, LocalServer},
+        ServiceError,
+    },
     state::NodeState,
     web::{Accept, Content},
 };
@@ -21,6 +25,11 @@ pub(super) fn routes() -> Router<NodeState> {
         .route("/icon", put(set_local_node_icon))
         .route("/image-max-size", put(set_image_max_size))
         .route("/enable-anonymous", put(enable_anonymous_read))
+        .route(
+            "/import/cotoami",
+            post(import_cotoami_json).layer(DefaultBodyLimit::max(IMPORT_BODY_LIMIT)),
+        )
+        .route("/backups", post(create_backup))
 }
 
 /////////////////////////////////////////////////////////////////////////////
@@ -95,3 +104,38 @@ async fn enable_anonymous_read(
         .await
         .map(|local| Content(local, accept))
 }
+
+/////////////////////////////////////////////////////////////////////////////
+// POST /api/data/nodes/local/import/cotoami
+/////////////////////////////////////////////////////////////////////////////
+
+/// The maximum size of a request body containing a whole JSON dump, which would
+/// easily exceed the default limit of axum (2 MB).
+const IMPORT_BODY_LIMIT: usize = 1 << 30; // 1 GiB
+
+async fn import_cotoami_json(
+    State(state): State<NodeState>,
+    Extension(operator): Extension<Operator>,
+    TypedHeader(accept): TypedHeader<Accept>,
+    Json(import): Json<CotoamiJsonImport>,
+) -> Result<Content<CotoamiImportReport>, ServiceError> {
+    state
+        .import_cotoami_json(import.json, import.options, Arc::new(operator))
+        .await
+        .map(|report| Content(report, accept))
+}
+
+/////////////////////////////////////////////////////////////////////////////
+// POST /api/data/nodes/local/backups
+/////////////////////////////////////////////////////////////////////////////
+
+async fn create_backup(
+    State(state): State<NodeState>,
+    Extension(operator): Extension<Operator>,
+    TypedHeader(accept): TypedHeader<Accept>,
+) -> Result<(StatusCode, Content<Backup>), ServiceError> {
+    state
+        .create_backup(Arc::new(operator))
+        .await
+        .map(|backup| (StatusCode::CREATED, Content(backup, accept)))
+}
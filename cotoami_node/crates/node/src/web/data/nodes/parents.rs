@@ -0,0 +1,70 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    routing::{get, post},
+    Extension, Router,
+};
+use axum_extra::TypedHeader;
+use cotoami_db::prelude::*;
+
+use crate::{
+    service::{models::ForkDivergence, ServiceError},
+    state::NodeState,
+    web::{Accept, Content},
+};
+
+pub(super) fn routes() -> Router<NodeState> {
+    Router::new()
+        .route("/{node_id}/fork", post(fork_from_parent))
+        .route("/{node_id}/divergence", get(fork_divergence))
+        .route("/{node_id}/reconcile", post(reconcile_with_parent))
+}
+
+/////////////////////////////////////////////////////////////////////////////
+// POST /api/data/nodes/parents/:node_id/fork
+/////////////////////////////////////////////////////////////////////////////
+
+async fn fork_from_parent(
+    State(state): State<NodeState>,
+    Extension(operator): Extension<Operator>,
+    TypedHeader(accept): TypedHeader<Accept>,
+    Path(node_id): Path<Id<Node>>,
+) -> Result<Content<ParentNode>, ServiceError> {
+    state
+        .fork_from_parent(node_id, Arc::new(operator))
+        .await
+        .map(|parent| Content(parent, accept))
+}
+
+/////////////////////////////////////////////////////////////////////////////
+// GET /api/data/nodes/parents/:node_id/divergence
+/////////////////////////////////////////////////////////////////////////////
+
+async fn fork_divergence(
+    State(state): State<NodeState>,
+    Extension(operator): Extension<Operator>,
+    TypedHeader(accept): TypedHeader<Accept>,
+    Path(node_id): Path<Id<Node>>,
+) -> Result<Content<ForkDivergence>, ServiceError> {
+    state
+        .fork_divergence(node_id, Arc::new(operator))
+        .await
+        .map(|divergence| Content(divergence, accept))
+}
+
+/////////////////////////////////////////////////////////////////////////////
+// POST /api/data/nodes/parents/:node_id/reconcile
+/////////////////////////////////////////////////////////////////////////////
+
+async fn reconcile_with_parent(
+    State(state): State<NodeState>,
+    Extension(operator): Extension<Operator>,
+    TypedHeader(accept): TypedHeader<Accept>,
+    Path(node_id): Path<Id<Node>>,
+) -> Result<Content<ForkDivergence>, ServiceError> {
+    state
+        .reconcile_with_parent(node_id, Arc::new(operator))
+        .await
+        .map(|divergence| Content(divergence, accept))
+}
@@ -2,7 +2,7 @@ use std::sync::Arc;
 
 use anyhow::Result;
 use axum::{
-    extract::{Path, Query, State},
+    extract::{Json, Path, Query, State},
     http::StatusCode,
     routing::{get, put},
     Extension, Form, Router,
@@ -24,6 +24,7 @@ pub(super) fn routes() -> Router<NodeState> {
         .route("/", get(all_servers).post(add_server))
         .route("/try", get(log_into_server))
         .route("/{node_id}", put(edit_server))
+        .route("/{node_id}/subscription", put(set_parent_subscription))
 }
 
 /////////////////////////////////////////////////////////////////////////////
@@ -88,3 +89,20 @@ async fn edit_server(
         .await
         .map(|server| Content(server, accept))
 }
+
+/////////////////////////////////////////////////////////////////////////////
+// PUT /api/data/nodes/servers/:node_id/subscription
+/////////////////////////////////////////////////////////////////////////////
+
+async fn set_parent_subscription(
+    State(state): State<NodeState>,
+    Extension(operator): Extension<Operator>,
+    TypedHeader(accept): TypedHeader<Accept>,
+    Path(node_id): Path<Id<Node>>,
+    Json(subscription): Json<Option<ChangeSubscription>>,
+) -> Result<Content<ParentNode>, ServiceError> {
+    state
+        .set_parent_subscription(node_id, subscription, Arc::new(operator))
+        .await
+        .map(|parent| Content(parent, accept))
+}
@@ -58,9 +58,7 @@ async fn stream_events(
         ClientSession::Operator(opr) => {
             // Stream of change events
             let changes = state
-                .pubsub()
-                .changes()
-                .subscribe(None::<()>)
+                .changes_for(opr)
                 .map(|change| sse_event(NodeSentEvent::NAME_CHANGE, change));
 
             if opr.has_owner_permission() {
@@ -0,0 +1,273 @@
+//! Atom feeds of recent cotos to be followed in feed readers.
+//!
+//! Since feed readers usually can't log in, a feed will be available without a session
+//! only if the local node has `anonymous_read_enabled`. A feed supports conditional
+//! GET by `If-None-Match` so that readers polling it periodically won't download it
+//! again until it changes. `If-Modified-Since` is ignored since `Last-Modified`, which
+//! is the timestamp of the newest entry, doesn't change when an entry is deleted.
+
+use std::{collections::HashSet, sync::Arc, time::SystemTime};
+
+use axum::{
+    extract::{OriginalUri, Path, Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware,
+    response::{IntoResponse, Response},
+    routing::get,
+    Extension, Router,
+};
+use axum_extra::{
+    headers::{ETag, HeaderMapExt, IfNoneMatch, LastModified},
+    TypedHeader,
+};
+use chrono::{NaiveDateTime, SecondsFormat, SubsecRound};
+use cotoami_db::prelude::*;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    config::ServerConfig,
+    service::{
+        models::{PaginatedCotos, Pagination},
+        ServiceError,
+    },
+    site::{escape_html, render_markdown},
+    state::NodeState,
+};
+
+/// The number of entries in a feed unless `page_size` is specified
+const DEFAULT_FEED_SIZE: i64 = 50;
+
+const ATOM_CONTENT_TYPE: &str = "application/atom+xml; charset=utf-8";
+
+pub(super) fn routes() -> Router<NodeState> {
+    Router::new()
+        .route("/node.atom", get(node_feed))
+        .route("/cotonomas/{file}", get(cotonoma_feed))
+        .layer(middleware::from_fn(super::require_operator))
+        .layer(middleware::from_fn(super::require_session))
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct FeedQuery {
+    page_size: Option<i64>,
+    #[serde(default)]
+    recursive: bool,
+}
+
+impl FeedQuery {
+    fn pagination(&self) -> Pagination {
+        Pagination {
+            page: 0,
+            page_size: Some(self.page_size.unwrap_or(DEFAULT_FEED_SIZE)),
+        }
+    }
+}
+
+/// Request headers for conditional GET
+struct Conditions {
+    if_none_match: Option<IfNoneMatch>,
+}
+
+impl From<&HeaderMap> for Conditions {
+    fn from(headers: &HeaderMap) -> Self {
+        // `IfNoneMatch` can be decoded from no values (as an empty list),
+        // so it has to be checked if the header exists.
+        Self {
+            if_none_match: headers
+                .contains_key(header::IF_NONE_MATCH)
+                .then(|| headers.typed_get())
+                .flatten(),
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+// GET /api/feeds/node.atom
+/////////////////////////////////////////////////////////////////////////////
+
+async fn node_feed(
+    State(state): State<NodeState>,
+    Extension(config): Extension<Arc<ServerConfig>>,
+    OriginalUri(uri): OriginalUri,
+    Query(query): Query<FeedQuery>,
+    headers: HeaderMap,
+) -> Result<Response, ServiceError> {
+    let node = state.local_node().await?;
+    let cotos = state
+        .recent_cotos(Scope::All, false, query.pagination())
+        .await?;
+    let feed = Feed {
+        id: node.uuid.to_string(),
+        title: node.name.clone(),
+        author: node.name,
+        self_url: self_url(&config, &uri.to_string()),
+        updated: node.created_at,
+    };
+    Ok(feed.into_response(cotos, Conditions::from(&headers)))
+}
+
+/////////////////////////////////////////////////////////////////////////////
+// GET /api/feeds/cotonomas/:cotonoma_id.atom
+/////////////////////////////////////////////////////////////////////////////
+
+async fn cotonoma_feed(
+    State(state): State<NodeState>,
+    Extension(config): Extension<Arc<ServerConfig>>,
+    OriginalUri(uri): OriginalUri,
+    Path(file): Path<String>,
+    Query(query): Query<FeedQuery>,
+    headers: HeaderMap,
+) -> Result<Response, ServiceError> {
+    let cotonoma_id: Id<Cotonoma> = file
+        .strip_suffix(".atom")
+        .and_then(|id| id.parse().ok())
+        .ok_or(ServiceError::NotFound(Some(format!(
+            "No such feed: {file}"
+        ))))?;
+    let (cotonoma, cotonoma_coto) = state.cotonoma_pair(cotonoma_id).await?;
+    let scope = if query.recursive {
+        Scope::cotonoma_recursive(cotonoma_id)
+    } else {
+        Scope::cotonoma_local(cotonoma_id)
+    };
+    let cotos = state.recent_cotos(scope, false, query.pagination()).await?;
+    let author = state.node_details(cotonoma.node_id).await?.node.name;
+    let feed = Feed {
+        id: cotonoma.uuid.to_string(),
+        title: cotonoma.name,
+        author,
+        self_url: self_url(&config, &uri.to_string()),
+        updated: cotonoma_coto.updated_at,
+    };
+    Ok(feed.into_response(cotos, Conditions::from(&headers)))
+}
+
+/////////////////////////////////////////////////////////////////////////////
+// Atom
+/////////////////////////////////////////////////////////////////////////////
+
+struct Feed {
+    /// UUID of the cotonoma or node
+    id: String,
+    title: String,
+    author: String,
+    self_url: String,
+
+    /// Timestamp of the feed if it has no entries
+    updated: NaiveDateTime,
+}
+
+impl Feed {
+    fn into_response(self, cotos: PaginatedCotos, conditions: Conditions) -> Response {
+        // Reposts are replaced with their originals.
+        let mut seen = HashSet::new();
+        let entries: Vec<&Coto> = cotos
+            .page
+            .rows
+            .iter()
+            .map(|coto| {
+                coto.repost_of_id
+                    .and_then(|id| {
+                        cotos
+                            .related_data
+                            .originals
+                            .iter()
+                            .find(|original| original.uuid == id)
+                    })
+                    .unwrap_or(coto)
+            })
+            .filter(|coto| seen.insert(coto.uuid))
+            .collect();
+
+        let updated = entries
+            .iter()
+            .map(|coto| coto.updated_at)
+            .max()
+            .unwrap_or(self.updated);
+        let xml = self.to_xml(&entries, updated);
+
+        let etag: ETag = format!("\"{:x}\"", Sha256::digest(xml.as_bytes()))
+            .parse()
+            .unwrap_or_else(|_| unreachable!());
+        // HTTP dates have no fractional seconds.
+        let last_modified = SystemTime::from(updated.and_utc().trunc_subsecs(0));
+
+        let not_modified = conditions
+            .if_none_match
+            .is_some_and(|if_none_match| !if_none_match.precondition_passes(&etag));
+        let headers = (
+            TypedHeader(etag),
+            TypedHeader(LastModified::from(last_modified)),
+        );
+        if not_modified {
+            (StatusCode::NOT_MODIFIED, headers).into_response()
+        } else {
+            (
+                headers,
+                [(
+                    header::CONTENT_TYPE,
+                    HeaderValue::from_static(ATOM_CONTENT_TYPE),
+                )],
+                xml,
+            )
+                .into_response()
+        }
+    }
+
+    fn to_xml(&self, entries: &[&Coto], updated: NaiveDateTime) -> String {
+        let mut lines = vec![
+            r#"<?xml version="1.0" encoding="utf-8"?>"#.to_owned(),
+            r#"<feed xmlns="http://www.w3.org/2005/Atom">"#.to_owned(),
+            format!("  <id>urn:uuid:{}</id>", self.id),
+            format!("  <title>{}</title>", escape_html(&self.title)),
+            format!("  <updated>{}</updated>", rfc3339(updated)),
+            format!(
+                "  <author><name>{}</name></author>",
+                escape_html(&self.author)
+            ),
+        ];
+        lines.push(format!(
+            r#"  <link rel="self" href="{}"/>"#,
+            escape_html(&self.self_url)
+        ));
+        for coto in entries {
+            lines.push("  <entry>".to_owned());
+            lines.push(format!("    <id>urn:uuid:{}</id>", coto.uuid));
+            lines.push(format!(
+                "    <title>{}</title>",
+                escape_html(&coto_title(coto))
+            ));
+            lines.push(format!(
+                "    <published>{}</published>",
+                rfc3339(coto.created_at)
+            ));
+            lines.push(format!(
+                "    <updated>{}</updated>",
+                rfc3339(coto.updated_at)
+            ));
+            if let Some(content) = coto.content.as_deref().filter(|_| !coto.is_cotonoma) {
+                lines.push(format!(
+                    r#"    <content type="html">{}</content>"#,
+                    escape_html(&render_markdown(content))
+                ));
+            }
+            lines.push("  </entry>".to_owned());
+        }
+        lines.push("</feed>\n".to_owned());
+        lines.join("\n")
+    }
+}
+
+fn rfc3339(timestamp: NaiveDateTime) -> String {
+    timestamp
+        .and_utc()
+        .to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+fn self_url(config: &ServerConfig, path: &str) -> String {
+    let port = config
+        .url_port
+        .map(|port| format!(":{port}"))
+        .unwrap_or_default();
+    format!("{}://{}{port}{path}", config.url_scheme, config.url_host)
+}
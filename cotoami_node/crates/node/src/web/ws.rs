@@ -5,6 +5,7 @@ use axum::{
         ws::{CloseFrame, Message, WebSocket, WebSocketUpgrade},
         ConnectInfo, State,
     },
+    http::HeaderMap,
     middleware,
     response::IntoResponse,
     routing::get,
@@ -22,6 +23,7 @@ use crate::{
         tungstenite::{communicate_with_operator, communicate_with_parent},
         CommunicationError,
     },
+    service::models::Compression,
     state::{ClientConnection, NodeState},
     Abortables,
 };
@@ -44,7 +46,10 @@ async fn ws_handler(
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
     State(state): State<NodeState>,
     Extension(session): Extension<ClientSession>,
+    headers: HeaderMap,
 ) -> impl IntoResponse {
+    let compression = super::requested_compression(&headers);
+
     ws = if let Some(max_message_size) = state.read_config().max_message_size_as_server {
         // tungstenite's high-level API doesn't seem to fragment messages,
         // so the size of a frame roughly matches that of the message.
@@ -56,8 +61,10 @@ async fn ws_handler(
     };
 
     ws.on_upgrade(move |socket| match session.client_node_id() {
-        Some(client_id) => handle_authenticated(socket, addr, state, session, client_id).boxed(),
-        None => handle_anonymous(socket, addr, state).boxed(),
+        Some(client_id) => {
+            handle_authenticated(socket, addr, state, session, client_id, compression).boxed()
+        }
+        None => handle_anonymous(socket, addr, state, compression).boxed(),
     })
 }
 
@@ -90,8 +97,10 @@ async fn handle_authenticated(
     state: NodeState,
     session: ClientSession,
     client_id: Id<Node>,
+    compression: Option<Compression>,
 ) {
     let (sink, stream) = split_socket(socket);
+    let max_message_size = state.read_config().max_message_size_as_server;
 
     // Container of tasks to maintain this client-server connection.
     let communication_tasks = Abortables::default();
@@ -133,6 +142,8 @@ async fn handle_authenticated(
                 stream,
                 on_disconnect,
                 None, // No pings
+                compression,
+                max_message_size,
                 communication_tasks,
             )
             .await;
@@ -146,6 +157,8 @@ async fn handle_authenticated(
                 stream,
                 on_disconnect,
                 None, // No pings
+                compression,
+                max_message_size,
                 communication_tasks,
             )
             .await;
@@ -170,8 +183,14 @@ fn on_client_disconnect(
 // Handle anonymous client
 /////////////////////////////////////////////////////////////////////////////
 
-async fn handle_anonymous(socket: WebSocket, remote_addr: SocketAddr, state: NodeState) {
+async fn handle_anonymous(
+    socket: WebSocket,
+    remote_addr: SocketAddr,
+    state: NodeState,
+    compression: Option<Compression>,
+) {
     let (sink, stream) = split_socket(socket);
+    let max_message_size = state.read_config().max_message_size_as_server;
 
     // Container of tasks to maintain this client-server connection.
     let communication_tasks = Abortables::default();
@@ -203,6 +222,8 @@ async fn handle_anonymous(socket: WebSocket, remote_addr: SocketAddr, state: Nod
         stream,
         on_disconnect,
         None, // No pings
+        compression,
+        max_message_size,
         communication_tasks,
     )
     .await;
@@ -0,0 +1,36 @@
+use std::{path::Path, sync::Arc, time::Duration};
+
+use anyhow::Result;
+use cotoami_node::prelude::*;
+use googletest::prelude::*;
+use tempfile::tempdir;
+use test_log::test;
+
+pub mod common;
+
+#[test(tokio::test)]
+async fn create_backups_with_retention() -> Result<()> {
+    let backup_dir = tempdir()?;
+    let mut config = common::new_node_config("My Node")?;
+    config.backup_dir = Some(backup_dir.path().to_string_lossy().into());
+    config.backup_retention = 2;
+    let state = NodeState::new(config).await?;
+    let owner = Arc::new(state.local_node_as_operator()?);
+
+    let mut backups = Vec::new();
+    for _ in 0..3 {
+        let mut request = Command::CreateBackup.into_request();
+        request.set_from(owner.clone());
+        backups.push(state.call(request).await?.content::<Backup>()?);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    // Only the latest two are kept.
+    assert!(!Path::new(&backups[0].path).exists());
+    assert!(Path::new(&backups[1].path).exists());
+    assert!(Path::new(&backups[2].path).exists());
+    assert_that!(std::fs::read_dir(backup_dir.path())?.count(), eq(2));
+    assert_that!(backups[2].size, gt(0));
+
+    Ok(())
+}
@@ -52,6 +52,8 @@ pub async fn connect_to_server(
         password: Some(password.into()),
         new_password: None,
         client_role: Some(role),
+        subscription: None,
+        lazy_media: false,
     })
     .into_request();
     request.set_from(Arc::new(client_state.local_node_as_operator()?));
@@ -0,0 +1,100 @@
+use anyhow::Result;
+use cotoami_db::prelude::*;
+use cotoami_node::prelude::*;
+use googletest::prelude::*;
+use test_log::test;
+
+pub mod common;
+
+#[test(tokio::test)]
+async fn compressed_chunk_of_changes() -> Result<()> {
+    /////////////////////////////////////////////////////////////////////////////
+    // Setup: a server with a large coto and a client node
+    /////////////////////////////////////////////////////////////////////////////
+
+    let port = 5112;
+    let url_prefix = format!("http://localhost:{port}");
+    let client_state = common::new_client_node_state("client").await?;
+    let client_id = client_state.try_get_local_node_id()?;
+
+    let (server_state, shutdown) = common::launch_server_node(
+        "server",
+        port,
+        false,
+        AddClient::new(
+            client_id,
+            Some("server-password"),
+            Some(ChildNodeInput::default()),
+        ),
+    )
+    .await?;
+    let mut server_ds = server_state.db().new_session()?;
+    let server_opr = server_state.local_node_as_operator()?;
+    let (root, _) = server_ds.try_get_local_node_root()?;
+    // Large enough for a chunk to be compressed in a blocking thread
+    let content = "compressible ".repeat(60_000);
+    let (coto, _) = server_ds.post_coto(&CotoInput::new(&content), &root.uuid, &server_opr)?;
+    let (coto2, _) = server_ds.post_coto(&CotoInput::new(&content), &root.uuid, &server_opr)?;
+
+    /////////////////////////////////////////////////////////////////////////////
+    // When: the client logs into the server
+    /////////////////////////////////////////////////////////////////////////////
+
+    let (session, http_client) = client_state
+        .log_into_server(LogIntoServer {
+            url_prefix: Some(url_prefix.clone()),
+            password: Some("server-password".into()),
+            new_password: None,
+            client_role: Some(NodeRole::Child),
+            subscription: None,
+            lazy_media: false,
+        })
+        .await
+        .map_err(BackendServiceError)?;
+
+    // Then: the compression has been agreed on
+    assert_that!(session.compression, some(eq(Compression::Zstd)));
+
+    /////////////////////////////////////////////////////////////////////////////
+    // When: the client requests a chunk of changes
+    /////////////////////////////////////////////////////////////////////////////
+
+    let response = reqwest::Client::new()
+        .get(format!("{url_prefix}/api/data/changes?from=1"))
+        .header("x-cotoami-session-token", &session.token.unwrap().token)
+        .header("x-cotoami-compression", Compression::Zstd.name())
+        .send()
+        .await?;
+
+    // Then: the chunk has been compressed
+    assert_that!(
+        response
+            .headers()
+            .get("content-encoding")
+            .and_then(|v| v.to_str().ok()),
+        some(eq("zstd"))
+    );
+
+    // And: the client can read the compressed chunk
+    let ChunkOfChanges::Fetched(changes) = http_client.chunk_of_changes(1, None).await? else {
+        panic!("Unexpected out of range");
+    };
+    assert_that!(
+        changes.chunk,
+        contains(field!(
+            ChangelogEntry.change,
+            pat!(Change::CreateCoto(field!(Coto.uuid, eq(&coto.uuid))))
+        ))
+    );
+    assert_that!(
+        changes.chunk,
+        contains(field!(
+            ChangelogEntry.change,
+            pat!(Change::CreateCoto(field!(Coto.uuid, eq(&coto2.uuid))))
+        ))
+    );
+
+    shutdown.send(()).ok();
+
+    Ok(())
+}
@@ -0,0 +1,146 @@
+use anyhow::Result;
+use cotoami_db::prelude::*;
+use cotoami_node::prelude::*;
+use googletest::prelude::*;
+use reqwest::{header, StatusCode};
+use test_log::test;
+
+pub mod common;
+
+#[test(tokio::test)]
+async fn cotonoma_feed() -> Result<()> {
+    /////////////////////////////////////////////////////////////////////////////
+    // Setup: a server node with a cotonoma
+    /////////////////////////////////////////////////////////////////////////////
+
+    let port = 5108;
+    let state = NodeState::new(common::new_node_config("My Node")?).await?;
+    let mut ds = state.db().new_session()?;
+    let opr = state.local_node_as_operator()?;
+    let (root, _) = ds.try_get_local_node_root()?;
+    let ((cotonoma, _), _) = ds.post_cotonoma(&CotonomaInput::new("Rust"), &root, &opr)?;
+    let (coto, _) = ds.post_coto(&CotoInput::new("**Hello** <b>"), &cotonoma.uuid, &opr)?;
+
+    let config = ServerConfig {
+        port,
+        url_port: Some(port),
+        ..Default::default()
+    };
+    let (_, _shutdown) = cotoami_node::launch_server(config, state.clone()).await?;
+
+    let client = reqwest::Client::new();
+    let url = format!(
+        "http://localhost:{port}/api/feeds/cotonomas/{}.atom",
+        cotonoma.uuid
+    );
+
+    /////////////////////////////////////////////////////////////////////////////
+    // When: anonymous read is disabled
+    /////////////////////////////////////////////////////////////////////////////
+
+    let response = client.get(&url).send().await?;
+    assert_that!(response.status(), eq(StatusCode::UNAUTHORIZED));
+
+    /////////////////////////////////////////////////////////////////////////////
+    // When: anonymous read is enabled
+    /////////////////////////////////////////////////////////////////////////////
+
+    ds.enable_anonymous_read(true, &opr)?;
+
+    let response = client.get(&url).send().await?;
+    assert_that!(response.status(), eq(StatusCode::OK));
+    assert_that!(
+        response.headers()[header::CONTENT_TYPE].to_str()?,
+        eq("application/atom+xml; charset=utf-8")
+    );
+    let etag = response.headers()[header::ETAG].clone();
+    let last_modified = response.headers()[header::LAST_MODIFIED].clone();
+    let feed = response.text().await?;
+    assert_that!(
+        feed,
+        all!(
+            contains_substring(format!("<id>urn:uuid:{}</id>", cotonoma.uuid)),
+            contains_substring("<title>Rust</title>"),
+            contains_substring("<author><name>My Node</name></author>"),
+            contains_substring(format!("<entry>\n    <id>urn:uuid:{}</id>", coto.uuid)),
+            contains_substring(
+                "<content type=\"html\">&lt;p&gt;&lt;strong&gt;Hello&lt;/strong&gt; \
+                &amp;lt;b&amp;gt;&lt;/p&gt;\n</content>"
+            )
+        )
+    );
+
+    /////////////////////////////////////////////////////////////////////////////
+    // When: conditional GET
+    /////////////////////////////////////////////////////////////////////////////
+
+    let response = client
+        .get(&url)
+        .header(header::IF_NONE_MATCH, etag.clone())
+        .send()
+        .await?;
+    assert_that!(response.status(), eq(StatusCode::NOT_MODIFIED));
+
+    // `If-Modified-Since` alone can't tell deletions.
+    let response = client
+        .get(&url)
+        .header(header::IF_MODIFIED_SINCE, last_modified)
+        .send()
+        .await?;
+    assert_that!(response.status(), eq(StatusCode::OK));
+
+    // A new coto changes the feed.
+    let (new_coto, _) = ds.post_coto(&CotoInput::new("Bye"), &cotonoma.uuid, &opr)?;
+    let response = client
+        .get(&url)
+        .header(header::IF_NONE_MATCH, etag)
+        .send()
+        .await?;
+    assert_that!(response.status(), eq(StatusCode::OK));
+    let etag = response.headers()[header::ETAG].clone();
+    assert_that!(
+        response.text().await?,
+        contains_substring(format!("<id>urn:uuid:{}</id>", new_coto.uuid))
+    );
+
+    // So does a deletion of a coto, which is not the newest.
+    let _ = ds.delete_coto(&coto.uuid, &opr)?;
+    let response = client
+        .get(&url)
+        .header(header::IF_NONE_MATCH, etag)
+        .send()
+        .await?;
+    assert_that!(response.status(), eq(StatusCode::OK));
+    assert_that!(
+        response.text().await?,
+        not(contains_substring(format!(
+            "<id>urn:uuid:{}</id>",
+            coto.uuid
+        )))
+    );
+
+    /////////////////////////////////////////////////////////////////////////////
+    // When: node-wide feed and a missing cotonoma
+    /////////////////////////////////////////////////////////////////////////////
+
+    let response = client
+        .get(format!("http://localhost:{port}/api/feeds/node.atom"))
+        .send()
+        .await?;
+    assert_that!(response.status(), eq(StatusCode::OK));
+    assert_that!(
+        response.text().await?,
+        contains_substring("<title>My Node</title>")
+    );
+
+    let response = client
+        .get(format!(
+            "http://localhost:{port}/api/feeds/cotonomas/{}.atom",
+            Id::<Cotonoma>::generate()
+        ))
+        .send()
+        .await?;
+    assert_that!(response.status(), eq(StatusCode::NOT_FOUND));
+
+    Ok(())
+}
@@ -0,0 +1,158 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use cotoami_db::prelude::*;
+use cotoami_node::prelude::*;
+use futures::stream::StreamExt;
+use googletest::prelude::*;
+use test_log::test;
+
+pub mod common;
+
+use self::common::wait_get;
+
+#[test(tokio::test)]
+async fn fork_and_reconcile() -> Result<()> {
+    /////////////////////////////////////////////////////////////////////////////
+    // Setup: a child that has replicated a coto of the parent
+    /////////////////////////////////////////////////////////////////////////////
+
+    let port = 5113;
+    let child_state = common::new_client_node_state("child").await?;
+    let child_id = child_state.try_get_local_node_id()?;
+    let child_opr = Arc::new(child_state.local_node_as_operator()?);
+    let mut child_events = child_state.pubsub().events().subscribe(None::<()>);
+    let mut child_changes = child_state.pubsub().changes().subscribe(None::<()>);
+
+    let (server_state, shutdown) = common::launch_server_node(
+        "server",
+        port,
+        true,
+        AddClient::new(
+            child_id,
+            Some("server-password"),
+            Some(ChildNodeInput::default()),
+        ),
+    )
+    .await?;
+    let server_id = server_state.try_get_local_node_id()?;
+    let mut server_ds = server_state.db().new_session()?;
+    let server_opr = server_state.local_node_as_operator()?;
+    let (root, root_coto) = server_ds.try_get_local_node_root()?;
+    let (coto, _) = server_ds.post_coto(&CotoInput::new("coto"), &root.uuid, &server_opr)?;
+
+    let mut request = Command::AddServer(LogIntoServer {
+        url_prefix: Some(format!("http://localhost:{port}")),
+        password: Some("server-password".into()),
+        new_password: None,
+        client_role: Some(NodeRole::Child),
+        subscription: None,
+        lazy_media: false,
+    })
+    .into_request();
+    request.set_from(child_opr.clone());
+    child_state.call(request).await?.content::<Server>()?;
+    wait_sync_end(&mut child_events).await;
+
+    // The parent root is pinned to the child root after the first import.
+    loop {
+        let change = wait_get(child_changes.next(), "pinning the parent root").await;
+        match change.map(|log| log.change) {
+            Some(Change::CreateIto(ito)) if ito.target_coto_id == root_coto.uuid => break,
+            Some(_) => continue,
+            None => panic!("The parent root has not been pinned"),
+        }
+    }
+
+    let mut child_ds = child_state.db().new_session()?;
+    assert_that!(child_ds.coto(&coto.uuid)?, some(anything()));
+
+    /////////////////////////////////////////////////////////////////////////////
+    // When: the child forks from the parent
+    /////////////////////////////////////////////////////////////////////////////
+
+    let mut request = Command::ForkFromParent { id: server_id }.into_request();
+    request.set_from(child_opr.clone());
+    let parent = child_state.call(request).await?.content::<ParentNode>()?;
+
+    // Then: the connection to the parent has been disabled
+    assert_that!(parent.forked, eq(true));
+    assert_that!(
+        child_state
+            .server_conns()
+            .try_get(&server_id)?
+            .not_connected(),
+        some(eq(&NotConnected::Disabled))
+    );
+
+    /////////////////////////////////////////////////////////////////////////////
+    // When: both nodes make changes during the fork
+    /////////////////////////////////////////////////////////////////////////////
+
+    let (_, child_root_coto) = child_ds.try_get_local_node_root()?;
+    let (_, ito_log) =
+        child_ds.create_ito(&ItoInput::new(child_root_coto.uuid, coto.uuid), &child_opr)?;
+    let delete_log = server_ds.delete_coto(&coto.uuid, &server_opr)?;
+
+    // Then: the divergence can be checked against the parent
+    let mut request = Command::ForkDivergence { id: server_id }.into_request();
+    request.set_from(child_opr.clone());
+    let divergence = child_state
+        .call(request)
+        .await?
+        .content::<ForkDivergence>()?;
+    assert_that!(divergence.local_changes, elements_are![eq(&ito_log)]);
+    assert_that!(
+        divergence.parent_changes,
+        some(contains(pat!(ChangeSummary {
+            serial_number: eq(&delete_log.serial_number),
+            kind: eq("DeleteCoto"),
+            cotos: elements_are![eq(&coto.uuid)],
+            ..
+        })))
+    );
+    assert_that!(
+        divergence.conflicts,
+        elements_are![pat!(ForkConflict {
+            parent_change: field!(ChangeSummary.serial_number, eq(&delete_log.serial_number)),
+            local_changes: elements_are![eq(&ito_log.serial_number)],
+        })]
+    );
+
+    /////////////////////////////////////////////////////////////////////////////
+    // When: the child reconciles with the parent
+    /////////////////////////////////////////////////////////////////////////////
+
+    let mut request = Command::ReconcileWithParent { id: server_id }.into_request();
+    request.set_from(child_opr.clone());
+    let report = child_state
+        .call(request)
+        .await?
+        .content::<ForkDivergence>()?;
+    assert_that!(report.parent.forked, eq(false));
+    assert_that!(report.conflicts, len(eq(1)));
+
+    // Then: the changes made in the parent during the fork have been imported
+    wait_sync_end(&mut child_events).await;
+    assert_that!(child_ds.coto(&coto.uuid)?, none());
+
+    shutdown.send(()).ok();
+
+    Ok(())
+}
+
+async fn wait_sync_end<S>(events: &mut S)
+where
+    S: futures::Stream<Item = LocalNodeEvent> + Unpin,
+{
+    loop {
+        match wait_get(events.next(), "ParentSyncEnd event").await {
+            Some(LocalNodeEvent::ParentSyncEnd { error, .. }) => {
+                assert_that!(error, none());
+                break;
+            }
+            Some(_) => continue,
+            None => panic!("No ParentSyncEnd event"),
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use cotoami_db::prelude::*;
+use cotoami_node::prelude::*;
+use futures::stream::StreamExt;
+use googletest::prelude::*;
+use test_log::test;
+
+pub mod common;
+
+use self::common::wait_get;
+
+#[test(tokio::test)]
+async fn fetch_omitted_media() -> Result<()> {
+    /////////////////////////////////////////////////////////////////////////////
+    // Setup: a server and a child node requesting lazy media
+    /////////////////////////////////////////////////////////////////////////////
+
+    let port = 5111;
+    let child_state = common::new_client_node_state("child").await?;
+    let child_id = child_state.try_get_local_node_id()?;
+    let mut child_events = child_state.pubsub().events().subscribe(None::<()>);
+
+    let (server_state, shutdown) = common::launch_server_node(
+        "server",
+        port,
+        true,
+        AddClient::new(
+            child_id,
+            Some("server-password"),
+            Some(ChildNodeInput::default()),
+        ),
+    )
+    .await?;
+    let server_id = server_state.try_get_local_node_id()?;
+    let mut server_ds = server_state.db().new_session()?;
+    let server_opr = server_state.local_node_as_operator()?;
+    let (root, _) = server_ds.try_get_local_node_root()?;
+
+    let mut request = Command::AddServer(LogIntoServer {
+        url_prefix: Some(format!("http://localhost:{port}")),
+        password: Some("server-password".into()),
+        new_password: None,
+        client_role: Some(NodeRole::Child),
+        subscription: None,
+        lazy_media: true,
+    })
+    .into_request();
+    request.set_from(Arc::new(child_state.local_node_as_operator()?));
+    child_state.call(request).await?.content::<Server>()?;
+
+    loop {
+        match wait_get(child_events.next(), "ParentSyncEnd event").await {
+            Some(LocalNodeEvent::ParentSyncEnd { error, .. }) => {
+                assert_that!(error, none());
+                break;
+            }
+            Some(_) => continue,
+            None => panic!("No ParentSyncEnd event"),
+        }
+    }
+    assert_that!(
+        server_ds.try_get_child_node(&child_id, &server_opr)?,
+        field!(ChildNode.lazy_media, eq(&true))
+    );
+
+    /////////////////////////////////////////////////////////////////////////////
+    // When: a coto with media is posted in the server
+    /////////////////////////////////////////////////////////////////////////////
+
+    let mut child_changes = child_state.pubsub().changes().subscribe(None::<()>);
+    let media_type = "application/octet-stream";
+    let (coto, log) = server_ds.post_coto(
+        &CotoInput::new("media").media_content(vec![1, 2, 3].into(), media_type),
+        &root.uuid,
+        &server_opr,
+    )?;
+    server_state.pubsub().publish_change(log);
+
+    // Then: the change has been received without the media
+    loop {
+        let change = wait_get(child_changes.next(), "changes").await.unwrap();
+        if change.origin_node_id == server_id {
+            assert_that!(
+                change.change,
+                pat!(Change::MediaOmitted {
+                    media: field!(OmittedMedia.hash, eq(&Coto::media_hash(&[1, 2, 3]))),
+                    ..
+                })
+            );
+            break;
+        }
+    }
+
+    // And: the media has been fetched afterwards
+    loop {
+        match wait_get(child_events.next(), "CotoMediaFetched event").await {
+            Some(LocalNodeEvent::CotoMediaFetched {
+                coto_id,
+                parent_node_id,
+            }) => {
+                assert_that!(coto_id, eq(coto.uuid));
+                assert_that!(parent_node_id, eq(server_id));
+                break;
+            }
+            Some(_) => continue,
+            None => panic!("No CotoMediaFetched event"),
+        }
+    }
+    let mut child_ds = child_state.db().new_session()?;
+    assert_that!(
+        child_ds.coto(&coto.uuid)?,
+        some(pat!(Coto {
+            media_content: some(eq(&Bytes::from(vec![1, 2, 3]))),
+            media_type: some(eq(media_type)),
+            ..
+        }))
+    );
+    assert_that!(child_ds.missing_media()?, is_empty());
+
+    shutdown.send(()).ok();
+
+    Ok(())
+}
@@ -0,0 +1,297 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Result;
+use cotoami_db::prelude::*;
+use cotoami_node::prelude::*;
+use futures::stream::StreamExt;
+use googletest::prelude::*;
+use test_log::test;
+
+pub mod common;
+
+use self::common::wait_get;
+
+#[test(tokio::test)]
+async fn outbox() -> Result<()> {
+    /////////////////////////////////////////////////////////////////////////////
+    // Setup: a child connected to a parent
+    /////////////////////////////////////////////////////////////////////////////
+
+    let port = 5114;
+    let child_state = common::new_client_node_state("child").await?;
+    let child_id = child_state.try_get_local_node_id()?;
+    let child_opr = Arc::new(child_state.local_node_as_operator()?);
+    let mut child_events = child_state.pubsub().events().subscribe(None::<()>);
+
+    let (server_state, shutdown) = common::launch_server_node(
+        "server",
+        port,
+        true,
+        AddClient::new(
+            child_id,
+            Some("server-password"),
+            Some(ChildNodeInput::default()),
+        ),
+    )
+    .await?;
+    let server_id = server_state.try_get_local_node_id()?;
+    let mut server_ds = server_state.db().new_session()?;
+    let server_opr = server_state.local_node_as_operator()?;
+    let (root, _) = server_ds.try_get_local_node_root()?;
+
+    let request = Command::AddServer(LogIntoServer {
+        url_prefix: Some(format!("http://localhost:{port}")),
+        password: Some("server-password".into()),
+        new_password: None,
+        client_role: Some(NodeRole::Child),
+        subscription: None,
+        lazy_media: false,
+    })
+    .into_request_from(child_opr.clone());
+    child_state.call(request).await?.content::<Server>()?;
+    wait_event(&mut child_events, "ParentSyncEnd", |e| {
+        matches!(e, LocalNodeEvent::ParentSyncEnd { error: None, .. })
+    })
+    .await;
+
+    let request = Command::PostCoto {
+        input: CotoInput::new("to be edited"),
+        post_to: root.uuid,
+    }
+    .into_request_from(child_opr.clone());
+    let coto_to_edit = child_state.call(request).await?.content::<Coto>()?;
+
+    /////////////////////////////////////////////////////////////////////////////
+    // When: the child makes changes in the parent while disconnected
+    /////////////////////////////////////////////////////////////////////////////
+
+    set_server_disabled(&child_state, server_id, &child_opr, true).await?;
+    wait_event(&mut child_events, "ParentDisconnected", |e| {
+        matches!(e, LocalNodeEvent::ParentDisconnected { .. })
+    })
+    .await;
+
+    let request = Command::PostCoto {
+        input: CotoInput::new("posted offline"),
+        post_to: root.uuid,
+    }
+    .into_request_from(child_opr.clone());
+    let post_error = queued_error(child_state.call(request).await?);
+
+    let request = Command::EditCoto {
+        id: coto_to_edit.uuid,
+        diff: CotoContentDiff::default().content("edited offline"),
+    }
+    .into_request_from(child_opr.clone());
+    let edit_error = queued_error(child_state.call(request).await?);
+
+    // Then: the requests have been queued into the outbox
+    let mut child_ds = child_state.db().new_session()?;
+    let outbox = child_ds.outbox(&server_id)?;
+    assert_that!(
+        outbox
+            .iter()
+            .map(|entry| entry.idempotency_key.to_string())
+            .collect::<Vec<_>>(),
+        elements_are![
+            eq(post_error.params["idempotency_key"].as_str().unwrap()),
+            eq(edit_error.params["idempotency_key"].as_str().unwrap())
+        ]
+    );
+
+    /////////////////////////////////////////////////////////////////////////////
+    // When: the parent deletes the coto to be edited and the child reconnects
+    /////////////////////////////////////////////////////////////////////////////
+
+    let _ = server_ds.delete_coto(&coto_to_edit.uuid, &server_opr)?;
+    set_server_disabled(&child_state, server_id, &child_opr, false).await?;
+
+    // Then: the post has been sent and the edit has been rejected
+    let sent = wait_event(&mut child_events, "OutboxRequestSent", |e| {
+        matches!(e, LocalNodeEvent::OutboxRequestSent { .. })
+    })
+    .await;
+    assert_that!(
+        sent,
+        pat!(LocalNodeEvent::OutboxRequestSent {
+            parent_node_id: eq(&server_id),
+            idempotency_key: eq(&outbox[0].idempotency_key),
+        })
+    );
+    let rejected = wait_event(&mut child_events, "OutboxRequestRejected", |e| {
+        matches!(e, LocalNodeEvent::OutboxRequestRejected { .. })
+    })
+    .await;
+    assert_that!(
+        rejected,
+        pat!(LocalNodeEvent::OutboxRequestRejected {
+            parent_node_id: eq(&server_id),
+            idempotency_key: eq(&outbox[1].idempotency_key),
+            error: contains_substring("NotFound"),
+        })
+    );
+    assert_that!(child_ds.outbox(&server_id)?, is_empty());
+    assert_that!(posted_offline(&mut server_ds)?, eq(1));
+
+    /////////////////////////////////////////////////////////////////////////////
+    // When: the same request is sent to the parent again
+    /////////////////////////////////////////////////////////////////////////////
+
+    let child_as_operator = Arc::new(Operator::ChildNode(
+        server_ds.try_get_child_node(&child_id, &server_opr)?,
+    ));
+    let mut request = Command::PostCoto {
+        input: CotoInput::new("posted offline"),
+        post_to: root.uuid,
+    }
+    .into_request_from(child_as_operator);
+    request.set_idempotency_key(outbox[0].idempotency_key);
+    let response = server_state.call(request).await?;
+
+    // Then: it won't be handled twice
+    assert_that!(
+        response.into_body(),
+        err(pat!(ServiceError::Request(field!(
+            RequestError.code,
+            eq("duplicate-request")
+        ))))
+    );
+    assert_that!(posted_offline(&mut server_ds)?, eq(1));
+
+    shutdown.send(()).ok();
+
+    Ok(())
+}
+
+#[test(tokio::test)]
+async fn outbox_keeps_request_failed_on_parent() -> Result<()> {
+    /////////////////////////////////////////////////////////////////////////////
+    // Setup: a child connected to a parent
+    /////////////////////////////////////////////////////////////////////////////
+
+    let port = 5115;
+    let child_state = common::new_client_node_state("child").await?;
+    let child_id = child_state.try_get_local_node_id()?;
+    let child_opr = Arc::new(child_state.local_node_as_operator()?);
+    let mut child_events = child_state.pubsub().events().subscribe(None::<()>);
+
+    let (server_state, shutdown) = common::launch_server_node(
+        "server",
+        port,
+        true,
+        AddClient::new(
+            child_id,
+            Some("server-password"),
+            Some(ChildNodeInput::default()),
+        ),
+    )
+    .await?;
+    let server_id = server_state.try_get_local_node_id()?;
+    let mut server_ds = server_state.db().new_session()?;
+    let (root, _) = server_ds.try_get_local_node_root()?;
+
+    let request = Command::AddServer(LogIntoServer {
+        url_prefix: Some(format!("http://localhost:{port}")),
+        password: Some("server-password".into()),
+        new_password: None,
+        client_role: Some(NodeRole::Child),
+        subscription: None,
+        lazy_media: false,
+    })
+    .into_request_from(child_opr.clone());
+    child_state.call(request).await?.content::<Server>()?;
+    wait_event(&mut child_events, "ParentSyncEnd", |e| {
+        matches!(e, LocalNodeEvent::ParentSyncEnd { error: None, .. })
+    })
+    .await;
+
+    /////////////////////////////////////////////////////////////////////////////
+    // When: a request that the parent fails to handle is queued before another
+    /////////////////////////////////////////////////////////////////////////////
+
+    set_server_disabled(&child_state, server_id, &child_opr, true).await?;
+    wait_event(&mut child_events, "ParentDisconnected", |e| {
+        matches!(e, LocalNodeEvent::ParentDisconnected { .. })
+    })
+    .await;
+
+    // An image that can't be decoded will be a server error in the parent.
+    let request = Command::PostCoto {
+        input: CotoInput::new("broken image")
+            .media_content(Bytes::from(b"not an image".to_vec()), "image/png"),
+        post_to: root.uuid,
+    }
+    .into_request_from(child_opr.clone());
+    queued_error(child_state.call(request).await?);
+
+    let request = Command::PostCoto {
+        input: CotoInput::new("posted offline"),
+        post_to: root.uuid,
+    }
+    .into_request_from(child_opr.clone());
+    queued_error(child_state.call(request).await?);
+
+    set_server_disabled(&child_state, server_id, &child_opr, false).await?;
+    wait_event(&mut child_events, "ParentSyncEnd", |e| {
+        matches!(e, LocalNodeEvent::ParentSyncEnd { error: None, .. })
+    })
+    .await;
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    // Then: the failed request stays in the outbox with the following one
+    let mut child_ds = child_state.db().new_session()?;
+    assert_that!(child_ds.outbox(&server_id)?.len(), eq(2));
+    assert_that!(posted_offline(&mut server_ds)?, eq(0));
+
+    shutdown.send(()).ok();
+
+    Ok(())
+}
+
+async fn set_server_disabled(
+    state: &NodeState,
+    id: Id<Node>,
+    operator: &Arc<Operator>,
+    disabled: bool,
+) -> Result<ServerNode> {
+    let request = Command::EditServer {
+        id,
+        values: EditServer {
+            disabled: Some(disabled),
+            password: None,
+            url_prefix: None,
+            lazy_media: None,
+        },
+    }
+    .into_request_from(operator.clone());
+    state.call(request).await?.content::<ServerNode>()
+}
+
+fn queued_error(response: Response) -> RequestError {
+    match response.into_body() {
+        Err(ServiceError::Request(e)) if e.code == "queued-in-outbox" => e,
+        other => panic!("The request has not been queued: {other:?}"),
+    }
+}
+
+fn posted_offline(ds: &mut DatabaseSession<'_>) -> Result<usize> {
+    Ok(ds
+        .all_cotos()?
+        .iter()
+        .filter(|coto| coto.content.as_deref() == Some("posted offline"))
+        .count())
+}
+
+async fn wait_event<S, F>(events: &mut S, description: &str, matches: F) -> LocalNodeEvent
+where
+    S: futures::Stream<Item = LocalNodeEvent> + Unpin,
+    F: Fn(&LocalNodeEvent) -> bool,
+{
+    loop {
+        match wait_get(events.next(), description).await {
+            Some(event) if matches(&event) => return event,
+            Some(_) => continue,
+            None => panic!("No {description} event"),
+        }
+    }
+}
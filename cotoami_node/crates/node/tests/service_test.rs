@@ -2,7 +2,10 @@ use std::{borrow::Cow, sync::Arc, time::Instant};
 
 use anyhow::{anyhow, Result};
 use chrono::NaiveDateTime;
-use cotoami_db::prelude::*;
+use cotoami_db::{
+    import::cotoami::{CotoamiImportOptions, CotoamiImportReport},
+    prelude::*,
+};
 use cotoami_node::prelude::*;
 use futures::{stream::StreamExt, Stream};
 use googletest::prelude::*;
@@ -794,6 +797,222 @@ where
     assert_that!(depth_geo_ids.contains(&geo_child3_coto.uuid), eq(false));
     assert_that!(depth_geo_ids.contains(&geo_root_coto.uuid), eq(false));
 
+    /////////////////////////////////////////////////////////////////////////////
+    // Command: GraphFromCoto
+    /////////////////////////////////////////////////////////////////////////////
+
+    let (graph_root, _) = backend_ds.post_coto(
+        &CotoInput::new("graph root"),
+        &backend_root_cotonoma.uuid,
+        &backend_owner,
+    )?;
+    for content in ["graph child1", "graph child2"] {
+        let (child, _) = backend_ds.post_coto(
+            &CotoInput::new(content),
+            &backend_root_cotonoma.uuid,
+            &backend_owner,
+        )?;
+        let (_, _) =
+            backend_ds.create_ito(&ItoInput::new(graph_root.uuid, child.uuid), &backend_owner)?;
+    }
+
+    // Unbounded
+    let request = Command::GraphFromCoto {
+        coto: graph_root.uuid,
+        options: None,
+        frontier: None,
+    }
+    .into_request();
+    let graph = service.call(request).await?.content::<CotoGraph>()?;
+    assert_that!(graph.cotos.len(), eq(3));
+    assert_that!(graph.itos.len(), eq(2));
+    assert_that!(graph.frontier, none());
+
+    // Bounded by the number of cotos
+    let request = Command::GraphFromCoto {
+        coto: graph_root.uuid,
+        options: Some(TraversalOptions::default().max_cotos(1)),
+        frontier: None,
+    }
+    .into_request();
+    let graph = service.call(request).await?.content::<CotoGraph>()?;
+    assert_that!(graph.cotos.len(), eq(2));
+    assert_that!(graph.itos.len(), eq(1));
+    let frontier = graph.frontier.unwrap();
+
+    // Resume from the frontier
+    let request = Command::GraphFromCoto {
+        coto: graph_root.uuid,
+        options: Some(TraversalOptions::default().max_cotos(1)),
+        frontier: Some(frontier),
+    }
+    .into_request();
+    let graph = service.call(request).await?.content::<CotoGraph>()?;
+    assert_that!(graph.itos.len(), eq(1));
+    assert_that!(graph.frontier, none());
+
+    /////////////////////////////////////////////////////////////////////////////
+    // Command: Descendants
+    /////////////////////////////////////////////////////////////////////////////
+
+    let request = Command::Descendants {
+        coto: graph_root.uuid,
+        max_depth: None,
+    }
+    .into_request();
+    let descendants = service
+        .call(request)
+        .await?
+        .content::<Vec<(Vec<Ito>, Vec<Coto>)>>()?;
+    assert_that!(
+        descendants,
+        elements_are![(
+            len(eq(2)),
+            unordered_elements_are![
+                pat!(Coto {
+                    content: some(eq("graph child1")),
+                    ..
+                }),
+                pat!(Coto {
+                    content: some(eq("graph child2")),
+                    ..
+                })
+            ]
+        )]
+    );
+
+    /////////////////////////////////////////////////////////////////////////////
+    // Command: GraphHealth
+    /////////////////////////////////////////////////////////////////////////////
+
+    let request = Command::GraphHealth { scope: Scope::All }.into_request();
+    let health = service.call(request).await?.content::<GraphHealth>()?;
+    assert_that!(health.orphans, contains(eq(&graph_root.uuid)));
+    assert_that!(health.cycles, is_empty());
+
+    /////////////////////////////////////////////////////////////////////////////
+    // Command: QueryGraph
+    /////////////////////////////////////////////////////////////////////////////
+
+    let request = Command::QueryGraph {
+        query: r#"MATCH (a {content: "graph root"})-->(b)"#.into(),
+    }
+    .into_request();
+    let graph = service
+        .call(request)
+        .await?
+        .content::<Option<CotoGraph>>()?
+        .unwrap();
+    assert_that!(graph.root_coto_id, eq(graph_root.uuid));
+    assert_that!(graph.cotos.len(), eq(3));
+    assert_that!(graph.itos.len(), eq(2));
+
+    let request = Command::QueryGraph {
+        query: r#"MATCH (a {content: "no such coto"})"#.into(),
+    }
+    .into_request();
+    let graph = service
+        .call(request)
+        .await?
+        .content::<Option<CotoGraph>>()?;
+    assert_that!(graph, none());
+
+    let request = Command::QueryGraph {
+        query: "MATCH (a".into(),
+    }
+    .into_request();
+    let error = service
+        .call(request)
+        .await?
+        .content::<Option<CotoGraph>>()
+        .unwrap_err();
+    assert_that!(
+        error.downcast_ref::<BackendServiceError>(),
+        some(pat!(BackendServiceError(pat!(ServiceError::Request(
+            pat!(RequestError {
+                code: eq("invalid-graph-query"),
+                ..
+            })
+        )))))
+    );
+
+    /////////////////////////////////////////////////////////////////////////////
+    // Command: ImportCotoamiJson
+    /////////////////////////////////////////////////////////////////////////////
+
+    let json = r#"{
+        "cotos": [{
+            "id": "f05c0f03-8bb0-430e-a4d2-714c2922e0cd",
+            "content": "Nginx Ingress Controller", "summary": null,
+            "posted_in_id": null, "as_cotonoma": false, "cotonoma": null, "repost_id": null,
+            "inserted_at": 1507106650888, "updated_at": 1507106650888
+        }],
+        "connections": []
+    }"#;
+    // A dump larger than the default body limit of axum (2 MB)
+    let large_json = format!("{json}{}", " ".repeat(3 << 20));
+    let request = Command::ImportCotoamiJson {
+        json: large_json,
+        options: CotoamiImportOptions {
+            dry_run: true,
+            ..Default::default()
+        },
+    }
+    .into_request();
+    let report = service
+        .call(request)
+        .await?
+        .content::<CotoamiImportReport>()?;
+    assert_that!(
+        report,
+        pat!(CotoamiImportReport {
+            cotos: eq(&1),
+            rejected_cotos: is_empty(),
+            ..
+        })
+    );
+    let imported_coto_id: Id<Coto> = "f05c0f03-8bb0-430e-a4d2-714c2922e0cd".parse()?;
+    assert_that!(backend_ds.contains_coto(&imported_coto_id)?, eq(false));
+
+    let request = Command::ImportCotoamiJson {
+        json: json.into(),
+        options: CotoamiImportOptions::default(),
+    }
+    .into_request();
+    let report = service
+        .call(request)
+        .await?
+        .content::<CotoamiImportReport>()?;
+    assert_that!(report.cotos, eq(1));
+    assert_that!(
+        backend_ds.coto(&imported_coto_id)?,
+        some(pat!(Coto {
+            content: some(eq("Nginx Ingress Controller")),
+            posted_in_id: some(eq(&backend_root_cotonoma.uuid)),
+            ..
+        }))
+    );
+
+    let request = Command::ImportCotoamiJson {
+        json: "{}".into(),
+        options: CotoamiImportOptions::default(),
+    }
+    .into_request();
+    let error = service
+        .call(request)
+        .await?
+        .content::<CotoamiImportReport>()
+        .unwrap_err();
+    assert_that!(
+        error.downcast_ref::<BackendServiceError>(),
+        some(pat!(BackendServiceError(pat!(ServiceError::Request(
+            pat!(RequestError {
+                code: eq("invalid-cotoami-json"),
+                ..
+            })
+        )))))
+    );
+
     /////////////////////////////////////////////////////////////////////////////
     // Command: MarkAsRead
     /////////////////////////////////////////////////////////////////////////////
@@ -810,6 +1029,31 @@ where
         some(eq(read_at))
     );
 
+    /////////////////////////////////////////////////////////////////////////////
+    // Command: ChunkOfChanges
+    /////////////////////////////////////////////////////////////////////////////
+
+    let request = Command::ChunkOfChanges {
+        from: 1,
+        byte_budget: None,
+    }
+    .into_request();
+    let ChunkOfChanges::Fetched(changes) = service.call(request).await?.content()? else {
+        panic!("Unexpected out of range");
+    };
+    assert_that!(changes.chunk.len(), gt(1));
+
+    // A chunk contains at least one change even if the byte budget is too small
+    let request = Command::ChunkOfChanges {
+        from: 1,
+        byte_budget: Some(1),
+    }
+    .into_request();
+    let ChunkOfChanges::Fetched(changes) = service.call(request).await?.content()? else {
+        panic!("Unexpected out of range");
+    };
+    assert_that!(changes.chunk, len(eq(1)));
+
     Ok(())
 }
 
@@ -825,6 +1069,50 @@ async fn service_based_on_local_node() -> Result<()> {
     test_service(&service, state, changes, operator_node_id).await
 }
 
+#[test(tokio::test)]
+async fn chunk_of_changes_acknowledged_by_child() -> Result<()> {
+    let config = common::new_node_config("test")?;
+    let state = NodeState::new(config).await?;
+    let mut ds = state.db().new_session()?;
+    let owner = state.local_node_as_operator()?;
+
+    let child_id = Id::generate();
+    let (_, _, child) = ds.register_client_node(
+        &child_id,
+        "child-password",
+        NewDatabaseRole::Child(ChildNodeInput::default()),
+        &owner,
+    )?;
+    let child = match child {
+        DatabaseRole::Child(child) => Arc::new(Operator::ChildNode(child)),
+        _ => panic!("Not a child"),
+    };
+    let last_serial_number = ds.last_change_number()?.unwrap();
+
+    // When: a child requests changes beyond the last one
+    let request = Command::ChunkOfChanges {
+        from: last_serial_number + 100,
+        byte_budget: None,
+    }
+    .into_request_from(child);
+    let chunk = state.call(request).await?.content::<ChunkOfChanges>()?;
+    assert_that!(
+        chunk,
+        pat!(ChunkOfChanges::OutOfRange {
+            max: eq(&last_serial_number)
+        })
+    );
+
+    // Then: the acknowledged number is clamped to the last one
+    assert_that!(
+        ds.try_get_child_node(&child_id, &owner)?
+            .changes_acknowledged,
+        eq(last_serial_number)
+    );
+
+    Ok(())
+}
+
 #[test(tokio::test)]
 async fn service_based_on_websocket_server() -> Result<()> {
     test_service_based_on_remote_node(5103, true, NodeRole::Child).await
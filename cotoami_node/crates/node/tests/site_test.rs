@@ -0,0 +1,148 @@
+use std::fs;
+
+use anyhow::Result;
+use cotoami_db::prelude::*;
+use cotoami_node::prelude::*;
+use googletest::prelude::*;
+use tempfile::tempdir;
+use test_log::test;
+
+pub mod common;
+
+#[test(tokio::test)]
+async fn generate_site() -> Result<()> {
+    /////////////////////////////////////////////////////////////////////////////
+    // Setup
+    /////////////////////////////////////////////////////////////////////////////
+
+    let state = NodeState::new(common::new_node_config("My Node")?).await?;
+    let mut ds = state.db().new_session()?;
+    let opr = state.local_node_as_operator()?;
+    let (root, root_coto) = ds.try_get_local_node_root()?;
+
+    let ((rust, rust_coto), _) = ds.post_cotonoma(&CotonomaInput::new("Rust"), &root, &opr)?;
+    let (coto1, _) = ds.post_coto(
+        &CotoInput::new("# Ownership\n\n**Hello** <script>alert(1)</script>")
+            .geolocation(Geolocation::from_lng_lat((139.7, 35.6))),
+        &rust.uuid,
+        &opr,
+    )?;
+    let (coto2, _) = ds.post_coto(&CotoInput::new("Borrowing"), &root.uuid, &opr)?;
+    let _ = ds.create_ito(&ItoInput::new(root_coto.uuid, rust_coto.uuid), &opr)?;
+    let _ = ds.create_ito(
+        &ItoInput::new(rust_coto.uuid, coto1.uuid).description("about"),
+        &opr,
+    )?;
+    let _ = ds.create_ito(&ItoInput::new(coto1.uuid, coto2.uuid), &opr)?;
+    let (secret, _) = ds.post_coto(&CotoInput::new("Secret"), &root.uuid, &opr)?;
+    let _ = ds.create_ito(&ItoInput::new(secret.uuid, coto2.uuid), &opr)?;
+
+    /////////////////////////////////////////////////////////////////////////////
+    // When: generate a site from the root cotonoma
+    /////////////////////////////////////////////////////////////////////////////
+
+    let dir = tempdir()?;
+    let options = SiteOptions {
+        title: Some("My Site".into()),
+        map: true,
+    };
+    let report = state
+        .generate_site(Scope::All, dir.path().to_owned(), options)
+        .await
+        .map_err(BackendServiceError)?;
+    // index, timeline, map, and 5 cotos (root, "Rust", coto1, coto2, secret)
+    assert_that!(report, eq(&SiteReport { pages: 8, media: 0 }));
+
+    let index = fs::read_to_string(dir.path().join("index.html"))?;
+    assert_that!(index, contains_substring("<title>My Site</title>"));
+    assert_that!(
+        index,
+        contains_substring(format!(
+            "<a href=\"cotos/{}.html\" class=\"cotonoma\">Rust</a>",
+            rust_coto.uuid
+        ))
+    );
+
+    let timeline = fs::read_to_string(dir.path().join("timeline.html"))?;
+    assert_that!(
+        timeline,
+        contains_substring(format!("cotos/{}.html\">Borrowing</a>", coto2.uuid))
+    );
+
+    let page = fs::read_to_string(dir.path().join(format!("cotos/{}.html", coto1.uuid)))?;
+    assert_that!(
+        page,
+        contains_substring("<title>Ownership - My Site</title>")
+    );
+    assert_that!(page, contains_substring("in Rust"));
+    assert_that!(
+        page,
+        contains_substring("<strong>Hello</strong> &lt;script&gt;alert(1)&lt;/script&gt;")
+    );
+    assert_that!(
+        page,
+        contains_substring(format!(
+            "<h2>Connections</h2>\n<ul>\n<li><a href=\"../cotos/{}.html\">Borrowing</a></li>",
+            coto2.uuid
+        ))
+    );
+    assert_that!(
+        page,
+        contains_substring(format!(
+            "<h2>Linked from</h2>\n<ul>\n<li><span class=\"description\">about</span>\
+            <a href=\"../cotos/{}.html\" class=\"cotonoma\">Rust</a></li>",
+            rust_coto.uuid
+        ))
+    );
+
+    let map = fs::read_to_string(dir.path().join("map.html"))?;
+    assert_that!(
+        map,
+        contains_substring(format!("\"url\":\"cotos/{}.html\"", coto1.uuid))
+    );
+    assert!(dir.path().join("style.css").exists());
+
+    /////////////////////////////////////////////////////////////////////////////
+    // When: generate a site from a cotonoma
+    /////////////////////////////////////////////////////////////////////////////
+
+    let dir = tempdir()?;
+    let report = state
+        .generate_site(
+            Scope::cotonoma_recursive(rust.uuid),
+            dir.path().to_owned(),
+            SiteOptions::default(),
+        )
+        .await
+        .map_err(BackendServiceError)?;
+    // index, timeline, and 3 cotos ("Rust", coto1, and coto2 in the stock)
+    assert_that!(report, eq(&SiteReport { pages: 5, media: 0 }));
+    assert!(!dir.path().join("map.html").exists());
+
+    let index = fs::read_to_string(dir.path().join("index.html"))?;
+    assert_that!(index, contains_substring("<title>Rust</title>"));
+    assert_that!(
+        index,
+        contains_substring(format!(
+            "<span class=\"description\">about</span><a href=\"cotos/{}.html\">Ownership</a>",
+            coto1.uuid
+        ))
+    );
+
+    let page = fs::read_to_string(dir.path().join(format!("cotos/{}.html", coto1.uuid)))?;
+    assert_that!(page, contains_substring("<title>Ownership - Rust</title>"));
+    // coto2 is out of the scope
+    let timeline = fs::read_to_string(dir.path().join("timeline.html"))?;
+    assert_that!(timeline, not(contains_substring("Borrowing")));
+
+    // The ito from the secret coto out of the scope is not published
+    let page = fs::read_to_string(dir.path().join(format!("cotos/{}.html", coto2.uuid)))?;
+    assert_that!(page, contains_substring("<h2>Linked from</h2>"));
+    assert_that!(page, not(contains_substring("Secret")));
+    assert!(!dir
+        .path()
+        .join(format!("cotos/{}.html", secret.uuid))
+        .exists());
+
+    Ok(())
+}
@@ -0,0 +1,95 @@
+use anyhow::Result;
+use cotoami_db::prelude::*;
+use cotoami_node::prelude::*;
+use futures::stream::StreamExt;
+use googletest::prelude::*;
+use test_log::test;
+
+pub mod common;
+
+use self::common::wait_get;
+
+#[test(tokio::test)]
+async fn bootstrap_child_from_snapshot() -> Result<()> {
+    /////////////////////////////////////////////////////////////////////////////
+    // Setup: a child node preferring a snapshot and a server with some changes
+    /////////////////////////////////////////////////////////////////////////////
+
+    let port = 5109;
+    let mut child_config = common::new_node_config("child")?;
+    child_config.owner_password = Some("master-password".into());
+    child_config.snapshot_threshold = Some(0);
+    let child_state = NodeState::new(child_config).await?;
+    let child_id = child_state.try_get_local_node_id()?;
+    let mut child_events = child_state.pubsub().events().subscribe(None::<()>);
+
+    let (server_state, shutdown) = common::launch_server_node(
+        "server",
+        port,
+        true,
+        AddClient::new(
+            child_id,
+            Some("server-password"),
+            Some(ChildNodeInput::default()),
+        ),
+    )
+    .await?;
+    let server_id = server_state.try_get_local_node_id()?;
+    let server_ds = server_state.db().new_session()?;
+    let server_opr = server_state.local_node_as_operator()?;
+    let (root, _) = server_state.db().new_session()?.try_get_local_node_root()?;
+    let (coto, _) = server_ds.post_coto(&CotoInput::new("hello"), &root.uuid, &server_opr)?;
+    let _ = server_ds.edit_coto(
+        &coto.uuid,
+        CotoContentDiff::default().content("hello!"),
+        &server_opr,
+    )?;
+
+    /////////////////////////////////////////////////////////////////////////////
+    // When: the child connects to the server
+    /////////////////////////////////////////////////////////////////////////////
+
+    common::connect_to_server(
+        &child_state,
+        format!("http://localhost:{port}"),
+        "server-password",
+        NodeRole::Child,
+    )
+    .await?;
+
+    let range = loop {
+        match wait_get(child_events.next(), "ParentSyncEnd event").await {
+            Some(LocalNodeEvent::ParentSyncEnd { range, error, .. }) => {
+                assert_that!(error, none());
+                break range;
+            }
+            Some(LocalNodeEvent::ParentSyncProgress { .. }) => {
+                panic!("No changes should be replayed after the snapshot")
+            }
+            Some(_) => continue,
+            None => panic!("No ParentSyncEnd event"),
+        }
+    };
+
+    // Then: the child has installed the snapshot covering all the changes
+    // (including the ones made by the connection)
+    let last_change_number = server_state.db().new_session()?.last_change_number()?;
+    assert_that!(range, some(eq((1, last_change_number.unwrap()))));
+    let mut child_ds = child_state.db().new_session()?;
+    assert_that!(
+        child_ds.try_get_coto(&coto.uuid)?.content,
+        some(eq("hello!"))
+    );
+    assert_that!(
+        child_state.db().globals().parent_node(&server_id),
+        some(pat!(ParentNode {
+            changes_received: eq(&last_change_number.unwrap()),
+            ..
+        }))
+    );
+    assert!(child_state.db().fsck()?.is_ok());
+
+    shutdown.send(()).ok();
+
+    Ok(())
+}
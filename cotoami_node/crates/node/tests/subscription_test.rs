@@ -0,0 +1,135 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use cotoami_db::prelude::*;
+use cotoami_node::prelude::*;
+use futures::stream::StreamExt;
+use googletest::prelude::*;
+use test_log::test;
+
+pub mod common;
+
+use self::common::wait_get;
+
+#[test(tokio::test)]
+async fn replicate_subscribed_cotonomas() -> Result<()> {
+    /////////////////////////////////////////////////////////////////////////////
+    // Setup: a server with two cotonomas and a child node
+    /////////////////////////////////////////////////////////////////////////////
+
+    let port = 5110;
+    let child_state = common::new_client_node_state("child").await?;
+    let child_id = child_state.try_get_local_node_id()?;
+    let mut child_events = child_state.pubsub().events().subscribe(None::<()>);
+
+    let (server_state, shutdown) = common::launch_server_node(
+        "server",
+        port,
+        true,
+        AddClient::new(
+            child_id,
+            Some("server-password"),
+            Some(ChildNodeInput::default()),
+        ),
+    )
+    .await?;
+    let server_id = server_state.try_get_local_node_id()?;
+    let mut server_ds = server_state.db().new_session()?;
+    let server_opr = server_state.local_node_as_operator()?;
+    let (root, _) = server_state.db().new_session()?.try_get_local_node_root()?;
+    let ((cotonoma_a, _), _) =
+        server_ds.post_cotonoma(&CotonomaInput::new("a"), &root, &server_opr)?;
+    let ((cotonoma_b, _), _) =
+        server_ds.post_cotonoma(&CotonomaInput::new("b"), &root, &server_opr)?;
+    let (coto_a, _) =
+        server_ds.post_coto(&CotoInput::new("in a"), &cotonoma_a.uuid, &server_opr)?;
+    let (coto_b, _) =
+        server_ds.post_coto(&CotoInput::new("in b"), &cotonoma_b.uuid, &server_opr)?;
+
+    /////////////////////////////////////////////////////////////////////////////
+    // When: the child connects to the server subscribing to the cotonoma "a"
+    /////////////////////////////////////////////////////////////////////////////
+
+    let subscription = ChangeSubscription::new(vec![cotonoma_a.uuid], false);
+    let mut request = Command::AddServer(LogIntoServer {
+        url_prefix: Some(format!("http://localhost:{port}")),
+        password: Some("server-password".into()),
+        new_password: None,
+        client_role: Some(NodeRole::Child),
+        subscription: Some(subscription.clone()),
+        lazy_media: false,
+    })
+    .into_request();
+    request.set_from(Arc::new(child_state.local_node_as_operator()?));
+    child_state.call(request).await?.content::<Server>()?;
+
+    loop {
+        match wait_get(child_events.next(), "ParentSyncEnd event").await {
+            Some(LocalNodeEvent::ParentSyncEnd { error, .. }) => {
+                assert_that!(error, none());
+                break;
+            }
+            Some(_) => continue,
+            None => panic!("No ParentSyncEnd event"),
+        }
+    }
+
+    // Then: only the subscribed cotonoma has been replicated
+    let mut child_ds = child_state.db().new_session()?;
+    assert_that!(child_ds.coto(&coto_a.uuid)?, some(anything()));
+    assert_that!(child_ds.coto(&coto_b.uuid)?, none());
+    assert_that!(
+        child_state
+            .db()
+            .globals()
+            .parent_node(&server_id)
+            .and_then(|parent| parent.subscription()),
+        some(eq(&subscription))
+    );
+    assert_that!(
+        server_ds
+            .try_get_child_node(&child_id, &server_opr)?
+            .subscription(),
+        some(eq(&subscription))
+    );
+
+    /////////////////////////////////////////////////////////////////////////////
+    // When: new cotos are posted in the server
+    /////////////////////////////////////////////////////////////////////////////
+
+    let mut child_changes = child_state.pubsub().changes().subscribe(None::<()>);
+    let (coto_b2, log) =
+        server_ds.post_coto(&CotoInput::new("b2"), &cotonoma_b.uuid, &server_opr)?;
+    server_state.pubsub().publish_change(log);
+    let (coto_a2, log) =
+        server_ds.post_coto(&CotoInput::new("a2"), &cotonoma_a.uuid, &server_opr)?;
+    server_state.pubsub().publish_change(log);
+
+    // Then: the coto in "b" has been received as a placeholder, which is not recorded
+    let mut changes_from_server = Vec::new();
+    while let Some(change) = wait_get(child_changes.next(), "changes").await {
+        if change.origin_node_id == server_id {
+            changes_from_server.push(change.change);
+            break;
+        }
+    }
+    assert_that!(
+        changes_from_server,
+        elements_are![pat!(Change::CreateCoto(field!(
+            Coto.uuid,
+            eq(&coto_a2.uuid)
+        )))]
+    );
+    assert_that!(child_ds.coto(&coto_b2.uuid)?, none());
+    assert_that!(
+        child_state.db().globals().parent_node(&server_id),
+        some(field!(
+            ParentNode.changes_received,
+            eq(&server_ds.last_change_number()?.unwrap())
+        ))
+    );
+
+    shutdown.send(()).ok();
+
+    Ok(())
+}
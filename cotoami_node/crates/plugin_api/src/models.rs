@@ -119,3 +119,10 @@ pub struct Ancestors {
     pub ancestors: Vec<(Vec<Ito>, Vec<Coto>)>,
     pub authors: HashMap<String, Node>,
 }
+
+#[derive(derive_more::Debug, serde::Serialize, serde::Deserialize, ToBytes, FromBytes)]
+#[encoding(Json)]
+pub struct Descendants {
+    pub descendants: Vec<(Vec<Ito>, Vec<Coto>)>,
+    pub authors: HashMap<String, Node>,
+}